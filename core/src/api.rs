@@ -265,6 +265,7 @@ impl Provider for ConfigProvider {
             base_url,
             model,
             api_key,
+            pricing: self.config.pricing,
         }
     }
 }
@@ -412,6 +413,7 @@ mod tests {
             base_url: "https://api.test.com".to_string(),
             model: "test-model".to_string(),
             api_key: "sk-test1234abcd".to_string(),
+            pricing: None,
         };
 
         assert_eq!(config.masked_api_key(), "sk-t...abcd");
@@ -424,6 +426,7 @@ mod tests {
             base_url: "https://api.test.com".to_string(),
             model: "test-model".to_string(),
             api_key: "short".to_string(),
+            pricing: None,
         };
 
         assert_eq!(config.masked_api_key(), "*****");
@@ -436,6 +439,7 @@ mod tests {
             base_url: "https://api.test.com".to_string(),
             model: "test-model".to_string(),
             api_key: String::new(),
+            pricing: None,
         };
 
         assert_eq!(config.masked_api_key(), "(no key)");
@@ -2,11 +2,18 @@
 //!
 //! Handles API calls and the agentic loop for tool execution.
 
+#[cfg(test)]
+pub mod fake;
 pub mod models;
+pub mod repair;
 pub mod schema;
 
-use crate::config::ProviderSettings;
+use crate::compaction;
+use crate::config::{Compaction, Pricing, ProviderSettings, Pruning};
 use crate::events;
+use crate::guard::Guard;
+use crate::history::{History, prune};
+use crate::steering::Steering;
 use crate::tools;
 use anyhow::Result;
 use futures::stream::Stream;
@@ -46,11 +53,81 @@ pub enum ApiError {
     Api(String),
 }
 
+/// Token usage reported by the API
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Usage {
+    /// Input tokens (excluding cache reads and writes)
+    #[serde(default)]
+    pub input_tokens: u64,
+    /// Output tokens
+    #[serde(default)]
+    pub output_tokens: u64,
+    /// Input tokens written to the prompt cache
+    #[serde(default)]
+    pub cache_creation_input_tokens: u64,
+    /// Input tokens read from the prompt cache
+    #[serde(default)]
+    pub cache_read_input_tokens: u64,
+}
+
+impl Usage {
+    /// Total input tokens, including cache reads and writes.
+    #[must_use]
+    pub const fn total_input(&self) -> u64 {
+        self.input_tokens + self.cache_creation_input_tokens + self.cache_read_input_tokens
+    }
+
+    /// Total tokens (input + output).
+    #[must_use]
+    pub const fn total(&self) -> u64 {
+        self.total_input() + self.output_tokens
+    }
+
+    /// Cost of this usage in USD.
+    #[must_use]
+    pub fn cost(&self, pricing: &Pricing) -> f64 {
+        let tokens = |n: u64| f64::from(u32::try_from(n).unwrap_or(u32::MAX));
+        tokens(self.total_input())
+            .mul_add(pricing.input, tokens(self.output_tokens) * pricing.output)
+            / 1_000_000.0
+    }
+
+    /// Add another usage to this one.
+    pub const fn add(&mut self, other: &Self) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+    }
+
+    /// Merge a later usage report for the same response.
+    ///
+    /// `message_delta` reports cumulative counts, so non-zero fields
+    /// replace the earlier values instead of being added.
+    pub const fn merge(&mut self, later: &Self) {
+        if later.input_tokens > 0 {
+            self.input_tokens = later.input_tokens;
+        }
+        if later.output_tokens > 0 {
+            self.output_tokens = later.output_tokens;
+        }
+        if later.cache_creation_input_tokens > 0 {
+            self.cache_creation_input_tokens = later.cache_creation_input_tokens;
+        }
+        if later.cache_read_input_tokens > 0 {
+            self.cache_read_input_tokens = later.cache_read_input_tokens;
+        }
+    }
+}
+
 /// Stream response event
 #[derive(Debug, Clone)]
 pub enum StreamEvent {
     /// Message start event, indicates the start of stream response
-    MessageStart,
+    MessageStart {
+        /// Initial usage (input tokens)
+        usage: Usage,
+    },
     /// Content block start event, contains content block type and index
     ContentBlockStart {
         /// Index position of the content block in the message
@@ -71,7 +148,10 @@ pub enum StreamEvent {
         index: u32,
     },
     /// Message delta event, contains message-level delta data
-    MessageDelta,
+    MessageDelta {
+        /// Cumulative usage for the message
+        usage: Usage,
+    },
     /// Message stop event, indicates the end of stream response
     MessageStop,
     /// Error event, contains API error information
@@ -101,6 +181,22 @@ pub enum ContentBlock {
         /// Input parameters for the tool call
         input: Value,
     },
+    /// Thinking content block, the model's reasoning before its reply
+    #[serde(rename = "thinking")]
+    Thinking {
+        /// Reasoning text
+        #[serde(default)]
+        thinking: String,
+        /// Signature verifying the reasoning when it is sent back
+        #[serde(default)]
+        signature: String,
+    },
+    /// Redacted thinking content block, reasoning encrypted by the provider
+    #[serde(rename = "redacted_thinking")]
+    RedactedThinking {
+        /// Encrypted reasoning
+        data: String,
+    },
 }
 
 /// Delta data
@@ -119,6 +215,18 @@ pub enum Delta {
         /// Partial JSON data, used to build complete JSON structure
         partial_json: String,
     },
+    /// Thinking delta, contains newly added reasoning text
+    #[serde(rename = "thinking_delta")]
+    Thinking {
+        /// Incremental reasoning text
+        thinking: String,
+    },
+    /// Signature delta, the signature of a thinking block
+    #[serde(rename = "signature_delta")]
+    Signature {
+        /// Signature of the thinking block
+        signature: String,
+    },
 }
 
 /// SSE event stream type
@@ -152,6 +260,50 @@ pub struct ToolCall {
     pub name: String,
     /// Input parameters for the tool call
     pub input: Value,
+    /// Why the input could not be used, and the raw input text
+    #[serde(skip)]
+    pub malformed_input: Option<(repair::Malformed, String)>,
+}
+
+impl ToolCall {
+    /// Input to record in the assistant message.
+    ///
+    /// The API requires `tool_use` input to be an object, so malformed
+    /// input is replaced by an empty object.
+    #[must_use]
+    pub fn recorded_input(&self) -> Value {
+        if self.input.is_object() {
+            self.input.clone()
+        } else {
+            json!({})
+        }
+    }
+
+    /// Error message explaining why the input could not be used.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the input is a valid JSON object.
+    #[must_use]
+    pub fn input_error(&self) -> Option<String> {
+        let (reason, raw) = self.malformed_input.as_ref()?;
+        if *reason == repair::Malformed::Truncated {
+            return Some(format!(
+                "error: tool input for '{}' was cut off before it was complete ({} bytes \
+                 received), likely by the output token limit. The call was not run. Retry it, \
+                 splitting large content into several smaller calls.",
+                self.name,
+                raw.len()
+            ));
+        }
+        let preview: String = raw.chars().take(200).collect();
+        let ellipsis = if raw.chars().count() > 200 { "..." } else { "" };
+        Some(format!(
+            "error: tool input for '{}' is not a valid JSON object. \
+             Received: {preview}{ellipsis}\nRetry the call with complete, valid JSON input.",
+            self.name
+        ))
+    }
 }
 
 impl ToolCallCollector {
@@ -192,11 +344,14 @@ impl ToolCallCollector {
                 }
             },
             StreamEvent::ContentBlockStart {
-                content_block: ContentBlock::Text { .. },
+                content_block:
+                    ContentBlock::Text { .. }
+                    | ContentBlock::Thinking { .. }
+                    | ContentBlock::RedactedThinking { .. },
                 ..
             }
-            | StreamEvent::MessageStart
-            | StreamEvent::MessageDelta
+            | StreamEvent::MessageStart { .. }
+            | StreamEvent::MessageDelta { .. }
             | StreamEvent::MessageStop
             | StreamEvent::Error { .. } => {}, // These events don't need special handling
 
@@ -231,10 +386,21 @@ impl ToolCallCollector {
             .calls
             .iter()
             .filter(|c| c.completed)
-            .map(|c| ToolCall {
-                id: c.id.clone(),
-                name: c.name.clone(),
-                input: serde_json::from_str(&c.input_buffer).unwrap_or_default(),
+            .map(|c| {
+                let input = repair::parse_tool_input(&c.input_buffer);
+                if input.is_ok() && serde_json::from_str::<Value>(&c.input_buffer).is_err() {
+                    tracing::warn!(tool = %c.name, "Dropped trailing garbage from tool input JSON");
+                }
+                let (input, malformed_input) = match input {
+                    Ok(input) => (input, None),
+                    Err(reason) => (Value::Null, Some((reason, c.input_buffer.clone()))),
+                };
+                ToolCall {
+                    id: c.id.clone(),
+                    name: c.name.clone(),
+                    input,
+                    malformed_input,
+                }
             })
             .collect();
 
@@ -255,6 +421,30 @@ impl Default for ToolCallCollector {
     }
 }
 
+/// Wait for the next stream event, until the wall-clock limit if any.
+///
+/// # Returns
+///
+/// The next event, or `None` if the limit passed first.
+async fn next_event(
+    stream: &mut EventStream,
+    guard: &Guard,
+) -> Option<Option<Result<StreamEvent, ApiError>>> {
+    use futures::stream::StreamExt;
+
+    match guard.time_left() {
+        Some(left) => tokio::time::timeout(left, stream.next()).await.ok(),
+        None => Some(stream.next().await),
+    }
+}
+
+/// Parse a usage object, defaulting to zero usage.
+fn parse_usage(value: Option<&Value>) -> Usage {
+    value
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
 /// Parse SSE response stream
 fn parse_sse_stream(response: reqwest::Response) -> EventStream {
     use futures::stream::StreamExt;
@@ -306,7 +496,9 @@ fn parse_sse_stream(response: reqwest::Response) -> EventStream {
                             Ok(value) => {
                                 if let Some(event_type) = value.get("type").and_then(|v| v.as_str()) {
                                     let event = match event_type {
-                                        "message_start" => StreamEvent::MessageStart,
+                                        "message_start" => StreamEvent::MessageStart {
+                                            usage: parse_usage(value.get("message").and_then(|m| m.get("usage"))),
+                                        },
                                         "content_block_start" => {
                                             if let Some(block) = value.get("content_block") {
     StreamEvent::ContentBlockStart {
@@ -328,7 +520,9 @@ fn parse_sse_stream(response: reqwest::Response) -> EventStream {
     "content_block_stop" => StreamEvent::ContentBlockStop {
                                             index: value.get("index").and_then(Value::as_u64).unwrap_or(0).try_into().unwrap_or(0),
                                         },
-                                        "message_delta" => StreamEvent::MessageDelta,
+                                        "message_delta" => StreamEvent::MessageDelta {
+                                            usage: parse_usage(value.get("usage")),
+                                        },
                                         "message_stop" => StreamEvent::MessageStop,
                                         "error" => StreamEvent::Error {
                                             error: ApiError::Api(
@@ -354,6 +548,50 @@ fn parse_sse_stream(response: reqwest::Response) -> EventStream {
         })
 }
 
+/// Add a streamed thinking block, or a delta of one, to the thinking of a response.
+///
+/// Blocks keep their signatures, which the API checks when they are sent back.
+fn collect_thinking(blocks: &mut Vec<Value>, event: &StreamEvent) {
+    match event {
+        StreamEvent::ContentBlockStart {
+            content_block:
+                ContentBlock::Thinking {
+                    thinking,
+                    signature,
+                },
+            ..
+        } => blocks.push(json!({
+            "type": "thinking",
+            "thinking": thinking,
+            "signature": signature
+        })),
+        StreamEvent::ContentBlockStart {
+            content_block: ContentBlock::RedactedThinking { data },
+            ..
+        } => blocks.push(json!({"type": "redacted_thinking", "data": data})),
+        StreamEvent::ContentBlockDelta {
+            delta: Delta::Thinking { thinking },
+            ..
+        } => {
+            if let Some(Value::String(current)) = blocks
+                .last_mut()
+                .and_then(|block| block.get_mut("thinking"))
+            {
+                current.push_str(thinking);
+            }
+        },
+        StreamEvent::ContentBlockDelta {
+            delta: Delta::Signature { signature },
+            ..
+        } => {
+            if let Some(block) = blocks.last_mut().and_then(Value::as_object_mut) {
+                block.insert("signature".to_string(), json!(signature));
+            }
+        },
+        _ => {},
+    }
+}
+
 /// API client.
 pub struct Client {
     /// HTTP client for making API requests
@@ -362,6 +600,12 @@ pub struct Client {
     config: ProviderSettings,
     /// Tool registry for executing tools
     tool_registry: Arc<tools::ToolRegistry>,
+    /// Pruning of stale tool results from requests
+    pruning: Pruning,
+    /// Automatic compaction between the tool rounds of a turn
+    compaction: Compaction,
+    /// Catalog of lazily loaded tools, `None` to offer all tools
+    tool_search: Option<tools::search::Catalog>,
 }
 
 impl Client {
@@ -372,9 +616,58 @@ impl Client {
             http: HttpClient::new(),
             config,
             tool_registry: Arc::new(tools::ToolRegistry::new()),
+            pruning: Pruning::default(),
+            compaction: Compaction::default(),
+            tool_search: None,
         }
     }
 
+    /// Replace the tool registry used to execute tools.
+    pub fn set_tool_registry(&mut self, tool_registry: Arc<tools::ToolRegistry>) {
+        self.tool_registry = tool_registry;
+    }
+
+    /// Get the tool registry used to execute tools.
+    #[must_use]
+    pub fn tool_registry(&self) -> &tools::ToolRegistry {
+        &self.tool_registry
+    }
+
+    /// Get mutable reference to the tool registry, to register more tools.
+    ///
+    /// # Returns
+    ///
+    /// `None` while the registry is shared, e.g. by tools running in a turn.
+    pub fn tool_registry_mut(&mut self) -> Option<&mut tools::ToolRegistry> {
+        Arc::get_mut(&mut self.tool_registry)
+    }
+
+    /// Switch to another provider or model, keeping the tools and pruning policy.
+    pub fn set_config(&mut self, config: ProviderSettings) {
+        self.config = config;
+    }
+
+    /// Set the policy for pruning stale tool results from requests.
+    pub fn set_pruning(&mut self, pruning: Pruning) {
+        self.pruning = pruning;
+    }
+
+    /// Set the settings of automatic compaction between tool rounds.
+    pub fn set_compaction(&mut self, compaction: Compaction) {
+        self.compaction = compaction;
+    }
+
+    /// Set the catalog deciding which tools are offered with each request.
+    pub fn set_tool_search(&mut self, catalog: tools::search::Catalog) {
+        self.tool_search = Some(catalog);
+    }
+
+    /// Get reference to the provider configuration.
+    #[must_use]
+    pub const fn config(&self) -> &ProviderSettings {
+        &self.config
+    }
+
     /// Send stream message request
     ///
     /// # Arguments
@@ -438,14 +731,59 @@ impl Client {
         Ok(parse_sse_stream(response))
     }
 
+    /// Send a request without tools and collect the full text reply.
+    ///
+    /// # Arguments
+    ///
+    /// * `messages` - Messages to send
+    /// * `system_prompt` - System prompt for the model
+    ///
+    /// # Returns
+    ///
+    /// The reply text and the usage of the request
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the request fails or the stream reports an error.
+    pub async fn complete(
+        &self,
+        messages: &[Value],
+        system_prompt: &str,
+    ) -> Result<(String, Usage), ApiError> {
+        use futures::stream::StreamExt;
+
+        let mut stream = self
+            .create_message_stream(messages, system_prompt, None)
+            .await?;
+        let mut text = String::new();
+        let mut usage = Usage::default();
+        while let Some(event) = stream.next().await {
+            match event? {
+                StreamEvent::MessageStart { usage: start } => usage.merge(&start),
+                StreamEvent::MessageDelta { usage: delta } => usage.merge(&delta),
+                StreamEvent::ContentBlockDelta {
+                    delta: Delta::Text { text: delta },
+                    ..
+                } => text.push_str(&delta),
+                StreamEvent::Error { error } => return Err(error),
+                StreamEvent::MessageStop => break,
+                _ => {},
+            }
+        }
+        Ok((text, usage))
+    }
+
     /// Run the agentic loop: keep calling API until no more tool calls.
     ///
     /// # Arguments
     ///
-    /// * `messages` - Mutable reference to conversation history
+    /// * `history` - Conversation history, extended with the model's replies (thinking
+    ///   blocks included) and tool results
     /// * `system_prompt` - System prompt for the model
     /// * `tools` - Tool definitions
     /// * `event_sender` - Optional sender for core events
+    /// * `guard` - Guard enforcing loop limits; stops the loop cleanly when one is reached
+    /// * `steering` - Optional queue of user messages to deliver with the next tool results
     ///
     /// # Returns
     ///
@@ -459,42 +797,71 @@ impl Client {
     /// - Response processing fails
     pub async fn run_agent_loop_stream(
         &self,
-        messages: &mut Vec<Value>,
+        history: &mut History,
         system_prompt: &str,
         tools: &[Value],
         event_sender: Option<&mpsc::UnboundedSender<events::CoreEvent>>,
+        guard: &mut Guard,
+        steering: Option<&Steering>,
     ) -> Result<(), ApiError> {
-        use futures::stream::StreamExt;
-
         let mut tool_collector = ToolCallCollector::new();
         // Initial check of collector state
         let _ = !tool_collector.is_active();
         let mut current_text = String::new();
+        guard.begin_turn();
 
         loop {
-            // Send message start event
-            if let Some(sender) = event_sender {
-                let _ = sender.send(events::CoreEvent::MessageStart);
+            // Stop cleanly once a limit has been reached
+            if let Some(exceeded) = guard.check() {
+                tracing::warn!(limit = %exceeded.limit, "{}", exceeded.message);
+                history.emit(event_sender, events::CoreEvent::LimitReached(exceeded));
+                break;
             }
 
-            // Create stream request
+            // Send message start event
+            history.emit(event_sender, events::CoreEvent::MessageStart);
+
+            // Create stream request, with stale tool results pruned and
+            // deferred tools left out until a search loads them
+            let messages = prune::apply(history.messages(), &self.pruning);
+            let offered = self
+                .tool_search
+                .as_ref()
+                .map(|catalog| catalog.offer(tools));
             let mut stream = self
-                .create_message_stream(messages, system_prompt, Some(tools))
+                .create_message_stream(
+                    &messages,
+                    system_prompt,
+                    Some(offered.as_deref().unwrap_or(tools)),
+                )
                 .await?;
 
-            // Process stream events
-            while let Some(event_result) = stream.next().await {
+            // Process stream events, until the wall-clock limit if any
+            let mut usage = Usage::default();
+            let mut thinking: Vec<Value> = Vec::new();
+            let mut timed_out = false;
+            loop {
+                let Some(next) = next_event(&mut stream, guard).await else {
+                    timed_out = true;
+                    break;
+                };
+                let Some(event_result) = next else {
+                    break;
+                };
                 let event = event_result?;
+                collect_thinking(&mut thinking, &event);
 
                 match &event {
+                    StreamEvent::MessageStart { usage: start } => usage.merge(start),
+
+                    StreamEvent::MessageDelta { usage: delta } => usage.merge(delta),
+
                     StreamEvent::ContentBlockDelta {
                         delta: Delta::Text { text },
                         ..
                     } => {
                         // Send text delta event
-                        if let Some(sender) = event_sender {
-                            let _ = sender.send(events::CoreEvent::TextDelta(text.clone()));
-                        }
+                        history.emit(event_sender, events::CoreEvent::TextDelta(text.clone()));
                         current_text.push_str(text);
                     },
 
@@ -503,26 +870,23 @@ impl Client {
                         ..
                     } => {
                         // Send tool call start event
-                        if let Some(sender) = event_sender {
-                            let _ = sender.send(events::CoreEvent::ToolCallStart {
+                        history.emit(
+                            event_sender,
+                            events::CoreEvent::ToolCallStart {
                                 id: id.clone(),
                                 name: name.clone(),
-                            });
-                        }
+                            },
+                        );
                     },
 
                     StreamEvent::Error { error } => {
                         // Send error event
-                        if let Some(sender) = event_sender {
-                            let _ = sender.send(events::CoreEvent::Error(error.to_string()));
-                        }
+                        history.emit(event_sender, events::CoreEvent::Error(error.to_string()));
                     },
 
                     StreamEvent::MessageStop => {
                         // Send message stop event
-                        if let Some(sender) = event_sender {
-                            let _ = sender.send(events::CoreEvent::MessageStop);
-                        }
+                        history.emit(event_sender, events::CoreEvent::MessageStop);
                         break;
                     },
 
@@ -534,82 +898,142 @@ impl Client {
                 // Process event for tool collection after match
                 tool_collector.process_event(&event);
             }
+            guard.record_usage(&usage);
+            history.emit(event_sender, events::CoreEvent::Usage(usage));
+
+            // Keep the partial reply, drop unfinished tool calls and stop
+            if timed_out {
+                let exceeded = guard.duration_exceeded();
+                tracing::warn!(limit = %exceeded.limit, "{}", exceeded.message);
+                if !current_text.is_empty() {
+                    history.push(json!({
+                        "role": "assistant",
+                        "content": [{"type": "text", "text": current_text}]
+                    }));
+                }
+                history.emit(event_sender, events::CoreEvent::LimitReached(exceeded));
+                break;
+            }
 
             // Check if there are completed tool calls
             if tool_collector.has_completed_calls() {
                 let tool_calls = tool_collector.take_completed();
+                let mut paused = false;
 
-                // Build assistant message content
-                let mut content_blocks = vec![json!({
+                // Build assistant message content, thinking first
+                let mut content_blocks = std::mem::take(&mut thinking);
+                content_blocks.push(json!({
                     "type": "text",
                     "text": current_text
-                })];
+                }));
 
                 for call in &tool_calls {
                     content_blocks.push(json!({
                         "type": "tool_use",
                         "id": call.id,
                         "name": call.name,
-                        "input": call.input
+                        "input": call.recorded_input()
                     }));
                 }
 
                 // Save assistant message
-                messages.push(json!({
+                history.push(json!({
                     "role": "assistant",
                     "content": content_blocks
                 }));
 
                 // Execute tools
+                let mut result_blocks = Vec::with_capacity(tool_calls.len());
                 for call in tool_calls {
                     // Send tool execution start event
-                    if let Some(sender) = event_sender {
-                        let _ = sender.send(events::CoreEvent::ToolExecuting {
+                    history.emit(
+                        event_sender,
+                        events::CoreEvent::ToolExecuting {
                             name: call.name.clone(),
-                        });
-                    }
+                        },
+                    );
+
+                    let (mut result, is_error) = match (call.input_error(), call.input.as_object())
+                    {
+                        (None, Some(input)) => self.run_tool_within(&call.name, input, guard).await,
+                        (error, _) => (
+                            error.unwrap_or_else(|| {
+                                format!("error: tool input for '{}' is not an object", call.name)
+                            }),
+                            true,
+                        ),
+                    };
 
-                    let result = self
-                        .run_tool(
-                            &call.name,
-                            call.input.as_object().ok_or_else(|| {
-                                ApiError::ParseError(format!(
-                                    "Tool input is not an object for tool: {}",
-                                    call.name
-                                ))
-                            })?,
-                        )
-                        .await;
+                    // Intervene when the model keeps repeating the same calls
+                    if let Some(repeat) = guard.record_tool_call(&call.name, &call.input) {
+                        let pause = guard.pauses_on_repetition();
+                        tracing::warn!(tool = %call.name, repeats = repeat.repeats, "Repeated tool call");
+                        result = format!("{result}\n\n{}", repeat.note());
+                        paused |= pause;
+                        history.emit(
+                            event_sender,
+                            events::CoreEvent::RepetitionDetected {
+                                name: call.name.clone(),
+                                repeats: repeat.repeats,
+                                paused: pause,
+                            },
+                        );
+                    }
 
                     // Send tool result event
-                    if let Some(sender) = event_sender {
-                        let _ = sender.send(events::CoreEvent::ToolResult {
+                    history.emit(
+                        event_sender,
+                        events::CoreEvent::ToolResult {
                             name: call.name.clone(),
                             result: result.clone(),
-                        });
+                        },
+                    );
+
+                    let mut tool_result = json!({
+                        "type": "tool_result",
+                        "tool_use_id": call.id,
+                        "content": result
+                    });
+                    if is_error && let Some(block) = tool_result.as_object_mut() {
+                        block.insert("is_error".to_string(), json!(true));
                     }
+                    result_blocks.push(tool_result);
+                }
 
-                    // Add tool result to message history
-                    messages.push(json!({
-                        "role": "user",
-                        "content": [{
-                            "type": "tool_result",
-                            "tool_use_id": call.id,
-                            "content": result
-                        }]
+                // Deliver messages the user typed while the tools were running
+                for text in steering.map(Steering::take).unwrap_or_default() {
+                    result_blocks.push(json!({
+                        "type": "text",
+                        "text": format!("[Message from the user while you were working]\n{text}")
                     }));
+                    history.emit(event_sender, events::CoreEvent::SteeringDelivered(text));
+                }
+
+                // Add tool results to message history
+                history.push(json!({
+                    "role": "user",
+                    "content": result_blocks
+                }));
+
+                guard.record_tool_round();
+                self.compact_if_due(history, guard, event_sender).await;
+
+                // Pause and let the user decide how to continue
+                if paused {
+                    break;
                 }
 
                 // Clear current text and continue loop
                 current_text = String::new();
             } else if !current_text.is_empty() {
                 // No tool calls, save final reply and exit
-                messages.push(json!({
+                thinking.push(json!({
+                    "type": "text",
+                    "text": current_text
+                }));
+                history.push(json!({
                     "role": "assistant",
-                    "content": [{
-                        "type": "text",
-                        "text": current_text
-                    }]
+                    "content": thinking
                 }));
                 break;
             } else {
@@ -620,6 +1044,66 @@ impl Client {
         Ok(())
     }
 
+    /// Compact the history between tool rounds if the context nears the limit.
+    ///
+    /// Counts the last request and the tool results added since. Failures
+    /// are reported and the turn continues uncompacted.
+    async fn compact_if_due(
+        &self,
+        history: &mut History,
+        guard: &mut Guard,
+        event_sender: Option<&mpsc::UnboundedSender<events::CoreEvent>>,
+    ) {
+        let added = compaction::estimate_tokens(
+            history
+                .messages()
+                .last_chunk::<1>()
+                .map_or(&[], |last| last.as_slice()),
+        );
+        if !self.compaction.is_due(guard.context_tokens() + added) {
+            return;
+        }
+        let Some(split) = compaction::split_point_in_turn(history.messages()) else {
+            return;
+        };
+        tracing::info!("Compacting conversation between tool rounds");
+        if let Err(e) =
+            compaction::compact(self, history, guard, split, None, true, event_sender).await
+        {
+            history.emit(
+                event_sender,
+                events::CoreEvent::Error(format!("Compaction failed: {e:#}")),
+            );
+        }
+    }
+
+    /// Execute a single tool call, stopping it at the wall-clock limit.
+    ///
+    /// # Returns
+    ///
+    /// Tool result as string, and whether it is an error because the tool
+    /// was stopped (or not started) at the limit.
+    async fn run_tool_within(
+        &self,
+        name: &str,
+        input: &serde_json::Map<String, Value>,
+        guard: &Guard,
+    ) -> (String, bool) {
+        let Some(left) = guard.time_left() else {
+            return (self.run_tool(name, input).await, false);
+        };
+        match tokio::time::timeout(left, self.run_tool(name, input)).await {
+            Ok(result) => (result, false),
+            Err(_) => (
+                format!(
+                    "error: {}; the tool was stopped before it finished",
+                    guard.duration_exceeded().message
+                ),
+                true,
+            ),
+        }
+    }
+
     /// Execute a single tool call.
     ///
     /// # Arguments
@@ -642,6 +1126,7 @@ impl Client {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Limits;
 
     #[test]
     fn test_content_block_deserialize() {
@@ -649,9 +1134,7 @@ mod tests {
         let text: ContentBlock = serde_json::from_value(text_json).unwrap();
         match text {
             ContentBlock::Text { text: t } => assert_eq!(t, "Hello"),
-            ContentBlock::ToolUse { .. } => {
-                panic!("Expected Text variant but got ToolUse in test");
-            },
+            other => panic!("Expected Text variant but got {other:?} in test"),
         }
 
         let tool_json = json!({
@@ -666,30 +1149,49 @@ mod tests {
                 assert_eq!(id, "call_123");
                 assert_eq!(name, "read");
             },
-            ContentBlock::Text { .. } => {
-                panic!("Expected ToolUse variant but got Text in test");
-            },
+            other => panic!("Expected ToolUse variant but got {other:?} in test"),
         }
     }
 
+    #[test]
+    fn test_usage_merge_and_cost() {
+        let mut usage = parse_usage(Some(&json!({"input_tokens": 1000, "output_tokens": 1})));
+        usage.merge(&parse_usage(Some(&json!({"output_tokens": 500}))));
+        assert_eq!(usage.input_tokens, 1000);
+        assert_eq!(usage.output_tokens, 500);
+        assert_eq!(usage.total(), 1500);
+
+        let pricing = Pricing {
+            input: 3.0,
+            output: 15.0,
+        };
+        let cost = usage.cost(&pricing);
+        assert!((cost - 0.0105).abs() < 1e-9);
+
+        assert_eq!(parse_usage(None), Usage::default());
+    }
+
     #[test]
     fn test_delta_deserialize() {
         let text_delta_json = json!({"type": "text_delta", "text": "Hello"});
         let delta: Delta = serde_json::from_value(text_delta_json).unwrap();
         match delta {
             Delta::Text { text } => assert_eq!(text, "Hello"),
-            Delta::InputJson { .. } => {
-                panic!("Expected Text variant but got InputJson in test");
-            },
+            other => panic!("Expected Text variant but got {other:?} in test"),
         }
 
         let json_delta_json = json!({"type": "input_json_delta", "partial_json": "{}"});
         let delta: Delta = serde_json::from_value(json_delta_json).unwrap();
         match delta {
             Delta::InputJson { partial_json } => assert_eq!(partial_json, "{}"),
-            Delta::Text { .. } => {
-                panic!("Expected InputJson variant but got Text in test");
-            },
+            other => panic!("Expected InputJson variant but got {other:?} in test"),
+        }
+
+        let thinking_json = json!({"type": "signature_delta", "signature": "sig"});
+        let delta: Delta = serde_json::from_value(thinking_json).unwrap();
+        match delta {
+            Delta::Signature { signature } => assert_eq!(signature, "sig"),
+            other => panic!("Expected Signature variant but got {other:?} in test"),
         }
     }
 
@@ -750,6 +1252,56 @@ mod tests {
         assert_eq!(second_call.name, "write");
     }
 
+    #[test]
+    fn test_tool_call_collector_malformed_input() {
+        let mut collector = ToolCallCollector::new();
+
+        collector.process_event(&StreamEvent::ContentBlockStart {
+            index: 0,
+            content_block: ContentBlock::ToolUse {
+                id: "call_1".to_string(),
+                name: "bash".to_string(),
+                input: json!(""),
+            },
+        });
+        collector.process_event(&StreamEvent::ContentBlockDelta {
+            index: 0,
+            delta: Delta::InputJson {
+                partial_json: r#"{"cmd":"ls -la"#.to_string(),
+            },
+        });
+        collector.process_event(&StreamEvent::ContentBlockStop { index: 0 });
+
+        collector.process_event(&StreamEvent::ContentBlockStart {
+            index: 1,
+            content_block: ContentBlock::ToolUse {
+                id: "call_2".to_string(),
+                name: "read".to_string(),
+                input: json!(""),
+            },
+        });
+        collector.process_event(&StreamEvent::ContentBlockDelta {
+            index: 1,
+            delta: Delta::InputJson {
+                partial_json: "garbage".to_string(),
+            },
+        });
+        collector.process_event(&StreamEvent::ContentBlockStop { index: 1 });
+
+        let calls = collector.take_completed();
+        let [truncated, broken] = calls.as_slice() else {
+            panic!("Expected two tool calls");
+        };
+
+        assert_eq!(truncated.recorded_input(), json!({}));
+        assert!(truncated.input_error().unwrap().contains("cut off"));
+
+        assert_eq!(broken.recorded_input(), json!({}));
+        let error = broken.input_error().unwrap();
+        assert!(error.contains("read"));
+        assert!(error.contains("garbage"));
+    }
+
     #[test]
     fn test_tool_call_collector_incomplete() {
         let mut collector = ToolCallCollector::new();
@@ -775,4 +1327,52 @@ mod tests {
         assert!(!collector.has_completed_calls());
         assert!(collector.is_active());
     }
+
+    #[tokio::test]
+    async fn test_loop_compacts_between_tool_rounds() {
+        let server = fake::serve(
+            vec![
+                fake::Reply::ToolUse("missing", json!({})),
+                fake::Reply::Text("summary of the turn"),
+                fake::Reply::Text("done"),
+            ],
+            190_000,
+        )
+        .await
+        .unwrap();
+        let client = Client::new(server.settings.clone());
+        let mut history = History::new();
+        history.push(json!({"role": "user", "content": "start"}));
+        let mut guard = Guard::new(Limits::default(), None);
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+
+        client
+            .run_agent_loop_stream(&mut history, "system", &[], Some(&sender), &mut guard, None)
+            .await
+            .unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        let summarised = requests.get(1).unwrap().to_string();
+        assert!(summarised.contains("start"));
+        let last = requests
+            .get(2)
+            .unwrap()
+            .pointer("/messages")
+            .unwrap()
+            .to_string();
+        assert!(last.contains("summary of the turn"));
+        assert!(last.contains("tool_result"));
+        let mut compacted = false;
+        while let Ok(event) = receiver.try_recv() {
+            compacted |= matches!(
+                event,
+                events::CoreEvent::Compacted {
+                    automatic: true,
+                    ..
+                }
+            );
+        }
+        assert!(compacted);
+    }
 }
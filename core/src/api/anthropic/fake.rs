@@ -0,0 +1,130 @@
+//! Fake Messages API serving scripted replies, for tests of the agent loop.
+
+use crate::config::ProviderSettings;
+use serde_json::{Value, json};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+/// A scripted reply of the fake API.
+pub enum Reply {
+    /// A text reply
+    Text(&'static str),
+    /// A call of a tool with the given input
+    ToolUse(&'static str, Value),
+    /// A signed thinking block followed by a text reply
+    Thinking(&'static str, &'static str),
+}
+
+impl Reply {
+    /// Server-sent events streaming the reply, reporting `input_tokens` of usage.
+    fn events(&self, input_tokens: u64) -> String {
+        let blocks = match self {
+            Self::Text(text) => vec![text_block(0, text)],
+            Self::ToolUse(name, input) => vec![vec![
+                json!({"type": "content_block_start", "index": 0, "content_block": {"type": "tool_use", "id": format!("call-{name}"), "name": name, "input": {}}}),
+                json!({"type": "content_block_delta", "index": 0, "delta": {"type": "input_json_delta", "partial_json": input.to_string()}}),
+                json!({"type": "content_block_stop", "index": 0}),
+            ]],
+            Self::Thinking(thinking, text) => vec![
+                vec![
+                    json!({"type": "content_block_start", "index": 0, "content_block": {"type": "thinking", "thinking": ""}}),
+                    json!({"type": "content_block_delta", "index": 0, "delta": {"type": "thinking_delta", "thinking": thinking}}),
+                    json!({"type": "content_block_delta", "index": 0, "delta": {"type": "signature_delta", "signature": "signed"}}),
+                    json!({"type": "content_block_stop", "index": 0}),
+                ],
+                text_block(1, text),
+            ],
+        };
+        std::iter::once(
+            json!({"type": "message_start", "message": {"usage": {"input_tokens": input_tokens}}}),
+        )
+        .chain(blocks.into_iter().flatten())
+        .chain([
+            json!({"type": "message_delta", "usage": {"output_tokens": 1}}),
+            json!({"type": "message_stop"}),
+        ])
+        .fold(String::new(), |mut out, event| {
+            let _ = write!(out, "data: {event}\n\n");
+            out
+        })
+    }
+}
+
+/// Events streaming a text block at `index`.
+fn text_block(index: u32, text: &str) -> Vec<Value> {
+    vec![
+        json!({"type": "content_block_start", "index": index, "content_block": {"type": "text", "text": ""}}),
+        json!({"type": "content_block_delta", "index": index, "delta": {"type": "text_delta", "text": text}}),
+        json!({"type": "content_block_stop", "index": index}),
+    ]
+}
+
+/// A running fake API.
+pub struct Server {
+    /// Provider settings pointing at the fake API
+    pub settings: ProviderSettings,
+    /// Bodies of the requests received, oldest first
+    pub requests: Arc<Mutex<Vec<Value>>>,
+}
+
+impl Server {
+    /// Bodies of the requests received so far.
+    pub fn requests(&self) -> Vec<Value> {
+        self.requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// Serve the replies in order, one per request, each reporting `input_tokens`.
+///
+/// # Errors
+///
+/// Returns error if no local port can be bound.
+pub async fn serve(replies: Vec<Reply>, input_tokens: u64) -> std::io::Result<Server> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let settings = ProviderSettings {
+        name: "anthropic".to_string(),
+        base_url: format!("http://{}", listener.local_addr()?),
+        model: "fake".to_string(),
+        api_key: "key".to_string(),
+        pricing: None,
+    };
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&requests);
+    tokio::spawn(async move {
+        for reply in replies {
+            let Ok((stream, _)) = listener.accept().await else {
+                return;
+            };
+            let mut reader = BufReader::new(stream);
+            let mut length = 0;
+            let mut line = String::new();
+            while reader.read_line(&mut line).await.is_ok_and(|read| read > 2) {
+                if let Some((name, value)) = line.trim_end().split_once(": ")
+                    && name.eq_ignore_ascii_case("content-length")
+                {
+                    length = value.parse().unwrap_or(0);
+                }
+                line.clear();
+            }
+            let mut body = vec![0; length];
+            if reader.read_exact(&mut body).await.is_err() {
+                return;
+            }
+            log.lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(serde_json::from_slice(&body).unwrap_or_default());
+            let events = reply.events(input_tokens);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{events}",
+                events.len()
+            );
+            let _ = reader.into_inner().write_all(response.as_bytes()).await;
+        }
+    });
+    Ok(Server { settings, requests })
+}
@@ -0,0 +1,109 @@
+//! Lenient parsing of streamed tool-call JSON.
+//!
+//! Streaming tool inputs occasionally arrive with trailing garbage after the
+//! object, which is dropped. Truncated input is rejected on purpose rather
+//! than repaired: input cut off mid-value (e.g. when the response hit the
+//! output token limit) is reported to the model as a tool error, since
+//! running a write or edit with partial content would be worse than asking
+//! the model to retry.
+
+use serde_json::Value;
+
+/// Why a tool input could not be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Malformed {
+    /// The input ended before the JSON object was complete
+    Truncated,
+    /// The input is not a JSON object
+    Invalid,
+}
+
+/// Parse a tool input buffer, dropping trailing garbage.
+///
+/// The following strategies are tried in order:
+/// 1. An empty buffer is treated as an empty object.
+/// 2. The buffer is parsed as-is.
+/// 3. The first complete JSON value is taken, ignoring trailing garbage.
+///
+/// # Arguments
+///
+/// * `input` - Raw accumulated JSON text
+///
+/// # Errors
+///
+/// Returns [`Malformed::Truncated`] if the input ends inside the object, and
+/// [`Malformed::Invalid`] if it does not start with a JSON object.
+pub fn parse_tool_input(input: &str) -> Result<Value, Malformed> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return Ok(Value::Object(serde_json::Map::new()));
+    }
+
+    if let Ok(value) = serde_json::from_str::<Value>(trimmed) {
+        return object(value);
+    }
+
+    match serde_json::Deserializer::from_str(trimmed)
+        .into_iter::<Value>()
+        .next()
+    {
+        Some(Ok(value)) => object(value),
+        Some(Err(e)) if e.is_eof() => Err(Malformed::Truncated),
+        _ => Err(Malformed::Invalid),
+    }
+}
+
+/// Keep a parsed value only if it is an object.
+fn object(value: Value) -> Result<Value, Malformed> {
+    if value.is_object() {
+        Ok(value)
+    } else {
+        Err(Malformed::Invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_valid_input() {
+        let value = parse_tool_input(r#"{"path":"a.rs"}"#);
+        assert_eq!(value, Ok(json!({"path": "a.rs"})));
+    }
+
+    #[test]
+    fn test_parse_empty_input() {
+        assert_eq!(parse_tool_input("  "), Ok(json!({})));
+    }
+
+    #[test]
+    fn test_parse_trailing_garbage() {
+        let value = parse_tool_input(r#"{"cmd":"ls"}}  extra"#);
+        assert_eq!(value, Ok(json!({"cmd": "ls"})));
+    }
+
+    #[test]
+    fn test_parse_truncated_input_is_not_completed() {
+        for input in [
+            r#"{"path":"a.rs","content":"fn main() {"#,
+            r#"{"path":"a.rs","offset":"#,
+            r#"{"path":"a.rs","lim"#,
+            r#"{"cmd":"echo \"#,
+            r#"{"path":"a.rs""#,
+        ] {
+            assert_eq!(
+                parse_tool_input(input),
+                Err(Malformed::Truncated),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_unrepairable() {
+        assert_eq!(parse_tool_input("not json at all"), Err(Malformed::Invalid));
+        assert_eq!(parse_tool_input("[1, 2, 3]"), Err(Malformed::Invalid));
+    }
+}
@@ -4,7 +4,7 @@
 //! sessions, event channels, and the main execution loop.
 
 use crate::command::Command;
-use crate::config::{Config, ProviderSettings};
+use crate::config::{Config, ProviderSettings, Resume};
 use crate::events::CoreEvent;
 use crate::input::Reader;
 use crate::session::Session;
@@ -60,17 +60,21 @@ impl App {
     ///
     /// # Errors
     ///
-    /// Returns an error if event channel creation fails.
+    /// Returns an error if event channel creation fails or the session to
+    /// resume cannot be loaded.
     pub fn new(provider_config: ProviderSettings, config: Config) -> Result<Self> {
         let (event_sender, event_receiver) = mpsc::unbounded_channel();
-        let session = Session::new(provider_config, &config.cwd);
+        let session = Session::new(provider_config, &config.cwd).with_config(&config)?;
 
-        Ok(Self {
+        let app = Self {
             session,
             event_sender,
             event_receiver: Some(event_receiver),
             config,
-        })
+        };
+        app.announce_resume();
+        app.warn_unenforced_limits();
+        Ok(app)
     }
 
     /// Internal constructor that uses existing event channels.
@@ -84,22 +88,55 @@ impl App {
     /// # Returns
     ///
     /// Returns a new App instance.
-    #[must_use]
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the session to resume cannot be loaded.
     fn new_internal(
         provider_config: ProviderSettings,
         config: Config,
         event_sender: mpsc::UnboundedSender<CoreEvent>,
-    ) -> Self {
-        let session = Session::new(provider_config, &config.cwd);
+    ) -> Result<Self> {
+        let session = Session::new(provider_config, &config.cwd).with_config(&config)?;
 
-        Self {
+        let app = Self {
             session,
             event_sender,
             event_receiver: None,
             config,
+        };
+        app.announce_resume();
+        app.warn_unenforced_limits();
+        Ok(app)
+    }
+
+    /// Report a resumed session to the renderer.
+    fn announce_resume(&self) {
+        if self.config.resume != Resume::New
+            && let Some(transcript) = self.session.transcript()
+        {
+            let _ = self.event_sender.send(CoreEvent::SessionResumed {
+                id: transcript.id().to_string(),
+                messages: self.session.messages().len(),
+            });
         }
     }
 
+    /// Warn about configured limits that the session cannot enforce.
+    fn warn_unenforced_limits(&self) {
+        if let Some(warning) = self.session.guard().unenforced_cost_limits() {
+            tracing::warn!("{warning}");
+            let _ = self.event_sender.send(CoreEvent::Error(warning));
+        }
+    }
+
+    /// Start the configured MCP servers before the first turn.
+    async fn connect_mcp(&mut self) {
+        self.session
+            .connect_mcp(&self.config.mcp_servers, &self.event_sender)
+            .await;
+    }
+
     /// Unified entry point for the application.
     ///
     /// This method creates the runtime, loads configuration, and starts the main loop.
@@ -139,7 +176,7 @@ impl App {
                 ProviderSettings::from_env().await?
             };
             let (event_sender, event_receiver) = mpsc::unbounded_channel();
-            let mut app = Self::new_internal(provider_config.clone(), config, event_sender);
+            let mut app = Self::new_internal(provider_config.clone(), config, event_sender)?;
 
             let handle = if let Some(msg) = message {
                 tokio::spawn(async move { app.run_single_async(msg).await })
@@ -217,6 +254,7 @@ impl App {
     ///
     /// Returns error if session execution fails.
     pub async fn run_interactive_async(&mut self, reader: impl Reader) -> Result<()> {
+        self.connect_mcp().await;
         self.session
             .run_interactive(reader, self.event_sender.clone())
             .await
@@ -257,6 +295,7 @@ impl App {
     ///
     /// Returns error if message execution fails.
     pub async fn run_single_async(&mut self, message: String) -> Result<()> {
+        self.connect_mcp().await;
         self.session
             .run_single(message, self.event_sender.clone())
             .await
@@ -265,6 +304,9 @@ impl App {
     /// Run interactive mode with input from a channel.
     ///
     /// This method reads user input from the provided channel and processes commands.
+    /// Messages typed while a turn is running are queued on the session's
+    /// steering queue and delivered to the model mid-turn instead of waiting
+    /// for the turn to finish.
     ///
     /// # Arguments
     ///
@@ -281,8 +323,31 @@ impl App {
         &mut self,
         mut input_receiver: mpsc::UnboundedReceiver<String>,
     ) -> Result<()> {
+        self.connect_mcp().await;
+        let steering = self.session.steering();
+        let commands = self.session.commands();
+        let event_sender = self.event_sender.clone();
+        let (command_sender, mut command_receiver) = mpsc::unbounded_channel::<String>();
+
+        // Keep reading input while a turn runs, diverting plain messages to steering
+        let forwarder = tokio::spawn(async move {
+            while let Some(line) = input_receiver.recv().await {
+                let text = line.trim();
+                if matches!(commands.parse(text), Command::Message(_))
+                    && !text.is_empty()
+                    && steering.offer(text)
+                {
+                    let _ = event_sender.send(CoreEvent::SteeringQueued(text.to_string()));
+                    continue;
+                }
+                if command_sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
         loop {
-            let Some(user_input) = input_receiver.recv().await else {
+            let Some(user_input) = command_receiver.recv().await else {
                 break;
             };
 
@@ -291,33 +356,17 @@ impl App {
                 continue;
             }
 
-            let command = Self::parse_input(user_input);
+            let command = self.session.commands().parse(user_input);
             let should_continue = self.handle_command(command).await?;
             if !should_continue {
                 break;
             }
         }
 
+        forwarder.abort();
         Ok(())
     }
 
-    /// Parse user input into a command.
-    ///
-    /// # Arguments
-    ///
-    /// * `input` - Raw user input string
-    ///
-    /// # Returns
-    /// Parsed command
-    #[must_use]
-    fn parse_input(input: &str) -> Command {
-        match input {
-            "/q" | "exit" => Command::Quit,
-            "/c" => Command::Clear,
-            msg => Command::Message(msg.to_string()),
-        }
-    }
-
     /// Handle a user command.
     ///
     /// # Arguments
@@ -328,28 +377,8 @@ impl App {
     ///
     /// Ok(true) to continue the loop, Ok(false) to exit, Err on error
     async fn handle_command(&mut self, command: Command) -> Result<bool> {
-        match command {
-            Command::Quit => Ok(false),
-            Command::Clear => {
-                self.session.clear_history();
-                let _ = self
-                    .event_sender
-                    .send(CoreEvent::Error("Conversation cleared".to_string()));
-                Ok(true)
-            },
-            Command::Message(msg) => {
-                if let Err(e) = self
-                    .session
-                    .run_single(msg, self.event_sender.clone())
-                    .await
-                {
-                    let _ = self
-                        .event_sender
-                        .send(CoreEvent::Error(format!("Error: {e}")));
-                }
-                Ok(true)
-            },
-        }
+        let event_sender = self.event_sender.clone();
+        self.session.handle_command(command, &event_sender).await
     }
 
     /// Get reference to the internal session.
@@ -0,0 +1,782 @@
+//! File checkpoints for undoing agent edits.
+//!
+//! Before a mutating tool call, the previous contents of the files it is
+//! about to change are saved into a per-session checkpoint store. Each user
+//! turn that changes files gets one checkpoint, so the changes of the last
+//! turns can be undone by restoring the snapshots. Snapshots are plain
+//! copies, so untracked and ignored files are covered as well.
+//!
+//! The content after each change is recorded too, so the consolidated diff
+//! of everything the agent changed can be rebuilt later without git.
+//!
+//! Only tools that name the files they change (`write`, `edit`) are
+//! snapshotted. Files changed through `bash` (`sed -i`, `mv`, generators)
+//! are not, so undo and diff cannot cover them; the checkpoints record which
+//! such tools ran, and undo and diff warn about them.
+//!
+//! Layout: `<dir>/<id>/manifest.json` lists the snapshotted files of a
+//! checkpoint, `<dir>/<id>/<n>` holds the previous content of file `n` and
+//! `<dir>/<id>/<n>.after` its content after the last change of the turn.
+
+use crate::{clock, diff};
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Name of the manifest file inside a checkpoint directory.
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Stored description of a checkpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Manifest {
+    /// Checkpoint number, increasing within a session
+    id: usize,
+    /// First line of the user message that started the turn
+    label: String,
+    /// Creation time (Unix seconds)
+    at: u64,
+    /// Files snapshotted in this checkpoint, in order
+    files: Vec<Entry>,
+    /// Tools that ran since the turn started and may have changed files
+    /// without a snapshot
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    untracked: Vec<String>,
+}
+
+/// A snapshotted file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    /// Absolute path of the file
+    path: PathBuf,
+    /// Name of the blob holding the previous content; `None` if the file did not exist
+    blob: Option<String>,
+    /// Name of the blob holding the content after the turn; `None` if not recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    after: Option<String>,
+}
+
+/// Summary of a checkpoint, for listings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Summary {
+    /// Checkpoint number
+    pub id: usize,
+    /// First line of the user message that started the turn
+    pub label: String,
+    /// Creation time (Unix seconds)
+    pub at: u64,
+    /// Snapshotted files
+    pub files: Vec<String>,
+}
+
+/// State of a file to restore.
+#[derive(Debug, Clone)]
+pub struct FileState {
+    /// Absolute path of the file
+    pub path: PathBuf,
+    /// Content to restore; `None` if the file is to be deleted
+    pub content: Option<Vec<u8>>,
+}
+
+/// Files to restore to go back to a checkpoint.
+#[derive(Debug, Clone)]
+pub struct Plan {
+    /// Checkpoint restored to
+    pub checkpoint: usize,
+    /// Files whose current content differs from their state before the checkpoint
+    pub files: Vec<FileState>,
+    /// Tools that ran since the checkpoint and may have changed other files
+    pub untracked: Vec<String>,
+}
+
+/// Snapshot of a file in a checkpoint: the checkpoint id and the file entry.
+type Snapshot<'a> = (usize, &'a Entry);
+
+/// Change of a file over a range of checkpoints.
+#[derive(Debug, Clone)]
+pub struct Change {
+    /// Absolute path of the file
+    pub path: PathBuf,
+    /// Content before the first checkpoint; `None` if the file did not exist
+    pub before: Option<Vec<u8>>,
+    /// Content after the last change; `None` if the file no longer exists
+    pub after: Option<Vec<u8>>,
+}
+
+/// Number of lines changed in a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stat {
+    /// Path of the file
+    pub path: String,
+    /// Added lines
+    pub added: usize,
+    /// Removed lines
+    pub removed: usize,
+}
+
+/// Mutable state of the store.
+#[derive(Debug)]
+struct State {
+    /// Directory holding the checkpoints
+    dir: PathBuf,
+    /// Checkpoints, oldest first
+    checkpoints: Vec<Manifest>,
+    /// Label of the current turn
+    label: String,
+    /// Checkpoint of the current turn, once a file was snapshotted
+    current: Option<usize>,
+    /// Tools of the current turn that ran before its checkpoint was created
+    untracked: Vec<String>,
+}
+
+/// Per-session store of file checkpoints.
+///
+/// Cloning the store gives another handle to the same checkpoints.
+#[derive(Debug, Clone)]
+pub struct Store {
+    /// Shared state
+    state: Arc<Mutex<State>>,
+}
+
+impl Store {
+    /// Open the checkpoint store in a directory, loading existing checkpoints.
+    ///
+    /// The directory is created on the first snapshot.
+    #[must_use]
+    pub fn open(dir: &Path) -> Self {
+        let mut checkpoints: Vec<Manifest> = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|entry| fs::read_to_string(entry.path().join(MANIFEST_FILE)).ok())
+            .filter_map(|text| serde_json::from_str(&text).ok())
+            .collect();
+        checkpoints.sort_by_key(|manifest| manifest.id);
+
+        Self {
+            state: Arc::new(Mutex::new(State {
+                dir: dir.to_path_buf(),
+                checkpoints,
+                label: String::new(),
+                current: None,
+                untracked: Vec::new(),
+            })),
+        }
+    }
+
+    /// Start a new turn; the next snapshot creates a new checkpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - User message that started the turn (its first line is kept)
+    pub fn begin(&self, label: &str) {
+        let mut state = self.lock();
+        state.label = label.lines().next().unwrap_or_default().to_string();
+        state.current = None;
+        state.untracked.clear();
+    }
+
+    /// Save the current content of a file before it is changed.
+    ///
+    /// Only the first snapshot of a file within a turn is kept, so the
+    /// checkpoint holds the content from before the turn.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read, or the
+    /// snapshot cannot be written.
+    pub fn snapshot(&self, path: &Path) -> Result<()> {
+        let mut state = self.lock();
+        let id = if let Some(id) = state.current {
+            id
+        } else {
+            let id = state.checkpoints.last().map_or(1, |last| last.id + 1);
+            let manifest = Manifest {
+                id,
+                label: state.label.clone(),
+                at: clock::now(),
+                files: Vec::new(),
+                untracked: state.untracked.clone(),
+            };
+            state.checkpoints.push(manifest);
+            state.current = Some(id);
+            id
+        };
+
+        let dir = state.dir.join(id.to_string());
+        let Some(manifest) = state.checkpoints.iter_mut().find(|m| m.id == id) else {
+            return Ok(());
+        };
+        if manifest.files.iter().any(|entry| entry.path == path) {
+            return Ok(());
+        }
+
+        let blob = match fs::read(path) {
+            Ok(content) => {
+                let name = manifest.files.len().to_string();
+                fs::create_dir_all(&dir)
+                    .with_context(|| format!("Failed to create {}", dir.display()))?;
+                fs::write(dir.join(&name), content)
+                    .with_context(|| format!("Failed to save snapshot of {}", path.display()))?;
+                Some(name)
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+            },
+        };
+        manifest.files.push(Entry {
+            path: path.to_path_buf(),
+            blob,
+            after: None,
+        });
+        save(&dir, manifest)
+    }
+
+    /// Record the content of a file after a tool changed it.
+    ///
+    /// Does nothing if the file was not snapshotted in the current turn.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the content cannot be saved.
+    pub fn record(&self, path: &Path) -> Result<()> {
+        let mut state = self.lock();
+        let Some(id) = state.current else {
+            return Ok(());
+        };
+        let dir = state.dir.join(id.to_string());
+        let Some(manifest) = state.checkpoints.iter_mut().find(|m| m.id == id) else {
+            return Ok(());
+        };
+        let Some(index) = manifest.files.iter().position(|entry| entry.path == path) else {
+            return Ok(());
+        };
+
+        let after = match fs::read(path) {
+            Ok(content) => {
+                let name = format!("{index}.after");
+                fs::create_dir_all(&dir)
+                    .with_context(|| format!("Failed to create {}", dir.display()))?;
+                fs::write(dir.join(&name), content)
+                    .with_context(|| format!("Failed to save content of {}", path.display()))?;
+                Some(name)
+            },
+            Err(_) => None,
+        };
+        if let Some(entry) = manifest.files.get_mut(index) {
+            entry.after = after;
+        }
+        save(&dir, manifest)
+    }
+
+    /// Note that a tool which may change files without a snapshot ran.
+    ///
+    /// The tool is recorded in the checkpoint of the current turn and in the
+    /// latest checkpoint, so every undo or diff range covering the run
+    /// reports it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a manifest cannot be saved.
+    pub fn record_untracked(&self, tool: &str) -> Result<()> {
+        let mut state = self.lock();
+        if state.current.is_none() && !state.untracked.iter().any(|name| name == tool) {
+            state.untracked.push(tool.to_string());
+        }
+        let dir = state.dir.clone();
+        let current = state.current;
+        let latest = state.checkpoints.last().map(|manifest| manifest.id);
+        for manifest in state
+            .checkpoints
+            .iter_mut()
+            .filter(|m| Some(m.id) == current || Some(m.id) == latest)
+        {
+            if !manifest.untracked.iter().any(|name| name == tool) {
+                manifest.untracked.push(tool.to_string());
+                save(&dir.join(manifest.id.to_string()), manifest)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Tools that ran since a checkpoint and may have changed files without a snapshot.
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - First checkpoint to include, or `None` for all
+    #[must_use]
+    pub fn untracked(&self, since: Option<usize>) -> Vec<String> {
+        untracked(&self.lock().checkpoints, since.unwrap_or(0))
+    }
+
+    /// List the checkpoints, oldest first.
+    #[must_use]
+    pub fn list(&self) -> Vec<Summary> {
+        self.lock()
+            .checkpoints
+            .iter()
+            .map(|manifest| Summary {
+                id: manifest.id,
+                label: manifest.label.clone(),
+                at: manifest.at,
+                files: manifest
+                    .files
+                    .iter()
+                    .map(|entry| entry.path.display().to_string())
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Checkpoint to restore to in order to undo the last `turns` turns that changed files.
+    ///
+    /// # Returns
+    ///
+    /// The checkpoint id, or `None` if there are fewer checkpoints.
+    #[must_use]
+    pub fn undo_target(&self, turns: usize) -> Option<usize> {
+        let state = self.lock();
+        let index = state.checkpoints.len().checked_sub(turns.max(1))?;
+        state.checkpoints.get(index).map(|manifest| manifest.id)
+    }
+
+    /// Work out which files to restore to go back to before a checkpoint.
+    ///
+    /// Each file gets its content from its earliest snapshot in the
+    /// checkpoint or any later one; files already in that state are left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such checkpoint or a snapshot cannot be read.
+    pub fn plan(&self, checkpoint: usize) -> Result<Plan> {
+        let state = self.lock();
+        if !state.checkpoints.iter().any(|m| m.id == checkpoint) {
+            let available = state.checkpoints.len();
+            anyhow::bail!("No checkpoint {checkpoint} ({available} available)");
+        }
+
+        let mut earliest: IndexMap<&Path, (usize, Option<&str>)> = IndexMap::new();
+        for manifest in state.checkpoints.iter().filter(|m| m.id >= checkpoint) {
+            for entry in &manifest.files {
+                earliest
+                    .entry(entry.path.as_path())
+                    .or_insert((manifest.id, entry.blob.as_deref()));
+            }
+        }
+
+        let mut files = Vec::new();
+        for (path, (id, blob)) in earliest {
+            let content = blob
+                .map(|name| read_blob(&state.dir, id, name))
+                .transpose()?;
+            if fs::read(path).ok() != content {
+                files.push(FileState {
+                    path: path.to_path_buf(),
+                    content,
+                });
+            }
+        }
+
+        Ok(Plan {
+            checkpoint,
+            files,
+            untracked: untracked(&state.checkpoints, checkpoint),
+        })
+    }
+
+    /// Changes made to files since a checkpoint, one per file.
+    ///
+    /// Each file goes from its content before its first snapshot to its
+    /// content after its last recorded change (or its current content if
+    /// none was recorded). Files that ended up unchanged are left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - First checkpoint to include, or `None` for all
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such checkpoint or a snapshot cannot be read.
+    pub fn changes(&self, since: Option<usize>) -> Result<Vec<Change>> {
+        let state = self.lock();
+        if let Some(since) = since
+            && !state.checkpoints.iter().any(|m| m.id == since)
+        {
+            let available = state.checkpoints.len();
+            anyhow::bail!("No checkpoint {since} ({available} available)");
+        }
+
+        // First and last snapshot of each file
+        let mut files: IndexMap<&Path, (Snapshot<'_>, Snapshot<'_>)> = IndexMap::new();
+        let first = since.unwrap_or(0);
+        for manifest in state.checkpoints.iter().filter(|m| m.id >= first) {
+            for entry in &manifest.files {
+                let snapshot = (manifest.id, entry);
+                files
+                    .entry(entry.path.as_path())
+                    .and_modify(|file| file.1 = snapshot)
+                    .or_insert((snapshot, snapshot));
+            }
+        }
+
+        let mut changes = Vec::new();
+        for (path, ((first, first_entry), (last, last_entry))) in files {
+            let before = first_entry
+                .blob
+                .as_deref()
+                .map(|name| read_blob(&state.dir, first, name))
+                .transpose()?;
+            let after = match &last_entry.after {
+                Some(name) => Some(read_blob(&state.dir, last, name)?),
+                None => fs::read(path).ok(),
+            };
+            if before != after {
+                changes.push(Change {
+                    path: path.to_path_buf(),
+                    before,
+                    after,
+                });
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Restore the files of a plan and drop the undone checkpoints.
+    ///
+    /// Files that did not exist before the checkpoint are deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file cannot be written or deleted.
+    pub fn restore(&self, plan: &Plan) -> Result<()> {
+        for file in &plan.files {
+            match &file.content {
+                Some(content) => {
+                    if let Some(parent) = file.path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(&file.path, content)
+                        .with_context(|| format!("Failed to restore {}", file.path.display()))?;
+                },
+                None => match fs::remove_file(&file.path) {
+                    Err(e) if e.kind() != ErrorKind::NotFound => {
+                        return Err(e)
+                            .with_context(|| format!("Failed to delete {}", file.path.display()));
+                    },
+                    _ => {},
+                },
+            }
+        }
+
+        let mut state = self.lock();
+        let dir = state.dir.clone();
+        state.checkpoints.retain(|manifest| {
+            let keep = manifest.id < plan.checkpoint;
+            if !keep {
+                let _ = fs::remove_dir_all(dir.join(manifest.id.to_string()));
+            }
+            keep
+        });
+        state.current = None;
+        Ok(())
+    }
+
+    /// Lock the shared state.
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Plan {
+    /// Unified diff from the current files to their restored state.
+    ///
+    /// Paths are shown relative to the working directory.
+    #[must_use]
+    pub fn preview(&self) -> String {
+        let base = std::env::current_dir().unwrap_or_default();
+        self.files
+            .iter()
+            .map(|file| {
+                let current = fs::read(&file.path).ok();
+                file_diff(
+                    &file.path,
+                    &base,
+                    current.as_deref(),
+                    file.content.as_deref(),
+                )
+            })
+            .collect()
+    }
+
+    /// Paths of the files to restore.
+    #[must_use]
+    pub fn paths(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .map(|file| file.path.display().to_string())
+            .collect()
+    }
+}
+
+impl Change {
+    /// Unified diff of the change, with paths relative to `base`.
+    #[must_use]
+    pub fn diff(&self, base: &Path) -> String {
+        file_diff(
+            &self.path,
+            base,
+            self.before.as_deref(),
+            self.after.as_deref(),
+        )
+    }
+
+    /// Number of added and removed lines, with the path relative to `base`.
+    ///
+    /// Binary files count no lines.
+    #[must_use]
+    pub fn stat(&self, base: &Path) -> Stat {
+        let (added, removed) = match (text(self.before.as_deref()), text(self.after.as_deref())) {
+            (Some(before), Some(after)) => diff::stats(&before, &after),
+            _ => (0, 0),
+        };
+        Stat {
+            path: relative(&self.path, base),
+            added,
+            removed,
+        }
+    }
+}
+
+/// Tools recorded in the checkpoints from `first` on, without duplicates.
+fn untracked(checkpoints: &[Manifest], first: usize) -> Vec<String> {
+    let mut tools: Vec<String> = Vec::new();
+    for tool in checkpoints
+        .iter()
+        .filter(|m| m.id >= first)
+        .flat_map(|m| &m.untracked)
+    {
+        if !tools.contains(tool) {
+            tools.push(tool.clone());
+        }
+    }
+    tools
+}
+
+/// Build a patch from changes, with paths relative to `base`.
+#[must_use]
+pub fn patch(changes: &[Change], base: &Path) -> String {
+    changes.iter().map(|change| change.diff(base)).collect()
+}
+
+/// Unified diff of one file, labelled `a/<path>` and `b/<path>` like git.
+fn file_diff(path: &Path, base: &Path, old: Option<&[u8]>, new: Option<&[u8]>) -> String {
+    let name = relative(path, base);
+    let (Some(old_text), Some(new_text)) = (text(old), text(new)) else {
+        return format!("Binary file {name} differs\n");
+    };
+    let old_label = old.map_or_else(|| "/dev/null".to_string(), |_| format!("a/{name}"));
+    let new_label = new.map_or_else(|| "/dev/null".to_string(), |_| format!("b/{name}"));
+    diff::unified(&old_text, &new_text, &old_label, &new_label)
+}
+
+/// Path relative to `base`, or the full path if it is outside `base`.
+fn relative(path: &Path, base: &Path) -> String {
+    path.strip_prefix(base)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+/// File content as text; a missing file is empty and a binary file has no text.
+fn text(content: Option<&[u8]>) -> Option<String> {
+    content.map_or(Some(String::new()), |bytes| {
+        String::from_utf8(bytes.to_vec()).ok()
+    })
+}
+
+/// Read a blob of a checkpoint.
+fn read_blob(dir: &Path, checkpoint: usize, name: &str) -> Result<Vec<u8>> {
+    let path = dir.join(checkpoint.to_string()).join(name);
+    fs::read(&path).with_context(|| format!("Failed to read snapshot {}", path.display()))
+}
+
+/// Write the manifest of a checkpoint.
+fn save(dir: &Path, manifest: &Manifest) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    fs::write(
+        dir.join(MANIFEST_FILE),
+        serde_json::to_string_pretty(manifest)?,
+    )
+    .context("Failed to write checkpoint manifest")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_undo_last_turn() {
+        let work = TempDir::new("checkpoint-undo-work");
+        let file = work.join("main.rs");
+        let created = work.join("new.rs");
+        fs::write(&file, "one\n").unwrap();
+
+        let store_dir = TempDir::new("checkpoint-undo-store");
+        let store = Store::open(&store_dir);
+        store.begin("first change\nmore text");
+        store.snapshot(&file).unwrap();
+        fs::write(&file, "two\n").unwrap();
+
+        store.begin("second change");
+        store.snapshot(&file).unwrap();
+        fs::write(&file, "three\n").unwrap();
+        store.snapshot(&file).unwrap();
+        fs::write(&file, "four\n").unwrap();
+        store.snapshot(&created).unwrap();
+        fs::write(&created, "new\n").unwrap();
+
+        let list = store.list();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.first().map(|s| s.label.as_str()), Some("first change"));
+        assert_eq!(list.get(1).map(|s| s.files.len()), Some(2));
+
+        let plan = store.plan(store.undo_target(1).unwrap()).unwrap();
+        assert_eq!(plan.checkpoint, 2);
+        let preview = plan.preview();
+        assert!(preview.contains("-four\n+two\n"));
+        assert!(preview.contains("+++ /dev/null"));
+
+        store.restore(&plan).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "two\n");
+        assert!(!created.exists());
+        assert_eq!(store.list().len(), 1);
+    }
+
+    #[test]
+    fn test_restore_to_checkpoint_and_reopen() {
+        let work = TempDir::new("checkpoint-restore-work");
+        let file = work.join("lib.rs");
+        fs::write(&file, "a\n").unwrap();
+        let store_dir = TempDir::new("checkpoint-restore-store");
+
+        let store = Store::open(&store_dir);
+        for (turn, content) in ["b\n", "c\n", "d\n"].iter().enumerate() {
+            store.begin(&format!("turn {turn}"));
+            store.snapshot(&file).unwrap();
+            fs::write(&file, content).unwrap();
+        }
+
+        let reopened = Store::open(&store_dir);
+        assert_eq!(reopened.list().len(), 3);
+        assert_eq!(reopened.undo_target(3), Some(1));
+        assert_eq!(reopened.undo_target(4), None);
+
+        let plan = reopened.plan(1).unwrap();
+        assert_eq!(plan.paths(), vec![file.display().to_string()]);
+        reopened.restore(&plan).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "a\n");
+        assert!(reopened.list().is_empty());
+
+        let error = reopened.plan(1).unwrap_err();
+        assert!(error.to_string().contains("No checkpoint 1"));
+    }
+
+    #[test]
+    fn test_untracked_tools_are_reported() {
+        let work = TempDir::new("checkpoint-untracked-work");
+        let file = work.join("lib.rs");
+        fs::write(&file, "a\n").unwrap();
+        let store_dir = TempDir::new("checkpoint-untracked-store");
+        let store = Store::open(&store_dir);
+
+        // Ran before any checkpoint: not in any range
+        store.begin("turn 1");
+        store.record_untracked("bash").unwrap();
+        store.snapshot(&file).unwrap();
+        store.begin("turn 2");
+        store.snapshot(&file).unwrap();
+        assert_eq!(store.untracked(Some(2)), Vec::<String>::new());
+        assert_eq!(store.untracked(None), vec!["bash".to_string()]);
+
+        // Ran in a turn without a checkpoint: in every range
+        store.begin("turn 3");
+        store.record_untracked("bash").unwrap();
+        assert_eq!(store.untracked(Some(2)), vec!["bash".to_string()]);
+
+        // Ran before the snapshot of its turn: in the range of that turn
+        store.begin("turn 4");
+        store.record_untracked("generator").unwrap();
+        store.snapshot(&file).unwrap();
+        let reopened = Store::open(&store_dir);
+        assert_eq!(reopened.untracked(Some(3)), vec!["generator".to_string()]);
+        assert_eq!(
+            reopened.plan(2).unwrap().untracked,
+            vec!["bash".to_string(), "generator".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_unchanged_files_are_not_restored() {
+        let work = TempDir::new("checkpoint-unchanged-work");
+        let file = work.join("same.rs");
+        fs::write(&file, "same\n").unwrap();
+
+        let store_dir = TempDir::new("checkpoint-unchanged-store");
+        let store = Store::open(&store_dir);
+        store.begin("failed edit");
+        store.snapshot(&file).unwrap();
+
+        let plan = store.plan(1).unwrap();
+        assert!(plan.files.is_empty());
+        assert_eq!(plan.preview(), "");
+    }
+
+    #[test]
+    fn test_changes_since_checkpoint() {
+        let work = TempDir::new("checkpoint-changes-work");
+        let file = work.join("src/lib.rs");
+        let created = work.join("notes.md");
+        fs::create_dir_all(work.join("src")).unwrap();
+        fs::write(&file, "one\n").unwrap();
+        let store_dir = TempDir::new("checkpoint-changes-store");
+        let store = Store::open(&store_dir);
+
+        store.begin("first");
+        store.snapshot(&file).unwrap();
+        fs::write(&file, "two\n").unwrap();
+        store.record(&file).unwrap();
+
+        store.begin("second");
+        store.snapshot(&file).unwrap();
+        fs::write(&file, "three\n").unwrap();
+        store.record(&file).unwrap();
+        store.snapshot(&created).unwrap();
+        fs::write(&created, "notes\n").unwrap();
+        store.record(&created).unwrap();
+
+        // Later changes outside the tools do not show up in the recorded diff
+        fs::write(&file, "edited by hand\n").unwrap();
+
+        let changes = store.changes(None).unwrap();
+        assert_eq!(changes.len(), 2);
+        let patch = patch(&changes, &work);
+        assert!(patch.contains("--- a/src/lib.rs\n+++ b/src/lib.rs\n"));
+        assert!(patch.contains("-one\n+three\n"));
+        assert!(patch.contains("--- /dev/null\n+++ b/notes.md\n"));
+        let stat = changes.first().map(|change| change.stat(&work)).unwrap();
+        assert_eq!(
+            (stat.path.as_str(), stat.added, stat.removed),
+            ("src/lib.rs", 1, 1)
+        );
+
+        let since = store.changes(Some(2)).unwrap();
+        assert!(super::patch(&since, &work).contains("-two\n+three\n"));
+        let error = store.changes(Some(5)).unwrap_err();
+        assert!(error.to_string().contains("No checkpoint 5"));
+    }
+}
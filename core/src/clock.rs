@@ -0,0 +1,95 @@
+//! Wall-clock helpers for timestamps and identifiers.
+//!
+//! Timestamps are stored as Unix seconds and formatted in UTC.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Current time as Unix seconds.
+#[must_use]
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Convert Unix seconds to a UTC date and time.
+///
+/// # Returns
+///
+/// `(year, month, day, hour, minute, second)`
+#[must_use]
+pub const fn civil(secs: u64) -> (u64, u64, u64, u64, u64, u64) {
+    let days = secs / 86_400;
+    let rem = secs % 86_400;
+
+    // Days-to-civil conversion (Howard Hinnant's algorithm), shifted to 0000-03-01
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+/// Format Unix seconds as `YYYY-MM-DD`.
+#[must_use]
+pub fn format_date(secs: u64) -> String {
+    let (year, month, day, ..) = civil(secs);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Format Unix seconds as `YYYY-MM-DD HH:MM`.
+#[must_use]
+pub fn format_datetime(secs: u64) -> String {
+    let (year, month, day, hour, minute, _) = civil(secs);
+    format!("{year:04}-{month:02}-{day:02} {hour:02}:{minute:02}")
+}
+
+/// Generate a unique, chronologically sortable identifier.
+///
+/// Format: `YYYYMMDD-HHMMSS-xxxx`, where `xxxx` is random hex.
+#[must_use]
+pub fn new_id() -> String {
+    use std::hash::{BuildHasher, RandomState};
+
+    let secs = now();
+    let (year, month, day, hour, minute, second) = civil(secs);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    let random = RandomState::new().hash_one((nanos, std::process::id())) & 0xffff;
+
+    format!("{year:04}{month:02}{day:02}-{hour:02}{minute:02}{second:02}-{random:04x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_civil() {
+        assert_eq!(civil(0), (1970, 1, 1, 0, 0, 0));
+        assert_eq!(civil(951_782_400), (2000, 2, 29, 0, 0, 0));
+        assert_eq!(civil(1_792_334_096), (2026, 10, 18, 14, 34, 56));
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format_date(1_792_334_096), "2026-10-18");
+        assert_eq!(format_datetime(1_792_334_096), "2026-10-18 14:34");
+    }
+
+    #[test]
+    fn test_new_id() {
+        let id = new_id();
+        assert_eq!(id.len(), 20);
+        assert_ne!(id, new_id());
+    }
+}
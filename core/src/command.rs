@@ -1,18 +1,24 @@
 //! User command types for interactive mode.
 //!
 //! Defines commands that can be issued by the user during the
-//! interactive REPL loop.
+//! interactive REPL loop. Slash commands are resolved by the
+//! [`CommandRegistry`](crate::commands::CommandRegistry).
 
 use std::fmt;
 
 /// User command that can be executed during the interactive session.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Command {
-    /// Quit the interactive session
-    Quit,
+    /// Slash command known to the registry
+    Slash {
+        /// Name of the command, with aliases resolved
+        name: String,
+        /// Unparsed arguments after the command name
+        args: String,
+    },
 
-    /// Clear the conversation history
-    Clear,
+    /// Input starting with `/` that names no known command
+    Unknown(String),
 
     /// Regular message to send to the AI
     Message(String),
@@ -21,8 +27,9 @@ pub enum Command {
 impl fmt::Display for Command {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Quit => write!(f, "quit"),
-            Self::Clear => write!(f, "clear"),
+            Self::Slash { name, args } if args.is_empty() => write!(f, "/{name}"),
+            Self::Slash { name, args } => write!(f, "/{name} {args}"),
+            Self::Unknown(name) => write!(f, "unknown: /{name}"),
             Self::Message(msg) => write!(f, "message: {msg}"),
         }
     }
@@ -34,8 +41,26 @@ mod tests {
 
     #[test]
     fn test_command_display() {
-        assert_eq!(Command::Quit.to_string(), "quit");
-        assert_eq!(Command::Clear.to_string(), "clear");
+        assert_eq!(
+            Command::Slash {
+                name: "quit".to_string(),
+                args: String::new()
+            }
+            .to_string(),
+            "/quit"
+        );
+        assert_eq!(
+            Command::Slash {
+                name: "compact".to_string(),
+                args: "the parser".to_string()
+            }
+            .to_string(),
+            "/compact the parser"
+        );
+        assert_eq!(
+            Command::Unknown("xyz".to_string()).to_string(),
+            "unknown: /xyz"
+        );
         assert_eq!(
             Command::Message("test".to_string()).to_string(),
             "message: test"
@@ -44,12 +69,13 @@ mod tests {
 
     #[test]
     fn test_command_equality() {
-        assert_eq!(Command::Quit, Command::Quit);
-        assert_eq!(Command::Clear, Command::Clear);
         assert_eq!(
             Command::Message("test".to_string()),
             Command::Message("test".to_string())
         );
-        assert_ne!(Command::Quit, Command::Clear);
+        assert_ne!(
+            Command::Unknown("q".to_string()),
+            Command::Message("/q".to_string())
+        );
     }
 }
@@ -0,0 +1,403 @@
+//! Slash commands for interactive mode.
+//!
+//! This module defines the command abstraction layer including:
+//! - `SlashCommand` trait for uniform command handling
+//! - `CommandRegistry` resolving names and aliases, and rejecting unknown commands
+//! - The built-in commands, grouped by what they act on
+
+use crate::command::Command;
+use crate::events::CoreEvent;
+use crate::session::Session;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+pub mod conversation;
+pub mod files;
+pub mod general;
+pub mod template;
+
+pub use conversation::{Clear, Compact, Fork, Rewind, Save};
+pub use files::{Checkpoints, Diff, Restore, Undo};
+pub use general::{Cost, Help, Memory, Model, Persona, Prompt, Quit, Tools};
+pub use template::Template;
+
+/// Slash command trait defining the interface for all commands.
+///
+/// All commands must implement this trait to be registered and run
+/// through the `CommandRegistry`.
+#[async_trait]
+pub trait SlashCommand: Send + Sync {
+    /// Returns the name of the command, without the leading `/`.
+    fn name(&self) -> &str;
+
+    /// Returns other names the command can be invoked by.
+    fn aliases(&self) -> &[&str] {
+        &[]
+    }
+
+    /// Returns the synopsis of the arguments, e.g. `[turns]`, empty if there are none.
+    fn args(&self) -> &str;
+
+    /// Returns a one-line description of what the command does.
+    fn help(&self) -> &str;
+
+    /// Parses the arguments and runs the command.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Arguments after the command name, trimmed
+    /// * `session` - The session the command acts on
+    /// * `event_sender` - Sender for core events
+    ///
+    /// # Returns
+    ///
+    /// Ok(true) to continue the session, Ok(false) to exit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the arguments are invalid or the command fails;
+    /// it is reported to the user and the session continues.
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool>;
+
+    /// Returns how to invoke the command, e.g. `/undo [turns]`.
+    fn usage(&self) -> String {
+        if self.args().is_empty() {
+            format!("/{}", self.name())
+        } else {
+            format!("/{} {}", self.name(), self.args())
+        }
+    }
+}
+
+/// Description of a command, as listed by `/help`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Info {
+    /// How to invoke the command, e.g. `/undo [turns]`
+    pub usage: String,
+    /// Other names of the command, with the leading `/`
+    pub aliases: Vec<String>,
+    /// What the command does
+    pub help: String,
+}
+
+/// Command registry for resolving and listing slash commands.
+pub struct CommandRegistry {
+    /// Registered commands indexed by name
+    commands: IndexMap<String, Arc<dyn SlashCommand>>,
+    /// Command names indexed by alias
+    aliases: IndexMap<String, String>,
+}
+
+impl CommandRegistry {
+    /// Create a new command registry with all built-in commands registered.
+    #[must_use]
+    pub fn new() -> Self {
+        let mut registry = Self {
+            commands: IndexMap::new(),
+            aliases: IndexMap::new(),
+        };
+        registry.register_all();
+        registry
+    }
+
+    /// Register all built-in commands.
+    fn register_all(&mut self) {
+        self.register(Arc::new(Help));
+        self.register(Arc::new(Model));
+        self.register(Arc::new(Cost));
+        self.register(Arc::new(Tools));
+        self.register(Arc::new(Prompt));
+        self.register(Arc::new(Persona));
+        self.register(Arc::new(Memory));
+        self.register(Arc::new(Clear));
+        self.register(Arc::new(Compact));
+        self.register(Arc::new(Rewind));
+        self.register(Arc::new(Fork));
+        self.register(Arc::new(Save));
+        self.register(Arc::new(Checkpoints));
+        self.register(Arc::new(Undo));
+        self.register(Arc::new(Restore));
+        self.register(Arc::new(Diff));
+        self.register(Arc::new(Quit));
+    }
+
+    /// Register user-defined commands from Markdown templates.
+    ///
+    /// Templates cannot replace built-in commands; those are skipped with a warning.
+    ///
+    /// # Arguments
+    ///
+    /// * `dirs` - Commands directories, lowest precedence first
+    pub fn register_templates(&mut self, dirs: &[std::path::PathBuf]) {
+        for template in template::load(dirs) {
+            if self.get(&template.name).is_some() {
+                tracing::warn!(
+                    command = %template.name,
+                    path = %template.path.display(),
+                    "Command template shadows a built-in command, skipping"
+                );
+                continue;
+            }
+            self.register(Arc::new(template));
+        }
+    }
+
+    /// Register skills as commands running them.
+    ///
+    /// Skills cannot replace built-in commands or command templates; those
+    /// are skipped with a warning.
+    ///
+    /// # Arguments
+    ///
+    /// * `skills` - Skills to register
+    pub fn register_skills(&mut self, skills: &[crate::skills::Skill]) {
+        for skill in skills {
+            if self.get(&skill.name).is_some() {
+                tracing::warn!(
+                    command = %skill.name,
+                    path = %skill.dir.display(),
+                    "Skill shadows another command, skipping"
+                );
+                continue;
+            }
+            self.register(Arc::new(skill.clone()));
+        }
+    }
+
+    /// Register a command, replacing any command or alias of the same name.
+    pub fn register(&mut self, command: Arc<dyn SlashCommand>) {
+        let name = command.name().to_string();
+        self.aliases.retain(|alias, _| *alias != name);
+        for alias in command.aliases() {
+            self.aliases.insert((*alias).to_string(), name.clone());
+        }
+        self.commands.insert(name, command);
+    }
+
+    /// Get a command by name or alias.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<Arc<dyn SlashCommand>> {
+        let name = self.aliases.get(name).map_or(name, String::as_str);
+        self.commands.get(name).cloned()
+    }
+
+    /// Parse user input into a command.
+    ///
+    /// Input starting with `/` is a slash command, unless its first word
+    /// looks like a path (e.g. `/etc/hosts`). `exit` quits like `/quit`.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Raw user input string
+    ///
+    /// # Returns
+    ///
+    /// Parsed command
+    #[must_use]
+    pub fn parse(&self, input: &str) -> Command {
+        let input = input.trim();
+        let slash = if input == "exit" { "/exit" } else { input };
+        let Some(invocation) = slash.strip_prefix('/') else {
+            return Command::Message(input.to_string());
+        };
+        let (name, args) = invocation
+            .split_once(char::is_whitespace)
+            .unwrap_or((invocation, ""));
+        if name.contains('/') {
+            return Command::Message(input.to_string());
+        }
+
+        match self.get(name) {
+            Some(command) => Command::Slash {
+                name: command.name().to_string(),
+                args: args.trim().to_string(),
+            },
+            None => Command::Unknown(name.to_string()),
+        }
+    }
+
+    /// Describe the registered commands, in registration order.
+    #[must_use]
+    pub fn list(&self) -> Vec<Info> {
+        self.commands
+            .values()
+            .map(|command| Info {
+                usage: command.usage(),
+                aliases: command
+                    .aliases()
+                    .iter()
+                    .map(|alias| format!("/{alias}"))
+                    .collect(),
+                help: command.help().to_string(),
+            })
+            .collect()
+    }
+
+    /// Explain that a command is unknown, suggesting a close match.
+    #[must_use]
+    pub fn unknown(&self, name: &str) -> String {
+        let closest = self
+            .commands
+            .keys()
+            .chain(self.aliases.keys())
+            .map(|candidate| (distance(name, candidate), candidate))
+            .filter(|(distance, _)| *distance <= 2)
+            .min_by_key(|(distance, _)| *distance);
+        match closest {
+            Some((_, candidate)) => format!(
+                "Unknown command /{name}. Did you mean /{candidate}? Type /help for the list of commands"
+            ),
+            None => format!("Unknown command /{name}. Type /help for the list of commands"),
+        }
+    }
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Edit distance between two command names.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = previous
+                .get(j)
+                .map_or(usize::MAX, |d| d + usize::from(ca != *cb));
+            let delete = previous.get(j + 1).map_or(usize::MAX, |d| d + 1);
+            let insert = current.get(j).map_or(usize::MAX, |d| d + 1);
+            current.push(substitute.min(delete).min(insert));
+        }
+        previous = current;
+    }
+    previous.last().copied().unwrap_or_default()
+}
+
+/// Reject arguments for a command that takes none.
+///
+/// # Errors
+///
+/// Returns the usage of the command if `args` is not empty.
+fn no_args(command: &dyn SlashCommand, args: &str) -> Result<()> {
+    if args.is_empty() {
+        Ok(())
+    } else {
+        anyhow::bail!("Usage: {}", command.usage())
+    }
+}
+
+/// Parse an optional number argument.
+///
+/// # Errors
+///
+/// Returns the usage of the command if `args` is neither empty nor a number.
+fn number(command: &dyn SlashCommand, args: &str) -> Result<Option<usize>> {
+    if args.is_empty() {
+        return Ok(None);
+    }
+    args.parse()
+        .map(Some)
+        .ok()
+        .with_context(|| format!("Usage: {}", command.usage()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build the command a registry is expected to parse.
+    fn slash(name: &str, args: &str) -> Command {
+        Command::Slash {
+            name: name.to_string(),
+            args: args.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_resolves_names_and_aliases() {
+        let registry = CommandRegistry::new();
+        assert_eq!(registry.parse("/q"), slash("quit", ""));
+        assert_eq!(registry.parse("exit"), slash("quit", ""));
+        assert_eq!(registry.parse("/c"), slash("clear", ""));
+        assert_eq!(
+            registry.parse("/compact  the parser "),
+            slash("compact", "the parser")
+        );
+        assert_eq!(registry.parse("/export a.html"), slash("save", "a.html"));
+        assert_eq!(
+            registry.parse("/diff 2 out.patch"),
+            slash("diff", "2 out.patch")
+        );
+        assert_eq!(
+            registry.parse("hello"),
+            Command::Message("hello".to_string())
+        );
+        assert_eq!(
+            registry.parse("/etc/hosts is empty"),
+            Command::Message("/etc/hosts is empty".to_string())
+        );
+        assert_eq!(registry.parse("/xyz"), Command::Unknown("xyz".to_string()));
+    }
+
+    #[test]
+    fn test_unknown_suggests_close_command() {
+        let registry = CommandRegistry::new();
+        assert!(
+            registry
+                .unknown("cmopact")
+                .contains("Did you mean /compact?")
+        );
+        assert!(registry.unknown("hepl").contains("Did you mean /help?"));
+        assert!(!registry.unknown("xyzzy").contains("Did you mean"));
+    }
+
+    #[test]
+    fn test_list_and_argument_parsing() {
+        let registry = CommandRegistry::new();
+        let list = registry.list();
+        assert!(list.iter().any(|info| info.usage == "/undo [turns]"));
+        assert!(
+            list.iter()
+                .any(|info| info.usage == "/quit" && info.aliases.contains(&"/q".to_string()))
+        );
+
+        assert_eq!(number(&Undo, "").unwrap(), None);
+        assert_eq!(number(&Undo, "2").unwrap(), Some(2));
+        let error = number(&Undo, "all").unwrap_err();
+        assert_eq!(error.to_string(), "Usage: /undo [turns]");
+        no_args(&Fork, "now").unwrap_err();
+    }
+
+    #[test]
+    fn test_register_templates_keeps_builtins() {
+        let dir = crate::testing::TempDir::new("commands");
+        std::fs::write(dir.join("help.md"), "Not the help").unwrap();
+        std::fs::write(
+            dir.join("review.md"),
+            "---\ndescription: Review\n---\nReview it",
+        )
+        .unwrap();
+
+        let mut registry = CommandRegistry::new();
+        registry.register_templates(&[dir.to_path_buf()]);
+        assert_eq!(registry.parse("/review src"), slash("review", "src"));
+        assert_eq!(registry.get("review").unwrap().help(), "Review");
+        assert_eq!(
+            registry.get("help").unwrap().help(),
+            "List the available commands"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! Commands acting on the conversation: clear, compact, rewind, fork and save.
+
+use super::{SlashCommand, no_args, number};
+use crate::events::CoreEvent;
+use crate::session::Session;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::path::Path;
+use tokio::sync::mpsc;
+
+/// Clear the conversation history.
+pub struct Clear;
+
+#[async_trait]
+impl SlashCommand for Clear {
+    fn name(&self) -> &'static str {
+        "clear"
+    }
+
+    fn aliases(&self) -> &[&str] {
+        &["c"]
+    }
+
+    fn args(&self) -> &'static str {
+        ""
+    }
+
+    fn help(&self) -> &'static str {
+        "Clear the conversation history"
+    }
+
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        no_args(self, args)?;
+        session.clear_history();
+        let _ = event_sender.send(CoreEvent::Cleared);
+        Ok(true)
+    }
+}
+
+/// Summarise older turns to free up context.
+pub struct Compact;
+
+#[async_trait]
+impl SlashCommand for Compact {
+    fn name(&self) -> &'static str {
+        "compact"
+    }
+
+    fn args(&self) -> &'static str {
+        "[focus]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Summarise older turns, optionally focusing on a topic"
+    }
+
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        let focus = (!args.is_empty()).then_some(args);
+        if !session.compact(focus, false, event_sender).await? {
+            let _ = event_sender.send(CoreEvent::Error("Nothing to compact yet".to_string()));
+        }
+        Ok(true)
+    }
+}
+
+/// List the user turns, or rewind to before one of them.
+pub struct Rewind;
+
+#[async_trait]
+impl SlashCommand for Rewind {
+    fn name(&self) -> &'static str {
+        "rewind"
+    }
+
+    fn args(&self) -> &'static str {
+        "[message]"
+    }
+
+    fn help(&self) -> &'static str {
+        "List your messages, or go back to before message n"
+    }
+
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        let event = match number(self, args)? {
+            Some(turn) => CoreEvent::Rewound {
+                turn,
+                text: session.rewind(turn)?,
+            },
+            None => CoreEvent::Turns(session.turns()),
+        };
+        let _ = event_sender.send(event);
+        Ok(true)
+    }
+}
+
+/// Continue the conversation in a new branch.
+pub struct Fork;
+
+#[async_trait]
+impl SlashCommand for Fork {
+    fn name(&self) -> &'static str {
+        "fork"
+    }
+
+    fn args(&self) -> &'static str {
+        ""
+    }
+
+    fn help(&self) -> &'static str {
+        "Continue in a new branch, keeping this session as it is"
+    }
+
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        no_args(self, args)?;
+        let parent = session.transcript().map(|t| t.id().to_string());
+        let id = session.fork()?;
+        let _ = event_sender.send(CoreEvent::Forked {
+            id,
+            parent: parent.unwrap_or_default(),
+        });
+        Ok(true)
+    }
+}
+
+/// Save the conversation to a Markdown or HTML file.
+pub struct Save;
+
+#[async_trait]
+impl SlashCommand for Save {
+    fn name(&self) -> &'static str {
+        "save"
+    }
+
+    fn aliases(&self) -> &[&str] {
+        &["export"]
+    }
+
+    fn args(&self) -> &'static str {
+        "[file.md|file.html]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Save the conversation, with secrets redacted"
+    }
+
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        let path = if args.is_empty() {
+            let id = session.transcript().map_or("session", |t| t.id());
+            format!("neco-{id}.md")
+        } else {
+            args.to_string()
+        };
+        session.export(Path::new(&path)).await?;
+        let _ = event_sender.send(CoreEvent::Exported { path });
+        Ok(true)
+    }
+}
@@ -0,0 +1,134 @@
+//! Commands acting on the files changed by tools: checkpoints, undo, restore and diff.
+
+use super::{SlashCommand, no_args, number};
+use crate::events::CoreEvent;
+use crate::session::Session;
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio::sync::mpsc;
+
+/// List the file checkpoints.
+pub struct Checkpoints;
+
+#[async_trait]
+impl SlashCommand for Checkpoints {
+    fn name(&self) -> &'static str {
+        "checkpoints"
+    }
+
+    fn args(&self) -> &'static str {
+        ""
+    }
+
+    fn help(&self) -> &'static str {
+        "List the file checkpoints taken before tools changed files"
+    }
+
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        no_args(self, args)?;
+        let _ = event_sender.send(CoreEvent::Checkpoints(session.checkpoints()));
+        Ok(true)
+    }
+}
+
+/// Undo the file changes of the last turns that changed files.
+pub struct Undo;
+
+#[async_trait]
+impl SlashCommand for Undo {
+    fn name(&self) -> &'static str {
+        "undo"
+    }
+
+    fn args(&self) -> &'static str {
+        "[turns]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Undo the file changes of the last turns (default 1)"
+    }
+
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        let turns = number(self, args)?.unwrap_or(1);
+        let plan = session.plan_undo(turns)?;
+        session.preview_restore(plan, event_sender);
+        Ok(true)
+    }
+}
+
+/// Restore files to their state before a checkpoint.
+pub struct Restore;
+
+#[async_trait]
+impl SlashCommand for Restore {
+    fn name(&self) -> &'static str {
+        "restore"
+    }
+
+    fn args(&self) -> &'static str {
+        "<checkpoint>"
+    }
+
+    fn help(&self) -> &'static str {
+        "Restore files to their state before a checkpoint"
+    }
+
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        let Some(checkpoint) = number(self, args)? else {
+            anyhow::bail!("Usage: {}", self.usage());
+        };
+        let plan = session.plan_restore(checkpoint)?;
+        session.preview_restore(plan, event_sender);
+        Ok(true)
+    }
+}
+
+/// Show the changes the tools made to files.
+pub struct Diff;
+
+#[async_trait]
+impl SlashCommand for Diff {
+    fn name(&self) -> &'static str {
+        "diff"
+    }
+
+    fn args(&self) -> &'static str {
+        "[checkpoint] [file.patch]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Show the file changes of the session, optionally saved as a patch"
+    }
+
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        let mut words = args.split_whitespace().peekable();
+        let since = words.next_if(|word| word.parse::<usize>().is_ok());
+        let since = number(self, since.unwrap_or_default())?;
+        let output = words.next().map(ToString::to_string);
+        no_args(self, &words.collect::<Vec<_>>().join(" "))?;
+
+        let event = session.show_diff(since, output).await?;
+        let _ = event_sender.send(event);
+        Ok(true)
+    }
+}
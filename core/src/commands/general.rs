@@ -0,0 +1,316 @@
+//! Commands about the session itself: help, model, cost, tools, prompt, persona,
+//! memory and quit.
+
+use super::{SlashCommand, no_args};
+use crate::config::ProviderSettings;
+use crate::events::CoreEvent;
+use crate::session::Session;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+/// List the available commands.
+pub struct Help;
+
+#[async_trait]
+impl SlashCommand for Help {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn aliases(&self) -> &[&str] {
+        &["?"]
+    }
+
+    fn args(&self) -> &'static str {
+        ""
+    }
+
+    fn help(&self) -> &'static str {
+        "List the available commands"
+    }
+
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        no_args(self, args)?;
+        let _ = event_sender.send(CoreEvent::Commands(session.commands().list()));
+        Ok(true)
+    }
+}
+
+/// Show the provider and model of the session, or switch to another one.
+pub struct Model;
+
+#[async_trait]
+impl SlashCommand for Model {
+    fn name(&self) -> &'static str {
+        "model"
+    }
+
+    fn args(&self) -> &'static str {
+        "[provider/model]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Show the current model, or switch to another one keeping the conversation"
+    }
+
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        if args.is_empty() {
+            let settings = session.client().config();
+            let _ = event_sender.send(CoreEvent::Model {
+                provider: settings.name.clone(),
+                model: settings.model.clone(),
+            });
+            return Ok(true);
+        }
+
+        let settings = ProviderSettings::from_model_string(args)?;
+        let event = session.switch_model(settings);
+        session.emit(event_sender, event);
+        Ok(true)
+    }
+}
+
+/// Show the token usage and cost of the session.
+pub struct Cost;
+
+#[async_trait]
+impl SlashCommand for Cost {
+    fn name(&self) -> &'static str {
+        "cost"
+    }
+
+    fn args(&self) -> &'static str {
+        ""
+    }
+
+    fn help(&self) -> &'static str {
+        "Show the token usage and cost of the session"
+    }
+
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        no_args(self, args)?;
+        let tally = session.guard().session();
+        let _ = event_sender.send(CoreEvent::Cost {
+            usage: tally.usage,
+            cost: session
+                .client()
+                .config()
+                .pricing
+                .is_some()
+                .then_some(tally.cost),
+        });
+        Ok(true)
+    }
+}
+
+/// Show the system prompt, or load a lazy section of it.
+pub struct Prompt;
+
+#[async_trait]
+impl SlashCommand for Prompt {
+    fn name(&self) -> &'static str {
+        "prompt"
+    }
+
+    fn args(&self) -> &'static str {
+        "[load <section>]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Show the system prompt with the size of each section, or load a lazy section"
+    }
+
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        if !args.is_empty() {
+            let Some(section) = args.strip_prefix("load ") else {
+                anyhow::bail!("Usage: {}", self.usage());
+            };
+            session.prompt_mut().load(section.trim())?;
+        }
+        let _ = event_sender.send(CoreEvent::Prompt {
+            text: session.system_prompt(),
+            sections: session.prompt().estimates(),
+        });
+        Ok(true)
+    }
+}
+
+/// Show the personas, or talk as another one.
+pub struct Persona;
+
+#[async_trait]
+impl SlashCommand for Persona {
+    fn name(&self) -> &'static str {
+        "persona"
+    }
+
+    fn args(&self) -> &'static str {
+        "[name|default]"
+    }
+
+    fn help(&self) -> &'static str {
+        "List the personas, or switch to another one (default: the plain assistant)"
+    }
+
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        let event = match args {
+            "" => CoreEvent::Personas {
+                current: session.persona().map(str::to_string),
+                names: session.persona_names(),
+            },
+            "default" => session.set_persona(None)?,
+            name => session.set_persona(Some(name))?,
+        };
+        let _ = event_sender.send(event);
+        Ok(true)
+    }
+}
+
+/// Review the memories of the project.
+pub struct Memory;
+
+#[async_trait]
+impl SlashCommand for Memory {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn aliases(&self) -> &[&str] {
+        &["memories"]
+    }
+
+    fn args(&self) -> &'static str {
+        "[show <id>|forget <id>]"
+    }
+
+    fn help(&self) -> &'static str {
+        "List the memories of the project, show one in full, or forget one"
+    }
+
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        let store = session
+            .memory()
+            .context("Memory is disabled (see [memory] in the configuration)")?;
+        let (action, id) = args.split_once(' ').unwrap_or((args, ""));
+        let event = match (action, id.trim()) {
+            ("", _) => CoreEvent::Memories(store.list()?),
+            ("show", id) if !id.is_empty() => {
+                let (memory, stale) = store.read(id)?;
+                CoreEvent::Memory { memory, stale }
+            },
+            ("forget", id) if !id.is_empty() => {
+                let memory = store.forget(id)?;
+                CoreEvent::MemoryForgotten {
+                    id: memory.id,
+                    summary: memory.summary,
+                }
+            },
+            _ => anyhow::bail!("Usage: {}", self.usage()),
+        };
+        let _ = event_sender.send(event);
+        Ok(true)
+    }
+}
+
+/// List the tools available to the model.
+pub struct Tools;
+
+#[async_trait]
+impl SlashCommand for Tools {
+    fn name(&self) -> &'static str {
+        "tools"
+    }
+
+    fn args(&self) -> &'static str {
+        ""
+    }
+
+    fn help(&self) -> &'static str {
+        "List the tools available to the model"
+    }
+
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        no_args(self, args)?;
+        let field = |tool: &Value, key: &str| {
+            tool.get(key)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+        let tools = session
+            .schema()
+            .iter()
+            .map(|tool| (field(tool, "name"), field(tool, "description")))
+            .collect();
+        let _ = event_sender.send(CoreEvent::Tools(tools));
+        Ok(true)
+    }
+}
+
+/// Quit the interactive session.
+pub struct Quit;
+
+#[async_trait]
+impl SlashCommand for Quit {
+    fn name(&self) -> &'static str {
+        "quit"
+    }
+
+    fn aliases(&self) -> &[&str] {
+        &["q", "exit"]
+    }
+
+    fn args(&self) -> &'static str {
+        ""
+    }
+
+    fn help(&self) -> &'static str {
+        "Quit neco"
+    }
+
+    async fn run(
+        &self,
+        _args: &str,
+        _session: &mut Session,
+        _event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        Ok(false)
+    }
+}
@@ -0,0 +1,481 @@
+//! User-defined commands from Markdown prompt templates.
+//!
+//! Each `<name>.md` file in a commands directory becomes a `/name` command.
+//! An optional frontmatter block sets the description, the tools offered
+//! to the model and a model override:
+//!
+//! ```markdown
+//! ---
+//! description: Review the staged changes
+//! argument-hint: [focus]
+//! allowed-tools: read, grep, glob
+//! model: anthropic/claude-sonnet-4-5
+//! ---
+//! Review this diff, focusing on $ARGUMENTS:
+//!
+//! !`git diff --cached`
+//! ```
+//!
+//! In the body, `$ARGUMENTS` is replaced by all arguments and `$1` to `$9`
+//! by single (optionally quoted) arguments. ``!`cmd` `` is replaced by the
+//! output of the shell command and `@path` by the contents of the project
+//! file; mentions of missing files and of files outside the project are
+//! left as they are. Arguments are substituted as text: they are quoted
+//! inside shell commands, and placeholders in arguments are not expanded.
+
+use super::SlashCommand;
+use crate::config::ProviderSettings;
+use crate::events::CoreEvent;
+use crate::session::Session;
+use crate::tools;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use regex::Regex;
+use std::fmt::Write as _;
+use std::path::{Component, Path, PathBuf};
+use std::sync::LazyLock;
+use tokio::sync::mpsc;
+
+/// Shell command placeholder, ``!`cmd` ``, or file placeholder, `@path` at
+/// the start of a word.
+static PLACEHOLDER: LazyLock<Result<Regex, regex::Error>> =
+    LazyLock::new(|| Regex::new(r"!`([^`]+)`|(^|\s)@([^\s`]+)"));
+
+/// Argument placeholder, `$ARGUMENTS` or `$1` to `$9`.
+static ARGUMENT: LazyLock<Result<Regex, regex::Error>> =
+    LazyLock::new(|| Regex::new(r"\$(ARGUMENTS|[1-9])"));
+
+/// Command defined by a Markdown prompt template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    /// Command name, the file name without extension
+    pub name: String,
+    /// One-line description shown by `/help`
+    pub description: String,
+    /// Synopsis of the arguments, e.g. `[focus]`
+    pub argument_hint: String,
+    /// Names of the tools offered to the model, or `None` for all tools
+    pub allowed_tools: Option<Vec<String>>,
+    /// Model to run the prompt with, as `provider/model` or `model`
+    pub model: Option<String>,
+    /// Prompt template
+    pub body: String,
+    /// File the template was read from
+    pub path: PathBuf,
+}
+
+impl Template {
+    /// Parse a template file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the Markdown file; its stem is the command name
+    /// * `text` - Contents of the file
+    ///
+    /// # Returns
+    ///
+    /// The template, or `None` if the file name is not a valid command name.
+    #[must_use]
+    pub fn parse(path: &Path, text: &str) -> Option<Self> {
+        let name = path.file_stem()?.to_str()?;
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return None;
+        }
+
+        let mut template = Self {
+            name: name.to_string(),
+            description: format!("Run the prompt in {}", path.display()),
+            argument_hint: String::new(),
+            allowed_tools: None,
+            model: None,
+            body: text.trim().to_string(),
+            path: path.to_path_buf(),
+        };
+        let Some((frontmatter, body)) = split_frontmatter(text) else {
+            return Some(template);
+        };
+        template.body = body.trim().to_string();
+        for line in frontmatter.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim().trim_matches('"');
+            match key.trim() {
+                "description" if !value.is_empty() => template.description = value.to_string(),
+                "argument-hint" | "argument_hint" => template.argument_hint = value.to_string(),
+                "allowed-tools" | "allowed_tools" => {
+                    template.allowed_tools = Some(tool_list(value));
+                },
+                "model" if !value.is_empty() => template.model = Some(value.to_string()),
+                _ => {},
+            }
+        }
+        Some(template)
+    }
+
+    /// Build the prompt for the given arguments.
+    ///
+    /// Shell commands and file placeholders are only taken from the template
+    /// body, never from the arguments. Arguments substituted into a shell
+    /// command are quoted. Only files inside the project are inlined; other
+    /// mentions are kept verbatim. Arguments given to a template without placeholders are appended to
+    /// the prompt.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Arguments after the command name
+    /// * `cwd` - Project directory; shell commands run in it and file paths are relative to it
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a shell command cannot be run or an inlined file
+    /// cannot be read.
+    pub async fn expand(&self, args: &str, cwd: &Path) -> Result<String> {
+        let mut prompt = String::new();
+        let mut last = 0;
+        let placeholder = PLACEHOLDER.as_ref().map_err(Clone::clone)?;
+        for captures in placeholder.captures_iter(&self.body) {
+            let Some(whole) = captures.get(0) else {
+                continue;
+            };
+            let before = self.body.get(last..whole.start()).unwrap_or_default();
+            prompt.push_str(&substitute(before, args, false)?);
+            last = whole.end();
+
+            if let Some(command) = captures.get(1) {
+                let command = substitute(command.as_str(), args, true)?;
+                let output = tools::bash(&format!(
+                    "cd {} && {command}",
+                    quote(&cwd.display().to_string())
+                ))
+                .await
+                .with_context(|| format!("Failed to run {}", whole.as_str()))?;
+                prompt.push_str(&output);
+            } else if let (Some(lead), Some(path)) = (captures.get(2), captures.get(3)) {
+                let path = substitute(path.as_str(), args, false)?;
+                prompt.push_str(lead.as_str());
+                match inside(cwd, &path) {
+                    Some(resolved) => {
+                        let content = tokio::fs::read_to_string(&resolved)
+                            .await
+                            .with_context(|| format!("Failed to read {path}"))?;
+                        let _ = write!(
+                            prompt,
+                            "<file path=\"{path}\">\n{}\n</file>",
+                            content.trim_end()
+                        );
+                    },
+                    None => {
+                        let _ = write!(prompt, "@{path}");
+                    },
+                }
+            }
+        }
+        let rest = self.body.get(last..).unwrap_or_default();
+        prompt.push_str(&substitute(rest, args, false)?);
+
+        let argument = ARGUMENT.as_ref().map_err(Clone::clone)?;
+        if !args.is_empty() && !argument.is_match(&self.body) {
+            prompt = format!("{prompt}\n\n{args}");
+        }
+        Ok(prompt)
+    }
+}
+
+#[async_trait]
+impl SlashCommand for Template {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn args(&self) -> &str {
+        &self.argument_hint
+    }
+
+    fn help(&self) -> &str {
+        &self.description
+    }
+
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        let model = self
+            .model
+            .as_deref()
+            .map(ProviderSettings::from_model_string)
+            .transpose()?;
+        let prompt = self.expand(args, Path::new(session.cwd())).await?;
+        session
+            .send_message_with(prompt, self.allowed_tools.as_deref(), model, event_sender)
+            .await;
+        Ok(true)
+    }
+}
+
+/// Load the templates of the given directories.
+///
+/// A template in a later directory replaces one of the same name in an
+/// earlier directory, so project commands override user commands.
+/// Missing directories and unreadable files are skipped.
+///
+/// # Arguments
+///
+/// * `dirs` - Commands directories, lowest precedence first
+#[must_use]
+pub fn load(dirs: &[PathBuf]) -> Vec<Template> {
+    let mut templates: Vec<Template> = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let Some(template) = std::fs::read_to_string(&path)
+                .ok()
+                .and_then(|text| Template::parse(&path, &text))
+            else {
+                tracing::warn!(path = %path.display(), "Skipping unreadable command template");
+                continue;
+            };
+            templates.retain(|loaded| loaded.name != template.name);
+            templates.push(template);
+        }
+    }
+    templates
+}
+
+/// Parse a frontmatter list of tool names, e.g. `read, grep` or `[read, grep]`.
+pub(crate) fn tool_list(value: &str) -> Vec<String> {
+    value
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(',')
+        .map(|tool| tool.trim().trim_matches('"').to_lowercase())
+        .filter(|tool| !tool.is_empty())
+        .collect()
+}
+
+/// Split a leading `---` frontmatter block from the body.
+pub(crate) fn split_frontmatter(text: &str) -> Option<(&str, &str)> {
+    let rest = text.strip_prefix("---")?;
+    let rest = rest
+        .strip_prefix("\r\n")
+        .or_else(|| rest.strip_prefix('\n'))?;
+    if let Some(body) = rest.strip_prefix("---") {
+        return Some(("", body));
+    }
+    let end = rest.find("\n---")?;
+    let frontmatter = rest.get(..end)?;
+    let body = rest.get(end + 4..)?;
+    Some((frontmatter, body))
+}
+
+/// Replace `$ARGUMENTS` and `$1` to `$9` in part of a template body.
+///
+/// # Arguments
+///
+/// * `text` - Part of the template body
+/// * `args` - All arguments, replacing `$ARGUMENTS`
+/// * `shell` - Whether the text is a shell command, so each argument is quoted
+///
+/// # Errors
+///
+/// Returns an error if the argument pattern cannot be compiled.
+fn substitute(text: &str, args: &str, shell: bool) -> Result<String> {
+    let pattern = ARGUMENT.as_ref().map_err(Clone::clone)?;
+    let words = split_args(args);
+    let value = |word: &str| if shell { quote(word) } else { word.to_string() };
+    Ok(pattern
+        .replace_all(text, |captures: &regex::Captures| {
+            match captures.get(1).map(|name| name.as_str()) {
+                Some("ARGUMENTS") if shell => words
+                    .iter()
+                    .map(|word| quote(word))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                Some("ARGUMENTS") => args.to_string(),
+                position => {
+                    let word = position
+                        .and_then(|position| position.parse::<usize>().ok())
+                        .and_then(|position| position.checked_sub(1))
+                        .and_then(|index| words.get(index));
+                    value(word.map_or("", String::as_str))
+                },
+            }
+        })
+        .into_owned())
+}
+
+/// Resolve a file placeholder inside the project directory.
+///
+/// # Returns
+///
+/// The file's path, or `None` if there is no such file or the path is
+/// absolute, leads or links outside the project.
+fn inside(root: &Path, path: &str) -> Option<PathBuf> {
+    let escapes = Path::new(path)
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir));
+    if escapes {
+        return None;
+    }
+    let resolved = root.join(path);
+    if !resolved.is_file() {
+        return None;
+    }
+    let canonical = resolved.canonicalize().ok()?;
+    canonical
+        .starts_with(root.canonicalize().ok()?)
+        .then_some(canonical)
+}
+
+/// Split arguments on whitespace, keeping quoted arguments together.
+fn split_args(args: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    let mut in_word = false;
+    for c in args.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_word = true;
+            },
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            },
+            (None, c) => {
+                current.push(c);
+                in_word = true;
+            },
+        }
+    }
+    if in_word {
+        words.push(current);
+    }
+    words
+}
+
+/// Quote a value for the shell.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_frontmatter() {
+        let text = "---\ndescription: Review a file\nargument-hint: <file>\nallowed-tools: [Read, grep]\nmodel: anthropic/cheap\n---\nReview $1.\n";
+        let template = Template::parse(Path::new("/x/review.md"), text).unwrap();
+        assert_eq!(template.name, "review");
+        assert_eq!(template.description, "Review a file");
+        assert_eq!(template.argument_hint, "<file>");
+        assert_eq!(
+            template.allowed_tools,
+            Some(vec!["read".to_string(), "grep".to_string()])
+        );
+        assert_eq!(template.model.as_deref(), Some("anthropic/cheap"));
+        assert_eq!(template.body, "Review $1.");
+        assert_eq!(template.usage(), "/review <file>");
+
+        let plain = Template::parse(Path::new("notes.md"), "Summarise the notes").unwrap();
+        assert_eq!(plain.body, "Summarise the notes");
+        assert!(plain.allowed_tools.is_none());
+        assert!(Template::parse(Path::new("two words.md"), "x").is_none());
+    }
+
+    #[test]
+    fn test_substitute_arguments() {
+        assert_eq!(
+            substitute(
+                "Compare $1 with $2 ($ARGUMENTS), not $3",
+                "a \"b c\"",
+                false
+            )
+            .unwrap(),
+            "Compare a with b c (a \"b c\"), not "
+        );
+        assert_eq!(
+            split_args("  one 'two three'  four"),
+            ["one", "two three", "four"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expand_inlines_files_and_shell_output() {
+        let dir = crate::testing::TempDir::new("template");
+        std::fs::write(dir.join("notes.txt"), "remember the milk\n").unwrap();
+
+        let template = Template::parse(
+            Path::new("check.md"),
+            "Read @$1 and @missing.txt; status: !`echo ok`",
+        )
+        .unwrap();
+        let prompt = template.expand("notes.txt", &dir).await.unwrap();
+        assert_eq!(
+            prompt,
+            "Read <file path=\"notes.txt\">\nremember the milk\n</file> and @missing.txt; status: ok"
+        );
+
+        let appended = Template::parse(Path::new("plain.md"), "Explain").unwrap();
+        assert_eq!(
+            appended.expand("the parser", &dir).await.unwrap(),
+            "Explain\n\nthe parser"
+        );
+
+        let quoted = Template::parse(
+            Path::new("quoted.md"),
+            "!`printf '%s|' $ARGUMENTS` !`printf %s $1`",
+        )
+        .unwrap();
+        assert_eq!(
+            quoted.expand("'a; echo b' \"$(id)\"", &dir).await.unwrap(),
+            "a; echo b|$(id)| a; echo b"
+        );
+
+        let verbatim = Template::parse(Path::new("say.md"), "Say $ARGUMENTS").unwrap();
+        assert_eq!(
+            verbatim
+                .expand("@notes.txt !`echo hi`", &dir)
+                .await
+                .unwrap(),
+            "Say @notes.txt !`echo hi`"
+        );
+
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        for (body, args) in [
+            ("@/etc/hostname", ""),
+            ("@../notes.txt", ""),
+            ("@$1", "../notes.txt"),
+        ] {
+            let outside = Template::parse(Path::new("outside.md"), body).unwrap();
+            let prompt = outside.expand(args, &dir.join("nested")).await.unwrap();
+            assert_eq!(prompt, body.replace("$1", args));
+        }
+
+        let project = dir.join("project");
+        let user = dir.join("user");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::create_dir_all(&user).unwrap();
+        std::fs::write(user.join("review.md"), "user review").unwrap();
+        std::fs::write(user.join("notes.md"), "user notes").unwrap();
+        std::fs::write(project.join("review.md"), "project review").unwrap();
+        let templates = load(&[user, project]);
+        let bodies: Vec<&str> = templates.iter().map(|t| t.body.as_str()).collect();
+        assert_eq!(bodies, ["user notes", "project review"]);
+    }
+}
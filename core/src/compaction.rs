@@ -0,0 +1,331 @@
+//! Context compaction by summarisation.
+//!
+//! When the conversation nears the model's context window, older turns are
+//! replaced by a structured summary written by the model, while the most
+//! recent turns stay verbatim. The full pre-compaction history remains in the
+//! session transcript. A long agentic turn is compacted between its tool
+//! rounds, keeping only the latest round verbatim.
+
+use crate::Client;
+use crate::events::CoreEvent;
+use crate::guard::Guard;
+use crate::history::{History, turn_starts};
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+use std::fmt::Write;
+use tokio::sync::mpsc;
+
+/// System prompt for the summarisation request.
+pub const SYSTEM_PROMPT: &str = "You summarise coding sessions so that work can continue \
+without the original conversation. Be specific: keep file paths, function names, commands, \
+error messages and decisions verbatim where they matter. Omit pleasantries.";
+
+/// Instructions describing the structure of the summary.
+const INSTRUCTIONS: &str = "Summarise the conversation above using exactly these sections:
+
+## Goals
+What the user wants to achieve, including constraints and preferences.
+
+## Decisions
+Choices made so far and why.
+
+## Files touched
+Each file read or modified, with a one-line note on what changed or why it matters.
+
+## Current state
+What has been done and what was happening when the conversation was cut.
+
+## Open TODOs
+Remaining work, unresolved errors and follow-ups.";
+
+/// Prefix of the message holding a summary in the compacted history.
+pub const SUMMARY_HEADER: &str = "[Summary of the earlier conversation]";
+
+/// Maximum characters of a single tool result included in the summarisation input.
+const MAX_RESULT_CHARS: usize = 2_000;
+
+/// Find where the verbatim part of the history starts.
+///
+/// The kept part starts at a user turn (see [`turn_starts`]).
+///
+/// # Arguments
+///
+/// * `messages` - Conversation history
+/// * `keep_turns` - Number of most recent user turns to keep (at least one is kept)
+///
+/// # Returns
+///
+/// Index of the first kept message, or `None` if there is nothing older to compact.
+#[must_use]
+pub fn split_point(messages: &[Value], keep_turns: usize) -> Option<usize> {
+    let starts = turn_starts(messages);
+    let keep = keep_turns.max(1);
+    let index = *starts.get(starts.len().checked_sub(keep)?)?;
+    (index > 0).then_some(index)
+}
+
+/// Find where the verbatim part of the history starts in the middle of a turn.
+///
+/// Only the latest tool round, the assistant's tool calls and their results,
+/// is kept.
+///
+/// # Returns
+///
+/// Index of the first kept message, or `None` if there is nothing older to compact.
+#[must_use]
+pub fn split_point_in_turn(messages: &[Value]) -> Option<usize> {
+    let index = messages
+        .iter()
+        .rposition(|message| message.get("role").and_then(Value::as_str) == Some("assistant"))?;
+    (index > 0).then_some(index)
+}
+
+/// Render messages as plain text for the summarisation request.
+///
+/// Tool calls and results are inlined so the request needs no tool
+/// definitions; long tool results are shortened.
+#[must_use]
+pub fn render(messages: &[Value]) -> String {
+    let mut out = String::new();
+    for message in messages {
+        let role = message.get("role").and_then(Value::as_str).unwrap_or("?");
+        let blocks = match message.get("content") {
+            Some(Value::String(text)) => vec![json!({"type": "text", "text": text})],
+            Some(Value::Array(blocks)) => blocks.clone(),
+            _ => Vec::new(),
+        };
+
+        for block in &blocks {
+            match block.get("type").and_then(Value::as_str) {
+                Some("text") => {
+                    let text = block.get("text").and_then(Value::as_str).unwrap_or("");
+                    if !text.is_empty() {
+                        let _ = writeln!(out, "{role}: {text}\n");
+                    }
+                },
+                Some("tool_use") => {
+                    let name = block.get("name").and_then(Value::as_str).unwrap_or("?");
+                    let input = block.get("input").map(Value::to_string).unwrap_or_default();
+                    let _ = writeln!(out, "{role} called {name} {input}\n");
+                },
+                Some("tool_result") => {
+                    let content = block.get("content").and_then(Value::as_str).unwrap_or("");
+                    let _ = writeln!(out, "tool result:\n{}\n", shorten(content));
+                },
+                _ => {},
+            }
+        }
+    }
+    out
+}
+
+/// Shorten a tool result to [`MAX_RESULT_CHARS`] characters.
+fn shorten(text: &str) -> String {
+    if text.chars().count() <= MAX_RESULT_CHARS {
+        return text.to_string();
+    }
+    let head: String = text.chars().take(MAX_RESULT_CHARS).collect();
+    format!("{head}\n... (shortened)")
+}
+
+/// Build the user message asking for a summary.
+///
+/// # Arguments
+///
+/// * `rendered` - Conversation rendered with [`render`]
+/// * `focus` - Optional instructions on what the summary should focus on
+#[must_use]
+pub fn request(rendered: &str, focus: Option<&str>) -> Value {
+    let mut text = format!("<conversation>\n{rendered}</conversation>\n\n{INSTRUCTIONS}");
+    if let Some(focus) = focus.filter(|f| !f.trim().is_empty()) {
+        let _ = write!(text, "\n\nFocus the summary on: {focus}");
+    }
+    json!({"role": "user", "content": text})
+}
+
+/// Build the compacted history from a summary and the kept messages.
+///
+/// The summary is prepended to the first kept message when it starts a user
+/// turn, and is a message of its own before kept assistant messages, so
+/// that roles keep alternating.
+#[must_use]
+pub fn compacted(summary: &str, kept: &[Value]) -> Vec<Value> {
+    let summary_block = json!({
+        "type": "text",
+        "text": format!("{SUMMARY_HEADER}\n{summary}")
+    });
+
+    let mut messages = kept.to_vec();
+    match messages
+        .first_mut()
+        .filter(|first| first.get("role").and_then(Value::as_str) == Some("user"))
+    {
+        Some(first) => {
+            let blocks = match first.get("content") {
+                Some(Value::String(text)) => vec![json!({"type": "text", "text": text})],
+                Some(Value::Array(blocks)) => blocks.clone(),
+                _ => Vec::new(),
+            };
+            let content: Vec<Value> = std::iter::once(summary_block).chain(blocks).collect();
+            if let Some(object) = first.as_object_mut() {
+                object.insert("content".to_string(), Value::Array(content));
+            }
+        },
+        None => messages.insert(0, json!({"role": "user", "content": [summary_block]})),
+    }
+    messages
+}
+
+/// Replace the messages before `split` by a summary written by the model.
+///
+/// # Arguments
+///
+/// * `client` - Client sending the summarisation request
+/// * `history` - Conversation history to compact
+/// * `guard` - Guard recording the usage of the request
+/// * `split` - Index of the first message kept verbatim
+/// * `focus` - Optional instructions on what the summary should focus on
+/// * `automatic` - Whether compaction was triggered by the context size
+/// * `event_sender` - Optional sender for core events
+///
+/// # Errors
+///
+/// Returns error if the summarisation request fails or returns no text.
+pub async fn compact(
+    client: &Client,
+    history: &mut History,
+    guard: &mut Guard,
+    split: usize,
+    focus: Option<&str>,
+    automatic: bool,
+    event_sender: Option<&mpsc::UnboundedSender<CoreEvent>>,
+) -> Result<()> {
+    let (older, kept) = history.messages().split_at(split.min(history.len()));
+
+    let request = request(&render(older), focus);
+    let (summary, usage) = client
+        .complete(&[request], SYSTEM_PROMPT)
+        .await
+        .context("Summarisation request failed")?;
+    if summary.trim().is_empty() {
+        anyhow::bail!("Summarisation returned no text");
+    }
+
+    let compacted = compacted(summary.trim(), kept);
+    let kept = kept.len();
+    history.replace(compacted);
+    guard.record_usage(&usage);
+    guard.reset_context();
+    history.emit(event_sender, CoreEvent::Usage(usage));
+    history.emit(
+        event_sender,
+        CoreEvent::Compacted {
+            replaced: split,
+            kept,
+            automatic,
+        },
+    );
+    Ok(())
+}
+
+/// Rough token estimate of a history, for when no usage is known yet.
+#[must_use]
+pub fn estimate_tokens(messages: &[Value]) -> u64 {
+    let chars: usize = messages.iter().map(|m| m.to_string().len()).sum();
+    (chars / 4) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<Value> {
+        vec![
+            json!({"role": "user", "content": "add a flag"}),
+            json!({"role": "assistant", "content": [
+                {"type": "text", "text": "Reading main.rs"},
+                {"type": "tool_use", "id": "c1", "name": "read", "input": {"path": "main.rs"}}
+            ]}),
+            json!({"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": "c1", "content": "fn main() {}"}
+            ]}),
+            json!({"role": "assistant", "content": [{"type": "text", "text": "Done"}]}),
+            json!({"role": "user", "content": "now test it"}),
+            json!({"role": "assistant", "content": [{"type": "text", "text": "Tested"}]}),
+        ]
+    }
+
+    #[test]
+    fn test_split_point() {
+        let messages = history();
+        assert_eq!(split_point(&messages, 1), Some(4));
+        assert_eq!(split_point(&messages, 0), Some(4));
+        assert_eq!(split_point(&messages, 2), None);
+        assert_eq!(split_point(&[], 1), None);
+    }
+
+    #[test]
+    fn test_render() {
+        let rendered = render(&history());
+        assert!(rendered.contains("user: add a flag"));
+        assert!(rendered.contains(r#"assistant called read {"path":"main.rs"}"#));
+        assert!(rendered.contains("tool result:\nfn main() {}"));
+
+        let long = "x".repeat(MAX_RESULT_CHARS + 1);
+        assert!(shorten(&long).ends_with("(shortened)"));
+    }
+
+    #[test]
+    fn test_request_focus() {
+        let with_focus = request("user: hi\n", Some("the parser"));
+        let text = with_focus.get("content").and_then(Value::as_str).unwrap();
+        assert!(text.contains("## Open TODOs"));
+        assert!(text.ends_with("Focus the summary on: the parser"));
+
+        let without = request("user: hi\n", Some("  "));
+        let text = without.get("content").and_then(Value::as_str).unwrap();
+        assert!(!text.contains("Focus"));
+    }
+
+    #[test]
+    fn test_compacted() {
+        let messages = history();
+        let kept = messages.get(4..).unwrap();
+        let result = compacted("## Goals\nAdd a flag", kept);
+
+        assert_eq!(result.len(), 2);
+        let blocks = result
+            .first()
+            .and_then(|m| m.get("content"))
+            .and_then(Value::as_array)
+            .unwrap();
+        assert_eq!(blocks.len(), 2);
+        let summary = blocks.first().and_then(|b| b.get("text")).unwrap();
+        assert!(summary.as_str().unwrap().starts_with(SUMMARY_HEADER));
+        assert_eq!(
+            blocks.get(1).and_then(|b| b.get("text")),
+            Some(&json!("now test it"))
+        );
+
+        assert_eq!(compacted("summary", &[]).len(), 1);
+    }
+
+    #[test]
+    fn test_compacted_in_turn() {
+        let messages = history();
+        let in_turn = messages.get(..3).unwrap();
+        assert_eq!(split_point_in_turn(in_turn), Some(1));
+        assert_eq!(split_point_in_turn(messages.get(..1).unwrap()), None);
+
+        let result = compacted("summary", in_turn.get(1..).unwrap());
+        let roles: Vec<&str> = result
+            .iter()
+            .filter_map(|m| m.get("role").and_then(Value::as_str))
+            .collect();
+        assert_eq!(roles, ["user", "assistant", "user"]);
+        assert_eq!(
+            result.first().and_then(|m| m.pointer("/content/0/text")),
+            Some(&json!(format!("{SUMMARY_HEADER}\nsummary")))
+        );
+    }
+}
@@ -2,7 +2,7 @@
 
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Application configuration file.
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -16,6 +16,45 @@ pub struct Configuration {
     /// Provider configurations
     #[serde(default)]
     pub model_providers: IndexMap<String, FileProvider>,
+    /// Agent loop limits
+    #[serde(default)]
+    pub limits: Limits,
+    /// Tool output size limits
+    #[serde(default)]
+    pub output_limits: OutputLimits,
+    /// Automatic context compaction
+    #[serde(default)]
+    pub compaction: Compaction,
+    /// Pruning of stale tool results
+    #[serde(default)]
+    pub pruning: Pruning,
+    /// Redaction of secrets in exported sessions
+    #[serde(default)]
+    pub redaction: Redaction,
+    /// Sections of the system prompt
+    #[serde(default)]
+    pub prompt: Prompt,
+    /// Project instruction files
+    #[serde(default)]
+    pub instructions: Instructions,
+    /// Environment section of the system prompt
+    #[serde(default)]
+    pub environment: Environment,
+    /// Persistent memory
+    #[serde(default)]
+    pub memory: Memory,
+    /// MCP servers
+    #[serde(default)]
+    pub mcp_servers: IndexMap<String, McpServer>,
+    /// Lazy loading of tools
+    #[serde(default)]
+    pub tool_search: ToolSearch,
+    /// Persona to start sessions with (optional)
+    #[serde(default)]
+    pub persona: Option<String>,
+    /// Persona profiles
+    #[serde(default)]
+    pub personas: IndexMap<String, Persona>,
 }
 
 impl Default for Configuration {
@@ -24,6 +63,19 @@ impl Default for Configuration {
             default_model_provider: None,
             default_model: None,
             model_providers: Self::builtin_providers(),
+            limits: Limits::default(),
+            output_limits: OutputLimits::default(),
+            compaction: Compaction::default(),
+            pruning: Pruning::default(),
+            redaction: Redaction::default(),
+            prompt: Prompt::default(),
+            instructions: Instructions::default(),
+            environment: Environment::default(),
+            memory: Memory::default(),
+            mcp_servers: IndexMap::new(),
+            tool_search: ToolSearch::default(),
+            persona: None,
+            personas: IndexMap::new(),
         }
     }
 }
@@ -40,6 +92,7 @@ impl Configuration {
                 api_key: None,
                 api_key_env: Some("ANTHROPIC_AUTH_TOKEN".to_string()),
                 default_model: Some("claude-opus-4-5".to_string()),
+                pricing: None,
             },
         );
 
@@ -68,11 +121,43 @@ impl Configuration {
             }
             config.default_model_provider = user_config.default_model_provider;
             config.default_model = user_config.default_model;
+            config.limits = user_config.limits;
+            config.output_limits = user_config.output_limits;
+            config.compaction = user_config.compaction;
+            config.pruning = user_config.pruning;
+            config.redaction = user_config.redaction;
+            config.prompt = user_config.prompt;
+            config.instructions = user_config.instructions;
+            config.environment = user_config.environment;
+            config.memory = user_config.memory;
+            config.mcp_servers = user_config.mcp_servers;
+            config.tool_search = user_config.tool_search;
+            config.persona = user_config.persona;
+            config.personas = user_config.personas;
         }
 
         config
     }
 
+    /// Get the API keys of all configured providers.
+    ///
+    /// Includes keys set in the configuration file and the values of the
+    /// providers' API key environment variables.
+    #[must_use]
+    pub fn api_keys(&self) -> Vec<String> {
+        self.model_providers
+            .values()
+            .flat_map(|provider| {
+                let from_env = provider
+                    .api_key_env
+                    .as_deref()
+                    .and_then(|name| std::env::var(name).ok());
+                provider.api_key.clone().into_iter().chain(from_env)
+            })
+            .filter(|key| !key.is_empty())
+            .collect()
+    }
+
     /// Get the configuration file path.
     ///
     /// Follows XDG Base Directory specification:
@@ -83,13 +168,7 @@ impl Configuration {
     ///
     /// The path to the configuration file, or `None` if home directory cannot be determined.
     fn get_config_path() -> Option<PathBuf> {
-        let config_dir = dirs::config_dir().map(|p| p.join("neco"));
-
-        if let Some(ref dir) = config_dir {
-            return Some(dir.join("config.toml"));
-        }
-
-        None
+        user_dir().map(|dir| dir.join("config.toml"))
     }
 
     /// Get the default model provider name.
@@ -132,6 +211,763 @@ pub struct FileProvider {
     pub api_key_env: Option<String>,
     /// Default model
     pub default_model: Option<String>,
+    /// Token pricing used for cost tracking (optional)
+    #[serde(default)]
+    pub pricing: Option<Pricing>,
+}
+
+/// Token pricing in USD per million tokens.
+///
+/// Cache reads and writes are billed at the input price.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Pricing {
+    /// Price per million input tokens
+    pub input: f64,
+    /// Price per million output tokens
+    pub output: f64,
+}
+
+/// Limits applied to the agent loop.
+///
+/// Every limit is optional; unset limits are not enforced. Turn limits
+/// apply to the processing of a single user message, session limits to
+/// the whole session.
+///
+/// ```toml
+/// [limits]
+/// max_turns = 25
+/// max_duration_secs = 600
+/// max_session_cost = 5.0
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Limits {
+    /// Maximum tool iterations per user message
+    #[serde(default)]
+    pub max_turns: Option<u32>,
+    /// Maximum wall-clock time per user message, in seconds
+    #[serde(default)]
+    pub max_duration_secs: Option<u64>,
+    /// Maximum tokens (input + output) per user message
+    #[serde(default)]
+    pub max_turn_tokens: Option<u64>,
+    /// Maximum tokens (input + output) per session
+    #[serde(default)]
+    pub max_session_tokens: Option<u64>,
+    /// Maximum cost in USD per user message (requires pricing)
+    #[serde(default)]
+    pub max_turn_cost: Option<f64>,
+    /// Maximum cost in USD per session (requires pricing)
+    #[serde(default)]
+    pub max_session_cost: Option<f64>,
+    /// Detection of repeated identical tool calls
+    #[serde(default)]
+    pub repetition: Repetition,
+}
+
+/// Detection of repeated identical tool calls.
+///
+/// ```toml
+/// [limits.repetition]
+/// threshold = 3
+/// action = "ask"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Repetition {
+    /// Number of back-to-back repeats of a call (or cycle of calls) that counts as a loop
+    #[serde(default = "Repetition::default_threshold")]
+    pub threshold: usize,
+    /// What to do when a loop is detected
+    #[serde(default)]
+    pub action: RepetitionAction,
+}
+
+impl Repetition {
+    /// Default repetition threshold.
+    const fn default_threshold() -> usize {
+        3
+    }
+}
+
+impl Default for Repetition {
+    fn default() -> Self {
+        Self {
+            threshold: Self::default_threshold(),
+            action: RepetitionAction::default(),
+        }
+    }
+}
+
+/// Action taken when repeated tool calls are detected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepetitionAction {
+    /// Do not detect repetition
+    Off,
+    /// Append a corrective note to the tool result and keep going
+    #[default]
+    Note,
+    /// Append the note, then pause the loop and ask the user how to continue
+    Ask,
+}
+
+/// Size limits for tool output returned to the model.
+///
+/// Output exceeding a limit is cut down to its head and tail, and the full
+/// output is saved to a scratch file the model can read in pieces.
+///
+/// ```toml
+/// [output_limits]
+/// max_bytes = 30000
+/// max_lines = 1000
+///
+/// [output_limits.tools.bash]
+/// max_lines = 400
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct OutputLimits {
+    /// Default maximum bytes per tool result
+    #[serde(default = "OutputLimits::default_max_bytes")]
+    pub max_bytes: usize,
+    /// Default maximum lines per tool result
+    #[serde(default = "OutputLimits::default_max_lines")]
+    pub max_lines: usize,
+    /// Per-tool overrides, keyed by tool name
+    #[serde(default)]
+    pub tools: IndexMap<String, OutputLimit>,
+}
+
+impl OutputLimits {
+    /// Default maximum bytes per tool result.
+    const fn default_max_bytes() -> usize {
+        30_000
+    }
+
+    /// Default maximum lines per tool result.
+    const fn default_max_lines() -> usize {
+        1_000
+    }
+
+    /// Get the effective limits for a tool.
+    ///
+    /// # Arguments
+    ///
+    /// * `tool` - Tool name
+    ///
+    /// # Returns
+    ///
+    /// The `(max_lines, max_bytes)` pair for the tool.
+    #[must_use]
+    pub fn for_tool(&self, tool: &str) -> (usize, usize) {
+        let limit = self.tools.get(tool);
+        (
+            limit.and_then(|l| l.max_lines).unwrap_or(self.max_lines),
+            limit.and_then(|l| l.max_bytes).unwrap_or(self.max_bytes),
+        )
+    }
+}
+
+impl Default for OutputLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: Self::default_max_bytes(),
+            max_lines: Self::default_max_lines(),
+            tools: IndexMap::new(),
+        }
+    }
+}
+
+/// Output size limit override for a single tool.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct OutputLimit {
+    /// Maximum bytes per result (defaults to the global limit)
+    #[serde(default)]
+    pub max_bytes: Option<usize>,
+    /// Maximum lines per result (defaults to the global limit)
+    #[serde(default)]
+    pub max_lines: Option<usize>,
+}
+
+/// Automatic compaction of the conversation context.
+///
+/// When the context of the last request reaches `threshold` of the model's
+/// context window, older turns are replaced by a model-generated summary
+/// and the most recent `keep_turns` user turns are kept verbatim.
+///
+/// ```toml
+/// [compaction]
+/// context_window = 200000
+/// threshold = 0.8
+/// keep_turns = 2
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Compaction {
+    /// Whether to compact automatically
+    #[serde(default = "Compaction::default_enabled")]
+    pub enabled: bool,
+    /// Context window of the model, in tokens
+    #[serde(default = "Compaction::default_context_window")]
+    pub context_window: u64,
+    /// Fraction of the context window at which to compact
+    #[serde(default = "Compaction::default_threshold")]
+    pub threshold: f64,
+    /// Number of most recent user turns kept verbatim
+    #[serde(default = "Compaction::default_keep_turns")]
+    pub keep_turns: usize,
+}
+
+impl Compaction {
+    /// Compaction is enabled by default.
+    const fn default_enabled() -> bool {
+        true
+    }
+
+    /// Default context window.
+    const fn default_context_window() -> u64 {
+        200_000
+    }
+
+    /// Default compaction threshold.
+    const fn default_threshold() -> f64 {
+        0.8
+    }
+
+    /// Default number of verbatim turns.
+    const fn default_keep_turns() -> usize {
+        2
+    }
+
+    /// Whether a context of the given size should be compacted.
+    ///
+    /// # Arguments
+    ///
+    /// * `context_tokens` - Tokens used by the current context
+    #[must_use]
+    pub fn is_due(&self, context_tokens: u64) -> bool {
+        let tokens = |n: u64| f64::from(u32::try_from(n).unwrap_or(u32::MAX));
+        self.enabled && tokens(context_tokens) >= tokens(self.context_window) * self.threshold
+    }
+}
+
+impl Default for Compaction {
+    fn default() -> Self {
+        Self {
+            enabled: Self::default_enabled(),
+            context_window: Self::default_context_window(),
+            threshold: Self::default_threshold(),
+            keep_turns: Self::default_keep_turns(),
+        }
+    }
+}
+
+/// Pruning of stale tool results from the requests sent to the model.
+///
+/// Off by default. When enabled, tool results older than `keep_turns` model
+/// turns are replaced by a short stub; the tool calls themselves stay. The
+/// pruning boundary advances `step` turns at a time, so the earlier part of
+/// the request changes only once every `step` turns.
+///
+/// ```toml
+/// [pruning]
+/// enabled = true
+/// keep_turns = 10
+/// step = 5
+///
+/// [pruning.tools.read]
+/// keep_turns = 4
+///
+/// [pruning.tools.edit]
+/// enabled = false
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Pruning {
+    /// Whether to prune tool results
+    #[serde(default = "Pruning::default_enabled")]
+    pub enabled: bool,
+    /// Number of most recent model turns whose tool results are kept
+    #[serde(default = "Pruning::default_keep_turns")]
+    pub keep_turns: usize,
+    /// Number of turns the pruning boundary advances at once
+    #[serde(default = "Pruning::default_step")]
+    pub step: usize,
+    /// Per-tool overrides, keyed by tool name
+    #[serde(default)]
+    pub tools: IndexMap<String, PruneRule>,
+}
+
+impl Pruning {
+    /// Pruning is opt-in.
+    const fn default_enabled() -> bool {
+        false
+    }
+
+    /// Default number of turns with intact tool results.
+    const fn default_keep_turns() -> usize {
+        10
+    }
+
+    /// Default boundary step.
+    const fn default_step() -> usize {
+        5
+    }
+
+    /// Get the number of turns a tool's results are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `tool` - Tool name
+    ///
+    /// # Returns
+    ///
+    /// `None` if results of the tool are never pruned.
+    #[must_use]
+    pub fn keep_turns_for(&self, tool: &str) -> Option<usize> {
+        let rule = self.tools.get(tool);
+        (self.enabled && rule.and_then(|r| r.enabled).unwrap_or(true))
+            .then(|| rule.and_then(|r| r.keep_turns).unwrap_or(self.keep_turns))
+    }
+}
+
+impl Default for Pruning {
+    fn default() -> Self {
+        Self {
+            enabled: Self::default_enabled(),
+            keep_turns: Self::default_keep_turns(),
+            step: Self::default_step(),
+            tools: IndexMap::new(),
+        }
+    }
+}
+
+/// Pruning override for a single tool.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PruneRule {
+    /// Whether results of the tool are pruned (defaults to the global setting)
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    /// Number of most recent turns whose results are kept (defaults to the global setting)
+    #[serde(default)]
+    pub keep_turns: Option<usize>,
+}
+
+/// Redaction of secrets in exported sessions.
+///
+/// Common API key formats and the configured providers' API keys are always
+/// redacted; `patterns` adds regular expressions for other secrets.
+///
+/// ```toml
+/// [redaction]
+/// patterns = ["corp-token-[0-9a-f]{32}"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Redaction {
+    /// Additional regular expressions matching secrets
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    /// Literal secrets such as the providers' API keys (not read from the file)
+    #[serde(skip)]
+    pub secrets: Vec<String>,
+}
+
+/// Sections of the system prompt.
+///
+/// Built-in sections can be disabled or have their text replaced; sections
+/// with other names are added after the built-in ones. Lazy sections are
+/// left out until loaded with `/prompt load <name>`.
+///
+/// ```toml
+/// [prompt.sections.tools]
+/// text = "Prefer edit over write for existing files."
+///
+/// [prompt.sections.environment]
+/// enabled = false
+///
+/// [prompt.sections.style-guide]
+/// text = "Follow the style guide in docs/STYLE.md."
+/// lazy = true
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Prompt {
+    /// Section settings by section name
+    #[serde(default)]
+    pub sections: IndexMap<String, PromptSection>,
+}
+
+/// Settings of a single system prompt section.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PromptSection {
+    /// Whether the section is included (default: true)
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    /// Text replacing the section's content
+    #[serde(default)]
+    pub text: Option<String>,
+    /// Whether the section is only included once loaded (default: false)
+    #[serde(default)]
+    pub lazy: Option<bool>,
+}
+
+/// Project instruction files merged into the system prompt.
+///
+/// Files with these names are read from the user configuration directory
+/// and from every directory between the git root and the working directory.
+///
+/// ```toml
+/// [instructions]
+/// file_names = ["AGENTS.md", "NECO.md", "CONTRIBUTING.md"]
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Instructions {
+    /// Names of instruction files, in the order they are read within a directory
+    #[serde(default = "Instructions::default_file_names")]
+    pub file_names: Vec<String>,
+}
+
+impl Instructions {
+    /// Default names of instruction files.
+    fn default_file_names() -> Vec<String> {
+        vec!["AGENTS.md".to_string(), "NECO.md".to_string()]
+    }
+}
+
+impl Default for Instructions {
+    fn default() -> Self {
+        Self {
+            file_names: Self::default_file_names(),
+        }
+    }
+}
+
+/// Items of the environment section of the system prompt.
+///
+/// The section is refreshed at the start of each user turn. The directory
+/// listing only shows top-level entries and leaves out files ignored by git.
+///
+/// ```toml
+/// [environment]
+/// shell = false
+/// recent_commits = 3
+/// listing_limit = 20
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Environment {
+    /// Whether to show the operating system
+    #[serde(default = "Environment::default_enabled")]
+    pub os: bool,
+    /// Whether to show the user's shell
+    #[serde(default = "Environment::default_enabled")]
+    pub shell: bool,
+    /// Whether to show the current date
+    #[serde(default = "Environment::default_enabled")]
+    pub date: bool,
+    /// Whether to show if the directory is a git repository, and its branch
+    #[serde(default = "Environment::default_enabled")]
+    pub git: bool,
+    /// Whether to show a summary of `git status`
+    #[serde(default = "Environment::default_enabled")]
+    pub git_status: bool,
+    /// Number of recent commits to show (0 disables)
+    #[serde(default = "Environment::default_recent_commits")]
+    pub recent_commits: usize,
+    /// Whether to show the top-level directory listing
+    #[serde(default = "Environment::default_enabled")]
+    pub listing: bool,
+    /// Maximum number of entries in the listing
+    #[serde(default = "Environment::default_listing_limit")]
+    pub listing_limit: usize,
+}
+
+impl Environment {
+    /// Items are shown by default.
+    const fn default_enabled() -> bool {
+        true
+    }
+
+    /// Default number of recent commits.
+    const fn default_recent_commits() -> usize {
+        5
+    }
+
+    /// Default maximum number of listing entries.
+    const fn default_listing_limit() -> usize {
+        50
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self {
+            os: Self::default_enabled(),
+            shell: Self::default_enabled(),
+            date: Self::default_enabled(),
+            git: Self::default_enabled(),
+            git_status: Self::default_enabled(),
+            recent_commits: Self::default_recent_commits(),
+            listing: Self::default_enabled(),
+            listing_limit: Self::default_listing_limit(),
+        }
+    }
+}
+
+/// Persistent memory shared between sessions.
+///
+/// Memory summaries are always part of the system prompt; their full
+/// content is read through the `memory` tool. Memories unused for
+/// `max_age_days` are forgotten. A memory whose files or commit changed
+/// is flagged as stale, or forgotten when `expire_stale` is set.
+///
+/// ```toml
+/// [memory]
+/// max_age_days = 30
+/// expire_stale = true
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Memory {
+    /// Whether the memory tool and summaries are available
+    #[serde(default = "Memory::default_enabled")]
+    pub enabled: bool,
+    /// Days after which an unused memory is forgotten (0 keeps memories forever)
+    #[serde(default = "Memory::default_max_age_days")]
+    pub max_age_days: u64,
+    /// Whether stale memories are forgotten instead of flagged
+    #[serde(default)]
+    pub expire_stale: bool,
+    /// Maximum number of summaries in the system prompt, most recently used first
+    #[serde(default = "Memory::default_max_injected")]
+    pub max_injected: usize,
+}
+
+impl Memory {
+    /// Memory is enabled by default.
+    const fn default_enabled() -> bool {
+        true
+    }
+
+    /// Default age after which unused memories are forgotten.
+    const fn default_max_age_days() -> u64 {
+        90
+    }
+
+    /// Default maximum number of injected summaries.
+    const fn default_max_injected() -> usize {
+        30
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self {
+            enabled: Self::default_enabled(),
+            max_age_days: Self::default_max_age_days(),
+            expire_stale: false,
+            max_injected: Self::default_max_injected(),
+        }
+    }
+}
+
+/// MCP server whose tools are offered to the model.
+///
+/// A server with a `command` is launched as a child process speaking
+/// JSON-RPC over its standard input and output. A server with a `url` is
+/// reached over the Streamable HTTP transport, or the legacy HTTP+SSE
+/// transport when it does not support it. `${VAR}` in header values is
+/// replaced by the environment variable. The server is restarted, or
+/// reconnected, when it fails, up to `max_restarts` times in a row.
+///
+/// ```toml
+/// [mcp_servers.github]
+/// command = "npx"
+/// args = ["-y", "@modelcontextprotocol/server-github"]
+/// env = { GITHUB_TOKEN = "..." }
+///
+/// [mcp_servers.wiki]
+/// url = "https://mcp.intranet.example/wiki"
+/// headers = { X-Team = "${TEAM}" }
+/// bearer_token_command = "vault read -field=token secret/wiki"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct McpServer {
+    /// Command launching the server
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Arguments of the command
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Environment variables set for the server
+    #[serde(default)]
+    pub env: IndexMap<String, String>,
+    /// Directory the server runs in (defaults to the working directory)
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// URL of a server reached over HTTP
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Headers sent with every HTTP request
+    #[serde(default)]
+    pub headers: IndexMap<String, String>,
+    /// Environment variable holding the bearer token of an HTTP server
+    #[serde(default)]
+    pub bearer_token_env: Option<String>,
+    /// Shell command printing the bearer token of an HTTP server
+    #[serde(default)]
+    pub bearer_token_command: Option<String>,
+    /// Whether the server is started
+    #[serde(default = "McpServer::default_enabled")]
+    pub enabled: bool,
+    /// Seconds to wait for an answer to a request
+    #[serde(default = "McpServer::default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Number of times in a row the server is restarted after crashing;
+    /// a successful handshake resets the count
+    #[serde(default = "McpServer::default_max_restarts")]
+    pub max_restarts: usize,
+}
+
+impl McpServer {
+    /// Servers are started by default.
+    const fn default_enabled() -> bool {
+        true
+    }
+
+    /// Default request timeout.
+    const fn default_timeout_secs() -> u64 {
+        60
+    }
+
+    /// Default number of restarts.
+    const fn default_max_restarts() -> usize {
+        3
+    }
+}
+
+impl Default for McpServer {
+    fn default() -> Self {
+        Self {
+            command: None,
+            args: Vec::new(),
+            env: IndexMap::new(),
+            cwd: None,
+            url: None,
+            headers: IndexMap::new(),
+            bearer_token_env: None,
+            bearer_token_command: None,
+            enabled: Self::default_enabled(),
+            timeout_secs: Self::default_timeout_secs(),
+            max_restarts: Self::default_max_restarts(),
+        }
+    }
+}
+
+/// Lazy loading of tools through the `tool_search` tool.
+///
+/// When enabled, only the tools matching `always_loaded` are offered with
+/// every request, along with `tool_search`. By default these are the
+/// built-in file and shell tools and the tools the system prompt refers
+/// to. The other tools matching
+/// `deferred` are left out until the model finds them by searching their
+/// names and descriptions; found tools are offered from the next request
+/// on. Patterns are globs matched against tool names, e.g. `mcp__*`.
+///
+/// ```toml
+/// [tool_search]
+/// enabled = true
+/// always_loaded = [
+///     "read", "write", "edit", "glob", "grep", "bash",
+///     "skill", "memory", "mcp_resources",
+/// ]
+/// deferred = ["*"]
+/// max_results = 5
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ToolSearch {
+    /// Whether deferred tools are loaded on demand
+    #[serde(default)]
+    pub enabled: bool,
+    /// Tools offered with every request
+    #[serde(default = "ToolSearch::default_always_loaded")]
+    pub always_loaded: Vec<String>,
+    /// Tools offered only once found by a search
+    #[serde(default = "ToolSearch::default_deferred")]
+    pub deferred: Vec<String>,
+    /// Maximum number of tools activated by one search
+    #[serde(default = "ToolSearch::default_max_results")]
+    pub max_results: usize,
+}
+
+impl ToolSearch {
+    /// The built-in file and shell tools, and the skill, memory and MCP
+    /// resources tools the system prompt refers to, are always loaded by default.
+    fn default_always_loaded() -> Vec<String> {
+        [
+            "read",
+            "write",
+            "edit",
+            "glob",
+            "grep",
+            "bash",
+            "skill",
+            "memory",
+            "mcp_resources",
+        ]
+        .map(str::to_string)
+        .to_vec()
+    }
+
+    /// All other tools are deferred by default.
+    fn default_deferred() -> Vec<String> {
+        vec!["*".to_string()]
+    }
+
+    /// Default maximum number of tools activated by one search.
+    const fn default_max_results() -> usize {
+        5
+    }
+}
+
+impl Default for ToolSearch {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            always_loaded: Self::default_always_loaded(),
+            deferred: Self::default_deferred(),
+            max_results: Self::default_max_results(),
+        }
+    }
+}
+
+/// Persona profile, shaping how the assistant talks.
+///
+/// A persona only replaces the identity section of the system prompt; the
+/// tool usage guidance is left unchanged. Profiles are defined in the
+/// configuration file or as `personas/<name>.toml` files.
+///
+/// ```toml
+/// persona = "mentor"
+///
+/// [personas.mentor]
+/// name = "Sensei"
+/// tone = "patient and encouraging, explains the reasoning behind each change"
+/// language = "English"
+/// greeting = "Ready when you are. What shall we learn today?"
+/// prompt = "Point out one thing the user could do better after each task."
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Persona {
+    /// Name the assistant goes by (defaults to the profile name)
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Tone of the replies
+    #[serde(default)]
+    pub tone: Option<String>,
+    /// Language to reply in
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Greeting shown when the persona is chosen
+    #[serde(default)]
+    pub greeting: Option<String>,
+    /// Additional instructions on the persona's conversational style
+    #[serde(default)]
+    pub prompt: Option<String>,
 }
 
 /// Application configuration read from environment variables.
@@ -139,6 +975,68 @@ pub struct FileProvider {
 pub struct Config {
     /// Current working directory
     pub cwd: String,
+    /// Agent loop limits
+    pub limits: Limits,
+    /// Tool output size limits
+    pub output_limits: OutputLimits,
+    /// Automatic context compaction
+    pub compaction: Compaction,
+    /// Pruning of stale tool results
+    pub pruning: Pruning,
+    /// Redaction of secrets in exported sessions
+    pub redaction: Redaction,
+    /// Sections of the system prompt
+    pub prompt: Prompt,
+    /// Project instruction files
+    pub instructions: Instructions,
+    /// Environment section of the system prompt
+    pub environment: Environment,
+    /// Persistent memory
+    pub memory: Memory,
+    /// Directory where memories are stored (`None` disables memory)
+    pub memory_dir: Option<PathBuf>,
+    /// Directory where session transcripts are stored (`None` disables persistence)
+    pub sessions_dir: Option<PathBuf>,
+    /// Which session to start with
+    pub resume: Resume,
+    /// Continue the resumed session in a new branch
+    pub fork: bool,
+    /// Directories of Markdown command templates, lowest precedence first
+    pub commands_dirs: Vec<PathBuf>,
+    /// Directories of skills, lowest precedence first
+    pub skills_dirs: Vec<PathBuf>,
+    /// MCP servers
+    pub mcp_servers: IndexMap<String, McpServer>,
+    /// Lazy loading of tools
+    pub tool_search: ToolSearch,
+    /// Persona to start the session with (`None` for the default identity)
+    pub persona: Option<String>,
+    /// Persona profiles from the configuration file
+    pub personas: IndexMap<String, Persona>,
+    /// Directories of persona profile files, lowest precedence first
+    pub personas_dirs: Vec<PathBuf>,
+}
+
+/// User configuration directory, `~/.config/neco`.
+///
+/// # Returns
+///
+/// `None` if the configuration directory cannot be determined.
+#[must_use]
+pub fn user_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("neco"))
+}
+
+/// Which session to start with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Resume {
+    /// Start a new session
+    #[default]
+    New,
+    /// Resume the most recent session started in the current directory
+    Latest,
+    /// Resume the session with the given id (or unique id prefix)
+    Id(String),
 }
 
 /// Provider configuration (unified for all providers).
@@ -152,6 +1050,8 @@ pub struct ProviderSettings {
     pub model: String,
     /// API key
     pub api_key: String,
+    /// Token pricing used for cost tracking
+    pub pricing: Option<Pricing>,
 }
 
 impl ProviderSettings {
@@ -182,6 +1082,11 @@ impl ProviderSettings {
 impl Config {
     /// Load configuration from environment variables.
     ///
+    /// Loop limits, tool output limits, compaction and pruning settings are
+    /// taken from the configuration file.
+    /// Sessions are stored in the default sessions directory and a new
+    /// session is started.
+    ///
     /// # Environment Variables
     ///
     /// None required - uses current working directory as default.
@@ -194,7 +1099,45 @@ impl Config {
         let cwd =
             std::env::current_dir().map_or_else(|_| ".".to_string(), |p| p.display().to_string());
 
-        Self { cwd }
+        let file_config = Configuration::load();
+        let mut redaction = file_config.redaction.clone();
+        redaction.secrets = file_config.api_keys();
+
+        Self {
+            limits: file_config.limits,
+            output_limits: file_config.output_limits,
+            compaction: file_config.compaction,
+            pruning: file_config.pruning,
+            redaction,
+            prompt: file_config.prompt,
+            instructions: file_config.instructions,
+            environment: file_config.environment,
+            memory: file_config.memory,
+            memory_dir: crate::memory::default_dir(),
+            sessions_dir: crate::transcript::default_sessions_dir(),
+            resume: Resume::New,
+            fork: false,
+            commands_dirs: user_dir()
+                .map(|dir| dir.join("commands"))
+                .into_iter()
+                .chain([Path::new(&cwd).join(".neco").join("commands")])
+                .collect(),
+            skills_dirs: user_dir()
+                .map(|dir| dir.join("skills"))
+                .into_iter()
+                .chain([Path::new(&cwd).join(".neco").join("skills")])
+                .collect(),
+            mcp_servers: file_config.mcp_servers,
+            tool_search: file_config.tool_search,
+            persona: file_config.persona,
+            personas: file_config.personas,
+            personas_dirs: user_dir()
+                .map(|dir| dir.join("personas"))
+                .into_iter()
+                .chain([Path::new(&cwd).join(".neco").join("personas")])
+                .collect(),
+            cwd,
+        }
     }
 }
 
@@ -235,9 +1178,110 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_limits_from_toml() {
+        let config: Configuration = toml::from_str(
+            r"
+            [limits]
+            max_turns = 10
+            max_session_cost = 2.5
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(config.limits.max_turns, Some(10));
+        assert_eq!(config.limits.max_session_cost, Some(2.5));
+        assert_eq!(config.limits.max_duration_secs, None);
+        assert_eq!(config.limits.repetition, Repetition::default());
+    }
+
+    #[test]
+    fn test_repetition_from_toml() {
+        let config: Configuration = toml::from_str(
+            r#"
+            [limits.repetition]
+            action = "ask"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.limits.repetition.action, RepetitionAction::Ask);
+        assert_eq!(config.limits.repetition.threshold, 3);
+    }
+
+    #[test]
+    fn test_output_limits_for_tool() {
+        let config: Configuration = toml::from_str(
+            r"
+            [output_limits]
+            max_lines = 200
+
+            [output_limits.tools.bash]
+            max_bytes = 1000
+            ",
+        )
+        .unwrap();
+        let limits = config.output_limits;
+
+        assert_eq!(limits.for_tool("read"), (200, 30_000));
+        assert_eq!(limits.for_tool("bash"), (200, 1000));
+    }
+
     #[test]
     fn test_config_from_env() {
         let config = Config::from_env();
         assert!(!config.cwd.is_empty());
     }
+
+    #[test]
+    fn test_compaction_is_due() {
+        let config: Configuration = toml::from_str(
+            r"
+            [compaction]
+            context_window = 1000
+            threshold = 0.5
+            ",
+        )
+        .unwrap();
+        let compaction = config.compaction;
+        assert_eq!(compaction.keep_turns, 2);
+        assert!(!compaction.is_due(499));
+        assert!(compaction.is_due(500));
+
+        let disabled = Compaction {
+            enabled: false,
+            ..compaction
+        };
+        assert!(!disabled.is_due(10_000));
+    }
+
+    #[test]
+    fn test_pruning_keep_turns_for() {
+        let config: Configuration = toml::from_str(
+            r"
+            [pruning]
+            enabled = true
+            keep_turns = 6
+
+            [pruning.tools.read]
+            keep_turns = 2
+
+            [pruning.tools.edit]
+            enabled = false
+            ",
+        )
+        .unwrap();
+        let pruning = config.pruning;
+        assert_eq!(pruning.step, 5);
+        assert_eq!(pruning.keep_turns_for("bash"), Some(6));
+        assert_eq!(pruning.keep_turns_for("read"), Some(2));
+        assert_eq!(pruning.keep_turns_for("edit"), None);
+
+        let disabled = Pruning {
+            enabled: false,
+            ..pruning
+        };
+        assert_eq!(disabled.keep_turns_for("read"), None);
+        assert_eq!(Pruning::default().keep_turns_for("read"), None);
+    }
 }
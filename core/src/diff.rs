@@ -0,0 +1,252 @@
+//! Line-based unified diffs.
+//!
+//! A small LCS diff, good enough for previewing file changes. Common
+//! prefixes and suffixes are stripped first; if the remaining middle is too
+//! large for the quadratic algorithm it is shown as a single replacement.
+
+use std::fmt::Write;
+
+/// Lines of context shown around each change.
+const CONTEXT: usize = 3;
+
+/// Maximum size of the LCS table (lines × lines) before falling back to a
+/// single replacement hunk.
+const MAX_TABLE: usize = 4_000_000;
+
+/// A line of a diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Line<'a> {
+    /// Line present in both texts
+    Same(&'a str),
+    /// Line only in the old text
+    Removed(&'a str),
+    /// Line only in the new text
+    Added(&'a str),
+}
+
+/// Produce a unified diff between two texts.
+///
+/// # Arguments
+///
+/// * `old` - Original text
+/// * `new` - Changed text
+/// * `old_label` - Label of the original (the `---` line)
+/// * `new_label` - Label of the changed text (the `+++` line)
+///
+/// # Returns
+///
+/// The diff, or an empty string if the texts are equal.
+#[must_use]
+pub fn unified(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    if old == new {
+        return String::new();
+    }
+
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let lines = diff_lines(&old_lines, &new_lines);
+
+    let mut out = format!("--- {old_label}\n+++ {new_label}\n");
+    for hunk in hunks(&lines) {
+        out.push_str(&hunk);
+    }
+    out
+}
+
+/// Count added and removed lines between two texts.
+///
+/// # Returns
+///
+/// `(added, removed)`
+#[must_use]
+pub fn stats(old: &str, new: &str) -> (usize, usize) {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    diff_lines(&old_lines, &new_lines)
+        .iter()
+        .fold((0, 0), |(added, removed), line| match line {
+            Line::Added(_) => (added + 1, removed),
+            Line::Removed(_) => (added, removed + 1),
+            Line::Same(_) => (added, removed),
+        })
+}
+
+/// Compute the line-by-line diff.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_rest = old.get(prefix..).unwrap_or_default();
+    let new_rest = new.get(prefix..).unwrap_or_default();
+    let suffix = old_rest
+        .iter()
+        .rev()
+        .zip(new_rest.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = old_rest.get(..old_rest.len() - suffix).unwrap_or_default();
+    let new_mid = new_rest.get(..new_rest.len() - suffix).unwrap_or_default();
+
+    let mut lines: Vec<Line<'a>> = old.iter().take(prefix).map(|l| Line::Same(l)).collect();
+    if old_mid.len().saturating_mul(new_mid.len()) > MAX_TABLE {
+        lines.extend(old_mid.iter().map(|l| Line::Removed(l)));
+        lines.extend(new_mid.iter().map(|l| Line::Added(l)));
+    } else {
+        lines.extend(lcs_diff(old_mid, new_mid));
+    }
+    lines.extend(old_rest.iter().skip(old_mid.len()).map(|l| Line::Same(l)));
+    lines
+}
+
+/// Diff two slices using a longest-common-subsequence table.
+fn lcs_diff<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let width = new.len() + 1;
+    let mut table = vec![0usize; (old.len() + 1) * width];
+    let at = |i: usize, j: usize| i * width + j;
+
+    for (i, a) in old.iter().enumerate().rev() {
+        for (j, b) in new.iter().enumerate().rev() {
+            let value = if a == b {
+                table.get(at(i + 1, j + 1)).copied().unwrap_or(0) + 1
+            } else {
+                let down = table.get(at(i + 1, j)).copied().unwrap_or(0);
+                let right = table.get(at(i, j + 1)).copied().unwrap_or(0);
+                down.max(right)
+            };
+            if let Some(cell) = table.get_mut(at(i, j)) {
+                *cell = value;
+            }
+        }
+    }
+
+    let mut lines = Vec::with_capacity(old.len() + new.len());
+    let (mut i, mut j) = (0, 0);
+    while let (Some(a), Some(b)) = (old.get(i), new.get(j)) {
+        if a == b {
+            lines.push(Line::Same(a));
+            i += 1;
+            j += 1;
+        } else if table.get(at(i + 1, j)) >= table.get(at(i, j + 1)) {
+            lines.push(Line::Removed(a));
+            i += 1;
+        } else {
+            lines.push(Line::Added(b));
+            j += 1;
+        }
+    }
+    lines.extend(old.iter().skip(i).map(|l| Line::Removed(l)));
+    lines.extend(new.iter().skip(j).map(|l| Line::Added(l)));
+    lines
+}
+
+/// Group diff lines into unified hunks with context.
+fn hunks(lines: &[Line<'_>]) -> Vec<String> {
+    let changes: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Line::Same(_)))
+        .map(|(index, _)| index)
+        .collect();
+
+    // Merge changes whose context overlaps into ranges of line indices
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for index in changes {
+        let start = index.saturating_sub(CONTEXT);
+        let end = (index + CONTEXT + 1).min(lines.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let before = lines.get(..start).unwrap_or_default();
+            let old_start = before
+                .iter()
+                .filter(|l| !matches!(l, Line::Added(_)))
+                .count();
+            let new_start = before
+                .iter()
+                .filter(|l| !matches!(l, Line::Removed(_)))
+                .count();
+            let body = lines.get(start..end).unwrap_or_default();
+            let old_len = body.iter().filter(|l| !matches!(l, Line::Added(_))).count();
+            let new_len = body
+                .iter()
+                .filter(|l| !matches!(l, Line::Removed(_)))
+                .count();
+
+            let mut hunk = format!(
+                "@@ -{},{old_len} +{},{new_len} @@\n",
+                old_start + usize::from(old_len > 0),
+                new_start + usize::from(new_len > 0)
+            );
+            for line in body {
+                let _ = match line {
+                    Line::Same(text) => writeln!(hunk, " {text}"),
+                    Line::Removed(text) => writeln!(hunk, "-{text}"),
+                    Line::Added(text) => writeln!(hunk, "+{text}"),
+                };
+            }
+            hunk
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_equal() {
+        assert_eq!(unified("a\nb\n", "a\nb\n", "a", "b"), "");
+    }
+
+    #[test]
+    fn test_unified_change() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n10\n11\n";
+        let diff = unified(old, new, "a/file", "b/file");
+
+        assert_eq!(
+            diff,
+            "--- a/file\n+++ b/file\n\
+             @@ -2,9 +2,10 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n 9\n 10\n+11\n"
+        );
+    }
+
+    #[test]
+    fn test_unified_separate_hunks() {
+        let lines = |changed: bool| {
+            let lines: Vec<String> = (1..=20)
+                .map(|i| match i {
+                    2 if changed => "two".to_string(),
+                    19 if changed => "nineteen".to_string(),
+                    _ => i.to_string(),
+                })
+                .collect();
+            lines.join("\n") + "\n"
+        };
+        let (old, new) = (lines(false), lines(true));
+        let diff = unified(&old, &new, "a", "b");
+
+        assert_eq!(diff.matches("@@ -").count(), 2);
+        assert!(diff.contains("@@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n"));
+        assert!(diff.contains("@@ -16,5 +16,5 @@\n 16\n 17\n 18\n-19\n+nineteen\n 20\n"));
+    }
+
+    #[test]
+    fn test_unified_new_file() {
+        let diff = unified("", "a\nb\n", "/dev/null", "b/file");
+        assert!(diff.contains("@@ -0,0 +1,2 @@\n+a\n+b\n"));
+    }
+
+    #[test]
+    fn test_stats() {
+        assert_eq!(stats("a\nb\nc\n", "a\nx\nc\nd\n"), (2, 1));
+    }
+}
@@ -0,0 +1,224 @@
+//! Environment section of the system prompt.
+//!
+//! Tells the model where it runs: working directory, operating system,
+//! shell, date, git branch and status, recent commits and the top-level
+//! directory listing, so it does not spend tool calls finding out. Each
+//! item can be switched off in the configuration (see
+//! [`config::Environment`]).
+
+use crate::clock;
+use crate::config;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::process::Command;
+
+/// Maximum number of `git status` lines shown.
+const MAX_STATUS_LINES: usize = 10;
+
+/// Render the environment section.
+///
+/// # Arguments
+///
+/// * `cwd` - Working directory of the session
+/// * `settings` - Items to include
+#[must_use]
+pub fn render(cwd: &str, settings: &config::Environment) -> String {
+    let mut text = format!("# Environment\nWorking directory: {cwd}");
+    if settings.os {
+        let _ = write!(
+            text,
+            "\nOperating system: {} ({})",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        );
+    }
+    if settings.shell
+        && let Ok(shell) = std::env::var("SHELL")
+    {
+        let _ = write!(text, "\nShell: {shell}");
+    }
+    if settings.date {
+        let _ = write!(text, "\nDate: {}", clock::format_date(clock::now()));
+    }
+
+    let dir = Path::new(cwd);
+    let repository = git(dir, &["rev-parse", "--is-inside-work-tree"]).is_some();
+    if settings.git {
+        if repository {
+            let branch = git(dir, &["branch", "--show-current"])
+                .filter(|branch| !branch.is_empty())
+                .unwrap_or_else(|| "(detached HEAD)".to_string());
+            let _ = write!(text, "\nGit repository: yes, on branch {branch}");
+        } else {
+            text.push_str("\nGit repository: no");
+        }
+    }
+    if repository && settings.git_status {
+        text.push_str(&status(dir));
+    }
+    if repository
+        && settings.recent_commits > 0
+        && let Some(log) = git(
+            dir,
+            &[
+                "log",
+                "--oneline",
+                "--no-decorate",
+                &format!("-{}", settings.recent_commits),
+            ],
+        )
+        && !log.is_empty()
+    {
+        let _ = write!(text, "\n\nRecent commits:\n{log}");
+    }
+    if settings.listing {
+        text.push_str(&listing(dir, repository, settings.listing_limit));
+    }
+    text
+}
+
+/// Summary of `git status`: the first changed paths and the number of others.
+fn status(dir: &Path) -> String {
+    let Some(output) = git(dir, &["status", "--porcelain"]) else {
+        return String::new();
+    };
+    let lines: Vec<&str> = output.lines().collect();
+    if lines.is_empty() {
+        return "\n\nGit status: clean".to_string();
+    }
+
+    let mut text = format!("\n\nGit status ({} changed):", lines.len());
+    for line in lines.iter().take(MAX_STATUS_LINES) {
+        let _ = write!(text, "\n{line}");
+    }
+    if lines.len() > MAX_STATUS_LINES {
+        let _ = write!(text, "\n... and {} more", lines.len() - MAX_STATUS_LINES);
+    }
+    text
+}
+
+/// Top-level entries of a directory, leaving out files ignored by git.
+///
+/// # Arguments
+///
+/// * `dir` - Directory to list
+/// * `repository` - Whether the directory is inside a git repository
+/// * `limit` - Maximum number of entries shown
+fn listing(dir: &Path, repository: bool, limit: usize) -> String {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return String::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_string();
+            let is_dir = entry.file_type().is_ok_and(|kind| kind.is_dir());
+            Some(if is_dir { format!("{name}/") } else { name })
+        })
+        .filter(|name| name != ".git/")
+        .collect();
+    if repository && !names.is_empty() {
+        let mut args = vec!["check-ignore", "--"];
+        args.extend(names.iter().map(String::as_str));
+        // check-ignore exits with 1 when nothing is ignored
+        let ignored = run(dir, &args).unwrap_or_default();
+        let ignored: Vec<&str> = ignored.lines().collect();
+        names.retain(|name| !ignored.contains(&name.as_str()));
+    }
+    if names.is_empty() {
+        return String::new();
+    }
+    names.sort();
+
+    let mut text = "\n\nTop-level entries:".to_string();
+    for name in names.iter().take(limit) {
+        let _ = write!(text, "\n{name}");
+    }
+    if names.len() > limit {
+        let _ = write!(text, "\n... and {} more", names.len() - limit);
+    }
+    text
+}
+
+/// Run a git command, returning its trimmed output if it succeeds.
+pub(crate) fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    output.status.success().then(|| {
+        String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .to_string()
+    })
+}
+
+/// Run a git command, returning its output whatever its exit status.
+fn run(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run a git command in a test repository.
+    fn git_ok(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    #[test]
+    fn test_render_git_repository() {
+        let dir = crate::testing::TempDir::new("environment");
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::create_dir_all(dir.join("target")).unwrap();
+        std::fs::write(dir.join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(dir.join("README.md"), "readme").unwrap();
+        std::fs::write(dir.join("src").join("main.rs"), "fn main() {}").unwrap();
+        git_ok(&dir, &["init", "-q", "-b", "trunk"]);
+        git_ok(&dir, &["add", "-A"]);
+        git_ok(&dir, &["commit", "-q", "-m", "Initial commit"]);
+        std::fs::write(dir.join("notes.txt"), "notes").unwrap();
+
+        let cwd = dir.display().to_string();
+        let text = render(&cwd, &config::Environment::default());
+        assert!(text.starts_with(&format!("# Environment\nWorking directory: {cwd}")));
+        assert!(text.contains("Operating system: "));
+        assert!(text.contains("Date: "));
+        assert!(text.contains("Git repository: yes, on branch trunk"));
+        assert!(text.contains("Git status (1 changed):\n?? notes.txt"));
+        assert!(text.contains("Recent commits:\n"));
+        assert!(text.contains("Initial commit"));
+        assert!(text.contains("Top-level entries:\n.gitignore\nREADME.md\nnotes.txt\nsrc/"));
+        assert!(!text.contains("target/\n") && !text.ends_with("target/"));
+        assert!(!text.contains(".git/"));
+
+        let settings = config::Environment {
+            os: false,
+            date: false,
+            git_status: false,
+            recent_commits: 0,
+            listing_limit: 1,
+            ..config::Environment::default()
+        };
+        let text = render(&cwd, &settings);
+        assert!(!text.contains("Operating system"));
+        assert!(!text.contains("Date: "));
+        assert!(!text.contains("Git status"));
+        assert!(!text.contains("Recent commits"));
+        assert!(text.ends_with("Top-level entries:\n.gitignore\n... and 3 more"));
+    }
+}
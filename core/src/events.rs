@@ -3,6 +3,12 @@
 //! Defines the core events used throughout the application for communication
 //! between different components of the system.
 
+use crate::api::anthropic::Usage;
+use crate::checkpoint;
+use crate::commands;
+use crate::guard::LimitExceeded;
+use crate::memory;
+use crate::prompt;
 use serde::{Deserialize, Serialize};
 
 /// Core event enumeration representing different types of events
@@ -63,6 +69,258 @@ pub enum CoreEvent {
     /// This event signals the completion of a message or conversation,
     /// used to indicate when processing should stop or continue.
     MessageStop,
+
+    /// Limit reached event, the agent loop stopped because of a guardrail
+    ///
+    /// This event is emitted when a configured limit (tool iterations,
+    /// wall-clock time, tokens or cost) stops the agent loop.
+    LimitReached(LimitExceeded),
+
+    /// Repetition detected event, the model keeps making the same tool calls
+    ///
+    /// This event is emitted when the same tool call (or cycle of calls)
+    /// is repeated back to back. When `paused` is true the agent loop has
+    /// stopped and waits for the user to decide how to continue.
+    RepetitionDetected {
+        /// Name of the repeated tool
+        name: String,
+        /// Number of back-to-back repeats
+        repeats: usize,
+        /// Whether the loop paused to ask the user
+        paused: bool,
+    },
+
+    /// Steering queued event, user input was queued while a turn is running
+    ///
+    /// The message will be delivered to the model together with the next
+    /// batch of tool results, or as the next turn if the model finishes first.
+    SteeringQueued(String),
+
+    /// Steering delivered event, a queued user message reached the model
+    ///
+    /// This event is emitted when a message previously reported by
+    /// `SteeringQueued` has been added to the conversation.
+    SteeringDelivered(String),
+
+    /// Session resumed event, a stored session was loaded
+    ///
+    /// This event is emitted once at startup when the conversation history
+    /// was restored from a transcript.
+    SessionResumed {
+        /// Session identifier
+        id: String,
+        /// Number of restored messages
+        messages: usize,
+    },
+
+    /// Cleared event, the conversation history was cleared
+    Cleared,
+
+    /// Turns event, lists the user turns that can be rewound to
+    ///
+    /// Each entry is the text of a user message, oldest first; turn numbers
+    /// start at 1.
+    Turns(Vec<String>),
+
+    /// Rewound event, the conversation was rewound to before a user turn
+    Rewound {
+        /// 1-based number of the removed user turn
+        turn: usize,
+        /// Text of the removed user message, for editing and resending
+        text: String,
+    },
+
+    /// Forked event, the session continues in a new branch
+    Forked {
+        /// Id of the new branch
+        id: String,
+        /// Id of the session it was branched from
+        parent: String,
+    },
+
+    /// Compacted event, older turns were replaced by a summary
+    ///
+    /// The full history before compaction is kept in the session transcript.
+    Compacted {
+        /// Number of messages replaced by the summary
+        replaced: usize,
+        /// Number of recent messages kept verbatim
+        kept: usize,
+        /// Whether compaction was triggered by the context size
+        automatic: bool,
+    },
+
+    /// Usage event, token usage of a single model response
+    ///
+    /// Recorded in the transcript so that usage totals of stored sessions
+    /// can be computed (e.g. for exports).
+    Usage(Usage),
+
+    /// Exported event, the session was written to a file
+    Exported {
+        /// Path of the written file
+        path: String,
+    },
+
+    /// Checkpoints event, lists the file checkpoints, oldest first
+    Checkpoints(Vec<checkpoint::Summary>),
+
+    /// Restore preview event, files are about to be restored
+    ///
+    /// The restore waits for the user to confirm with `y`; any other input
+    /// cancels it.
+    RestorePreview {
+        /// Checkpoint the files are restored to
+        checkpoint: usize,
+        /// Unified diff from the current files to their restored state
+        diff: String,
+        /// Files to restore
+        files: Vec<String>,
+        /// Tools that ran since the checkpoint and whose file changes are not restored
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        untracked: Vec<String>,
+    },
+
+    /// Restored event, files were restored from a checkpoint
+    Restored {
+        /// Checkpoint the files were restored to
+        checkpoint: usize,
+        /// Restored files
+        files: Vec<String>,
+        /// Tools that ran since the checkpoint and whose file changes were not restored
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        untracked: Vec<String>,
+    },
+
+    /// Restore cancelled event, the user did not confirm a restore
+    RestoreCancelled,
+
+    /// Diff event, the consolidated changes the agent made to files
+    Diff {
+        /// First checkpoint included, or `None` for the whole session
+        since: Option<usize>,
+        /// Changed lines per file
+        files: Vec<checkpoint::Stat>,
+        /// Unified diff of all changed files
+        patch: String,
+        /// Path of the patch file the diff was saved to, if any
+        saved: Option<String>,
+        /// Tools that ran in the range and whose file changes are not in the diff
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        untracked: Vec<String>,
+    },
+
+    /// Commands event, lists the slash commands for `/help`
+    Commands(Vec<commands::Info>),
+
+    /// Model event, the provider and model of the session
+    Model {
+        /// Name of the provider
+        provider: String,
+        /// Name of the model
+        model: String,
+    },
+
+    /// Model switched event, the session continues with another provider or model
+    ///
+    /// All providers speak the Anthropic Messages format, so the history is
+    /// sent to the new model unchanged.
+    ModelSwitched {
+        /// Previous provider and model, as `provider/model`
+        from: String,
+        /// Name of the new provider
+        provider: String,
+        /// Name of the new model
+        model: String,
+    },
+
+    /// Cost event, the token usage and cost of the session so far
+    Cost {
+        /// Total token usage
+        usage: Usage,
+        /// Cost in USD, or `None` when no pricing is known for the model
+        cost: Option<f64>,
+    },
+
+    /// Prompt event, the assembled system prompt with the size of each section
+    Prompt {
+        /// The system prompt sent to the model
+        text: String,
+        /// Size estimate of each section, in prompt order
+        sections: Vec<prompt::Estimate>,
+    },
+
+    /// Tools event, the name and description of each tool available to the model
+    Tools(Vec<(String, String)>),
+
+    /// Persona event, the assistant talks as another persona
+    Persona {
+        /// Name the assistant goes by, or `None` for the default identity
+        name: Option<String>,
+        /// Greeting of the persona, if it has one
+        greeting: Option<String>,
+    },
+
+    /// Memories event, lists the memories of the project for `/memory`
+    Memories(Vec<memory::Summary>),
+
+    /// Memory event, a memory shown in full by `/memory show`
+    Memory {
+        /// The memory
+        memory: memory::Memory,
+        /// Why the memory is stale, if it is
+        stale: Option<String>,
+    },
+
+    /// Memory forgotten event, a memory deleted by `/memory forget`
+    MemoryForgotten {
+        /// Identifier of the memory
+        id: String,
+        /// Summary of the memory
+        summary: String,
+    },
+
+    /// MCP connected event, a server started and its tools offered
+    McpConnected {
+        /// Name of the server in the configuration
+        server: String,
+        /// Number of tools the server offers
+        tools: usize,
+        /// Number of prompts the server offers as commands
+        prompts: usize,
+    },
+
+    /// MCP resource event, a resource referenced in a message was inlined
+    McpResource {
+        /// Name of the server offering the resource
+        server: String,
+        /// URI of the resource
+        uri: String,
+    },
+
+    /// Prompt argument event, an MCP prompt asks for a missing argument
+    PromptArgument {
+        /// Command invoking the prompt
+        command: String,
+        /// Name of the argument
+        name: String,
+        /// Description of the argument
+        description: String,
+    },
+
+    /// Prompt cancelled event, the user did not give the missing arguments
+    PromptCancelled {
+        /// Command invoking the prompt
+        command: String,
+    },
+
+    /// Personas event, lists the persona profiles for `/persona`
+    Personas {
+        /// Profile of the current persona, `None` for the default identity
+        current: Option<String>,
+        /// Names of the available profiles
+        names: Vec<String>,
+    },
 }
 
 #[cfg(test)]
@@ -117,6 +375,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_core_event_limit_reached() {
+        let event = CoreEvent::LimitReached(LimitExceeded {
+            limit: crate::guard::Limit::MaxTurns,
+            message: "Stopped after 3 tool iterations".to_string(),
+        });
+        let serialized = serde_json::to_string(&event).unwrap();
+        let deserialized: CoreEvent = serde_json::from_str(&serialized).unwrap();
+
+        match deserialized {
+            CoreEvent::LimitReached(exceeded) => {
+                assert_eq!(exceeded.limit, crate::guard::Limit::MaxTurns);
+            },
+            _ => panic!("Wrong event type"),
+        }
+    }
+
     #[test]
     fn test_core_event_clone() {
         let original = CoreEvent::TextDelta("test".to_string());
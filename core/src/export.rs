@@ -0,0 +1,653 @@
+//! Export of sessions to Markdown and self-contained HTML.
+//!
+//! A session is rendered from its messages: user and assistant turns,
+//! thinking blocks, tool calls with their inputs and (collapsible) outputs,
+//! followed by the token usage totals taken from the recorded
+//! [`CoreEvent::Usage`] events. Secrets are redacted before rendering.
+
+use crate::api::anthropic::Usage;
+use crate::clock;
+use crate::config::Redaction;
+use crate::events::CoreEvent;
+use crate::transcript::{self, Record};
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Text replacing redacted secrets.
+const REDACTED: &str = "[REDACTED]";
+
+/// Secrets shorter than this are not redacted literally, to avoid hiding common words.
+const MIN_SECRET_LEN: usize = 8;
+
+/// Patterns of common API keys and credentials, always redacted.
+///
+/// When a pattern has a group named `secret`, only that group is replaced.
+const BUILTIN_PATTERNS: &[&str] = &[
+    r"sk-[A-Za-z0-9_\-]{20,}",
+    r"AKIA[0-9A-Z]{16}",
+    r"gh[pousr]_[A-Za-z0-9]{36,}",
+    r"github_pat_[A-Za-z0-9_]{22,}",
+    r"xox[abprs]-[A-Za-z0-9\-]{10,}",
+    r"AIza[0-9A-Za-z_\-]{35}",
+    r"-----BEGIN [A-Z ]*PRIVATE KEY-----[\s\S]*?-----END [A-Z ]*PRIVATE KEY-----",
+    r"(?i)\bbearer\s+(?P<secret>[A-Za-z0-9._~+/\-]{16,}=*)",
+    r#"(?i)(?:api[_-]?key|secret|token|password|passwd)["']?\s*[:=]\s*["']?(?P<secret>[^\s"'&,;]{8,})"#,
+];
+
+/// Stylesheet of the HTML export.
+const STYLE: &str = "body{margin:0;background:#f6f8fa;color:#1f2328;\
+font:15px/1.5 -apple-system,BlinkMacSystemFont,'Segoe UI',Helvetica,Arial,sans-serif}\
+main{max-width:960px;margin:0 auto;padding:24px}\
+h1{font-size:22px;margin:0 0 8px}\
+.meta{color:#59636e;font-size:13px;margin-bottom:24px}.meta span{margin-right:16px}\
+.turn{background:#fff;border:1px solid #d1d9e0;border-radius:6px;margin:12px 0;padding:12px 16px}\
+.turn.user{border-left:4px solid #0969da}.turn.assistant{border-left:4px solid #8250df}\
+.role{font-weight:600;font-size:13px;color:#59636e;margin-bottom:4px}\
+.text{white-space:pre-wrap;word-wrap:break-word}\
+pre{background:#f6f8fa;border-radius:6px;padding:8px 12px;overflow-x:auto;font-size:13px;margin:6px 0}\
+.tool{margin:8px 0}.tool .name{font-family:monospace;font-weight:600}\
+details>summary{cursor:pointer;color:#59636e;font-size:13px}\
+details.error>summary{color:#d1242f}\
+table{border-collapse:collapse;margin-top:8px}td,th{border:1px solid #d1d9e0;padding:4px 10px;text-align:right}";
+
+/// Export format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Markdown, with tool outputs in `<details>` blocks
+    Markdown,
+    /// Single-file HTML page with inline styles
+    Html,
+}
+
+impl Format {
+    /// Guess the format from a file extension; anything but `.html`/`.htm` is Markdown.
+    #[must_use]
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm") => {
+                Self::Html
+            },
+            _ => Self::Markdown,
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Ok(Self::Markdown),
+            "html" | "htm" => Ok(Self::Html),
+            other => anyhow::bail!("Unknown export format '{other}' (expected md or html)"),
+        }
+    }
+}
+
+/// Redacts secrets from exported text.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    /// Built-in and configured patterns
+    patterns: Vec<Regex>,
+    /// Literal secrets, longest first
+    secrets: Vec<String>,
+}
+
+impl Redactor {
+    /// Create a redactor from the redaction settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a configured pattern is not a valid regular expression.
+    pub fn new(redaction: &Redaction) -> Result<Self> {
+        let mut patterns = BUILTIN_PATTERNS
+            .iter()
+            .filter_map(|pattern| Regex::new(pattern).ok())
+            .collect::<Vec<_>>();
+        for pattern in &redaction.patterns {
+            patterns.push(
+                Regex::new(pattern)
+                    .with_context(|| format!("Invalid redaction pattern: {pattern}"))?,
+            );
+        }
+
+        let mut secrets: Vec<String> = redaction
+            .secrets
+            .iter()
+            .filter(|secret| secret.len() >= MIN_SECRET_LEN)
+            .cloned()
+            .collect();
+        secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+        Ok(Self { patterns, secrets })
+    }
+
+    /// Replace secrets in a text with `[REDACTED]`.
+    #[must_use]
+    pub fn redact(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for secret in &self.secrets {
+            if text.contains(secret.as_str()) {
+                text = text.replace(secret.as_str(), REDACTED);
+            }
+        }
+        for pattern in &self.patterns {
+            if pattern.is_match(&text) {
+                text = pattern.replace_all(&text, replace_secret).into_owned();
+            }
+        }
+        text
+    }
+}
+
+/// Replacement of a pattern match: the `secret` group if any, else the whole match.
+fn replace_secret(captures: &Captures<'_>) -> String {
+    let Some(whole) = captures.get(0) else {
+        return REDACTED.to_string();
+    };
+    match captures.name("secret") {
+        Some(secret) => {
+            let text = whole.as_str();
+            let start = secret.start() - whole.start();
+            let end = secret.end() - whole.start();
+            format!(
+                "{}{REDACTED}{}",
+                text.get(..start).unwrap_or_default(),
+                text.get(end..).unwrap_or_default()
+            )
+        },
+        None => REDACTED.to_string(),
+    }
+}
+
+/// A session to export.
+#[derive(Debug, Clone, Default)]
+pub struct Document {
+    /// Session id, if persisted
+    pub id: Option<String>,
+    /// Model used
+    pub model: String,
+    /// Working directory
+    pub cwd: String,
+    /// Start time (Unix seconds), if known
+    pub created_at: Option<u64>,
+    /// Messages, oldest first
+    pub messages: Vec<Value>,
+    /// Total token usage
+    pub usage: Usage,
+}
+
+impl Document {
+    /// Build a document from the records of a stored session.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the records contain no metadata.
+    #[must_use]
+    pub fn from_records(records: &[Record]) -> Option<Self> {
+        let info = transcript::summarize(records)?;
+        Some(Self {
+            id: Some(info.id),
+            model: info.model,
+            cwd: info.cwd,
+            created_at: Some(info.created_at),
+            messages: transcript::rebuild_history(records),
+            usage: usage_totals(records),
+        })
+    }
+
+    /// Render the document.
+    #[must_use]
+    pub fn render(&self, format: Format, redactor: &Redactor) -> String {
+        let turns = turns(&self.messages, redactor);
+        match format {
+            Format::Markdown => self.markdown(&turns),
+            Format::Html => self.html(&turns),
+        }
+    }
+
+    /// Title of the document.
+    fn title(&self) -> String {
+        self.id
+            .as_ref()
+            .map_or_else(|| "Session".to_string(), |id| format!("Session {id}"))
+    }
+
+    /// Render as Markdown.
+    fn markdown(&self, turns: &[Turn]) -> String {
+        let mut out = format!("# {}\n\n", self.title());
+        let _ = writeln!(out, "- **Model:** {}", self.model);
+        let _ = writeln!(out, "- **Directory:** `{}`", self.cwd);
+        if let Some(at) = self.created_at {
+            let _ = writeln!(out, "- **Started:** {}", clock::format_datetime(at));
+        }
+
+        for turn in turns {
+            let _ = write!(out, "\n## {}\n", turn.role);
+            for part in &turn.parts {
+                match part {
+                    Part::Text(text) => {
+                        let _ = write!(out, "\n{text}\n");
+                    },
+                    Part::Thinking(text) => {
+                        let _ = write!(
+                            out,
+                            "\n<details>\n<summary>Thinking</summary>\n\n{text}\n\n</details>\n"
+                        );
+                    },
+                    Part::Tool(call) => {
+                        let input_fence = fence(&call.input);
+                        let _ = write!(
+                            out,
+                            "\n**Tool: `{}`**\n\n{input_fence}json\n{}\n{input_fence}\n",
+                            call.name, call.input
+                        );
+                        if let Some(output) = &call.output {
+                            let output_fence = fence(output);
+                            let _ = write!(
+                                out,
+                                "\n<details>\n<summary>{}</summary>\n\n{output_fence}\n{output}\n{output_fence}\n\n</details>\n",
+                                call.summary()
+                            );
+                        }
+                    },
+                }
+            }
+        }
+
+        let usage = &self.usage;
+        let _ = write!(
+            out,
+            "\n## Usage\n\n\
+             | Input tokens | Output tokens | Cache writes | Cache reads |\n\
+             |---:|---:|---:|---:|\n\
+             | {} | {} | {} | {} |\n",
+            usage.input_tokens,
+            usage.output_tokens,
+            usage.cache_creation_input_tokens,
+            usage.cache_read_input_tokens
+        );
+        out
+    }
+
+    /// Render as a single HTML page.
+    fn html(&self, turns: &[Turn]) -> String {
+        let title = escape(&self.title());
+        let mut out = format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+             <title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<main>\n\
+             <h1>{title}</h1>\n<div class=\"meta\"><span>Model: {}</span><span>Directory: <code>{}</code></span>",
+            escape(&self.model),
+            escape(&self.cwd)
+        );
+        if let Some(at) = self.created_at {
+            let _ = write!(out, "<span>Started: {}</span>", clock::format_datetime(at));
+        }
+        out.push_str("</div>\n");
+
+        for turn in turns {
+            let _ = write!(
+                out,
+                "<section class=\"turn {}\">\n<div class=\"role\">{}</div>\n",
+                turn.role.to_ascii_lowercase(),
+                turn.role
+            );
+            for part in &turn.parts {
+                match part {
+                    Part::Text(text) => {
+                        let _ = writeln!(out, "<div class=\"text\">{}</div>", escape(text));
+                    },
+                    Part::Thinking(text) => {
+                        let _ = writeln!(
+                            out,
+                            "<details class=\"thinking\"><summary>Thinking</summary><div class=\"text\">{}</div></details>",
+                            escape(text)
+                        );
+                    },
+                    Part::Tool(call) => {
+                        let _ = write!(
+                            out,
+                            "<div class=\"tool\"><span class=\"name\">{}</span><pre>{}</pre>",
+                            escape(&call.name),
+                            escape(&call.input)
+                        );
+                        if let Some(output) = &call.output {
+                            let class = if call.is_error {
+                                " class=\"error\""
+                            } else {
+                                ""
+                            };
+                            let _ = write!(
+                                out,
+                                "<details{class}><summary>{}</summary><pre>{}</pre></details>",
+                                call.summary(),
+                                escape(output)
+                            );
+                        }
+                        out.push_str("</div>\n");
+                    },
+                }
+            }
+            out.push_str("</section>\n");
+        }
+
+        let usage = &self.usage;
+        let _ = write!(
+            out,
+            "<h2>Usage</h2>\n<table>\n\
+             <tr><th>Input tokens</th><th>Output tokens</th><th>Cache writes</th><th>Cache reads</th></tr>\n\
+             <tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n\
+             </table>\n</main>\n</body>\n</html>\n",
+            usage.input_tokens,
+            usage.output_tokens,
+            usage.cache_creation_input_tokens,
+            usage.cache_read_input_tokens
+        );
+        out
+    }
+}
+
+/// Sum the usage of the model responses recorded in a transcript.
+#[must_use]
+pub fn usage_totals(records: &[Record]) -> Usage {
+    records
+        .iter()
+        .filter_map(|record| match record {
+            Record::Event {
+                event: CoreEvent::Usage(usage),
+                ..
+            } => Some(usage),
+            _ => None,
+        })
+        .fold(Usage::default(), |mut total, usage| {
+            total.add(usage);
+            total
+        })
+}
+
+/// A message of the conversation, as rendered.
+struct Turn {
+    /// "User" or "Assistant"
+    role: &'static str,
+    /// Rendered content
+    parts: Vec<Part>,
+}
+
+/// Part of a rendered message.
+enum Part {
+    /// Plain text
+    Text(String),
+    /// Model reasoning
+    Thinking(String),
+    /// Tool call with its result
+    Tool(Call),
+}
+
+/// A tool call with its result.
+struct Call {
+    /// Tool name
+    name: String,
+    /// Pretty-printed input
+    input: String,
+    /// Result content, if the call was answered
+    output: Option<String>,
+    /// Whether the result is an error
+    is_error: bool,
+}
+
+impl Call {
+    /// Summary line of the collapsible output.
+    fn summary(&self) -> String {
+        let lines = self.output.as_deref().map_or(0, |o| o.lines().count());
+        let kind = if self.is_error { "Error" } else { "Output" };
+        format!("{kind} ({lines} lines)")
+    }
+}
+
+/// Group messages into rendered turns, pairing tool calls with their results.
+///
+/// User messages holding only tool results are folded into the calls.
+fn turns(messages: &[Value], redactor: &Redactor) -> Vec<Turn> {
+    let mut results: HashMap<String, (String, bool)> = HashMap::new();
+    for block in messages.iter().flat_map(blocks) {
+        if block.get("type").and_then(Value::as_str) == Some("tool_result")
+            && let Some(id) = block.get("tool_use_id").and_then(Value::as_str)
+        {
+            let is_error = block
+                .get("is_error")
+                .and_then(Value::as_bool)
+                .unwrap_or(false);
+            results.insert(
+                id.to_string(),
+                (result_text(block.get("content")), is_error),
+            );
+        }
+    }
+
+    let mut turns = Vec::new();
+    for message in messages {
+        let role = match message.get("role").and_then(Value::as_str) {
+            Some("assistant") => "Assistant",
+            _ => "User",
+        };
+        let parts: Vec<Part> = blocks(message)
+            .iter()
+            .filter_map(|block| part(block, &results, redactor))
+            .collect();
+        if !parts.is_empty() {
+            turns.push(Turn { role, parts });
+        }
+    }
+    turns
+}
+
+/// Content blocks of a message; string content becomes a single text block.
+fn blocks(message: &Value) -> Vec<Value> {
+    match message.get("content") {
+        Some(Value::String(text)) => vec![serde_json::json!({"type": "text", "text": text})],
+        Some(Value::Array(blocks)) => blocks.clone(),
+        _ => Vec::new(),
+    }
+}
+
+/// Render a content block, or `None` for blocks not shown on their own.
+fn part(
+    block: &Value,
+    results: &HashMap<String, (String, bool)>,
+    redactor: &Redactor,
+) -> Option<Part> {
+    let text = |key: &str| block.get(key).and_then(Value::as_str).unwrap_or_default();
+    match block.get("type").and_then(Value::as_str)? {
+        "text" if !text("text").trim().is_empty() => {
+            Some(Part::Text(redactor.redact(text("text"))))
+        },
+        "thinking" if !text("thinking").trim().is_empty() => {
+            Some(Part::Thinking(redactor.redact(text("thinking"))))
+        },
+        "tool_use" => {
+            let input = block
+                .get("input")
+                .and_then(|input| serde_json::to_string_pretty(input).ok())
+                .unwrap_or_default();
+            let result = block
+                .get("id")
+                .and_then(Value::as_str)
+                .and_then(|id| results.get(id));
+            Some(Part::Tool(Call {
+                name: text("name").to_string(),
+                input: redactor.redact(&input),
+                output: result.map(|(output, _)| redactor.redact(output)),
+                is_error: result.is_some_and(|(_, is_error)| *is_error),
+            }))
+        },
+        _ => None,
+    }
+}
+
+/// Text of a tool result, whose content is a string or a list of text blocks.
+fn result_text(content: Option<&Value>) -> String {
+    match content {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(blocks)) => blocks
+            .iter()
+            .filter_map(|block| block.get("text").and_then(Value::as_str))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// Markdown code fence longer than any backtick run in the text.
+fn fence(text: &str) -> String {
+    let longest = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    "`".repeat(longest.max(2) + 1)
+}
+
+/// Escape text for HTML.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn document() -> Document {
+        Document {
+            id: Some("20260101-120000-abcd".to_string()),
+            model: "claude".to_string(),
+            cwd: "/project".to_string(),
+            created_at: Some(0),
+            messages: vec![
+                json!({"role": "user", "content": "Show <main.rs>"}),
+                json!({"role": "assistant", "content": [
+                    {"type": "thinking", "thinking": "Read the file first"},
+                    {"type": "text", "text": "Reading it"},
+                    {"type": "tool_use", "id": "c1", "name": "read", "input": {"path": "main.rs"}}
+                ]}),
+                json!({"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "c1", "content": "fn main() {}\n```"}
+                ]}),
+                json!({"role": "assistant", "content": [{"type": "text", "text": "Done"}]}),
+            ],
+            usage: Usage {
+                input_tokens: 120,
+                output_tokens: 30,
+                ..Usage::default()
+            },
+        }
+    }
+
+    fn redactor() -> Redactor {
+        Redactor::new(&Redaction::default()).unwrap()
+    }
+
+    #[test]
+    fn test_markdown() {
+        let markdown = document().render(Format::Markdown, &redactor());
+
+        assert!(markdown.starts_with("# Session 20260101-120000-abcd\n"));
+        assert!(markdown.contains("## User\n\nShow <main.rs>\n"));
+        assert!(markdown.contains("<summary>Thinking</summary>\n\nRead the file first"));
+        assert!(markdown.contains("**Tool: `read`**"));
+        assert!(
+            markdown
+                .contains("<summary>Output (2 lines)</summary>\n\n````\nfn main() {}\n```\n````")
+        );
+        assert!(markdown.contains("| 120 | 30 | 0 | 0 |"));
+        // The tool result message is folded into the call
+        assert_eq!(markdown.matches("## User").count(), 1);
+    }
+
+    #[test]
+    fn test_html() {
+        let html = document().render(Format::Html, &redactor());
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("Show &lt;main.rs&gt;"));
+        assert!(html.contains("<details class=\"thinking\">"));
+        assert!(html.contains("<summary>Output (2 lines)</summary><pre>fn main() {}"));
+        assert!(html.contains("<td>120</td>"));
+        assert!(!html.contains("<main.rs>"));
+    }
+
+    #[test]
+    fn test_redaction() {
+        let redaction = Redaction {
+            patterns: vec![r"corp-[0-9a-f]{8}".to_string()],
+            secrets: vec!["my-provider-key-123".to_string(), "short".to_string()],
+        };
+        let redactor = Redactor::new(&redaction).unwrap();
+
+        let text = "key sk-ant-REDACTED and corp-deadbeef, \
+                    my-provider-key-123, password=hunter2hunter2, short";
+        let redacted = redactor.redact(text);
+        assert!(!redacted.contains("sk-ant"));
+        assert!(!redacted.contains("deadbeef"));
+        assert!(!redacted.contains("my-provider-key"));
+        assert!(redacted.contains("password=[REDACTED]"));
+        assert!(redacted.ends_with("short"));
+
+        let invalid = Redaction {
+            patterns: vec!["(".to_string()],
+            secrets: Vec::new(),
+        };
+        let error = Redactor::new(&invalid).unwrap_err();
+        assert!(error.to_string().contains("Invalid redaction pattern"));
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Format::from_path(Path::new("out.html")), Format::Html);
+        assert_eq!(Format::from_path(Path::new("out.md")), Format::Markdown);
+        assert_eq!("HTML".parse::<Format>().unwrap(), Format::Html);
+        "pdf".parse::<Format>().unwrap_err();
+    }
+
+    #[test]
+    fn test_usage_totals() {
+        let usage = Usage {
+            input_tokens: 10,
+            output_tokens: 5,
+            ..Usage::default()
+        };
+        let records = vec![
+            Record::Event {
+                at: 0,
+                event: CoreEvent::Usage(usage),
+            },
+            Record::Event {
+                at: 1,
+                event: CoreEvent::MessageStop,
+            },
+            Record::Event {
+                at: 2,
+                event: CoreEvent::Usage(usage),
+            },
+        ];
+        let total = usage_totals(&records);
+        assert_eq!(total.input_tokens, 20);
+        assert_eq!(total.output_tokens, 10);
+    }
+}
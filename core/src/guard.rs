@@ -0,0 +1,446 @@
+//! Agent loop guardrails.
+//!
+//! Tracks tool iterations, wall-clock time, token usage and cost for the
+//! current user message and the whole session, and reports when one of the
+//! configured [`Limits`] has been reached. Also detects repeated identical
+//! tool calls (see [`repetition`]).
+
+pub mod repetition;
+
+use crate::api::anthropic::Usage;
+use crate::config::{Limits, Pricing, RepetitionAction};
+use repetition::{Repeat, Tracker};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// A limit that can stop the agent loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Limit {
+    /// Tool iterations per user message
+    MaxTurns,
+    /// Wall-clock time per user message
+    Duration,
+    /// Tokens per user message
+    TurnTokens,
+    /// Tokens per session
+    SessionTokens,
+    /// Cost per user message
+    TurnCost,
+    /// Cost per session
+    SessionCost,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key = match self {
+            Self::MaxTurns => "max_turns",
+            Self::Duration => "max_duration_secs",
+            Self::TurnTokens => "max_turn_tokens",
+            Self::SessionTokens => "max_session_tokens",
+            Self::TurnCost => "max_turn_cost",
+            Self::SessionCost => "max_session_cost",
+        };
+        write!(f, "{key}")
+    }
+}
+
+/// Details about a limit that has been reached.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LimitExceeded {
+    /// The limit that was reached
+    pub limit: Limit,
+    /// Human-readable explanation
+    pub message: String,
+}
+
+/// Accumulated usage for a turn or a session.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Tally {
+    /// Token usage
+    pub usage: Usage,
+    /// Cost in USD (zero when no pricing is configured)
+    pub cost: f64,
+}
+
+impl Tally {
+    /// Add a response's usage and cost.
+    fn add(&mut self, usage: &Usage, cost: f64) {
+        self.usage.add(usage);
+        self.cost += cost;
+    }
+}
+
+/// Guard enforcing [`Limits`] on the agent loop.
+#[derive(Debug, Clone)]
+pub struct Guard {
+    /// Configured limits
+    limits: Limits,
+    /// Token pricing of the active model
+    pricing: Option<Pricing>,
+    /// Usage since the session started
+    session: Tally,
+    /// Usage since the current user message
+    turn: Tally,
+    /// Usage of the most recent response
+    last: Usage,
+    /// Tool iterations since the current user message
+    tool_rounds: u32,
+    /// When the current user message started processing
+    turn_started: Instant,
+    /// Recent tool calls, for repetition detection
+    tracker: Tracker,
+}
+
+impl Guard {
+    /// Create a new guard.
+    ///
+    /// # Arguments
+    ///
+    /// * `limits` - Limits to enforce
+    /// * `pricing` - Token pricing used for cost limits, if known
+    #[must_use]
+    pub fn new(limits: Limits, pricing: Option<Pricing>) -> Self {
+        Self {
+            limits,
+            pricing,
+            session: Tally::default(),
+            turn: Tally::default(),
+            last: Usage::default(),
+            tool_rounds: 0,
+            turn_started: Instant::now(),
+            tracker: Tracker::default(),
+        }
+    }
+
+    /// Set the token pricing used for the cost of later responses.
+    pub const fn set_pricing(&mut self, pricing: Option<Pricing>) {
+        self.pricing = pricing;
+    }
+
+    /// Reset per-turn counters at the start of a user message.
+    pub fn begin_turn(&mut self) {
+        self.turn = Tally::default();
+        self.tool_rounds = 0;
+        self.turn_started = Instant::now();
+        self.tracker.clear();
+    }
+
+    /// Record the usage of a single model response.
+    pub fn record_usage(&mut self, usage: &Usage) {
+        let cost = self.pricing.map_or(0.0, |p| usage.cost(&p));
+        self.turn.add(usage, cost);
+        self.session.add(usage, cost);
+        self.last = *usage;
+    }
+
+    /// Tokens in the context of the most recent response.
+    ///
+    /// Counts the request's input and the generated output, which is now
+    /// part of the history. Zero until a response has been recorded.
+    #[must_use]
+    pub const fn context_tokens(&self) -> u64 {
+        self.last.total_input() + self.last.output_tokens
+    }
+
+    /// Forget the context size after the history has been replaced.
+    pub fn reset_context(&mut self) {
+        self.last = Usage::default();
+    }
+
+    /// Record a tool call and check whether the model is repeating itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Tool name
+    /// * `input` - Tool input
+    ///
+    /// # Returns
+    ///
+    /// The detected repetition, or `None` (always `None` when detection is off).
+    pub fn record_tool_call(&mut self, name: &str, input: &Value) -> Option<Repeat> {
+        let repetition = &self.limits.repetition;
+        if repetition.action == RepetitionAction::Off {
+            return None;
+        }
+        self.tracker.record(name, input, repetition.threshold)
+    }
+
+    /// Whether the loop should pause and ask the user after a repetition.
+    #[must_use]
+    pub fn pauses_on_repetition(&self) -> bool {
+        self.limits.repetition.action == RepetitionAction::Ask
+    }
+
+    /// Record that a batch of tool calls has been executed.
+    pub fn record_tool_round(&mut self) {
+        self.tool_rounds += 1;
+    }
+
+    /// Time left before the wall-clock limit of the current user message.
+    ///
+    /// # Returns
+    ///
+    /// `None` when there is no wall-clock limit; zero once it has passed.
+    #[must_use]
+    pub fn time_left(&self) -> Option<Duration> {
+        self.limits
+            .max_duration_secs
+            .map(|max| Duration::from_secs(max).saturating_sub(self.turn_started.elapsed()))
+    }
+
+    /// The wall-clock limit, reported when it interrupts a response or a tool.
+    #[must_use]
+    pub fn duration_exceeded(&self) -> LimitExceeded {
+        LimitExceeded {
+            limit: Limit::Duration,
+            message: format!(
+                "Stopped after {}s of wall-clock time",
+                self.limits.max_duration_secs.unwrap_or_default()
+            ),
+        }
+    }
+
+    /// Warning about cost limits that cannot be enforced.
+    ///
+    /// # Returns
+    ///
+    /// A warning when a cost limit is set but the model has no pricing.
+    #[must_use]
+    pub fn unenforced_cost_limits(&self) -> Option<String> {
+        let limits = &self.limits;
+        let set: Vec<String> = [
+            (Limit::TurnCost, limits.max_turn_cost),
+            (Limit::SessionCost, limits.max_session_cost),
+        ]
+        .into_iter()
+        .filter(|(_, max)| max.is_some())
+        .map(|(limit, _)| limit.to_string())
+        .collect();
+        (!set.is_empty() && self.pricing.is_none()).then(|| {
+            format!(
+                "{} set but the model has no pricing, so costs cannot be limited; \
+                 set `pricing` for the provider in the configuration file",
+                set.join(" and ")
+            )
+        })
+    }
+
+    /// Check whether any limit has been reached.
+    ///
+    /// # Returns
+    ///
+    /// The first limit that has been reached, or `None`.
+    #[must_use]
+    pub fn check(&self) -> Option<LimitExceeded> {
+        let limits = &self.limits;
+
+        if let Some(max) = limits.max_turns
+            && self.tool_rounds >= max
+        {
+            return Some(LimitExceeded {
+                limit: Limit::MaxTurns,
+                message: format!("Stopped after {} tool iterations", self.tool_rounds),
+            });
+        }
+
+        if self.time_left() == Some(Duration::ZERO) {
+            return Some(self.duration_exceeded());
+        }
+
+        let token_checks = [
+            (Limit::TurnTokens, limits.max_turn_tokens, &self.turn),
+            (
+                Limit::SessionTokens,
+                limits.max_session_tokens,
+                &self.session,
+            ),
+        ];
+        for (limit, max, tally) in token_checks {
+            if let Some(max) = max
+                && tally.usage.total() >= max
+            {
+                return Some(LimitExceeded {
+                    limit,
+                    message: format!("Used {} of {max} tokens", tally.usage.total()),
+                });
+            }
+        }
+
+        let cost_checks = [
+            (Limit::TurnCost, limits.max_turn_cost, &self.turn),
+            (Limit::SessionCost, limits.max_session_cost, &self.session),
+        ];
+        for (limit, max, tally) in cost_checks {
+            if let Some(max) = max
+                && self.pricing.is_some()
+                && tally.cost >= max
+            {
+                return Some(LimitExceeded {
+                    limit,
+                    message: format!("Spent ${:.4} of ${max:.4} budget", tally.cost),
+                });
+            }
+        }
+
+        None
+    }
+
+    /// Usage accumulated over the whole session.
+    #[must_use]
+    pub const fn session(&self) -> &Tally {
+        &self.session
+    }
+
+    /// Usage accumulated since the current user message.
+    #[must_use]
+    pub const fn turn(&self) -> &Tally {
+        &self.turn
+    }
+
+    /// Configured limits.
+    #[must_use]
+    pub const fn limits(&self) -> &Limits {
+        &self.limits
+    }
+}
+
+impl Default for Guard {
+    fn default() -> Self {
+        Self::new(Limits::default(), None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(input: u64, output: u64) -> Usage {
+        Usage {
+            input_tokens: input,
+            output_tokens: output,
+            ..Usage::default()
+        }
+    }
+
+    #[test]
+    fn test_guard_unlimited() {
+        let mut guard = Guard::default();
+        guard.record_usage(&usage(1_000_000, 1_000_000));
+        guard.record_tool_round();
+        assert!(guard.check().is_none());
+    }
+
+    #[test]
+    fn test_guard_max_turns() {
+        let limits = Limits {
+            max_turns: Some(2),
+            ..Limits::default()
+        };
+        let mut guard = Guard::new(limits, None);
+
+        guard.record_tool_round();
+        assert!(guard.check().is_none());
+        guard.record_tool_round();
+        assert_eq!(guard.check().map(|e| e.limit), Some(Limit::MaxTurns));
+
+        guard.begin_turn();
+        assert!(guard.check().is_none());
+    }
+
+    #[test]
+    fn test_guard_session_tokens_survive_turns() {
+        let limits = Limits {
+            max_turn_tokens: Some(500),
+            max_session_tokens: Some(1000),
+            ..Limits::default()
+        };
+        let mut guard = Guard::new(limits, None);
+
+        guard.record_usage(&usage(300, 100));
+        assert!(guard.check().is_none());
+
+        guard.begin_turn();
+        guard.record_usage(&usage(300, 100));
+        assert!(guard.check().is_none());
+
+        guard.begin_turn();
+        guard.record_usage(&usage(200, 50));
+        assert_eq!(guard.check().map(|e| e.limit), Some(Limit::SessionTokens));
+        assert_eq!(guard.session().usage.total(), 1050);
+        assert_eq!(guard.context_tokens(), 250);
+
+        guard.reset_context();
+        assert_eq!(guard.context_tokens(), 0);
+    }
+
+    #[test]
+    fn test_guard_cost_requires_pricing() {
+        let limits = Limits {
+            max_turn_cost: Some(1.0),
+            ..Limits::default()
+        };
+        let pricing = Pricing {
+            input: 5.0,
+            output: 25.0,
+        };
+
+        let mut unpriced = Guard::new(limits.clone(), None);
+        unpriced.record_usage(&usage(1_000_000, 1_000_000));
+        assert!(unpriced.check().is_none());
+        assert!(
+            unpriced
+                .unenforced_cost_limits()
+                .unwrap()
+                .starts_with("max_turn_cost set but the model has no pricing")
+        );
+
+        let mut priced = Guard::new(limits, Some(pricing));
+        assert!(priced.unenforced_cost_limits().is_none());
+        priced.record_usage(&usage(100_000, 10_000));
+        assert!(priced.check().is_none());
+        priced.record_usage(&usage(100_000, 10_000));
+        assert_eq!(priced.check().map(|e| e.limit), Some(Limit::TurnCost));
+    }
+
+    #[test]
+    fn test_guard_time_left() {
+        assert!(Guard::default().time_left().is_none());
+
+        let limits = Limits {
+            max_duration_secs: Some(0),
+            ..Limits::default()
+        };
+        let guard = Guard::new(limits, None);
+        assert_eq!(guard.time_left(), Some(Duration::ZERO));
+        assert_eq!(guard.check(), Some(guard.duration_exceeded()));
+    }
+
+    #[test]
+    fn test_guard_repetition_action() {
+        let input = serde_json::json!({"cmd": "ls"});
+
+        let mut guard = Guard::default();
+        assert!(!guard.pauses_on_repetition());
+        guard.record_tool_call("bash", &input);
+        guard.record_tool_call("bash", &input);
+        assert!(guard.record_tool_call("bash", &input).is_some());
+
+        guard.begin_turn();
+        assert!(guard.record_tool_call("bash", &input).is_none());
+
+        let mut limits = Limits::default();
+        limits.repetition.action = RepetitionAction::Off;
+        let mut off = Guard::new(limits, None);
+        for _ in 0..5 {
+            assert!(off.record_tool_call("bash", &input).is_none());
+        }
+    }
+
+    #[test]
+    fn test_limit_display() {
+        assert_eq!(Limit::MaxTurns.to_string(), "max_turns");
+        assert_eq!(Limit::SessionCost.to_string(), "max_session_cost");
+    }
+}
@@ -0,0 +1,232 @@
+//! Detection of repeated identical tool calls.
+//!
+//! Keeps fingerprints (tool name + canonical input) of the most recent tool
+//! calls and reports when the tail of that history is the same call, or the
+//! same short cycle of calls, repeated back to back.
+
+use serde_json::Value;
+use std::collections::VecDeque;
+
+/// Longest cycle of calls that is recognised as a loop (e.g. two alternating edits).
+const MAX_PERIOD: usize = 3;
+
+/// Number of recent calls kept to count how often a cycle repeated.
+const MAX_RECENT: usize = 64;
+
+/// Fingerprint of a tool call, independent of the key order of its input.
+fn fingerprint(name: &str, input: &Value) -> String {
+    let mut text = format!("{name}:");
+    canonical(input, &mut text);
+    text
+}
+
+/// Write a JSON value with the keys of every object sorted.
+fn canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            out.push('{');
+            for (index, (key, value)) in entries.into_iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                canonical(value, out);
+            }
+            out.push('}');
+        },
+        Value::Array(items) => {
+            out.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                canonical(item, out);
+            }
+            out.push(']');
+        },
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+/// A detected repetition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repeat {
+    /// Name of the tool whose call completed the repetition
+    pub tool: String,
+    /// How many times the call (or cycle of calls) has been repeated
+    pub repeats: usize,
+    /// Number of distinct calls in the repeated cycle (1 for identical calls)
+    pub period: usize,
+}
+
+impl Repeat {
+    /// Corrective note appended to the tool result.
+    #[must_use]
+    pub fn note(&self) -> String {
+        if self.period == 1 {
+            format!(
+                "[neco] You have called `{}` with identical input {} times in a row. \
+                 The result will not change; stop repeating it and try a different approach.",
+                self.tool, self.repeats
+            )
+        } else {
+            format!(
+                "[neco] Your last {} tool calls repeat the same cycle of {} calls {} times. \
+                 You appear to be stuck in a loop; step back and try a different approach.",
+                self.period * self.repeats,
+                self.period,
+                self.repeats
+            )
+        }
+    }
+}
+
+/// Tracker of recent tool call fingerprints.
+#[derive(Debug, Clone, Default)]
+pub struct Tracker {
+    /// Most recent fingerprints, oldest first
+    recent: VecDeque<String>,
+}
+
+impl Tracker {
+    /// Forget all recorded calls.
+    pub fn clear(&mut self) {
+        self.recent.clear();
+    }
+
+    /// Record a tool call and check for repetition.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Tool name
+    /// * `input` - Tool input
+    /// * `threshold` - Number of back-to-back repeats that counts as a loop
+    ///
+    /// # Returns
+    ///
+    /// The detected repetition, or `None`.
+    pub fn record(&mut self, name: &str, input: &Value, threshold: usize) -> Option<Repeat> {
+        let threshold = threshold.max(2);
+        self.recent.push_back(fingerprint(name, input));
+        while self.recent.len() > MAX_RECENT.max(MAX_PERIOD * threshold) {
+            self.recent.pop_front();
+        }
+
+        (1..=MAX_PERIOD)
+            .find(|&period| self.is_cycle(period, threshold))
+            .map(|period| Repeat {
+                tool: name.to_string(),
+                repeats: self.repeats(period),
+                period,
+            })
+    }
+
+    /// How many times the last cycle of `period` calls has been repeated back to back.
+    fn repeats(&self, period: usize) -> usize {
+        let recent: Vec<&String> = self.recent.iter().collect();
+        let matching = recent
+            .iter()
+            .rev()
+            .zip(recent.iter().rev().skip(period))
+            .take_while(|(a, b)| a == b)
+            .count();
+        (matching + period) / period
+    }
+
+    /// Whether the last `period * threshold` calls are one cycle repeated.
+    fn is_cycle(&self, period: usize, threshold: usize) -> bool {
+        let span = period * threshold;
+        if self.recent.len() < span {
+            return false;
+        }
+        let tail: Vec<&String> = self.recent.iter().skip(self.recent.len() - span).collect();
+
+        // A cycle made of one repeated call is reported as period 1
+        let distinct = tail.iter().take(period).any(|f| Some(f) != tail.first());
+        if period > 1 && !distinct {
+            return false;
+        }
+
+        tail.iter()
+            .zip(tail.iter().skip(period))
+            .all(|(a, b)| a == b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_identical_calls() {
+        let mut tracker = Tracker::default();
+        let input = json!({"pat": "fn main"});
+
+        assert!(tracker.record("grep", &input, 3).is_none());
+        assert!(tracker.record("grep", &input, 3).is_none());
+        let repeat = tracker.record("grep", &input, 3).unwrap();
+
+        assert_eq!(repeat.period, 1);
+        assert_eq!(repeat.repeats, 3);
+        assert!(repeat.note().contains("`grep`"));
+
+        let repeat = tracker.record("grep", &input, 3).unwrap();
+        assert_eq!(repeat.repeats, 4);
+        assert!(repeat.note().contains("4 times"));
+    }
+
+    #[test]
+    fn test_different_inputs_are_not_repeats() {
+        let mut tracker = Tracker::default();
+        for i in 0..10 {
+            assert!(tracker.record("bash", &json!({"cmd": i}), 3).is_none());
+        }
+    }
+
+    #[test]
+    fn test_interleaved_builds_are_not_repeats() {
+        let mut tracker = Tracker::default();
+        let build = json!({"cmd": "cargo build"});
+        for i in 0..5 {
+            assert!(tracker.record("edit", &json!({"new": i}), 3).is_none());
+            assert!(tracker.record("bash", &build, 3).is_none());
+        }
+    }
+
+    #[test]
+    fn test_alternating_calls() {
+        let mut tracker = Tracker::default();
+        let a = json!({"old": "a", "new": "b"});
+        let b = json!({"old": "b", "new": "a"});
+
+        let mut detected = None;
+        for _ in 0..3 {
+            assert!(detected.is_none());
+            tracker.record("edit", &a, 3);
+            detected = tracker.record("edit", &b, 3);
+        }
+
+        assert_eq!(detected.map(|r| (r.period, r.repeats)), Some((2, 3)));
+    }
+
+    #[test]
+    fn test_key_order_does_not_matter() {
+        let mut tracker = Tracker::default();
+        let first: Value = serde_json::from_str(r#"{"a":1,"b":2}"#).unwrap();
+        let second: Value = serde_json::from_str(r#"{"b":2,"a":1}"#).unwrap();
+
+        assert_eq!(fingerprint("read", &first), r#"read:{"a":1,"b":2}"#);
+        tracker.record("read", &first, 2);
+        assert!(tracker.record("read", &second, 2).is_some());
+
+        let nested: Value = serde_json::from_str(r#"{"z":[{"y":1,"x":2}],"a":"s"}"#).unwrap();
+        assert_eq!(
+            fingerprint("edit", &nested),
+            r#"edit:{"a":"s","z":[{"x":2,"y":1}]}"#
+        );
+    }
+}
@@ -0,0 +1,286 @@
+//! Conversation history.
+//!
+//! Holds the messages sent to the model and, when the session is persisted,
+//! mirrors every change to the session [`Transcript`]. Stale tool results
+//! are pruned from the requests sent to the model (see [`prune`]).
+
+pub mod prune;
+
+use crate::events::CoreEvent;
+use crate::transcript::Transcript;
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+/// Message history of a conversation, in Anthropic Messages format.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    /// Messages, oldest first
+    messages: Vec<Value>,
+    /// Transcript receiving every appended message, if persisted
+    transcript: Option<Transcript>,
+}
+
+impl History {
+    /// Create an empty, unpersisted history.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a history from existing messages without recording them.
+    ///
+    /// # Arguments
+    ///
+    /// * `messages` - Messages already stored (e.g. rebuilt from a transcript)
+    /// * `transcript` - Transcript receiving future changes
+    #[must_use]
+    pub const fn restored(messages: Vec<Value>, transcript: Option<Transcript>) -> Self {
+        Self {
+            messages,
+            transcript,
+        }
+    }
+
+    /// Append a message and record it in the transcript.
+    pub fn push(&mut self, message: Value) {
+        if let Some(transcript) = &self.transcript {
+            transcript.record_message(&message);
+        }
+        self.messages.push(message);
+    }
+
+    /// Remove all messages and record the reset in the transcript.
+    pub fn clear(&mut self) {
+        if let Some(transcript) = &self.transcript {
+            transcript.record_clear();
+        }
+        self.messages.clear();
+    }
+
+    /// Replace all messages, e.g. with a compacted history.
+    ///
+    /// The transcript keeps the previous messages and records the replacement.
+    pub fn replace(&mut self, messages: Vec<Value>) {
+        if let Some(transcript) = &self.transcript {
+            transcript.record_compact(&messages);
+        }
+        self.messages = messages;
+    }
+
+    /// Keep only the first `len` messages and record the rewind in the transcript.
+    pub fn truncate(&mut self, len: usize) {
+        if let Some(transcript) = &self.transcript {
+            transcript.record_rewind(len);
+        }
+        self.messages.truncate(len);
+    }
+
+    /// Continue the history in a new transcript branched from the current one.
+    ///
+    /// The shared messages are copied into the branch, which from now on
+    /// receives all changes; the original transcript is left as it is.
+    ///
+    /// # Arguments
+    ///
+    /// * `transcript` - Transcript of the new branch
+    pub fn fork(&mut self, transcript: Transcript) {
+        for message in &self.messages {
+            transcript.record_message(message);
+        }
+        self.transcript = Some(transcript);
+    }
+
+    /// Indices of the messages that start user turns.
+    #[must_use]
+    pub fn turn_starts(&self) -> Vec<usize> {
+        turn_starts(&self.messages)
+    }
+
+    /// Messages, oldest first.
+    #[must_use]
+    pub fn messages(&self) -> &[Value] {
+        &self.messages
+    }
+
+    /// Mutable access to the messages.
+    ///
+    /// Changes made through this reference are not recorded in the transcript.
+    #[must_use]
+    pub const fn messages_mut(&mut self) -> &mut Vec<Value> {
+        &mut self.messages
+    }
+
+    /// Number of messages.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Whether the history is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Transcript the history is persisted to, if any.
+    #[must_use]
+    pub const fn transcript(&self) -> Option<&Transcript> {
+        self.transcript.as_ref()
+    }
+
+    /// Record an event in the transcript and send it to the renderer.
+    ///
+    /// Recording where the event is sent keeps the transcript in the order
+    /// the renderer sees.
+    ///
+    /// # Arguments
+    ///
+    /// * `event_sender` - Optional sender for core events
+    /// * `event` - Event to record and send
+    pub fn emit(&self, event_sender: Option<&mpsc::UnboundedSender<CoreEvent>>, event: CoreEvent) {
+        if let Some(transcript) = &self.transcript {
+            transcript.record_event(&event);
+        }
+        if let Some(sender) = event_sender {
+            let _ = sender.send(event);
+        }
+    }
+}
+
+/// Whether a message starts a user turn.
+///
+/// A user turn starts at a user message without tool results. Messages that
+/// carry tool results continue a tool round, even when a steering note was
+/// delivered with them.
+#[must_use]
+pub fn is_turn_start(message: &Value) -> bool {
+    if message.get("role").and_then(Value::as_str) != Some("user") {
+        return false;
+    }
+    match message.get("content") {
+        Some(Value::Array(blocks)) => !blocks
+            .iter()
+            .any(|block| block.get("type").and_then(Value::as_str) == Some("tool_result")),
+        _ => true,
+    }
+}
+
+/// Indices of the messages that start user turns.
+#[must_use]
+pub fn turn_starts(messages: &[Value]) -> Vec<usize> {
+    messages
+        .iter()
+        .enumerate()
+        .filter(|(_, message)| is_turn_start(message))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Text typed by the user in a message that starts a user turn.
+///
+/// For a message with several text blocks (e.g. a summary prepended by
+/// compaction), the last block is the user's own text.
+#[must_use]
+pub fn user_text(message: &Value) -> Option<String> {
+    if !is_turn_start(message) {
+        return None;
+    }
+    match message.get("content")? {
+        Value::String(text) => Some(text.clone()),
+        Value::Array(blocks) => blocks.iter().rev().find_map(|block| {
+            (block.get("type").and_then(Value::as_str) == Some("text"))
+                .then(|| block.get("text").and_then(Value::as_str))
+                .flatten()
+                .map(ToString::to_string)
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript;
+    use serde_json::json;
+
+    #[test]
+    fn test_history_records_changes() {
+        let dir = crate::testing::TempDir::new("history");
+        let transcript = Transcript::create(&dir, "/project", "anthropic", "claude");
+        let mut history = History::restored(Vec::new(), Some(transcript.clone()));
+
+        history.push(json!({"role": "user", "content": "one"}));
+        history.clear();
+        history.push(json!({"role": "user", "content": "two"}));
+        history.replace(vec![json!({"role": "user", "content": "summary"})]);
+        history.push(json!({"role": "assistant", "content": "ok"}));
+        assert_eq!(history.len(), 2);
+
+        let records = transcript::load(&dir, transcript.id()).unwrap();
+        assert_eq!(
+            transcript::rebuild_history(&records),
+            history.messages().to_vec()
+        );
+    }
+
+    #[test]
+    fn test_history_rewind_and_fork() {
+        let dir = crate::testing::TempDir::new("history-fork");
+        let original = Transcript::create(&dir, "/project", "anthropic", "claude");
+        let mut history = History::restored(Vec::new(), Some(original.clone()));
+        history.push(json!({"role": "user", "content": "one"}));
+        history.push(json!({"role": "assistant", "content": "1"}));
+        history.push(json!({"role": "user", "content": "two"}));
+        history.push(json!({"role": "assistant", "content": "2"}));
+        assert_eq!(history.turn_starts(), vec![0, 2]);
+
+        let branch = original.branch("/project", "anthropic", "claude");
+        history.fork(branch.clone());
+        history.truncate(2);
+        history.push(json!({"role": "user", "content": "three"}));
+
+        let original_records = transcript::load(&dir, original.id()).unwrap();
+        assert_eq!(transcript::rebuild_history(&original_records).len(), 4);
+
+        let branch_records = transcript::load(&dir, branch.id()).unwrap();
+        assert_eq!(
+            transcript::rebuild_history(&branch_records),
+            history.messages().to_vec()
+        );
+        let info = transcript::summarize(&branch_records).unwrap();
+        assert_eq!(info.parent.as_deref(), Some(original.id()));
+    }
+
+    #[test]
+    fn test_user_text() {
+        assert_eq!(
+            user_text(&json!({"role": "user", "content": "hi"})),
+            Some("hi".to_string())
+        );
+        let compacted = json!({"role": "user", "content": [
+            {"type": "text", "text": "[Summary]"},
+            {"type": "text", "text": "next step"}
+        ]});
+        assert_eq!(user_text(&compacted), Some("next step".to_string()));
+        let results = json!({"role": "user", "content": [
+            {"type": "tool_result", "tool_use_id": "c1", "content": "ok"}
+        ]});
+        assert!(!is_turn_start(&results));
+        assert_eq!(user_text(&results), None);
+        let steered = json!({"role": "user", "content": [
+            {"type": "tool_result", "tool_use_id": "c1", "content": "ok"},
+            {"type": "text", "text": "use the other file"}
+        ]});
+        assert!(!is_turn_start(&steered));
+        assert_eq!(user_text(&steered), None);
+    }
+
+    #[test]
+    fn test_history_unpersisted() {
+        let mut history = History::new();
+        assert!(history.is_empty());
+        history.push(json!({"role": "user", "content": "hi"}));
+        assert_eq!(history.len(), 1);
+        assert!(history.transcript().is_none());
+    }
+}
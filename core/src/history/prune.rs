@@ -0,0 +1,276 @@
+//! Pruning of stale tool results.
+//!
+//! Before each request, tool results older than the configured number of
+//! model turns are replaced by a short stub such as
+//! `[elided 412 lines of read src/main.rs]`. The `tool_use`/`tool_result`
+//! pairing is left intact; only the result content changes. The stored
+//! history is not modified.
+//!
+//! Pruning is opt-in. The boundary only advances in steps of
+//! [`Pruning::step`] turns, so between two steps the request prefix stays
+//! byte-identical.
+
+use crate::config::Pruning;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Input keys describing what a tool call operated on, in order of preference.
+const SUBJECT_KEYS: &[&str] = &["path", "pat", "pattern", "cmd", "command"];
+
+/// Maximum characters of the subject shown in a stub.
+const MAX_SUBJECT_CHARS: usize = 60;
+
+/// A tool call that produced a result.
+struct Call {
+    /// Tool name
+    name: String,
+    /// Short description of what the call operated on
+    subject: String,
+    /// Model turn (0-based) in which the call was made
+    turn: usize,
+}
+
+/// Prune stale tool results from the messages of a request.
+///
+/// # Arguments
+///
+/// * `messages` - Conversation history
+/// * `policy` - Pruning policy
+///
+/// # Returns
+///
+/// The messages to send, borrowed unchanged when nothing was pruned.
+#[must_use]
+pub fn apply<'a>(messages: &'a [Value], policy: &Pruning) -> Cow<'a, [Value]> {
+    if !policy.enabled {
+        return Cow::Borrowed(messages);
+    }
+
+    let calls = collect_calls(messages);
+    let turns = messages
+        .iter()
+        .filter(|m| role(m) == Some("assistant"))
+        .count();
+    let step = policy.step.max(1);
+
+    let mut pruned: Option<Vec<Value>> = None;
+    for (index, message) in messages.iter().enumerate() {
+        if role(message) != Some("user") {
+            continue;
+        }
+        let Some(blocks) = message.get("content").and_then(Value::as_array) else {
+            continue;
+        };
+
+        for (position, block) in blocks.iter().enumerate() {
+            let Some(stub) = stub_for(block, &calls, turns, step, policy) else {
+                continue;
+            };
+            let target = pruned
+                .get_or_insert_with(|| messages.to_vec())
+                .get_mut(index)
+                .and_then(|m| m.get_mut("content"))
+                .and_then(Value::as_array_mut)
+                .and_then(|blocks| blocks.get_mut(position))
+                .and_then(Value::as_object_mut);
+            if let Some(target) = target {
+                target.insert("content".to_string(), Value::String(stub));
+            }
+        }
+    }
+
+    pruned.map_or(Cow::Borrowed(messages), Cow::Owned)
+}
+
+/// Role of a message.
+fn role(message: &Value) -> Option<&str> {
+    message.get("role").and_then(Value::as_str)
+}
+
+/// Index all tool calls by id.
+fn collect_calls(messages: &[Value]) -> HashMap<&str, Call> {
+    let mut calls = HashMap::new();
+    let assistant = messages.iter().filter(|m| role(m) == Some("assistant"));
+    for (turn, message) in assistant.enumerate() {
+        let Some(blocks) = message.get("content").and_then(Value::as_array) else {
+            continue;
+        };
+        for block in blocks {
+            if block.get("type").and_then(Value::as_str) != Some("tool_use") {
+                continue;
+            }
+            let Some(id) = block.get("id").and_then(Value::as_str) else {
+                continue;
+            };
+            let name = block.get("name").and_then(Value::as_str).unwrap_or("tool");
+            calls.insert(
+                id,
+                Call {
+                    name: name.to_string(),
+                    subject: subject(block.get("input")),
+                    turn,
+                },
+            );
+        }
+    }
+    calls
+}
+
+/// Short description of what a tool call operated on.
+fn subject(input: Option<&Value>) -> String {
+    let value = SUBJECT_KEYS
+        .iter()
+        .find_map(|key| input?.get(*key)?.as_str())
+        .unwrap_or_default();
+    let first_line = value.lines().next().unwrap_or_default();
+    if first_line.chars().count() > MAX_SUBJECT_CHARS || value.lines().nth(1).is_some() {
+        let short: String = first_line.chars().take(MAX_SUBJECT_CHARS).collect();
+        format!("{short}…")
+    } else {
+        first_line.to_string()
+    }
+}
+
+/// Stub replacing a tool result, if the result is stale and worth pruning.
+fn stub_for(
+    block: &Value,
+    calls: &HashMap<&str, Call>,
+    turns: usize,
+    step: usize,
+    policy: &Pruning,
+) -> Option<String> {
+    if block.get("type").and_then(Value::as_str) != Some("tool_result") {
+        return None;
+    }
+    let call = calls.get(block.get("tool_use_id")?.as_str()?)?;
+    let keep_turns = policy.keep_turns_for(&call.name)?;
+
+    // Results of turns before the boundary are pruned; the boundary only moves every `step` turns
+    let boundary = turns.saturating_sub(keep_turns) / step * step;
+    if call.turn >= boundary {
+        return None;
+    }
+
+    let content = block.get("content")?.as_str()?;
+    let lines = content.lines().count();
+    let stub = if call.subject.is_empty() {
+        format!("[elided {lines} lines of {} output]", call.name)
+    } else {
+        format!("[elided {lines} lines of {} {}]", call.name, call.subject)
+    };
+    (stub.len() < content.len()).then_some(stub)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// History of `turns` model turns, each reading a file with a long result.
+    fn history(turns: usize) -> Vec<Value> {
+        let mut messages = vec![json!({"role": "user", "content": "read everything"})];
+        for turn in 0..turns {
+            let id = format!("call_{turn}");
+            messages.push(json!({"role": "assistant", "content": [
+                {"type": "tool_use", "id": id, "name": "read", "input": {"path": format!("src/{turn}.rs")}}
+            ]}));
+            messages.push(json!({"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": id, "content": "line\n".repeat(50)}
+            ]}));
+        }
+        messages
+    }
+
+    fn result_content(messages: &[Value], turn: usize) -> &str {
+        messages
+            .get(2 + turn * 2)
+            .and_then(|m| m.pointer("/content/0/content"))
+            .and_then(Value::as_str)
+            .unwrap()
+    }
+
+    fn policy(keep_turns: usize, step: usize) -> Pruning {
+        Pruning {
+            enabled: true,
+            keep_turns,
+            step,
+            ..Pruning::default()
+        }
+    }
+
+    #[test]
+    fn test_prunes_old_results() {
+        let messages = history(6);
+        let pruned = apply(&messages, &policy(2, 1));
+
+        assert_eq!(
+            result_content(&pruned, 0),
+            "[elided 50 lines of read src/0.rs]"
+        );
+        assert_eq!(
+            result_content(&pruned, 3),
+            "[elided 50 lines of read src/3.rs]"
+        );
+        assert!(result_content(&pruned, 4).starts_with("line"));
+        assert!(result_content(&pruned, 5).starts_with("line"));
+        assert_eq!(
+            pruned
+                .get(2)
+                .and_then(|m| m.pointer("/content/0/tool_use_id")),
+            Some(&json!("call_0"))
+        );
+    }
+
+    #[test]
+    fn test_boundary_moves_in_steps() {
+        let policy = policy(2, 4);
+
+        // 5 turns: boundary = 3 / 4 * 4 = 0, nothing pruned
+        let messages = history(5);
+        assert!(matches!(apply(&messages, &policy), Cow::Borrowed(_)));
+
+        // 6 turns: boundary = 4, turns 0..4 pruned
+        let messages = history(6);
+        let pruned = apply(&messages, &policy);
+        assert!(result_content(&pruned, 3).starts_with("[elided"));
+        assert!(result_content(&pruned, 4).starts_with("line"));
+
+        // 9 turns: boundary still 4, identical prefix
+        let longer = history(9);
+        let pruned_longer = apply(&longer, &policy);
+        assert_eq!(pruned_longer.get(..12), pruned.get(..12));
+    }
+
+    #[test]
+    fn test_per_tool_rules_and_small_results() {
+        let mut rules = policy(0, 1);
+        rules.tools.insert(
+            "read".to_string(),
+            crate::config::PruneRule {
+                enabled: Some(false),
+                keep_turns: None,
+            },
+        );
+        let messages = history(3);
+        assert!(matches!(apply(&messages, &rules), Cow::Borrowed(_)));
+
+        let small = vec![
+            json!({"role": "assistant", "content": [
+                {"type": "tool_use", "id": "c1", "name": "edit", "input": {"path": "a.rs"}}
+            ]}),
+            json!({"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": "c1", "content": "ok"}
+            ]}),
+            json!({"role": "assistant", "content": [{"type": "text", "text": "done"}]}),
+        ];
+        assert!(matches!(apply(&small, &policy(0, 1)), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_subject() {
+        assert_eq!(subject(Some(&json!({"cmd": "cargo test"}))), "cargo test");
+        assert_eq!(subject(Some(&json!({"cmd": "a\nb"}))), "a…");
+        assert_eq!(subject(None), "");
+    }
+}
@@ -0,0 +1,322 @@
+//! Project instruction files (`AGENTS.md`, `NECO.md`).
+//!
+//! Instruction files are read from the user configuration directory and
+//! from every directory between the git root and the working directory,
+//! most general first. They are merged into the `project` section of the
+//! system prompt, each under its path, and re-read when they change on
+//! disk. An `@path` in a file includes another file, relative to the
+//! including one. Includes stay within the directory of the including
+//! file's origin: the git root (or working directory) for project files,
+//! the user configuration directory for user files.
+
+use regex::Regex;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Include reference, `@path` at the start of a word.
+const INCLUDE: &str = r"(^|\s)@([^\s`]+)";
+
+/// Maximum nesting of includes.
+const MAX_DEPTH: usize = 5;
+
+/// Instruction files of a working directory, with the state they were read in.
+#[derive(Debug, Clone)]
+pub struct Files {
+    /// Working directory of the session
+    cwd: PathBuf,
+    /// Names of instruction files, in the order they are read within a directory
+    names: Vec<String>,
+    /// User configuration directory holding global instructions
+    user_dir: Option<PathBuf>,
+    /// Every file read, including includes, with its modification time
+    stamps: Vec<(PathBuf, Option<SystemTime>)>,
+    /// Instruction files found by the last discovery
+    found: Vec<PathBuf>,
+}
+
+impl Files {
+    /// Create the instruction files of a working directory, not yet read.
+    ///
+    /// # Arguments
+    ///
+    /// * `cwd` - Working directory of the session
+    /// * `names` - Names of instruction files, e.g. `AGENTS.md`
+    /// * `user_dir` - User configuration directory holding global instructions
+    #[must_use]
+    pub fn new(cwd: &Path, names: &[String], user_dir: Option<PathBuf>) -> Self {
+        Self {
+            cwd: cwd.to_path_buf(),
+            names: names.to_vec(),
+            user_dir,
+            stamps: Vec::new(),
+            found: Vec::new(),
+        }
+    }
+
+    /// Find the instruction files, most general first.
+    #[must_use]
+    pub fn discover(&self) -> Vec<PathBuf> {
+        let root = git_root(&self.cwd).unwrap_or_else(|| self.cwd.clone());
+        let mut dirs: Vec<&Path> = self
+            .cwd
+            .ancestors()
+            .take_while(|dir| dir.starts_with(&root))
+            .collect();
+        dirs.reverse();
+
+        self.user_dir
+            .iter()
+            .map(PathBuf::as_path)
+            .chain(dirs)
+            .flat_map(|dir| self.names.iter().map(move |name| dir.join(name)))
+            .filter(|path| path.is_file())
+            .collect()
+    }
+
+    /// Read the instruction files and render the `project` prompt section.
+    ///
+    /// # Returns
+    ///
+    /// The section text, empty if there are no instruction files.
+    pub fn load(&mut self) -> String {
+        self.found = self.discover();
+        self.stamps.clear();
+
+        let project = canonical(&git_root(&self.cwd).unwrap_or_else(|| self.cwd.clone()));
+        let user = self.user_dir.as_deref().map(canonical);
+        let mut text = String::new();
+        for path in self.found.clone() {
+            let scope = match &user {
+                Some(user) if canonical(&path).starts_with(user) => user.clone(),
+                _ => project.clone(),
+            };
+            let Some(content) = self.read(&path, &scope, &mut Vec::new()) else {
+                continue;
+            };
+            if text.is_empty() {
+                text.push_str(
+                    "# Project instructions\n\
+                     Instructions from the user's files follow, most general first; \
+                     later files take precedence.",
+                );
+            }
+            let _ = write!(text, "\n\n## {}\n\n{}", self.display(&path), content.trim());
+        }
+        text
+    }
+
+    /// Whether an instruction file was added, removed or changed since the last load.
+    #[must_use]
+    pub fn changed(&self) -> bool {
+        self.discover() != self.found
+            || self
+                .stamps
+                .iter()
+                .any(|(path, modified)| modified_time(path) != *modified)
+    }
+
+    /// Re-read the instruction files if they changed since the last load.
+    ///
+    /// # Returns
+    ///
+    /// The new section text, or `None` if nothing changed.
+    pub fn refresh(&mut self) -> Option<String> {
+        self.changed().then(|| self.load())
+    }
+
+    /// Read a file, replacing its includes by their contents.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - File to read
+    /// * `scope` - Canonical directory that included files must be inside
+    /// * `stack` - Canonical paths of the files being included, to stop at cycles
+    fn read(&mut self, path: &Path, scope: &Path, stack: &mut Vec<PathBuf>) -> Option<String> {
+        self.stamps.push((path.to_path_buf(), modified_time(path)));
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                tracing::warn!(path = %path.display(), "Failed to read instructions: {e}");
+                return None;
+            },
+        };
+        if stack.len() >= MAX_DEPTH {
+            return Some(content);
+        }
+        let Ok(include) = Regex::new(INCLUDE) else {
+            return Some(content);
+        };
+
+        stack.push(canonical(path));
+        let base = path.parent().unwrap_or(&self.cwd).to_path_buf();
+        let mut expanded = String::new();
+        let mut last = 0;
+        for captures in include.captures_iter(&content) {
+            let (Some(whole), Some(lead), Some(reference)) =
+                (captures.get(0), captures.get(1), captures.get(2))
+            else {
+                continue;
+            };
+            let included = base.join(reference.as_str());
+            if !included.is_file() {
+                continue;
+            }
+            let resolved = canonical(&included);
+            if Path::new(reference.as_str()).is_absolute() || !resolved.starts_with(scope) {
+                tracing::warn!(
+                    path = %path.display(),
+                    "Not including {}: outside {}",
+                    reference.as_str(),
+                    scope.display()
+                );
+                continue;
+            }
+            if stack.contains(&resolved) {
+                continue;
+            }
+            let Some(text) = self.read(&included, scope, stack) else {
+                continue;
+            };
+            expanded.push_str(content.get(last..lead.end()).unwrap_or_default());
+            let _ = write!(
+                expanded,
+                "<file path=\"{}\">\n{}\n</file>",
+                reference.as_str(),
+                text.trim_end()
+            );
+            last = whole.end();
+        }
+        expanded.push_str(content.get(last..).unwrap_or_default());
+        stack.pop();
+        Some(expanded)
+    }
+
+    /// Path of a file as shown in the prompt, relative to the working directory if inside it.
+    fn display(&self, path: &Path) -> String {
+        path.strip_prefix(&self.cwd).map_or_else(
+            |_| path.display().to_string(),
+            |relative| relative.display().to_string(),
+        )
+    }
+}
+
+/// Find the root of the git repository containing a directory.
+#[must_use]
+pub fn git_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// Canonical form of a path, to recognise a file reached through different paths.
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Modification time of a file, `None` if it does not exist.
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+/// Prompt asking the agent to write a starter instruction file.
+///
+/// # Arguments
+///
+/// * `name` - Name of the file to write, e.g. `AGENTS.md`
+#[must_use]
+pub fn init_prompt(name: &str) -> String {
+    format!(
+        "Analyse this repository and write a starter {name} in the current directory. \
+         It is read by coding agents before they work here, so keep it short and specific \
+         to this project:\n\
+         - What the project is and how the code is organised (main directories and modules).\n\
+         - How to build, run, lint and test it, with the exact commands.\n\
+         - Coding conventions visible in the code: naming, error handling, documentation, \
+         test layout, formatting and lint settings.\n\
+         - Anything an agent must not do (generated files, protected branches, secrets).\n\
+         Read the build files, configuration and a few representative source files first. \
+         Do not invent commands or conventions you did not see. Write the file with the \
+         write tool, then summarise what you put in it."
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_merges_files_from_root_to_cwd() {
+        let root = crate::testing::TempDir::new("instructions");
+        let user = root.join("user");
+        let repo = root.join("repo");
+        let cwd = repo.join("crates").join("core");
+        std::fs::create_dir_all(&user).unwrap();
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::create_dir_all(repo.join("docs")).unwrap();
+        std::fs::create_dir_all(&cwd).unwrap();
+        std::fs::write(
+            user.join("AGENTS.md"),
+            "Answer in English. @../repo/docs/style.md",
+        )
+        .unwrap();
+        std::fs::write(root.join("AGENTS.md"), "Outside the repository").unwrap();
+        std::fs::write(
+            repo.join("AGENTS.md"),
+            format!(
+                "Style: see @docs/style.md but not @../AGENTS.md or @{}",
+                root.join("AGENTS.md").display()
+            ),
+        )
+        .unwrap();
+        std::fs::write(
+            repo.join("docs").join("style.md"),
+            "Use tabs. @../AGENTS.md",
+        )
+        .unwrap();
+        std::fs::write(cwd.join("NECO.md"), "Core crate rules").unwrap();
+
+        let names = ["AGENTS.md".to_string(), "NECO.md".to_string()];
+        let mut files = Files::new(&cwd, &names, Some(user.clone()));
+        assert_eq!(
+            files.discover(),
+            vec![
+                user.join("AGENTS.md"),
+                repo.join("AGENTS.md"),
+                cwd.join("NECO.md")
+            ]
+        );
+
+        let text = files.load();
+        assert!(!text.contains("Outside the repository"));
+        assert!(text.contains("not @../AGENTS.md or @/"));
+        assert!(text.contains("Answer in English. @../repo/docs/style.md"));
+        let english = text.find("Answer in English.").unwrap();
+        let style = text
+            .find("<file path=\"docs/style.md\">\nUse tabs.")
+            .unwrap();
+        let core = text.find("## NECO.md\n\nCore crate rules").unwrap();
+        assert!(english < style && style < core);
+        assert_eq!(text.matches("Use tabs.").count(), 1);
+        assert!(!files.changed());
+        assert!(files.refresh().is_none());
+
+        std::fs::write(cwd.join("AGENTS.md"), "New rules").unwrap();
+        assert!(
+            files
+                .refresh()
+                .unwrap()
+                .contains("## AGENTS.md\n\nNew rules")
+        );
+    }
+
+    #[test]
+    fn test_no_files() {
+        let dir = crate::testing::TempDir::new("instructions-empty");
+        let mut files = Files::new(&dir, &["AGENTS.md".to_string()], None);
+        assert_eq!(files.load(), "");
+        assert!(init_prompt("AGENTS.md").contains("write a starter AGENTS.md"));
+    }
+}
@@ -8,25 +8,57 @@ use tracing as _;
 
 pub mod api;
 pub mod app;
+pub mod checkpoint;
+pub mod clock;
 pub mod command;
+pub mod commands;
+pub mod compaction;
 pub mod config;
+pub mod diff;
+pub mod environment;
 pub mod events;
+pub mod export;
+pub mod guard;
+pub mod history;
 pub mod input;
+pub mod instructions;
+pub mod mcp;
+pub mod memory;
+pub mod persona;
+pub mod prompt;
 pub mod session;
+pub mod skills;
+pub mod steering;
+#[cfg(test)]
+pub mod testing;
 pub mod tools;
+pub mod transcript;
 
 pub use api::anthropic::{ApiError, Client};
 
 pub use api::{Provider, ProviderRegistry};
 
-pub use config::{Config, Configuration, FileProvider, ProviderSettings};
+pub use config::{
+    Compaction, Config, Configuration, FileProvider, Limits, Pricing, ProviderSettings, Pruning,
+    Redaction, Resume,
+};
 
 pub use events::CoreEvent;
 
+pub use guard::Guard;
+
+pub use history::History;
+
 pub use command::Command;
 
+pub use commands::{CommandRegistry, SlashCommand};
+
 pub use input::{Reader, StdinReader};
 
 pub use session::Session;
 
+pub use steering::Steering;
+
+pub use transcript::Transcript;
+
 pub use app::App;
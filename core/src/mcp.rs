@@ -0,0 +1,936 @@
+//! Model Context Protocol client.
+//!
+//! Connects to the MCP servers configured in `[mcp_servers]`, performs the
+//! initialize handshake and offers the servers' tools to the model. Remote
+//! tools are wrapped as [`Tool`]s named `mcp__<server>__<tool>`. Server
+//! prompts become slash commands of the same form, and resources can be
+//! referenced in messages as `@<server>:<uri>`.
+//!
+//! A server that crashed is restarted before the next request, up to its
+//! configured number of restarts in a row; a successful handshake resets
+//! the count. A request that was in flight when the server died is not
+//! sent again, as tool calls may not be idempotent. A request rejected
+//! because the server dropped the session was not processed, so it is sent
+//! again once after a new handshake.
+
+use crate::config::McpServer;
+use crate::tools::Tool;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+#[cfg(all(test, unix))]
+pub mod fake;
+pub mod http;
+pub mod prompts;
+pub mod resources;
+pub mod stdio;
+
+pub use prompts::{Invocation, Prompt, PromptInfo};
+pub use resources::Resource;
+
+/// Protocol version requested in the handshake.
+pub const PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// Maximum length of a tool name accepted by the API.
+const MAX_TOOL_NAME: usize = 64;
+
+/// Callback receiving the notifications sent by a server.
+pub type Handler = Arc<dyn Fn(&Value) + Send + Sync>;
+
+/// Requests waiting for their responses on a connection read by a background task.
+#[derive(Clone, Default)]
+struct Pending {
+    /// Senders waiting for the responses, by request id
+    senders: Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>,
+    /// Set when the connection was lost
+    closed: Arc<AtomicBool>,
+}
+
+impl Pending {
+    /// Register a request and get the receiver of its response.
+    fn wait(&self, id: u64) -> oneshot::Receiver<Value> {
+        let (sender, receiver) = oneshot::channel();
+        self.lock().insert(id, sender);
+        receiver
+    }
+
+    /// Forget a request that could not be sent.
+    fn cancel(&self, id: u64) {
+        self.lock().remove(&id);
+    }
+
+    /// Mark the connection as lost, failing the waiting requests.
+    fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        self.lock().clear();
+    }
+
+    /// Whether the connection was lost.
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+
+    /// Route a message read from the server.
+    ///
+    /// # Returns
+    ///
+    /// The reply to send back when the message is a request.
+    fn dispatch(&self, message: Value, handler: &Handler) -> Option<Value> {
+        match (message.get("id"), message.get("method")) {
+            (Some(_), Some(_)) => Some(reply(&message)),
+            (Some(id), None) => {
+                let sender = id.as_u64().and_then(|id| self.lock().remove(&id));
+                if let Some(sender) = sender {
+                    let _ = sender.send(message);
+                }
+                None
+            },
+            (None, Some(_)) => {
+                handler(&message);
+                None
+            },
+            (None, None) => None,
+        }
+    }
+
+    /// Lock the senders.
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, oneshot::Sender<Value>>> {
+        self.senders.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Resource contents read from a server, kept while it notifies their changes.
+#[derive(Default)]
+struct Cache {
+    /// Contents of the subscribed resources, by URI
+    contents: HashMap<String, Vec<Value>>,
+    /// URIs of the resources subscribed to
+    subscribed: HashSet<String>,
+    /// Number of change notifications received, by URI
+    generations: HashMap<String, u64>,
+}
+
+impl Cache {
+    /// Number of change notifications received for a resource.
+    fn generation(&self, uri: &str) -> u64 {
+        self.generations.get(uri).copied().unwrap_or_default()
+    }
+}
+
+/// Error of a transport whose server no longer knows the session.
+///
+/// The request was not processed, so it can be sent again after a new
+/// handshake.
+#[derive(Debug)]
+pub struct SessionExpired;
+
+impl std::fmt::Display for SessionExpired {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("session expired")
+    }
+}
+
+impl std::error::Error for SessionExpired {}
+
+/// Connection carrying JSON-RPC messages to a server.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Open the connection, closing any previous one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server cannot be reached.
+    async fn start(&self) -> Result<()>;
+
+    /// Whether the connection is open.
+    async fn is_running(&self) -> bool;
+
+    /// Send a request and wait for the response with the same id.
+    ///
+    /// Requests the server sends meanwhile are answered by the transport.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Id of the request
+    /// * `message` - The JSON-RPC request
+    ///
+    /// # Returns
+    ///
+    /// The JSON-RPC response, which may hold an error.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection is closed or lost.
+    async fn request(&self, id: u64, message: &Value) -> Result<Value>;
+
+    /// Send a notification.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection is closed or lost.
+    async fn notify(&self, message: &Value) -> Result<()>;
+}
+
+/// A tool offered by a server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteInfo {
+    /// Name of the tool on the server
+    pub name: String,
+    /// Description of the tool
+    pub description: String,
+    /// JSON Schema of the tool's input
+    pub input_schema: Value,
+}
+
+/// Connection to an MCP server.
+pub struct Server {
+    /// Name of the server in the configuration
+    name: String,
+    /// Connection to the server
+    transport: Box<dyn Transport>,
+    /// Id of the next request
+    next_id: AtomicU64,
+    /// Number of restarts since the last successful handshake
+    restarts: AtomicUsize,
+    /// Maximum number of restarts
+    max_restarts: usize,
+    /// Time to wait for an answer
+    timeout: Duration,
+    /// Serializes restarts
+    starting: tokio::sync::Mutex<()>,
+    /// Capabilities announced in the handshake
+    capabilities: Mutex<Value>,
+    /// Cached resource contents
+    cache: Arc<Mutex<Cache>>,
+}
+
+impl Server {
+    /// Create a server from its configuration, without starting it.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the server in the configuration
+    /// * `settings` - Server configuration
+    /// * `cwd` - Working directory of the session, the default directory of the server
+    ///
+    /// # Errors
+    ///
+    /// Returns an error unless exactly one of `command` and `url` is set.
+    pub fn new(name: &str, settings: &McpServer, cwd: &Path) -> Result<Self> {
+        match (&settings.command, &settings.url) {
+            (Some(command), None) => Ok(Self::with_transport(name, settings, |handler| {
+                Box::new(stdio::Stdio::new(command, settings, cwd, handler))
+            })),
+            (None, Some(url)) => Ok(Self::with_transport(name, settings, |handler| {
+                Box::new(http::Http::new(url, settings, handler))
+            })),
+            (Some(_), Some(_)) => anyhow::bail!("MCP server '{name}' sets both command and url"),
+            (None, None) => anyhow::bail!("MCP server '{name}' sets neither command nor url"),
+        }
+    }
+
+    /// Create a server communicating over the transport built by `transport`.
+    ///
+    /// The transport is given the handler of the server's notifications.
+    fn with_transport(
+        name: &str,
+        settings: &McpServer,
+        transport: impl FnOnce(Handler) -> Box<dyn Transport>,
+    ) -> Self {
+        let cache = Arc::new(Mutex::new(Cache::default()));
+        let handler: Handler = {
+            let cache = Arc::clone(&cache);
+            let name = name.to_string();
+            Arc::new(move |message: &Value| {
+                let method = message.get("method").and_then(Value::as_str);
+                if method == Some("notifications/resources/updated")
+                    && let Some(uri) = message.pointer("/params/uri").and_then(Value::as_str)
+                {
+                    tracing::debug!(server = %name, %uri, "MCP resource updated");
+                    let mut cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+                    cache.contents.remove(uri);
+                    *cache.generations.entry(uri.to_string()).or_default() += 1;
+                }
+            })
+        };
+        Self {
+            name: name.to_string(),
+            transport: transport(handler),
+            next_id: AtomicU64::new(1),
+            restarts: AtomicUsize::new(0),
+            max_restarts: settings.max_restarts,
+            timeout: Duration::from_secs(settings.timeout_secs),
+            starting: tokio::sync::Mutex::new(()),
+            capabilities: Mutex::new(Value::Null),
+            cache,
+        }
+    }
+
+    /// Name of the server in the configuration.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Start the server and perform the initialize handshake.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server cannot be started or rejects the handshake.
+    pub async fn connect(&self) -> Result<()> {
+        let _starting = self.starting.lock().await;
+        self.transport.start().await?;
+        self.initialize().await
+    }
+
+    /// Whether the server announced the given capability, e.g. `prompts`.
+    #[must_use]
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(capability)
+            .is_some()
+    }
+
+    /// Perform the initialize handshake on a started connection.
+    ///
+    /// Subscriptions do not survive a restart, so cached contents are dropped.
+    /// Once the handshake succeeds, the restart count starts over.
+    async fn initialize(&self) -> Result<()> {
+        *self.cache.lock().unwrap_or_else(PoisonError::into_inner) = Cache::default();
+        let result = self
+            .exchange(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {"name": "neco", "version": env!("CARGO_PKG_VERSION")}
+                }),
+            )
+            .await?;
+        *self
+            .capabilities
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = result
+            .get("capabilities")
+            .cloned()
+            .unwrap_or_else(|| json!({}));
+        self.transport
+            .notify(&json!({"jsonrpc": "2.0", "method": "notifications/initialized"}))
+            .await?;
+        self.restarts.store(0, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Restart the server if it is not running.
+    async fn ensure_running(&self) -> Result<()> {
+        let _starting = self.starting.lock().await;
+        if self.transport.is_running().await {
+            return Ok(());
+        }
+        let restarts = self.restarts.fetch_add(1, Ordering::Relaxed);
+        if restarts >= self.max_restarts {
+            anyhow::bail!(
+                "MCP server '{}' stopped and was already restarted {restarts} times",
+                self.name
+            );
+        }
+        tracing::warn!(server = %self.name, "Restarting MCP server");
+        self.transport.start().await?;
+        self.initialize().await
+    }
+
+    /// Send a request, restarting the server first if it crashed.
+    ///
+    /// When the server dropped the session, a new one is started and the
+    /// request is sent again once.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - JSON-RPC method
+    /// * `params` - Parameters of the method
+    ///
+    /// # Returns
+    ///
+    /// The result of the request.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server cannot be restarted, the connection is
+    /// lost, the request times out or the server answers with an error.
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        self.ensure_running().await?;
+        match self.exchange(method, params.clone()).await {
+            Err(error) if error.downcast_ref::<SessionExpired>().is_some() => {
+                tracing::warn!(server = %self.name, "MCP session expired, reconnecting");
+                self.connect().await?;
+                self.exchange(method, params).await
+            },
+            result => result,
+        }
+    }
+
+    /// Send a request on the current connection and wait for its result.
+    async fn exchange(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let message = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        let response = tokio::time::timeout(self.timeout, self.transport.request(id, &message))
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "MCP server '{}' did not answer {method} within {}s",
+                    self.name,
+                    self.timeout.as_secs()
+                )
+            })?
+            .with_context(|| format!("MCP server '{}' failed during {method}", self.name))?;
+
+        if let Some(error) = response.get("error") {
+            anyhow::bail!(
+                "MCP server '{}' returned an error for {method}: {} (code {})",
+                self.name,
+                error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or("unknown error"),
+                error.get("code").unwrap_or(&Value::Null)
+            );
+        }
+        Ok(response.get("result").cloned().unwrap_or(Value::Null))
+    }
+
+    /// List the items of a paginated list method, e.g. `tools/list`.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - List method
+    /// * `key` - Field of the result holding the items
+    async fn list(&self, method: &str, key: &str) -> Result<Vec<Value>> {
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = cursor.map_or_else(|| json!({}), |cursor| json!({"cursor": cursor}));
+            let result = self.request(method, params).await?;
+            if let Some(page) = result.get(key).and_then(Value::as_array) {
+                items.extend(page.iter().cloned());
+            }
+            cursor = result
+                .get("nextCursor")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            if cursor.is_none() {
+                return Ok(items);
+            }
+        }
+    }
+
+    /// List the tools of the server, following pagination.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails.
+    pub async fn list_tools(&self) -> Result<Vec<RemoteInfo>> {
+        Ok(self
+            .list("tools/list", "tools")
+            .await?
+            .iter()
+            .filter_map(|tool| {
+                Some(RemoteInfo {
+                    name: tool.get("name")?.as_str()?.to_string(),
+                    description: text_field(tool, "description"),
+                    input_schema: tool
+                        .get("inputSchema")
+                        .cloned()
+                        .unwrap_or_else(|| json!({"type": "object"})),
+                })
+            })
+            .collect())
+    }
+
+    /// List the resources of the server, following pagination.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails.
+    pub async fn list_resources(&self) -> Result<Vec<Resource>> {
+        Ok(self
+            .list("resources/list", "resources")
+            .await?
+            .iter()
+            .filter_map(Resource::parse)
+            .collect())
+    }
+
+    /// Read a resource of the server.
+    ///
+    /// When the server supports subscriptions, the resource is subscribed to
+    /// and its contents are cached until the server notifies a change. Contents
+    /// read while a change was notified are not cached, as they may be stale.
+    ///
+    /// # Arguments
+    ///
+    /// * `uri` - URI of the resource
+    ///
+    /// # Returns
+    ///
+    /// The contents of the resource: objects with a `uri`, a `mimeType` and
+    /// either a `text` or a base64 `blob`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails.
+    pub async fn read_resource(&self, uri: &str) -> Result<Vec<Value>> {
+        if let Some(contents) = self.lock_cache().contents.get(uri) {
+            return Ok(contents.clone());
+        }
+        let subscribable = self
+            .capabilities
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pointer("/resources/subscribe")
+            .and_then(Value::as_bool)
+            == Some(true);
+        if subscribable && !self.lock_cache().subscribed.contains(uri) {
+            self.request("resources/subscribe", json!({"uri": uri}))
+                .await?;
+            self.lock_cache().subscribed.insert(uri.to_string());
+        }
+
+        let generation = self.lock_cache().generation(uri);
+        let result = self.request("resources/read", json!({"uri": uri})).await?;
+        let contents = result
+            .get("contents")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        let mut cache = self.lock_cache();
+        if cache.subscribed.contains(uri) && cache.generation(uri) == generation {
+            cache.contents.insert(uri.to_string(), contents.clone());
+        }
+        Ok(contents)
+    }
+
+    /// List the prompts of the server, following pagination.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails.
+    pub async fn list_prompts(&self) -> Result<Vec<PromptInfo>> {
+        Ok(self
+            .list("prompts/list", "prompts")
+            .await?
+            .iter()
+            .filter_map(PromptInfo::parse)
+            .collect())
+    }
+
+    /// Get a prompt of the server, filled with the given arguments.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the prompt on the server
+    /// * `arguments` - Values of the prompt arguments
+    ///
+    /// # Returns
+    ///
+    /// The text of the prompt messages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails.
+    pub async fn get_prompt(
+        &self,
+        name: &str,
+        arguments: &indexmap::IndexMap<String, String>,
+    ) -> Result<String> {
+        let result = self
+            .request("prompts/get", json!({"name": name, "arguments": arguments}))
+            .await?;
+        Ok(result
+            .get("messages")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|message| message.get("content"))
+            .map(block_text)
+            .collect::<Vec<_>>()
+            .join("\n\n"))
+    }
+
+    /// Lock the resource cache.
+    fn lock_cache(&self) -> std::sync::MutexGuard<'_, Cache> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Call a tool of the server.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the tool on the server
+    /// * `arguments` - Input of the tool
+    ///
+    /// # Returns
+    ///
+    /// The text of the tool result.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the tool reports an error.
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<String> {
+        let result = self
+            .request("tools/call", json!({"name": name, "arguments": arguments}))
+            .await?;
+        let text = content_text(&result);
+        if result.get("isError").and_then(Value::as_bool) == Some(true) {
+            anyhow::bail!("{text}");
+        }
+        Ok(text)
+    }
+}
+
+/// Answer a request sent by the server.
+///
+/// Only `ping` is supported; other methods get a "method not found" error.
+fn reply(request: &Value) -> Value {
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    match request.get("method").and_then(Value::as_str) {
+        Some("ping") => json!({"jsonrpc": "2.0", "id": id, "result": {}}),
+        method => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {"code": -32601, "message": format!("Method not found: {}", method.unwrap_or_default())}
+        }),
+    }
+}
+
+/// Text of the content blocks of a tool or prompt result.
+///
+/// Non-text content is described in brackets. Structured content is used
+/// when there is no content.
+#[must_use]
+pub fn content_text(result: &Value) -> String {
+    let blocks = result
+        .get("content")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    if blocks.is_empty() {
+        return result
+            .get("structuredContent")
+            .map(Value::to_string)
+            .unwrap_or_default();
+    }
+
+    blocks.iter().map(block_text).collect::<Vec<_>>().join("\n")
+}
+
+/// Text of a content block; non-text content is described in brackets.
+fn block_text(block: &Value) -> String {
+    match block.get("type").and_then(Value::as_str) {
+        Some("text") => text_field(block, "text"),
+        Some("resource") => {
+            let resource = block.get("resource").unwrap_or(&Value::Null);
+            resource.get("text").and_then(Value::as_str).map_or_else(
+                || format!("[resource {}]", text_field(resource, "uri")),
+                str::to_string,
+            )
+        },
+        Some("resource_link") => format!("[resource {}]", text_field(block, "uri")),
+        Some(kind) => format!("[{kind} {}]", text_field(block, "mimeType")),
+        None => block.to_string(),
+    }
+}
+
+/// A string field of an object, empty if missing.
+fn text_field(value: &Value, key: &str) -> String {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Name of a remote tool or prompt as offered: `mcp__<server>__<name>`.
+///
+/// Characters the API does not accept are replaced by `_` and the name is
+/// cut to the maximum length.
+#[must_use]
+pub fn qualified_name(server: &str, name: &str) -> String {
+    format!("mcp__{server}__{name}")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(MAX_TOOL_NAME)
+        .collect()
+}
+
+/// A tool of an MCP server.
+pub struct RemoteTool {
+    /// Server offering the tool
+    server: Arc<Server>,
+    /// Name offered to the model
+    name: String,
+    /// The tool as listed by the server
+    info: RemoteInfo,
+}
+
+impl RemoteTool {
+    /// Wrap a tool of a server.
+    #[must_use]
+    pub fn new(server: Arc<Server>, info: RemoteInfo) -> Self {
+        Self {
+            name: qualified_name(server.name(), &info.name),
+            server,
+            info,
+        }
+    }
+
+    /// Name of the tool on its server.
+    #[must_use]
+    pub fn remote_name(&self) -> &str {
+        &self.info.name
+    }
+}
+
+#[async_trait]
+impl Tool for RemoteTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.info.description
+    }
+
+    fn input_schema(&self) -> Value {
+        self.info.input_schema.clone()
+    }
+
+    async fn execute(&self, input: &Value) -> Result<String> {
+        self.server.call_tool(&self.info.name, input.clone()).await
+    }
+}
+
+/// Start a server and wrap its tools.
+///
+/// # Errors
+///
+/// Returns an error if the server cannot be started or its tools cannot be listed.
+pub async fn connect(server: &Arc<Server>) -> Result<Vec<RemoteTool>> {
+    server.connect().await?;
+    Ok(server
+        .list_tools()
+        .await?
+        .into_iter()
+        .map(|info| RemoteTool::new(Arc::clone(server), info))
+        .collect())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::tools::Tool;
+
+    #[tokio::test]
+    async fn test_stdio_tools_and_restart() {
+        let (dir, settings) = fake::fixture().unwrap();
+        let server = Arc::new(Server::new("fix ture", &settings, &dir).unwrap());
+        let tools = connect(&server).await.unwrap();
+        let names: Vec<&str> = tools.iter().map(Tool::name).collect();
+        assert_eq!(names, ["mcp__fix_ture__echo", "mcp__fix_ture__crash"]);
+        let echo = tools.first().unwrap();
+        assert_eq!(echo.description(), "Echo text");
+        assert_eq!(
+            echo.input_schema()
+                .pointer("/properties/text/type")
+                .unwrap(),
+            "string"
+        );
+        assert_eq!(
+            echo.execute(&json!({"text": "hi"})).await.unwrap(),
+            "echo: hi"
+        );
+        server
+            .request("unknown/method", json!({}))
+            .await
+            .unwrap_err();
+
+        let crash = tools.get(1).unwrap();
+        let error = crash.execute(&json!({})).await.unwrap_err();
+        assert!(format!("{error:#}").contains("failed during tools/call"));
+
+        // Restarted before the next request; each successful handshake
+        // resets the count
+        assert_eq!(
+            echo.execute(&json!({"text": "again"})).await.unwrap(),
+            "echo: again"
+        );
+        crash.execute(&json!({})).await.unwrap_err();
+        assert_eq!(
+            echo.execute(&json!({"text": "more"})).await.unwrap(),
+            "echo: more"
+        );
+
+        // A restart that fails counts, and the limit stops further ones
+        std::fs::remove_file(dir.join("server.sh")).unwrap();
+        crash.execute(&json!({})).await.unwrap_err();
+        echo.execute(&json!({"text": "x"})).await.unwrap_err();
+        let error = echo.execute(&json!({"text": "x"})).await.unwrap_err();
+        assert!(error.to_string().contains("already restarted 1 times"));
+
+        let log = std::fs::read_to_string(dir.join("server.sh.log")).unwrap();
+        assert_eq!(log.lines().count(), 3);
+    }
+
+    /// In-process server with subscribable resources and a prompt.
+    struct Fake {
+        /// Handler of the notifications sent by the server
+        handler: Handler,
+        /// Methods of the requests received
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Transport for Fake {
+        async fn start(&self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn is_running(&self) -> bool {
+            true
+        }
+
+        async fn request(&self, id: u64, message: &Value) -> Result<Value> {
+            let method = message
+                .get("method")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let mut log = self.log.lock().unwrap_or_else(PoisonError::into_inner);
+            log.push(method.to_string());
+            let reads = log
+                .iter()
+                .filter(|method| *method == "resources/read")
+                .count();
+            drop(log);
+            let result = match method {
+                "initialize" => {
+                    json!({"capabilities": {"resources": {"subscribe": true}, "prompts": {}}})
+                },
+                "resources/read" => {
+                    let uri = message.pointer("/params/uri").cloned().unwrap_or_default();
+                    if uri == "doc://changing" {
+                        (self.handler)(&json!({
+                            "jsonrpc": "2.0",
+                            "method": "notifications/resources/updated",
+                            "params": {"uri": uri}
+                        }));
+                    }
+                    json!({"contents": [{"uri": uri, "text": format!("v{reads}")}]})
+                },
+                "tools/call" => {
+                    (self.handler)(&json!({
+                        "jsonrpc": "2.0",
+                        "method": "notifications/resources/updated",
+                        "params": {"uri": "doc://a"}
+                    }));
+                    json!({"content": [{"type": "text", "text": "touched"}]})
+                },
+                "prompts/get" => json!({"messages": [
+                    {"role": "user", "content": {"type": "text", "text": format!(
+                        "Review {}",
+                        message.pointer("/params/arguments/branch").and_then(Value::as_str).unwrap_or_default()
+                    )}},
+                    {"role": "assistant", "content": {"type": "text", "text": "Sure"}}
+                ]}),
+                _ => json!({}),
+            };
+            Ok(json!({"jsonrpc": "2.0", "id": id, "result": result}))
+        }
+
+        async fn notify(&self, _message: &Value) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resource_cache_and_prompts() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let server = Server::with_transport("docs", &McpServer::default(), |handler| {
+            Box::new(Fake {
+                handler,
+                log: Arc::clone(&log),
+            })
+        });
+        server.connect().await.unwrap();
+        assert!(server.supports("resources"));
+        assert!(!server.supports("tools"));
+
+        let text = |contents: Vec<Value>| resources::text(&contents);
+        assert_eq!(text(server.read_resource("doc://a").await.unwrap()), "v1");
+        assert_eq!(text(server.read_resource("doc://a").await.unwrap()), "v1");
+        server.call_tool("touch", json!({})).await.unwrap();
+        assert_eq!(text(server.read_resource("doc://a").await.unwrap()), "v2");
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "initialize",
+                "resources/subscribe",
+                "resources/read",
+                "tools/call",
+                "resources/read"
+            ]
+        );
+
+        // Changed while being read: not cached
+        assert_eq!(
+            text(server.read_resource("doc://changing").await.unwrap()),
+            "v3"
+        );
+        assert_eq!(
+            text(server.read_resource("doc://changing").await.unwrap()),
+            "v4"
+        );
+
+        let arguments = indexmap::IndexMap::from([("branch".to_string(), "main".to_string())]);
+        assert_eq!(
+            server.get_prompt("review", &arguments).await.unwrap(),
+            "Review main\n\nSure"
+        );
+    }
+
+    #[test]
+    fn test_tool_name_and_content() {
+        assert_eq!(
+            qualified_name("git hub", "list.issues"),
+            "mcp__git_hub__list_issues"
+        );
+        assert_eq!(qualified_name("s", &"x".repeat(100)).len(), MAX_TOOL_NAME);
+        let result = json!({"content": [
+            {"type": "text", "text": "one"},
+            {"type": "image", "mimeType": "image/png", "data": "..."},
+            {"type": "resource", "resource": {"uri": "file:///a", "text": "body"}},
+            {"type": "resource_link", "uri": "file:///b"}
+        ]});
+        assert_eq!(
+            content_text(&result),
+            "one\n[image image/png]\nbody\n[resource file:///b]"
+        );
+        assert_eq!(
+            content_text(&json!({"structuredContent": {"n": 1}})),
+            "{\"n\":1}"
+        );
+    }
+}
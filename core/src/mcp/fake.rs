@@ -0,0 +1,46 @@
+//! Fake MCP server over stdio, for tests of servers and sessions.
+
+use crate::config::McpServer;
+use crate::testing::TempDir;
+
+/// MCP server fixture: answers the handshake, lists `echo` and `crash`,
+/// echoes the `text` argument and exits on `crash`.
+const FIXTURE: &str = r#"#!/bin/sh
+echo "started" >> "$0.log"
+while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+  case "$line" in
+    *'"method":"initialize"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2025-06-18","capabilities":{"tools":{}},"serverInfo":{"name":"fixture","version":"1"}}}\n' "$id" ;;
+    *'"method":"tools/list"'*)
+      printf '{"jsonrpc":"2.0","method":"notifications/message","params":{}}\n'
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[{"name":"echo","description":"Echo text","inputSchema":{"type":"object","properties":{"text":{"type":"string"}}}},{"name":"crash","description":"Exit"}]}}\n' "$id" ;;
+    *'"name":"crash"'*)
+      exit 1 ;;
+    *'"method":"tools/call"'*)
+      text=$(printf '%s' "$line" | sed -n 's/.*"text":"\([^"]*\)".*/\1/p')
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"echo: %s"}]}}\n' "$id" "$text" ;;
+    *'"id":'*)
+      printf '{"jsonrpc":"2.0","id":%s,"error":{"code":-32601,"message":"Method not found"}}\n' "$id" ;;
+  esac
+done
+"#;
+
+/// Write the fixture server to a temporary directory, removed on drop.
+///
+/// # Errors
+///
+/// Returns error if the script cannot be written.
+pub fn fixture() -> std::io::Result<(TempDir, McpServer)> {
+    let dir = TempDir::new("mcp");
+    let script = dir.join("server.sh");
+    std::fs::write(&script, FIXTURE)?;
+    let settings = McpServer {
+        command: Some("sh".to_string()),
+        args: vec![script.display().to_string()],
+        timeout_secs: 10,
+        max_restarts: 1,
+        ..McpServer::default()
+    };
+    Ok((dir, settings))
+}
@@ -0,0 +1,894 @@
+//! Transports over HTTP.
+//!
+//! The Streamable HTTP transport posts each message to the server URL; the
+//! answer comes back as JSON or as a stream of server-sent events. When the
+//! server rejects the first post as a bad request, not found or method not
+//! allowed, the legacy HTTP+SSE transport is used: a
+//! long-lived event stream carries the answers, and messages are posted to
+//! the endpoint announced on that stream.
+
+use super::{Handler, Pending, SessionExpired, Transport};
+use crate::config::McpServer;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::StreamExt;
+use indexmap::IndexMap;
+use reqwest::header::{ACCEPT, AUTHORIZATION, HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Response, StatusCode, Url};
+use serde_json::Value;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Header carrying the session id of the Streamable HTTP transport.
+const SESSION_HEADER: &str = "mcp-session-id";
+
+/// Header carrying the negotiated protocol version.
+const VERSION_HEADER: &str = "mcp-protocol-version";
+
+/// Media types accepted for answers to posted messages.
+const ACCEPT_POST: &str = "application/json, text/event-stream";
+
+/// A server-sent event.
+#[derive(Debug, PartialEq, Eq)]
+struct Event {
+    /// Event type, `message` unless set
+    kind: String,
+    /// Data lines of the event, joined by newlines
+    data: String,
+}
+
+/// Incremental parser of a server-sent event stream.
+#[derive(Default)]
+struct Events {
+    /// Bytes of the incomplete last line
+    buffer: Vec<u8>,
+    /// Type of the event being read
+    kind: String,
+    /// Data of the event being read
+    data: Vec<String>,
+}
+
+impl Events {
+    /// Feed a chunk of the stream and return the events it completes.
+    fn push(&mut self, chunk: &[u8]) -> Vec<Event> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    events.push(Event {
+                        kind: if self.kind.is_empty() {
+                            "message".to_string()
+                        } else {
+                            std::mem::take(&mut self.kind)
+                        },
+                        data: std::mem::take(&mut self.data).join("\n"),
+                    });
+                }
+                self.kind.clear();
+                continue;
+            }
+            let (field, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "event" => self.kind = value.to_string(),
+                "data" => self.data.push(value.to_string()),
+                _ => {},
+            }
+        }
+        events
+    }
+}
+
+/// Connection of the legacy HTTP+SSE transport.
+struct Legacy {
+    /// URL messages are posted to
+    endpoint: Url,
+    /// Requests waiting for their responses on the event stream
+    pending: Pending,
+    /// Task reading the event stream
+    reader: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for Legacy {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/// Task reading the messages a Streamable HTTP server sends on its own.
+struct Listener(tokio::task::JoinHandle<()>);
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// State of the connection to the server.
+enum State {
+    /// Not connected, or connection lost
+    Closed,
+    /// Streamable HTTP transport
+    Streamable {
+        /// Session id assigned by the server
+        session: Option<String>,
+        /// Whether no message was accepted yet, so the legacy transport may be tried
+        probing: bool,
+        /// Listener for server messages, opened after the handshake
+        listener: Option<Listener>,
+    },
+    /// Legacy HTTP+SSE transport
+    Legacy(Legacy),
+}
+
+/// Post a message with the Streamable HTTP transport.
+async fn post(
+    client: &reqwest::Client,
+    url: &str,
+    headers: HeaderMap,
+    session: Option<&str>,
+    message: &Value,
+) -> Result<Response> {
+    let mut request = client
+        .post(url)
+        .headers(headers)
+        .header(ACCEPT, ACCEPT_POST)
+        .json(message);
+    if let Some(session) = session {
+        request = request.header(SESSION_HEADER, session);
+    }
+    if message.get("method").and_then(Value::as_str) != Some("initialize") {
+        request = request.header(VERSION_HEADER, super::PROTOCOL_VERSION);
+    }
+    Ok(request.send().await?)
+}
+
+/// Transport reaching the server over HTTP.
+pub struct Http {
+    /// URL of the server
+    url: String,
+    /// Configured headers, with `${VAR}` placeholders
+    headers: IndexMap<String, String>,
+    /// Environment variable holding the bearer token
+    bearer_token_env: Option<String>,
+    /// Shell command printing the bearer token
+    bearer_token_command: Option<String>,
+    /// Handler of the server's notifications
+    handler: Handler,
+    /// HTTP client
+    client: reqwest::Client,
+    /// Headers resolved when the connection was opened
+    resolved: Mutex<HeaderMap>,
+    /// State of the connection
+    state: tokio::sync::Mutex<State>,
+}
+
+impl Http {
+    /// Create the transport of a configured server, without connecting.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - URL of the server
+    /// * `settings` - Server configuration
+    /// * `handler` - Handler of the server's notifications
+    #[must_use]
+    pub fn new(url: &str, settings: &McpServer, handler: Handler) -> Self {
+        Self {
+            url: url.to_string(),
+            headers: settings.headers.clone(),
+            bearer_token_env: settings.bearer_token_env.clone(),
+            bearer_token_command: settings.bearer_token_command.clone(),
+            handler,
+            client: reqwest::Client::new(),
+            resolved: Mutex::new(HeaderMap::new()),
+            state: tokio::sync::Mutex::new(State::Closed),
+        }
+    }
+
+    /// Headers of the connection: configured headers and bearer token.
+    fn headers(&self) -> HeaderMap {
+        self.resolved
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Resolve the configured headers and the bearer token.
+    async fn resolve_headers(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes())
+                    .with_context(|| format!("Invalid header name '{name}'"))?,
+                HeaderValue::from_str(&expand(value))
+                    .with_context(|| format!("Invalid value for header '{name}'"))?,
+            );
+        }
+        let token = if let Some(command) = &self.bearer_token_command {
+            let output = tokio::process::Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .output()
+                .await
+                .with_context(|| format!("Failed to run '{command}'"))?;
+            anyhow::ensure!(
+                output.status.success(),
+                "Bearer token command '{command}' failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        } else if let Some(name) = &self.bearer_token_env {
+            Some(std::env::var(name).with_context(|| format!("{name} is not set"))?)
+        } else {
+            None
+        };
+        if let Some(token) = token {
+            headers.insert(
+                AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {token}"))
+                    .context("Invalid bearer token")?,
+            );
+        }
+        Ok(headers)
+    }
+
+    /// Post a message with the Streamable HTTP transport and check the status.
+    ///
+    /// # Returns
+    ///
+    /// The response, or `None` when the server rejected the first message and
+    /// the legacy transport should be tried.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SessionExpired`] when the server no longer knows the session.
+    async fn post_streamable(
+        &self,
+        state: &mut State,
+        message: &Value,
+    ) -> Result<Option<Response>> {
+        let State::Streamable {
+            session, probing, ..
+        } = state
+        else {
+            anyhow::bail!("not connected");
+        };
+        let response = match post(
+            &self.client,
+            &self.url,
+            self.headers(),
+            session.as_deref(),
+            message,
+        )
+        .await
+        {
+            Ok(response) => response,
+            Err(error) => {
+                *state = State::Closed;
+                return Err(error);
+            },
+        };
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND && session.is_some() {
+            *state = State::Closed;
+            return Err(SessionExpired.into());
+        }
+        if *probing
+            && matches!(
+                status,
+                StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED
+            )
+        {
+            tracing::debug!(url = %self.url, %status, "Falling back to the HTTP+SSE transport");
+            return Ok(None);
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!("HTTP {status}: {}", body.trim());
+        }
+        *probing = false;
+        if let Some(id) = response
+            .headers()
+            .get(SESSION_HEADER)
+            .and_then(|value| value.to_str().ok())
+        {
+            *session = Some(id.to_string());
+        }
+        Ok(Some(response))
+    }
+
+    /// Read the answer to a request posted with the Streamable HTTP transport.
+    ///
+    /// Requests and notifications sent before the answer are handled.
+    async fn receive(&self, session: Option<&str>, id: u64, response: Response) -> Result<Value> {
+        let event_stream = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|kind| kind.starts_with("text/event-stream"));
+        if !event_stream {
+            return Ok(response.json().await?);
+        }
+
+        let mut events = Events::default();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            for event in events.push(&chunk?) {
+                let Ok(message) = serde_json::from_str::<Value>(&event.data) else {
+                    continue;
+                };
+                match (message.get("id"), message.get("method")) {
+                    (Some(_), Some(_)) => {
+                        let reply = super::reply(&message);
+                        post(&self.client, &self.url, self.headers(), session, &reply).await?;
+                    },
+                    (None, Some(_)) => (self.handler)(&message),
+                    (Some(response_id), None) if response_id.as_u64() == Some(id) => {
+                        return Ok(message);
+                    },
+                    _ => {},
+                }
+            }
+        }
+        anyhow::bail!("event stream ended without a response")
+    }
+
+    /// Listen for the requests and notifications the server sends on its own.
+    ///
+    /// Servers that do not offer the stream answer with an error, which ends
+    /// the listener.
+    fn listen(&self, session: Option<String>) -> Listener {
+        let client = self.client.clone();
+        let url = self.url.clone();
+        let headers = self.headers();
+        let handler = Arc::clone(&self.handler);
+        Listener(tokio::spawn(async move {
+            let mut request = client
+                .get(&url)
+                .headers(headers.clone())
+                .header(ACCEPT, "text/event-stream")
+                .header(VERSION_HEADER, super::PROTOCOL_VERSION);
+            if let Some(session) = &session {
+                request = request.header(SESSION_HEADER, session);
+            }
+            let Ok(response) = request.send().await else {
+                return;
+            };
+            if !response.status().is_success() {
+                tracing::debug!(%url, status = %response.status(), "No MCP server event stream");
+                return;
+            }
+            let mut events = Events::default();
+            let mut stream = response.bytes_stream();
+            while let Some(Ok(chunk)) = stream.next().await {
+                for event in events.push(&chunk) {
+                    let Ok(message) = serde_json::from_str::<Value>(&event.data) else {
+                        continue;
+                    };
+                    match (message.get("id"), message.get("method")) {
+                        (Some(_), Some(_)) => {
+                            let reply = super::reply(&message);
+                            let _ =
+                                post(&client, &url, headers.clone(), session.as_deref(), &reply)
+                                    .await;
+                        },
+                        (None, Some(_)) => handler(&message),
+                        _ => {},
+                    }
+                }
+            }
+        }))
+    }
+
+    /// Open the event stream of the legacy HTTP+SSE transport.
+    async fn open_legacy(&self) -> Result<Legacy> {
+        let response = self
+            .client
+            .get(&self.url)
+            .headers(self.headers())
+            .header(ACCEPT, "text/event-stream")
+            .send()
+            .await?
+            .error_for_status()?;
+        let mut stream = response.bytes_stream();
+        let mut events = Events::default();
+        let mut backlog = Vec::new();
+        let endpoint = 'endpoint: loop {
+            let chunk = stream
+                .next()
+                .await
+                .context("event stream ended before the endpoint event")??;
+            let mut received = events.push(&chunk).into_iter();
+            while let Some(event) = received.next() {
+                if event.kind == "endpoint" {
+                    backlog.extend(received);
+                    break 'endpoint Url::parse(&self.url)?.join(event.data.trim())?;
+                }
+            }
+        };
+
+        let pending = Pending::default();
+        let reader = {
+            let pending = pending.clone();
+            let handler = Arc::clone(&self.handler);
+            let client = self.client.clone();
+            let headers = self.headers();
+            let endpoint = endpoint.clone();
+            tokio::spawn(async move {
+                let mut backlog = backlog;
+                loop {
+                    for event in backlog.drain(..).filter(|event| event.kind == "message") {
+                        let Ok(message) = serde_json::from_str::<Value>(&event.data) else {
+                            continue;
+                        };
+                        if let Some(reply) = pending.dispatch(message, &handler) {
+                            let _ = client
+                                .post(endpoint.clone())
+                                .headers(headers.clone())
+                                .json(&reply)
+                                .send()
+                                .await;
+                        }
+                    }
+                    match stream.next().await {
+                        Some(Ok(chunk)) => backlog = events.push(&chunk),
+                        _ => break,
+                    }
+                }
+                pending.close();
+            })
+        };
+        Ok(Legacy {
+            endpoint,
+            pending,
+            reader,
+        })
+    }
+
+    /// Post a message with the legacy transport.
+    async fn post_legacy(&self, endpoint: Url, message: &Value) -> Result<()> {
+        self.client
+            .post(endpoint)
+            .headers(self.headers())
+            .json(message)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Send a request with the legacy transport and wait for its response.
+    async fn request_legacy(&self, state: &State, id: u64, message: &Value) -> Result<Value> {
+        let State::Legacy(legacy) = state else {
+            anyhow::bail!("not connected");
+        };
+        let receiver = legacy.pending.wait(id);
+        if let Err(error) = self.post_legacy(legacy.endpoint.clone(), message).await {
+            legacy.pending.cancel(id);
+            return Err(error);
+        }
+        receiver
+            .await
+            .context("event stream ended without a response")
+    }
+}
+
+/// Replace `${VAR}` placeholders with environment variables, empty if unset.
+fn expand(value: &str) -> String {
+    let mut text = String::new();
+    let mut rest = value;
+    while let Some((before, after)) = rest.split_once("${")
+        && let Some((name, after)) = after.split_once('}')
+    {
+        text.push_str(before);
+        text.push_str(&std::env::var(name).unwrap_or_default());
+        rest = after;
+    }
+    text.push_str(rest);
+    text
+}
+
+#[async_trait]
+impl Transport for Http {
+    async fn start(&self) -> Result<()> {
+        let mut state = self.state.lock().await;
+        if let State::Streamable {
+            session: Some(session),
+            ..
+        } = &*state
+        {
+            let _ = self
+                .client
+                .delete(&self.url)
+                .headers(self.headers())
+                .header(SESSION_HEADER, session)
+                .send()
+                .await;
+        }
+        *state = State::Closed;
+        let headers = self.resolve_headers().await?;
+        *self.resolved.lock().unwrap_or_else(PoisonError::into_inner) = headers;
+        *state = State::Streamable {
+            session: None,
+            probing: true,
+            listener: None,
+        };
+        Ok(())
+    }
+
+    async fn is_running(&self) -> bool {
+        match &*self.state.lock().await {
+            State::Closed => false,
+            State::Streamable { .. } => true,
+            State::Legacy(legacy) => !legacy.pending.is_closed(),
+        }
+    }
+
+    async fn request(&self, id: u64, message: &Value) -> Result<Value> {
+        let mut state = self.state.lock().await;
+        if let State::Streamable { .. } = &*state {
+            if let Some(response) = self.post_streamable(&mut state, message).await? {
+                let session = match &*state {
+                    State::Streamable { session, .. } => session.clone(),
+                    _ => None,
+                };
+                let result = self.receive(session.as_deref(), id, response).await;
+                if result.is_err() {
+                    *state = State::Closed;
+                }
+                return result;
+            }
+            *state = match self.open_legacy().await {
+                Ok(legacy) => State::Legacy(legacy),
+                Err(error) => {
+                    *state = State::Closed;
+                    return Err(error.context("server rejected Streamable HTTP and HTTP+SSE"));
+                },
+            };
+        }
+        let result = self.request_legacy(&state, id, message).await;
+        if result.is_err() {
+            *state = State::Closed;
+        }
+        result
+    }
+
+    async fn notify(&self, message: &Value) -> Result<()> {
+        let mut state = self.state.lock().await;
+        match &mut *state {
+            State::Closed => anyhow::bail!("not connected"),
+            State::Streamable { .. } => {
+                self.post_streamable(&mut state, message)
+                    .await?
+                    .context("notification rejected")?;
+                if message.get("method").and_then(Value::as_str)
+                    == Some("notifications/initialized")
+                    && let State::Streamable {
+                        session, listener, ..
+                    } = &mut *state
+                {
+                    *listener = Some(self.listen(session.clone()));
+                }
+                Ok(())
+            },
+            State::Legacy(legacy) => {
+                let endpoint = legacy.endpoint.clone();
+                let result = self.post_legacy(endpoint, message).await;
+                if result.is_err() {
+                    *state = State::Closed;
+                }
+                result
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::{Server, connect};
+    use crate::tools::Tool;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::mpsc;
+
+    /// Requests received by the fixture, as `METHOD path header=value body`.
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    /// Answer of the fixture to a JSON-RPC request.
+    fn answer(request: &Value) -> Value {
+        let id = request.get("id").cloned().unwrap_or_default();
+        let result = match request.get("method").and_then(Value::as_str) {
+            Some("initialize") => json!({"protocolVersion": "2025-06-18", "capabilities": {}}),
+            Some("tools/list") => json!({"tools": [{"name": "echo", "description": "Echo"}]}),
+            _ => {
+                let text = request
+                    .pointer("/params/arguments/text")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default();
+                json!({"content": [{"type": "text", "text": format!("echo: {text}")}]})
+            },
+        };
+        json!({"jsonrpc": "2.0", "id": id, "result": result})
+    }
+
+    /// Read one HTTP request: method, path, headers and body.
+    async fn read_request(
+        stream: TcpStream,
+    ) -> std::io::Result<(TcpStream, String, String, HashMap<String, String>, Value)> {
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).await?;
+        let mut parts = line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_string();
+        let path = parts.next().unwrap_or_default().to_string();
+        let mut headers = HashMap::new();
+        loop {
+            line.clear();
+            reader.read_line(&mut line).await?;
+            let Some((name, value)) = line.trim_end().split_once(": ") else {
+                break;
+            };
+            headers.insert(name.to_lowercase(), value.to_string());
+        }
+        let length = headers
+            .get("content-length")
+            .and_then(|length| length.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).await?;
+        let body = serde_json::from_slice(&body).unwrap_or_default();
+        Ok((reader.into_inner(), method, path, headers, body))
+    }
+
+    /// Write a complete HTTP response and close the connection.
+    async fn respond(mut stream: TcpStream, status: &str, headers: &str, body: &str) {
+        let response = format!(
+            "HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+    }
+
+    /// Serve an MCP server over Streamable HTTP, or over HTTP+SSE when `legacy`.
+    ///
+    /// While `expire` is set, the next post in a session is answered with
+    /// 404 Not Found, as if the server dropped the session.
+    async fn serve(legacy: bool, log: Log, expire: Arc<AtomicBool>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+        let (events, receiver) = mpsc::unbounded_channel::<Value>();
+        let receiver = Arc::new(tokio::sync::Mutex::new(receiver));
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let log = Arc::clone(&log);
+                let events = events.clone();
+                let receiver = Arc::clone(&receiver);
+                let expire = Arc::clone(&expire);
+                tokio::spawn(async move {
+                    let Ok((mut stream, method, path, headers, body)) = read_request(stream).await
+                    else {
+                        return;
+                    };
+                    let header = |name: &str| headers.get(name).cloned().unwrap_or_default();
+                    log.lock().unwrap().push(format!(
+                        "{method} {path} auth={} session={} {body}",
+                        header("authorization"),
+                        header("mcp-session-id")
+                    ));
+                    let request = body.get("method").is_some() && body.get("id").is_some();
+                    match (legacy, method.as_str(), path.as_str()) {
+                        (false, "POST", _)
+                            if !header("mcp-session-id").is_empty()
+                                && expire.swap(false, Ordering::Relaxed) =>
+                        {
+                            respond(stream, "404 Not Found", "", "").await;
+                        },
+                        (true, "POST", "/mcp") => {
+                            respond(stream, "405 Method Not Allowed", "", "").await;
+                        },
+                        (true, "GET", "/mcp") => {
+                            let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\n\
+                                        event: endpoint\ndata: /messages?s=1\n\n";
+                            let _ = stream.write_all(head.as_bytes()).await;
+                            let mut receiver = receiver.lock().await;
+                            while let Some(message) = receiver.recv().await {
+                                let event = format!("event: message\ndata: {message}\n\n");
+                                if stream.write_all(event.as_bytes()).await.is_err() {
+                                    break;
+                                }
+                            }
+                        },
+                        (true, "POST", _) => {
+                            if request {
+                                let _ = events.send(answer(&body));
+                            }
+                            respond(stream, "202 Accepted", "", "").await;
+                        },
+                        (false, "POST", _) if !request => {
+                            respond(stream, "202 Accepted", "", "").await;
+                        },
+                        (false, "POST", _) if body.get("method") == Some(&json!("tools/list")) => {
+                            let ping = json!({"jsonrpc": "2.0", "id": "p1", "method": "ping"});
+                            let events = format!("data: {ping}\n\ndata: {}\n\n", answer(&body));
+                            respond(
+                                stream,
+                                "200 OK",
+                                "Content-Type: text/event-stream\r\n",
+                                &events,
+                            )
+                            .await;
+                        },
+                        (false, "POST", _) => {
+                            respond(
+                                stream,
+                                "200 OK",
+                                "Content-Type: application/json\r\nMcp-Session-Id: abc\r\n",
+                                &answer(&body).to_string(),
+                            )
+                            .await;
+                        },
+                        _ => respond(stream, "404 Not Found", "", "").await,
+                    }
+                });
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_events_across_chunks() {
+        let mut events = Events::default();
+        assert!(events.push(b"event: endpoint\r\ndata: /mess").is_empty());
+        assert_eq!(
+            events.push(b"ages\r\n\r\n: comment\ndata: a\ndata: b\n\n"),
+            [
+                Event {
+                    kind: "endpoint".to_string(),
+                    data: "/messages".to_string()
+                },
+                Event {
+                    kind: "message".to_string(),
+                    data: "a\nb".to_string()
+                }
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_streamable_http() {
+        let log = Log::default();
+        let settings = McpServer {
+            url: Some(serve(false, Arc::clone(&log), Arc::default()).await),
+            bearer_token_command: Some("echo secret".to_string()),
+            headers: IndexMap::from([("X-Team".to_string(), "${NECO_UNSET_VAR}core".to_string())]),
+            ..McpServer::default()
+        };
+        let server = Arc::new(Server::new("web", &settings, Path::new(".")).unwrap());
+        let tools = connect(&server).await.unwrap();
+        let echo = tools.first().unwrap();
+        assert_eq!(echo.name(), "mcp__web__echo");
+        assert_eq!(
+            echo.execute(&json!({"text": "hi"})).await.unwrap(),
+            "echo: hi"
+        );
+
+        let log = log.lock().unwrap().clone();
+        assert!(log.iter().all(|line| line.contains("auth=Bearer secret")));
+        assert!(log.first().unwrap().contains("session= "));
+        assert!(log.get(1).unwrap().contains("notifications/initialized"));
+        assert!(log.iter().skip(1).all(|line| line.contains("session=abc")));
+        assert!(
+            log.iter()
+                .any(|line| line.contains(r#""id":"p1","jsonrpc":"2.0","result":{}"#))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_expired_reinitializes_once() {
+        let log = Log::default();
+        let expire = Arc::new(AtomicBool::new(false));
+        let settings = McpServer {
+            url: Some(serve(false, Arc::clone(&log), Arc::clone(&expire)).await),
+            max_restarts: 0,
+            ..McpServer::default()
+        };
+        let server = Arc::new(Server::new("web", &settings, Path::new(".")).unwrap());
+        let tools = connect(&server).await.unwrap();
+        let echo = tools.first().unwrap();
+
+        expire.store(true, Ordering::Relaxed);
+        assert_eq!(
+            echo.execute(&json!({"text": "hi"})).await.unwrap(),
+            "echo: hi"
+        );
+        let posts: Vec<String> = log
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|line| line.starts_with("POST"))
+            .cloned()
+            .collect();
+        let count = |method: &str| {
+            posts
+                .iter()
+                .filter(|line| line.contains(&format!(r#""method":"{method}""#)))
+                .count()
+        };
+        assert_eq!(count("initialize"), 2);
+        assert_eq!(count("tools/call"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_no_legacy_fallback_on_other_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+        let log = Log::default();
+        let requests = Arc::clone(&log);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let Ok((stream, method, ..)) = read_request(stream).await else {
+                    return;
+                };
+                requests.lock().unwrap().push(method);
+                respond(stream, "401 Unauthorized", "", "bad token").await;
+            }
+        });
+        let settings = McpServer {
+            url: Some(url),
+            ..McpServer::default()
+        };
+        let server = Arc::new(Server::new("web", &settings, Path::new(".")).unwrap());
+        let Err(error) = connect(&server).await else {
+            panic!("connected despite 401 Unauthorized");
+        };
+        assert!(format!("{error:#}").contains("401 Unauthorized: bad token"));
+        assert_eq!(*log.lock().unwrap(), ["POST"]);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_sse_fallback() {
+        let log = Log::default();
+        let settings = McpServer {
+            url: Some(serve(true, Arc::clone(&log), Arc::default()).await),
+            ..McpServer::default()
+        };
+        let server = Arc::new(Server::new("old", &settings, Path::new(".")).unwrap());
+        let tools = connect(&server).await.unwrap();
+        let echo = tools.first().unwrap();
+        assert_eq!(
+            echo.execute(&json!({"text": "sse"})).await.unwrap(),
+            "echo: sse"
+        );
+
+        let paths: Vec<String> = log
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|line| line.split(' ').take(2).collect::<Vec<_>>().join(" "))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "POST /mcp",
+                "GET /mcp",
+                "POST /messages?s=1",
+                "POST /messages?s=1",
+                "POST /messages?s=1",
+                "POST /messages?s=1"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(expand("a ${NECO_UNSET_VAR}b ${c"), "a b ${c");
+    }
+}
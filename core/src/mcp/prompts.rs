@@ -0,0 +1,283 @@
+//! MCP prompts offered as slash commands.
+//!
+//! A prompt is invoked as `/mcp__<server>__<prompt> [arguments]`. Arguments
+//! are given as `name=value` words or in order; the last argument takes the
+//! remaining words. Missing required arguments are asked for one by one.
+
+use super::{Server, qualified_name, text_field};
+use crate::commands::SlashCommand;
+use crate::events::CoreEvent;
+use crate::session::Session;
+use anyhow::Result;
+use async_trait::async_trait;
+use indexmap::IndexMap;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// An argument of a prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptArgument {
+    /// Name of the argument
+    pub name: String,
+    /// Description of the argument
+    pub description: String,
+    /// Whether the argument must be given
+    pub required: bool,
+}
+
+/// A prompt listed by a server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptInfo {
+    /// Name of the prompt on the server
+    pub name: String,
+    /// Description of the prompt
+    pub description: String,
+    /// Arguments of the prompt
+    pub arguments: Vec<PromptArgument>,
+}
+
+impl PromptInfo {
+    /// Parse a prompt of a `prompts/list` result.
+    pub(super) fn parse(value: &Value) -> Option<Self> {
+        Some(Self {
+            name: value.get("name")?.as_str()?.to_string(),
+            description: text_field(value, "description"),
+            arguments: value
+                .get("arguments")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(|argument| {
+                    Some(PromptArgument {
+                        name: argument.get("name")?.as_str()?.to_string(),
+                        description: text_field(argument, "description"),
+                        required: argument.get("required").and_then(Value::as_bool) == Some(true),
+                    })
+                })
+                .collect(),
+        })
+    }
+}
+
+/// A server prompt offered as a slash command.
+pub struct Prompt {
+    /// Server offering the prompt
+    server: Arc<Server>,
+    /// Name of the command
+    command: String,
+    /// Synopsis of the arguments, e.g. `<language> [style]`
+    synopsis: String,
+    /// Description shown by `/help`
+    help: String,
+    /// The prompt as listed by the server
+    info: PromptInfo,
+}
+
+impl Prompt {
+    /// Wrap a prompt of a server.
+    #[must_use]
+    pub fn new(server: Arc<Server>, info: PromptInfo) -> Self {
+        let synopsis = info
+            .arguments
+            .iter()
+            .map(|argument| {
+                if argument.required {
+                    format!("<{}>", argument.name)
+                } else {
+                    format!("[{}]", argument.name)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let help = if info.description.is_empty() {
+            format!("Prompt '{}' of MCP server {}", info.name, server.name())
+        } else {
+            info.description.clone()
+        };
+        Self {
+            command: qualified_name(server.name(), &info.name),
+            server,
+            synopsis,
+            help,
+            info,
+        }
+    }
+
+    /// Start an invocation of the prompt with the arguments typed after the command.
+    #[must_use]
+    pub fn invocation(&self, args: &str) -> Invocation {
+        let mut arguments = IndexMap::new();
+        let mut positional = VecDeque::new();
+        for word in args.split_whitespace() {
+            match word.split_once('=') {
+                Some((name, value)) if self.info.arguments.iter().any(|a| a.name == name) => {
+                    arguments.insert(name.to_string(), value.to_string());
+                },
+                _ => positional.push_back(word),
+            }
+        }
+        let unset: Vec<&PromptArgument> = self
+            .info
+            .arguments
+            .iter()
+            .filter(|argument| !arguments.contains_key(&argument.name))
+            .collect();
+        for (index, argument) in unset.iter().enumerate() {
+            let value = if index + 1 == unset.len() {
+                positional.drain(..).collect::<Vec<_>>().join(" ")
+            } else {
+                positional.pop_front().unwrap_or_default().to_string()
+            };
+            if value.is_empty() {
+                break;
+            }
+            arguments.insert(argument.name.clone(), value);
+        }
+
+        Invocation {
+            server: Arc::clone(&self.server),
+            command: self.command.clone(),
+            prompt: self.info.name.clone(),
+            missing: self
+                .info
+                .arguments
+                .iter()
+                .filter(|argument| argument.required && !arguments.contains_key(&argument.name))
+                .cloned()
+                .collect(),
+            arguments,
+        }
+    }
+}
+
+#[async_trait]
+impl SlashCommand for Prompt {
+    fn name(&self) -> &str {
+        &self.command
+    }
+
+    fn args(&self) -> &str {
+        &self.synopsis
+    }
+
+    fn help(&self) -> &str {
+        &self.help
+    }
+
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        session
+            .invoke_prompt(self.invocation(args), event_sender)
+            .await?;
+        Ok(true)
+    }
+}
+
+/// A prompt being invoked, with the arguments given so far.
+pub struct Invocation {
+    /// Server offering the prompt
+    server: Arc<Server>,
+    /// Name of the command
+    command: String,
+    /// Name of the prompt on the server
+    prompt: String,
+    /// Values of the given arguments
+    arguments: IndexMap<String, String>,
+    /// Required arguments not given yet
+    missing: Vec<PromptArgument>,
+}
+
+impl Invocation {
+    /// Name of the command invoking the prompt.
+    #[must_use]
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Values of the given arguments.
+    #[must_use]
+    pub const fn arguments(&self) -> &IndexMap<String, String> {
+        &self.arguments
+    }
+
+    /// The next required argument to ask the user for.
+    #[must_use]
+    pub fn next_missing(&self) -> Option<&PromptArgument> {
+        self.missing.first()
+    }
+
+    /// Give the value of the next missing argument; an empty value is ignored.
+    pub fn answer(&mut self, value: &str) {
+        if value.is_empty() || self.missing.is_empty() {
+            return;
+        }
+        let argument = self.missing.remove(0);
+        self.arguments.insert(argument.name, value.to_string());
+    }
+
+    /// Get the prompt text from the server.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails.
+    pub async fn text(&self) -> Result<String> {
+        self.server.get_prompt(&self.prompt, &self.arguments).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::McpServer;
+    use serde_json::json;
+
+    #[test]
+    fn test_arguments() {
+        let settings = McpServer {
+            command: Some("true".to_string()),
+            ..McpServer::default()
+        };
+        let server = Arc::new(Server::new("git", &settings, std::path::Path::new(".")).unwrap());
+        let info = PromptInfo::parse(&json!({
+            "name": "review",
+            "description": "Review a change",
+            "arguments": [
+                {"name": "branch", "required": true},
+                {"name": "focus", "description": "What to look at", "required": true},
+                {"name": "style"}
+            ]
+        }))
+        .unwrap();
+        let prompt = Prompt::new(server, info);
+        assert_eq!(prompt.usage(), "/mcp__git__review <branch> <focus> [style]");
+        assert_eq!(prompt.help(), "Review a change");
+
+        let invocation = prompt.invocation("style=terse main error handling");
+        assert_eq!(
+            invocation.arguments(),
+            &IndexMap::from([
+                ("style".to_string(), "terse".to_string()),
+                ("branch".to_string(), "main".to_string()),
+                ("focus".to_string(), "error handling".to_string())
+            ])
+        );
+        assert!(invocation.next_missing().is_none());
+
+        let mut invocation = prompt.invocation("main");
+        assert_eq!(invocation.next_missing().unwrap().name, "focus");
+        invocation.answer("");
+        assert_eq!(
+            invocation.next_missing().unwrap().description,
+            "What to look at"
+        );
+        invocation.answer("tests");
+        assert!(invocation.next_missing().is_none());
+        assert_eq!(invocation.arguments().get("focus").unwrap(), "tests");
+    }
+}
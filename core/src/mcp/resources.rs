@@ -0,0 +1,200 @@
+//! MCP resources: listing, references in messages and rendering.
+//!
+//! A message references a resource as `@<server>:<uri>`, e.g.
+//! `@docs:file:///guide.md`. Referenced resources are read and inlined in
+//! the message as content blocks before it is sent.
+
+use super::text_field;
+use serde_json::{Value, json};
+use std::fmt::Write as _;
+
+/// Image types the model accepts as image blocks.
+const IMAGE_TYPES: &[&str] = &["image/png", "image/jpeg", "image/gif", "image/webp"];
+
+/// A resource listed by a server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resource {
+    /// URI of the resource
+    pub uri: String,
+    /// Name of the resource
+    pub name: String,
+    /// Description of the resource
+    pub description: String,
+    /// Media type of the resource, if known
+    pub mime_type: Option<String>,
+}
+
+impl Resource {
+    /// Parse a resource of a `resources/list` result.
+    pub(super) fn parse(value: &Value) -> Option<Self> {
+        Some(Self {
+            uri: value.get("uri")?.as_str()?.to_string(),
+            name: text_field(value, "name"),
+            description: text_field(value, "description"),
+            mime_type: value
+                .get("mimeType")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+        })
+    }
+}
+
+/// A reference to a resource in a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    /// Name of the server offering the resource
+    pub server: String,
+    /// URI of the resource
+    pub uri: String,
+}
+
+/// Find the resource references in a message.
+///
+/// A reference is a word `@<server>:<uri>` naming one of the given servers.
+/// Trailing punctuation is not part of the URI.
+///
+/// # Arguments
+///
+/// * `text` - The message
+/// * `servers` - Names of the connected servers
+#[must_use]
+pub fn references(text: &str, servers: &[&str]) -> Vec<Reference> {
+    let mut found: Vec<Reference> = Vec::new();
+    for word in text.split_whitespace() {
+        let Some((server, uri)) = word.strip_prefix('@').and_then(|word| word.split_once(':'))
+        else {
+            continue;
+        };
+        let uri = uri.trim_end_matches(['.', ',', ';', '!', '?']);
+        if uri.is_empty() || !servers.contains(&server) {
+            continue;
+        }
+        let reference = Reference {
+            server: server.to_string(),
+            uri: uri.to_string(),
+        };
+        if !found.contains(&reference) {
+            found.push(reference);
+        }
+    }
+    found
+}
+
+/// Render resource contents as text.
+///
+/// Binary contents are described by their type and size.
+#[must_use]
+pub fn text(contents: &[Value]) -> String {
+    let mut text = String::new();
+    for content in contents {
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        match content.get("text").and_then(Value::as_str) {
+            Some(body) => text.push_str(body),
+            None => {
+                let _ = write!(text, "[{}]", binary(content));
+            },
+        }
+    }
+    text
+}
+
+/// Content blocks inlining a referenced resource in a user message.
+///
+/// Text is wrapped in a `<resource>` element naming the reference; images
+/// become image blocks and other binary contents are described.
+#[must_use]
+pub fn blocks(reference: &Reference, contents: &[Value]) -> Vec<Value> {
+    let wrap = |body: &str| {
+        json!({
+            "type": "text",
+            "text": format!("<resource uri=\"@{}:{}\">\n{body}\n</resource>", reference.server, reference.uri)
+        })
+    };
+    contents
+        .iter()
+        .map(|content| {
+            let mime_type = content
+                .get("mimeType")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            match (content.get("text"), content.get("blob")) {
+                (Some(Value::String(body)), _) => wrap(body),
+                (_, Some(Value::String(data))) if IMAGE_TYPES.contains(&mime_type) => json!({
+                    "type": "image",
+                    "source": {"type": "base64", "media_type": mime_type, "data": data}
+                }),
+                _ => wrap(&format!("[{}]", binary(content))),
+            }
+        })
+        .collect()
+}
+
+/// Describe binary contents by their type and size.
+fn binary(content: &Value) -> String {
+    let size = content
+        .get("blob")
+        .and_then(Value::as_str)
+        .map_or(0, |blob| blob.len() / 4 * 3);
+    format!(
+        "binary {}, about {size} bytes",
+        content
+            .get("mimeType")
+            .and_then(Value::as_str)
+            .unwrap_or("application/octet-stream")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_references_and_blocks() {
+        let found = references(
+            "Compare @docs:file:///a.md, @docs:file:///a.md and @other:x with @db:table/users.",
+            &["docs", "db"],
+        );
+        assert_eq!(
+            found,
+            [
+                Reference {
+                    server: "docs".to_string(),
+                    uri: "file:///a.md".to_string()
+                },
+                Reference {
+                    server: "db".to_string(),
+                    uri: "table/users".to_string()
+                }
+            ]
+        );
+
+        let contents = [
+            json!({"uri": "file:///a.md", "mimeType": "text/markdown", "text": "# A"}),
+            json!({"uri": "file:///a.png", "mimeType": "image/png", "blob": "AAAA"}),
+            json!({"uri": "file:///a.bin", "blob": "AAAAAAAA"}),
+        ];
+        let reference = found.first().unwrap();
+        let blocks = blocks(reference, &contents);
+        assert_eq!(
+            blocks.first().unwrap(),
+            &json!({"type": "text", "text": "<resource uri=\"@docs:file:///a.md\">\n# A\n</resource>"})
+        );
+        assert_eq!(
+            blocks.get(1).unwrap().pointer("/source/media_type"),
+            Some(&json!("image/png"))
+        );
+        assert!(
+            blocks
+                .get(2)
+                .unwrap()
+                .to_string()
+                .contains("[binary application/octet-stream, about 6 bytes]")
+        );
+        assert_eq!(
+            text(&contents),
+            "# A\n[binary image/png, about 3 bytes]\n[binary application/octet-stream, about 6 bytes]"
+        );
+    }
+}
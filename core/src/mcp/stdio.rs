@@ -0,0 +1,183 @@
+//! Transport over the standard input and output of a child process.
+//!
+//! Messages are JSON objects, one per line. A background task reads the
+//! output of the server, so notifications are handled as they arrive. The
+//! standard error of the server is forwarded to the log.
+
+use super::{Handler, Pending, Transport};
+use crate::config::McpServer;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use indexmap::IndexMap;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::Stdio as Pipe;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::Mutex;
+
+/// Write a message as one line.
+async fn send(stdin: &Mutex<ChildStdin>, message: &Value) -> Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(line.as_bytes()).await?;
+    stdin.flush().await?;
+    Ok(())
+}
+
+/// A running server process.
+struct Process {
+    /// The child process, killed when dropped
+    _child: Child,
+    /// Standard input of the server
+    stdin: Arc<Mutex<ChildStdin>>,
+    /// Requests waiting for their responses
+    pending: Pending,
+    /// Task reading the output of the server
+    reader: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/// Transport launching the server as a child process.
+pub struct Stdio {
+    /// Command launching the server
+    command: String,
+    /// Arguments of the command
+    args: Vec<String>,
+    /// Environment variables set for the server
+    env: IndexMap<String, String>,
+    /// Directory the server runs in
+    cwd: PathBuf,
+    /// Handler of the server's notifications
+    handler: Handler,
+    /// The running process, if any
+    process: std::sync::Mutex<Option<Process>>,
+}
+
+impl Stdio {
+    /// Create the transport of a configured server, without starting it.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - Command launching the server
+    /// * `settings` - Server configuration
+    /// * `cwd` - Working directory of the session, used when the server sets none
+    /// * `handler` - Handler of the server's notifications
+    #[must_use]
+    pub fn new(command: &str, settings: &McpServer, cwd: &Path, handler: Handler) -> Self {
+        Self {
+            command: command.to_string(),
+            args: settings.args.clone(),
+            env: settings.env.clone(),
+            cwd: settings
+                .cwd
+                .as_ref()
+                .map_or_else(|| cwd.to_path_buf(), |dir| cwd.join(dir)),
+            handler,
+            process: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Input and pending requests of the running process.
+    fn connection(&self) -> Result<(Arc<Mutex<ChildStdin>>, Pending)> {
+        self.process
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .as_ref()
+            .filter(|process| !process.pending.is_closed())
+            .map(|process| (Arc::clone(&process.stdin), process.pending.clone()))
+            .context("server is not running")
+    }
+}
+
+#[async_trait]
+impl Transport for Stdio {
+    async fn start(&self) -> Result<()> {
+        let mut child = Command::new(&self.command)
+            .args(&self.args)
+            .envs(&self.env)
+            .current_dir(&self.cwd)
+            .stdin(Pipe::piped())
+            .stdout(Pipe::piped())
+            .stderr(Pipe::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to run '{}'", self.command))?;
+        let stdin = Arc::new(Mutex::new(
+            child.stdin.take().context("No stdin for the server")?,
+        ));
+        let stdout = child.stdout.take().context("No stdout for the server")?;
+        if let Some(stderr) = child.stderr.take() {
+            let command = self.command.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    tracing::debug!(server = %command, "{line}");
+                }
+            });
+        }
+
+        let pending = Pending::default();
+        let reader = {
+            let pending = pending.clone();
+            let stdin = Arc::clone(&stdin);
+            let handler = Arc::clone(&self.handler);
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stdout).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                        tracing::debug!(%line, "Ignoring non-JSON output of MCP server");
+                        continue;
+                    };
+                    if let Some(reply) = pending.dispatch(message, &handler)
+                        && send(&stdin, &reply).await.is_err()
+                    {
+                        break;
+                    }
+                }
+                pending.close();
+            })
+        };
+        *self
+            .process
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(Process {
+            _child: child,
+            stdin,
+            pending,
+            reader,
+        });
+        Ok(())
+    }
+
+    async fn is_running(&self) -> bool {
+        self.connection().is_ok()
+    }
+
+    async fn request(&self, id: u64, message: &Value) -> Result<Value> {
+        let (stdin, pending) = self.connection()?;
+        let response = pending.wait(id);
+        if let Err(error) = send(&stdin, message).await {
+            pending.cancel(id);
+            pending.close();
+            return Err(error);
+        }
+        response.await.context("server closed its output")
+    }
+
+    async fn notify(&self, message: &Value) -> Result<()> {
+        let (stdin, pending) = self.connection()?;
+        let result = send(&stdin, message).await;
+        if result.is_err() {
+            pending.close();
+        }
+        result
+    }
+}
@@ -0,0 +1,577 @@
+//! Persistent two-tier memory.
+//!
+//! A memory has a one-line summary, always shown in the `memories` section
+//! of the system prompt, and a full content the model reads on demand with
+//! the `memory` tool. Memories belong to a project (the git root, or the
+//! working directory outside a repository) or are global.
+//!
+//! A memory can depend on files, whose content hashes are recorded, or on
+//! the commit checked out when it was saved. When they change the memory
+//! is stale: it is flagged, or forgotten if so configured. Memories not
+//! used for a while are forgotten too (see [`config::Memory`]).
+//!
+//! All memories are stored in a single JSON file.
+
+use crate::clock;
+use crate::config;
+use crate::environment;
+use crate::instructions;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Name of the file holding the memories.
+const MEMORIES_FILE: &str = "memories.json";
+
+/// Seconds in a day.
+const DAY: u64 = 24 * 60 * 60;
+
+/// Default directory holding the memories.
+///
+/// Follows the XDG Base Directory specification:
+/// `$XDG_DATA_HOME/neco/memory` (default `~/.local/share/neco/memory`).
+#[must_use]
+pub fn default_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("neco").join("memory"))
+}
+
+/// Where a memory applies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// The current project only
+    #[default]
+    Project,
+    /// Every project
+    Global,
+}
+
+/// A file a memory depends on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dependency {
+    /// Absolute path of the file
+    pub path: PathBuf,
+    /// Hash of the file's content when the memory was saved; `None` if it did not exist
+    pub hash: Option<String>,
+}
+
+/// A stored memory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Memory {
+    /// Unique identifier
+    pub id: String,
+    /// One-line summary, always shown to the model
+    pub summary: String,
+    /// Full content, read on demand
+    pub content: String,
+    /// Project the memory belongs to; `None` for global memories
+    pub project: Option<String>,
+    /// Creation time (Unix seconds)
+    pub created: u64,
+    /// Last time the memory was read or found (Unix seconds)
+    pub used: u64,
+    /// Commit checked out when the memory was saved, if it depends on it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Files the memory depends on
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<Dependency>,
+}
+
+impl Memory {
+    /// Scope of the memory.
+    #[must_use]
+    pub const fn scope(&self) -> Scope {
+        if self.project.is_some() {
+            Scope::Project
+        } else {
+            Scope::Global
+        }
+    }
+}
+
+/// Summary of a memory, for listings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Summary {
+    /// Unique identifier
+    pub id: String,
+    /// One-line summary
+    pub summary: String,
+    /// Scope of the memory
+    pub scope: Scope,
+    /// Last time the memory was read or found (Unix seconds)
+    pub used: u64,
+    /// Why the memory is stale, if it is
+    pub stale: Option<String>,
+}
+
+/// A memory to save.
+#[derive(Debug, Clone, Default)]
+pub struct Draft {
+    /// One-line summary
+    pub summary: String,
+    /// Full content
+    pub content: String,
+    /// Where the memory applies
+    pub scope: Scope,
+    /// Files the memory depends on, absolute or relative to the working directory
+    pub depends_on: Vec<PathBuf>,
+    /// Whether the memory depends on the checked out commit
+    pub pin_commit: bool,
+}
+
+/// Memories visible from a working directory.
+///
+/// Cheap to clone; clones share the same file lock.
+#[derive(Debug, Clone)]
+pub struct Store {
+    /// File holding the memories
+    path: PathBuf,
+    /// Working directory, against which dependencies are resolved
+    cwd: PathBuf,
+    /// Project of the working directory
+    project: String,
+    /// Memory settings
+    settings: config::Memory,
+    /// Serializes reads and writes of the file
+    lock: Arc<Mutex<()>>,
+}
+
+impl Store {
+    /// Open the memories in a directory, as seen from a working directory.
+    ///
+    /// The directory is created on the first save.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Directory holding the memories file
+    /// * `cwd` - Working directory of the session
+    /// * `settings` - Memory settings
+    #[must_use]
+    pub fn open(dir: &Path, cwd: &Path, settings: config::Memory) -> Self {
+        let project = instructions::git_root(cwd).unwrap_or_else(|| cwd.to_path_buf());
+        Self {
+            path: dir.join(MEMORIES_FILE),
+            cwd: cwd.to_path_buf(),
+            project: project.display().to_string(),
+            settings,
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Save a memory.
+    ///
+    /// # Returns
+    ///
+    /// The identifier of the new memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the summary is empty, the commit to pin cannot be
+    /// determined, or the memories cannot be written.
+    pub fn save(&self, draft: &Draft) -> Result<String> {
+        let summary = draft.summary.lines().next().unwrap_or_default().trim();
+        if summary.is_empty() {
+            anyhow::bail!("A memory needs a summary");
+        }
+        let commit = if draft.pin_commit {
+            Some(
+                head(Path::new(&self.project))
+                    .context("Cannot pin a commit outside a git repository")?,
+            )
+        } else {
+            None
+        };
+        let files = draft
+            .depends_on
+            .iter()
+            .map(|path| {
+                let path = self.cwd.join(path);
+                Dependency {
+                    hash: hash_file(&path),
+                    path,
+                }
+            })
+            .collect();
+
+        let now = clock::now();
+        let memory = Memory {
+            id: clock::new_id(),
+            summary: summary.to_string(),
+            content: draft.content.trim().to_string(),
+            project: (draft.scope == Scope::Project).then(|| self.project.clone()),
+            created: now,
+            used: now,
+            commit,
+            files,
+        };
+        let id = memory.id.clone();
+        self.update(|memories| {
+            memories.push(memory);
+            Ok(())
+        })?;
+        Ok(id)
+    }
+
+    /// Find the memories containing every word of a query, oldest first.
+    ///
+    /// The memories found count as used.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the memories cannot be read or written.
+    pub fn search(&self, query: &str) -> Result<Vec<Summary>> {
+        let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        self.update(|memories| {
+            let now = clock::now();
+            let mut found = Vec::new();
+            for memory in memories.iter_mut().filter(|memory| self.visible(memory)) {
+                let text = format!("{}\n{}", memory.summary, memory.content).to_lowercase();
+                if words.iter().all(|word| text.contains(word.as_str())) {
+                    memory.used = now;
+                    found.push(self.summarize(memory));
+                }
+            }
+            Ok(found)
+        })
+    }
+
+    /// Read a memory in full. The memory counts as used.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier or unique identifier prefix
+    ///
+    /// # Returns
+    ///
+    /// The memory and why it is stale, if it is.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no visible memory or more than one matches.
+    pub fn read(&self, id: &str) -> Result<(Memory, Option<String>)> {
+        self.update(|memories| {
+            let index = self.find(memories, id)?;
+            let memory = memories.get_mut(index).context("Memory vanished")?;
+            memory.used = clock::now();
+            Ok((memory.clone(), self.staleness(memory)))
+        })
+    }
+
+    /// Forget a memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier or unique identifier prefix
+    ///
+    /// # Returns
+    ///
+    /// The forgotten memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no visible memory or more than one matches.
+    pub fn forget(&self, id: &str) -> Result<Memory> {
+        self.update(|memories| {
+            let index = self.find(memories, id)?;
+            Ok(memories.remove(index))
+        })
+    }
+
+    /// List the visible memories, most recently used first.
+    ///
+    /// Memories that aged out or expired are forgotten first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the memories cannot be read or written.
+    pub fn list(&self) -> Result<Vec<Summary>> {
+        self.update(|memories| {
+            let mut summaries: Vec<Summary> = memories
+                .iter()
+                .filter(|memory| self.visible(memory))
+                .map(|memory| self.summarize(memory))
+                .collect();
+            summaries.sort_by_key(|summary| std::cmp::Reverse(summary.used));
+            Ok(summaries)
+        })
+    }
+
+    /// Render the `memories` section of the system prompt.
+    #[must_use]
+    pub fn section(&self) -> String {
+        let summaries = match self.list() {
+            Ok(summaries) => summaries,
+            Err(e) => {
+                tracing::warn!("Failed to read memories: {e:#}");
+                return String::new();
+            },
+        };
+        if summaries.is_empty() {
+            return "# Memories\n\
+                    No memories yet. Use the memory tool to save knowledge worth keeping \
+                    across sessions, such as user preferences and project decisions."
+                .to_string();
+        }
+
+        let mut text = "# Memories\n\
+                        Summaries of memories from earlier sessions. Use the memory tool to \
+                        read one in full, search, save new ones, or forget wrong ones. Verify \
+                        stale memories before relying on them."
+            .to_string();
+        for summary in summaries.iter().take(self.settings.max_injected) {
+            let _ = write!(text, "\n- [{}] {}", summary.id, summary.summary);
+            if summary.scope == Scope::Global {
+                text.push_str(" (global)");
+            }
+            if let Some(reason) = &summary.stale {
+                let _ = write!(text, " (stale: {reason})");
+            }
+        }
+        if summaries.len() > self.settings.max_injected {
+            let _ = write!(
+                text,
+                "\n... and {} more; search to find them",
+                summaries.len() - self.settings.max_injected
+            );
+        }
+        text
+    }
+
+    /// Why a memory is stale, if it is.
+    #[must_use]
+    pub fn staleness(&self, memory: &Memory) -> Option<String> {
+        for file in &memory.files {
+            if hash_file(&file.path) != file.hash {
+                let verb = if file.path.exists() {
+                    "changed"
+                } else {
+                    "was deleted"
+                };
+                return Some(format!("{} {verb}", self.display(&file.path)));
+            }
+        }
+        let commit = memory.commit.as_ref()?;
+        let project = memory.project.as_deref().unwrap_or(&self.project);
+        match head(Path::new(project)) {
+            Some(head) if head == *commit => None,
+            _ => Some(format!(
+                "saved at commit {}",
+                commit.get(..7).unwrap_or(commit)
+            )),
+        }
+    }
+
+    /// Whether a memory applies to the current project.
+    fn visible(&self, memory: &Memory) -> bool {
+        memory
+            .project
+            .as_ref()
+            .is_none_or(|project| *project == self.project)
+    }
+
+    /// Summarize a memory for listings.
+    fn summarize(&self, memory: &Memory) -> Summary {
+        Summary {
+            id: memory.id.clone(),
+            summary: memory.summary.clone(),
+            scope: memory.scope(),
+            used: memory.used,
+            stale: self.staleness(memory),
+        }
+    }
+
+    /// Path of a file as shown to the model, relative to the project if inside it.
+    fn display(&self, path: &Path) -> String {
+        path.strip_prefix(&self.project).map_or_else(
+            |_| path.display().to_string(),
+            |relative| relative.display().to_string(),
+        )
+    }
+
+    /// Find a visible memory by identifier or unique identifier prefix.
+    fn find(&self, memories: &[Memory], id: &str) -> Result<usize> {
+        let matches: Vec<usize> = memories
+            .iter()
+            .enumerate()
+            .filter(|(_, memory)| self.visible(memory) && memory.id.starts_with(id))
+            .map(|(index, _)| index)
+            .collect();
+        match matches.as_slice() {
+            [index] => Ok(*index),
+            [] => anyhow::bail!("No memory '{id}'"),
+            _ => anyhow::bail!("Memory id '{id}' is ambiguous ({} matches)", matches.len()),
+        }
+    }
+
+    /// Forget the memories that aged out, and the stale ones if they expire.
+    ///
+    /// # Returns
+    ///
+    /// Whether any memory was forgotten.
+    fn prune(&self, memories: &mut Vec<Memory>) -> bool {
+        let before = memories.len();
+        let now = clock::now();
+        let max_age = self.settings.max_age_days.saturating_mul(DAY);
+        memories.retain(|memory| {
+            let aged = max_age > 0 && now.saturating_sub(memory.used) > max_age;
+            let expired = self.settings.expire_stale
+                && self.visible(memory)
+                && self.staleness(memory).is_some();
+            !aged && !expired
+        });
+        memories.len() != before
+    }
+
+    /// Read the memories, apply a change and write them back if needed.
+    ///
+    /// Memories that aged out or expired are forgotten before the change.
+    fn update<T>(&self, change: impl FnOnce(&mut Vec<Memory>) -> Result<T>) -> Result<T> {
+        let _guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut memories: Vec<Memory> = match std::fs::read_to_string(&self.path) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("Failed to parse {}", self.path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", self.path.display()));
+            },
+        };
+        let original = memories.clone();
+        self.prune(&mut memories);
+        let result = change(&mut memories)?;
+
+        if memories != original {
+            if let Some(dir) = self.path.parent() {
+                std::fs::create_dir_all(dir)
+                    .with_context(|| format!("Failed to create {}", dir.display()))?;
+            }
+            std::fs::write(&self.path, serde_json::to_string_pretty(&memories)?)
+                .with_context(|| format!("Failed to write {}", self.path.display()))?;
+        }
+        Ok(result)
+    }
+}
+
+/// Commit checked out in a repository.
+fn head(dir: &Path) -> Option<String> {
+    environment::git(dir, &["rev-parse", "HEAD"])
+}
+
+/// Hash of a file's content (64-bit FNV-1a), `None` if it cannot be read.
+fn hash_file(path: &Path) -> Option<String> {
+    let bytes = std::fs::read(path).ok()?;
+    let hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    Some(format!("{hash:016x}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    /// Temporary directory for a test.
+    #[test]
+    fn test_scopes_search_and_forget() {
+        let dir = TempDir::new("memory-scopes");
+        let project = dir.join("project");
+        let other = dir.join("other");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::create_dir_all(&other).unwrap();
+        let store = Store::open(&dir, &project, config::Memory::default());
+
+        let local = store
+            .save(&Draft {
+                summary: "Tests need the fixtures server\nignored line".to_string(),
+                content: "Run ./fixtures.sh before cargo test.".to_string(),
+                ..Draft::default()
+            })
+            .unwrap();
+        let global = store
+            .save(&Draft {
+                summary: "User prefers British spelling".to_string(),
+                content: "Use colour, organise.".to_string(),
+                scope: Scope::Global,
+                ..Draft::default()
+            })
+            .unwrap();
+        store.save(&Draft::default()).unwrap_err();
+
+        let found = store.search("FIXTURES cargo").unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found.first().unwrap().id, local);
+        assert_eq!(
+            found.first().unwrap().summary,
+            "Tests need the fixtures server"
+        );
+
+        let elsewhere = Store::open(&dir, &other, config::Memory::default());
+        let listed = elsewhere.list().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed.first().unwrap().id, global);
+        elsewhere.read(&local).unwrap_err();
+
+        let section = store.section();
+        assert!(section.contains(&format!("- [{local}] Tests need the fixtures server")));
+        assert!(section.contains(&format!(
+            "- [{global}] User prefers British spelling (global)"
+        )));
+
+        let (memory, stale) = store.read(&local).unwrap();
+        assert_eq!(memory.content, "Run ./fixtures.sh before cargo test.");
+        assert!(stale.is_none());
+        assert_eq!(store.forget(&local).unwrap().id, local);
+        assert_eq!(store.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_stale_files_and_aging() {
+        let dir = TempDir::new("memory-stale");
+        std::fs::write(dir.join("schema.sql"), "create table a;").unwrap();
+        let store = Store::open(&dir, &dir, config::Memory::default());
+        let id = store
+            .save(&Draft {
+                summary: "Table a holds accounts".to_string(),
+                content: "See schema.sql".to_string(),
+                depends_on: vec![PathBuf::from("schema.sql")],
+                ..Draft::default()
+            })
+            .unwrap();
+        assert!(store.list().unwrap().first().unwrap().stale.is_none());
+
+        std::fs::write(dir.join("schema.sql"), "create table b;").unwrap();
+        let (_, stale) = store.read(&id).unwrap();
+        assert_eq!(stale.as_deref(), Some("schema.sql changed"));
+        assert!(store.section().contains("(stale: schema.sql changed)"));
+
+        let expiring = Store::open(
+            &dir,
+            &dir,
+            config::Memory {
+                expire_stale: true,
+                ..config::Memory::default()
+            },
+        );
+        assert!(expiring.list().unwrap().is_empty());
+
+        let id = store
+            .save(&Draft {
+                summary: "Old knowledge".to_string(),
+                ..Draft::default()
+            })
+            .unwrap();
+        store
+            .update(|memories| {
+                for memory in memories.iter_mut() {
+                    memory.used -= 100 * DAY;
+                }
+                Ok(())
+            })
+            .unwrap();
+        assert!(store.list().unwrap().is_empty());
+        store.read(&id).unwrap_err();
+    }
+}
@@ -0,0 +1,136 @@
+//! Persona profiles.
+//!
+//! A persona shapes how the assistant talks: its name, tone, language and
+//! greeting. It is rendered into the identity section of the system prompt
+//! only, so switching personas never touches the tool usage guidance.
+//! Profiles come from the built-in set, the configuration file and
+//! `personas/<name>.toml` files, later ones overriding earlier ones.
+
+use crate::config::Persona;
+use indexmap::IndexMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+/// Built-in persona profiles.
+#[must_use]
+pub fn builtin() -> IndexMap<String, Persona> {
+    IndexMap::from([(
+        "neko".to_string(),
+        Persona {
+            name: Some("Neco".to_string()),
+            tone: Some(
+                "playful and affectionate like a cat-girl, with an occasional \"nya\", \
+                 but precise and to the point about code"
+                    .to_string(),
+            ),
+            language: None,
+            greeting: Some("Nya~ Neco is here! What are we working on today?".to_string()),
+            prompt: Some(
+                "Keep the cat mannerisms to your own messages; never put them in code, \
+                 comments, commit messages or files you write."
+                    .to_string(),
+            ),
+        },
+    )])
+}
+
+/// Load persona profiles from `<name>.toml` files.
+///
+/// # Arguments
+///
+/// * `dirs` - Directories to read, lowest precedence first
+///
+/// # Returns
+///
+/// The profiles by name; a later directory overrides a profile of the same name.
+#[must_use]
+pub fn load(dirs: &[PathBuf]) -> IndexMap<String, Persona> {
+    let mut personas = IndexMap::new();
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let persona = std::fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|text| Ok(toml::from_str::<Persona>(&text)?));
+            match persona {
+                Ok(persona) => {
+                    personas.insert(name.to_string(), persona);
+                },
+                Err(e) => {
+                    tracing::warn!(path = %path.display(), "Skipping invalid persona: {e}");
+                },
+            }
+        }
+    }
+    personas
+}
+
+/// Render the identity section of the system prompt for a persona.
+///
+/// # Arguments
+///
+/// * `id` - Profile name, used when the persona sets no name
+/// * `persona` - The persona profile
+#[must_use]
+pub fn identity(id: &str, persona: &Persona) -> String {
+    let name = persona.name.as_deref().unwrap_or(id);
+    let mut text = format!("You are {name}, a coding assistant working in the user's terminal.");
+    if let Some(tone) = &persona.tone {
+        let _ = write!(text, "\nTone: {tone}.");
+    }
+    if let Some(language) = &persona.language {
+        let _ = write!(
+            text,
+            "\nReply in {language} unless the user asks otherwise."
+        );
+    }
+    if let Some(prompt) = &persona.prompt {
+        let _ = write!(text, "\n{}", prompt.trim());
+    }
+    text.push_str(
+        "\nThe persona only shapes how you talk; use the tools and follow the \
+         instructions below as usual.",
+    );
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_and_render() {
+        let dir = crate::testing::TempDir::new("personas");
+        std::fs::write(
+            dir.join("mentor.toml"),
+            "tone = \"patient\"\nlanguage = \"French\"\ngreeting = \"Bonjour\"",
+        )
+        .unwrap();
+        std::fs::write(dir.join("broken.toml"), "tone = ").unwrap();
+        std::fs::write(dir.join("notes.md"), "tone = \"ignored\"").unwrap();
+
+        let personas = load(&[dir.to_path_buf()]);
+        assert_eq!(personas.keys().collect::<Vec<_>>(), ["mentor"]);
+        let mentor = personas.get("mentor").unwrap();
+        assert_eq!(mentor.greeting.as_deref(), Some("Bonjour"));
+
+        let text = identity("mentor", mentor);
+        assert!(text.starts_with("You are mentor,"));
+        assert!(text.contains("Tone: patient."));
+        assert!(text.contains("Reply in French"));
+
+        let neko = identity("neko", builtin().get("neko").unwrap());
+        assert!(neko.starts_with("You are Neco,"));
+        assert!(neko.contains("cat-girl"));
+    }
+}
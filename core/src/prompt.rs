@@ -0,0 +1,266 @@
+//! Modular system prompt.
+//!
+//! The system prompt is assembled from ordered, named sections: identity,
+//! environment, tool usage, project instructions, memories and the skills
+//! index. Each part of neco fills in its own section; the configuration
+//! can disable sections, replace their text or add sections of its own
+//! (see [`config::Prompt`]). Empty sections are left out.
+
+use crate::config::{self, PromptSection};
+use anyhow::Result;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+/// Built-in sections, in prompt order.
+pub const SECTIONS: [&str; 6] = [
+    "identity",
+    "environment",
+    "tools",
+    "project",
+    "memories",
+    "skills",
+];
+
+/// Default identity of the assistant.
+pub(crate) const IDENTITY: &str =
+    "You are neco, a concise coding assistant working in the user's terminal.";
+
+/// Default guidance on using the tools.
+const TOOLS: &str = "\
+# Using tools
+- Read a file before editing it, and prefer edit over write for existing files.
+- Use glob and grep to find code instead of listing directories with bash.
+- Keep bash commands non-interactive and scoped to the working directory.
+- Make independent tool calls in the same response.";
+
+/// A section of the system prompt.
+#[derive(Debug, Clone)]
+struct Section {
+    /// Content set by the part of neco owning the section
+    text: String,
+    /// Settings from the configuration
+    settings: PromptSection,
+    /// Whether a lazy section was loaded
+    loaded: bool,
+}
+
+impl Section {
+    /// Text of the section, with the configured replacement applied.
+    fn text(&self) -> &str {
+        self.settings.text.as_deref().unwrap_or(&self.text)
+    }
+
+    /// Whether the section is part of the rendered prompt.
+    fn included(&self) -> bool {
+        self.settings.enabled.unwrap_or(true)
+            && (!self.lazy() || self.loaded)
+            && !self.text().trim().is_empty()
+    }
+
+    /// Whether the section is only included once loaded.
+    fn lazy(&self) -> bool {
+        self.settings.lazy.unwrap_or(false)
+    }
+}
+
+/// Size estimate of a section, as shown by `/prompt`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Estimate {
+    /// Section name
+    pub name: String,
+    /// Estimated tokens of the section's text
+    pub tokens: u64,
+    /// Whether the section is part of the prompt
+    pub included: bool,
+    /// Whether the section is only included once loaded
+    pub lazy: bool,
+}
+
+/// Builder assembling the system prompt from its sections.
+#[derive(Debug, Clone)]
+pub struct Builder {
+    /// Sections in prompt order
+    sections: IndexMap<String, Section>,
+}
+
+impl Builder {
+    /// Create a builder with the built-in sections and the configured ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - Section settings from the configuration
+    #[must_use]
+    pub fn new(settings: &config::Prompt) -> Self {
+        let mut builder = Self {
+            sections: IndexMap::new(),
+        };
+        for name in SECTIONS {
+            builder.add(name, settings);
+        }
+        for name in settings.sections.keys() {
+            builder.add(name, settings);
+        }
+        builder.set("identity", IDENTITY);
+        builder.set("tools", TOOLS);
+        builder
+    }
+
+    /// Add an empty section unless it exists.
+    fn add(&mut self, name: &str, settings: &config::Prompt) {
+        self.sections
+            .entry(name.to_string())
+            .or_insert_with(|| Section {
+                text: String::new(),
+                settings: settings.sections.get(name).cloned().unwrap_or_default(),
+                loaded: false,
+            });
+    }
+
+    /// Set the content of a section, adding it at the end if it is new.
+    ///
+    /// A text configured for the section takes precedence.
+    pub fn set(&mut self, name: &str, text: impl Into<String>) {
+        let text = text.into();
+        match self.sections.get_mut(name) {
+            Some(section) => section.text = text,
+            None => {
+                self.sections.insert(
+                    name.to_string(),
+                    Section {
+                        text,
+                        settings: PromptSection::default(),
+                        loaded: false,
+                    },
+                );
+            },
+        }
+    }
+
+    /// Include a lazy section in the prompt from now on.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such section or it is not lazy.
+    pub fn load(&mut self, name: &str) -> Result<()> {
+        let Some(section) = self.sections.get_mut(name) else {
+            anyhow::bail!(
+                "No prompt section '{name}' (sections: {})",
+                self.names().join(", ")
+            );
+        };
+        if !section.lazy() {
+            anyhow::bail!("Prompt section '{name}' is not lazy; it is always included");
+        }
+        section.loaded = true;
+        Ok(())
+    }
+
+    /// Names of the sections, in prompt order.
+    #[must_use]
+    pub fn names(&self) -> Vec<String> {
+        self.sections.keys().cloned().collect()
+    }
+
+    /// Assemble the system prompt from the included sections.
+    #[must_use]
+    pub fn render(&self) -> String {
+        self.sections
+            .values()
+            .filter(|section| section.included())
+            .map(|section| section.text().trim())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// Estimate the size of each section.
+    ///
+    /// Tokens are estimated at four characters per token.
+    #[must_use]
+    pub fn estimates(&self) -> Vec<Estimate> {
+        self.sections
+            .iter()
+            .map(|(name, section)| Estimate {
+                name: name.clone(),
+                tokens: (section.text().trim().len() / 4) as u64,
+                included: section.included(),
+                lazy: section.lazy(),
+            })
+            .collect()
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new(&config::Prompt::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Settings of a section from a TOML snippet.
+    fn section(toml: &str) -> PromptSection {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn test_render_orders_and_skips_empty_sections() {
+        let mut builder = Builder::default();
+        builder.set("environment", "# Environment\nWorking directory: /repo");
+        builder.set("extra", "Added last");
+
+        let prompt = builder.render();
+        assert!(prompt.starts_with(IDENTITY));
+        let environment = prompt.find("Working directory").unwrap();
+        let tools = prompt.find("# Using tools").unwrap();
+        assert!(environment < tools);
+        assert!(prompt.ends_with("Added last"));
+        assert!(!prompt.contains("\n\n\n"));
+
+        let estimates = builder.estimates();
+        let names: Vec<&str> = estimates.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "identity",
+                "environment",
+                "tools",
+                "project",
+                "memories",
+                "skills",
+                "extra"
+            ]
+        );
+        assert!(estimates.iter().any(|e| e.name == "project" && !e.included));
+    }
+
+    #[test]
+    fn test_configured_sections() {
+        let mut settings = config::Prompt::default();
+        settings.sections.insert(
+            "tools".to_string(),
+            section("text = \"Use tools sparingly.\""),
+        );
+        settings
+            .sections
+            .insert("environment".to_string(), section("enabled = false"));
+        settings.sections.insert(
+            "style".to_string(),
+            section("text = \"Follow STYLE.md\"\nlazy = true"),
+        );
+
+        let mut builder = Builder::new(&settings);
+        builder.set("environment", "Working directory: /repo");
+        let prompt = builder.render();
+        assert!(prompt.contains("Use tools sparingly."));
+        assert!(!prompt.contains("# Using tools"));
+        assert!(!prompt.contains("/repo"));
+        assert!(!prompt.contains("STYLE.md"));
+
+        builder.load("style").unwrap();
+        assert!(builder.render().ends_with("Follow STYLE.md"));
+        builder.load("tools").unwrap_err();
+        builder.load("missing").unwrap_err();
+    }
+}
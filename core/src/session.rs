@@ -4,12 +4,34 @@
 //! interactive REPL loops and single-message execution.
 
 use crate::Client;
+use crate::checkpoint;
 use crate::command::Command;
-use crate::config::ProviderSettings;
+use crate::commands::CommandRegistry;
+use crate::compaction;
+use crate::config::{
+    Compaction, Config, Environment, Limits, McpServer, Persona, ProviderSettings, Redaction,
+    Resume,
+};
+use crate::environment;
 use crate::events::CoreEvent;
+use crate::export::{self, Document, Format};
+use crate::guard::Guard;
+use crate::history::{self, History};
 use crate::input::Reader;
+use crate::instructions;
+use crate::mcp;
+use crate::memory;
+use crate::persona;
+use crate::prompt;
+use crate::skills;
+use crate::steering::Steering;
+use crate::tools::{self, Tool, ToolRegistry};
+use crate::transcript::{self, Transcript};
 use anyhow::{Context, Result};
+use indexmap::IndexMap;
 use serde_json::json;
+use std::path::Path;
+use std::sync::Arc;
 use tokio::sync::mpsc;
 
 /// Session for managing conversations with the AI.
@@ -19,12 +41,42 @@ use tokio::sync::mpsc;
 pub struct Session {
     /// API client for communicating with Anthropic
     client: Client,
-    /// System prompt to use for all messages
-    system_prompt: String,
+    /// Sections of the system prompt
+    prompt: prompt::Builder,
     /// Tool schemas available to the AI
     schema: Vec<serde_json::Value>,
     /// Conversation history
-    messages: Vec<serde_json::Value>,
+    history: History,
+    /// Guard enforcing agent loop limits
+    guard: Guard,
+    /// Queue of user messages typed while a turn is running
+    steering: Steering,
+    /// Automatic compaction settings
+    compaction: Compaction,
+    /// Working directory of the session
+    cwd: String,
+    /// Snapshots of files changed by tools, for undo
+    checkpoints: Option<checkpoint::Store>,
+    /// Restore previewed to the user and waiting for confirmation
+    pending_restore: Option<checkpoint::Plan>,
+    /// Redaction of secrets in exports
+    redaction: Redaction,
+    /// Slash commands available in interactive mode
+    commands: Arc<CommandRegistry>,
+    /// Instruction files merged into the project section of the prompt
+    instructions: Option<instructions::Files>,
+    /// Persona profiles by name
+    personas: IndexMap<String, Persona>,
+    /// Profile of the current persona, `None` for the default identity
+    persona: Option<String>,
+    /// Items of the environment section of the prompt
+    environment: Environment,
+    /// Persistent memories of the project, `None` when memory is disabled
+    memory: Option<memory::Store>,
+    /// Connected MCP servers
+    mcp: Vec<Arc<mcp::Server>>,
+    /// MCP prompt waiting for the user to give a missing argument
+    pending_prompt: Option<mcp::Invocation>,
 }
 
 impl Session {
@@ -36,22 +88,208 @@ impl Session {
     /// * `cwd` - Current working directory for context
     #[must_use]
     pub fn new(config: ProviderSettings, cwd: &str) -> Self {
+        let guard = Guard::new(Limits::default(), config.pricing);
         let client = Client::new(config);
-        let system_prompt = format!("Concise coding assistant. cwd: {cwd}");
+        let environment = Environment::default();
+        let mut prompt = prompt::Builder::default();
+        prompt.set("environment", environment::render(cwd, &environment));
         let schema = crate::api::anthropic::schema::tool_schemas();
 
         Self {
             client,
-            system_prompt,
+            prompt,
             schema,
-            messages: Vec::new(),
+            history: History::new(),
+            guard,
+            steering: Steering::new(),
+            compaction: Compaction::default(),
+            cwd: cwd.to_string(),
+            checkpoints: None,
+            pending_restore: None,
+            redaction: Redaction::default(),
+            commands: Arc::new(CommandRegistry::new()),
+            instructions: None,
+            personas: persona::builtin(),
+            persona: None,
+            environment,
+            memory: None,
+            mcp: Vec::new(),
+            pending_prompt: None,
         }
     }
 
+    /// Apply application configuration to the session.
+    ///
+    /// - Limits, compaction, pruning and redaction are applied.
+    /// - The system prompt is built: environment, instruction files, skill
+    ///   index, memories and the configured persona.
+    /// - Command templates and skills are registered as slash commands.
+    /// - When a sessions directory is configured, the conversation is
+    ///   persisted to a transcript, or restored from one when resuming
+    ///   (optionally into a new branch).
+    /// - The tools get output limits, a scratch directory and checkpoints;
+    ///   the memory, skill and tool search tools are offered when enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Application configuration
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configured persona does not exist, or the
+    /// session to resume cannot be found or read.
+    pub fn with_config(mut self, config: &Config) -> Result<Self> {
+        self.apply_limits(config);
+        self.build_prompt(config)?;
+        let skills = Arc::new(skills::load(&config.skills_dirs));
+        self.prompt.set("skills", skills::index(&skills));
+        let mut commands = CommandRegistry::new();
+        commands.register_templates(&config.commands_dirs);
+        commands.register_skills(&skills);
+        self.commands = Arc::new(commands);
+
+        self.history = match &config.sessions_dir {
+            Some(dir) => self.open_history(dir, config)?,
+            None if config.resume == Resume::New => History::new(),
+            None => anyhow::bail!("Cannot resume: no sessions directory available"),
+        };
+        if config.fork && config.resume != Resume::New {
+            self.fork()?;
+        }
+
+        let registry = self.tool_registry(config, skills);
+        self.client.set_tool_registry(Arc::new(registry));
+        Ok(self)
+    }
+
+    /// Apply the loop limits, compaction, pruning and redaction settings.
+    fn apply_limits(&mut self, config: &Config) {
+        self.guard = Guard::new(config.limits.clone(), self.client.config().pricing);
+        self.compaction = config.compaction.clone();
+        self.client.set_compaction(config.compaction.clone());
+        self.redaction = config.redaction.clone();
+        self.client.set_pruning(config.pruning.clone());
+    }
+
+    /// Build the system prompt: environment, instruction files and persona.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configured persona does not exist.
+    fn build_prompt(&mut self, config: &Config) -> Result<()> {
+        self.prompt = prompt::Builder::new(&config.prompt);
+        self.environment = config.environment.clone();
+        self.prompt.set(
+            "environment",
+            environment::render(&self.cwd, &self.environment),
+        );
+        let mut files = instructions::Files::new(
+            Path::new(&self.cwd),
+            &config.instructions.file_names,
+            crate::config::user_dir(),
+        );
+        self.prompt.set("project", files.load());
+        self.instructions = Some(files);
+        self.personas = persona::builtin();
+        self.personas.extend(config.personas.clone());
+        self.personas.extend(persona::load(&config.personas_dirs));
+        self.set_persona(config.persona.as_deref())?;
+        Ok(())
+    }
+
+    /// Create the tool registry, with the optional tools that are enabled.
+    ///
+    /// Oversized output is spilled to a scratch directory next to the
+    /// transcript, or to a temporary one, and files are checkpointed before
+    /// the tools change them.
+    fn tool_registry(&mut self, config: &Config, skills: Arc<Vec<skills::Skill>>) -> ToolRegistry {
+        let checkpoint_dir = self.history.transcript().map_or_else(
+            || {
+                std::env::temp_dir()
+                    .join("neco")
+                    .join(format!("checkpoints-{}", std::process::id()))
+            },
+            |transcript| transcript.dir().join("checkpoints"),
+        );
+        let checkpoints = checkpoint::Store::open(&checkpoint_dir);
+
+        let mut registry = ToolRegistry::new();
+        registry.set_output_limits(config.output_limits.clone());
+        match self.history.transcript() {
+            Some(transcript) => registry.set_scratch_dir(transcript.dir().join("scratch")),
+            None => registry.set_temporary_scratch_dir(std::env::temp_dir().join("neco").join(
+                format!("scratch-{}-{}", std::process::id(), crate::clock::new_id()),
+            )),
+        }
+        registry.set_checkpoints(checkpoints.clone());
+        self.checkpoints = Some(checkpoints);
+        self.memory = config
+            .memory_dir
+            .as_deref()
+            .filter(|_| config.memory.enabled)
+            .map(|dir| memory::Store::open(dir, Path::new(&self.cwd), config.memory.clone()));
+        if let Some(store) = &self.memory {
+            let tool = tools::Memory::new(store.clone());
+            self.schema.push(tools::definition(&tool));
+            registry.register(Arc::new(tool));
+            self.prompt.set("memories", store.section());
+        }
+        if !skills.is_empty() {
+            let tool = tools::Skill::new(skills);
+            self.schema.push(tools::definition(&tool));
+            registry.register(Arc::new(tool));
+        }
+        if config.tool_search.enabled {
+            let catalog = tools::search::Catalog::new(&config.tool_search);
+            let tool = tools::Search::new(catalog.clone());
+            self.schema.push(tools::definition(&tool));
+            registry.register(Arc::new(tool));
+            self.client.set_tool_search(catalog);
+        }
+        registry
+    }
+
+    /// Create a persisted history, restoring it from a transcript when resuming.
+    ///
+    /// A trailing unanswered tool call (e.g. after a crash) is answered with
+    /// an error result so that the restored history is valid for the API.
+    fn open_history(&self, sessions_dir: &Path, config: &Config) -> Result<History> {
+        let settings = self.client.config();
+        let id = match &config.resume {
+            Resume::New => {
+                let transcript =
+                    Transcript::create(sessions_dir, &config.cwd, &settings.name, &settings.model);
+                return Ok(History::restored(Vec::new(), Some(transcript)));
+            },
+            Resume::Latest => {
+                transcript::latest_for_cwd(sessions_dir, &config.cwd)
+                    .with_context(|| format!("No previous session found for {}", config.cwd))?
+                    .id
+            },
+            Resume::Id(id) => id.clone(),
+        };
+
+        let (transcript, records) = Transcript::resume(
+            sessions_dir,
+            &id,
+            &config.cwd,
+            &settings.name,
+            &settings.model,
+        )?;
+        let mut history =
+            History::restored(transcript::rebuild_history(&records), Some(transcript));
+        if let Some(repair) = transcript::unanswered_tool_results(history.messages()) {
+            tracing::warn!(session = %id, "Answering unfinished tool calls of resumed session");
+            history.push(repair);
+        }
+        Ok(history)
+    }
+
     /// Run the session in interactive mode.
     ///
     /// This method enters a REPL loop, continuously reading user input
-    /// and processing commands until the user quits.
+    /// and processing commands until the user quits. A persona chosen in
+    /// the configuration greets the user first.
     ///
     /// # Arguments
     ///
@@ -89,6 +327,9 @@ impl Session {
         mut reader: impl Reader,
         event_sender: mpsc::UnboundedSender<CoreEvent>,
     ) -> Result<()> {
+        if self.persona.is_some() {
+            let _ = event_sender.send(self.persona_event());
+        }
         loop {
             let Some(user_input) = reader.read_line().await else {
                 break;
@@ -99,7 +340,7 @@ impl Session {
                 continue;
             }
 
-            let command = Self::parse_input(user_input);
+            let command = self.commands.parse(user_input);
 
             let should_continue = self.handle_command(command, &event_sender).await?;
             if !should_continue {
@@ -140,7 +381,7 @@ impl Session {
 
     /// Clear the conversation history.
     pub fn clear_history(&mut self) {
-        self.messages.clear();
+        self.history.clear();
     }
 
     /// Get reference to the API client.
@@ -152,19 +393,50 @@ impl Session {
     /// Get reference to the messages history.
     #[must_use]
     pub fn messages(&self) -> &[serde_json::Value] {
-        &self.messages
+        self.history.messages()
     }
 
     /// Get mutable reference to the messages history.
+    ///
+    /// Changes made through this reference are not recorded in the transcript.
     #[must_use]
-    pub fn messages_mut(&mut self) -> &mut Vec<serde_json::Value> {
-        &mut self.messages
+    pub const fn messages_mut(&mut self) -> &mut Vec<serde_json::Value> {
+        self.history.messages_mut()
     }
 
-    /// Get reference to the system prompt.
+    /// Get the transcript the session is persisted to, if any.
     #[must_use]
-    pub fn system_prompt(&self) -> &str {
-        &self.system_prompt
+    pub const fn transcript(&self) -> Option<&Transcript> {
+        self.history.transcript()
+    }
+
+    /// Record an event in the transcript and send it to the renderer.
+    pub(crate) fn emit(&self, event_sender: &mpsc::UnboundedSender<CoreEvent>, event: CoreEvent) {
+        self.history.emit(Some(event_sender), event);
+    }
+
+    /// Assemble the system prompt from its sections.
+    #[must_use]
+    pub fn system_prompt(&self) -> String {
+        self.prompt.render()
+    }
+
+    /// Get reference to the sections of the system prompt.
+    #[must_use]
+    pub const fn prompt(&self) -> &prompt::Builder {
+        &self.prompt
+    }
+
+    /// Get mutable reference to the sections of the system prompt.
+    #[must_use]
+    pub const fn prompt_mut(&mut self) -> &mut prompt::Builder {
+        &mut self.prompt
+    }
+
+    /// Get the working directory of the session.
+    #[must_use]
+    pub fn cwd(&self) -> &str {
+        &self.cwd
     }
 
     /// Get reference to the tool schemas.
@@ -173,6 +445,240 @@ impl Session {
         &self.schema
     }
 
+    /// Get a handle to the steering queue.
+    ///
+    /// Input received while a turn is running can be offered to this queue
+    /// to be delivered to the model mid-turn.
+    #[must_use]
+    pub fn steering(&self) -> Steering {
+        self.steering.clone()
+    }
+
+    /// Get the persistent memories of the project, `None` when memory is disabled.
+    #[must_use]
+    pub const fn memory(&self) -> Option<&memory::Store> {
+        self.memory.as_ref()
+    }
+
+    /// Get the connected MCP servers.
+    #[must_use]
+    pub fn mcp_servers(&self) -> &[Arc<mcp::Server>] {
+        &self.mcp
+    }
+
+    /// Start the enabled MCP servers and offer their tools and prompts.
+    ///
+    /// Server prompts are registered as slash commands, and the resources
+    /// tool is offered when a server has resources. A tool whose qualified
+    /// name is already taken, e.g. by a server whose name differs only in
+    /// replaced characters, is reported and skipped. Must be called before
+    /// the first turn, while the tool and command registries are not shared.
+    /// A server that fails to start is reported and skipped; no server is
+    /// started while the tool registry is shared.
+    ///
+    /// # Arguments
+    ///
+    /// * `servers` - Configured servers by name
+    /// * `event_sender` - Channel for connection events
+    pub async fn connect_mcp(
+        &mut self,
+        servers: &IndexMap<String, McpServer>,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) {
+        let enabled: Vec<_> = servers
+            .iter()
+            .filter(|(_, server)| server.enabled)
+            .collect();
+        if !enabled.is_empty() && self.client.tool_registry_mut().is_none() {
+            let _ = event_sender.send(CoreEvent::Error(
+                "MCP servers not started: the tools are in use".to_string(),
+            ));
+            return;
+        }
+        for (name, settings) in enabled {
+            let connected = match mcp::Server::new(name, settings, Path::new(&self.cwd)) {
+                Ok(server) => {
+                    let server = Arc::new(server);
+                    mcp::connect(&server).await.map(|remote| (server, remote))
+                },
+                Err(e) => Err(e),
+            };
+            let (server, remote) = match connected {
+                Ok(connected) => connected,
+                Err(e) => {
+                    let _ = event_sender.send(CoreEvent::Error(format!(
+                        "MCP server '{name}' failed to start: {e:#}"
+                    )));
+                    continue;
+                },
+            };
+            let Some(registry) = self.client.tool_registry_mut() else {
+                // Dropping the server closes its connection
+                let _ = event_sender.send(CoreEvent::Error(format!(
+                    "Tools of MCP server '{name}' not offered: the tools are in use"
+                )));
+                continue;
+            };
+            let mut tools = 0;
+            for tool in remote {
+                if registry.contains(tool.name()) {
+                    let _ = event_sender.send(CoreEvent::Error(format!(
+                        "MCP tool '{}' of server '{name}' skipped: the name {} is already taken",
+                        tool.remote_name(),
+                        tool.name()
+                    )));
+                    continue;
+                }
+                self.schema.push(tools::definition(&tool));
+                registry.register(Arc::new(tool));
+                tools += 1;
+            }
+            let prompts = if server.supports("prompts") {
+                self.register_prompts(&server, event_sender).await
+            } else {
+                0
+            };
+            self.mcp.push(server);
+            let _ = event_sender.send(CoreEvent::McpConnected {
+                server: name.clone(),
+                tools,
+                prompts,
+            });
+        }
+
+        let servers: Vec<Arc<mcp::Server>> = self
+            .mcp
+            .iter()
+            .filter(|server| server.supports("resources"))
+            .cloned()
+            .collect();
+        if !servers.is_empty()
+            && let Some(registry) = self.client.tool_registry_mut()
+        {
+            let tool = tools::Resources::new(servers);
+            self.schema.push(tools::definition(&tool));
+            registry.register(Arc::new(tool));
+        }
+    }
+
+    /// Register the prompts of an MCP server as slash commands.
+    ///
+    /// # Returns
+    ///
+    /// The number of prompts registered.
+    async fn register_prompts(
+        &mut self,
+        server: &Arc<mcp::Server>,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> usize {
+        let prompts = match server.list_prompts().await {
+            Ok(prompts) => prompts,
+            Err(e) => {
+                let _ = event_sender.send(CoreEvent::Error(format!("{e:#}")));
+                return 0;
+            },
+        };
+        let Some(commands) = Arc::get_mut(&mut self.commands) else {
+            let _ = event_sender.send(CoreEvent::Error(format!(
+                "Prompts of MCP server '{}' not offered: the commands are in use",
+                server.name()
+            )));
+            return 0;
+        };
+        let count = prompts.len();
+        for info in prompts {
+            commands.register(Arc::new(mcp::Prompt::new(Arc::clone(server), info)));
+        }
+        count
+    }
+
+    /// Run an MCP prompt, first asking the user for its missing required arguments.
+    ///
+    /// While an argument is missing, the next message the user types is
+    /// taken as its value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the prompt cannot be got from the server.
+    pub(crate) async fn invoke_prompt(
+        &mut self,
+        invocation: mcp::Invocation,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<()> {
+        if let Some(argument) = invocation.next_missing() {
+            let _ = event_sender.send(CoreEvent::PromptArgument {
+                command: invocation.command().to_string(),
+                name: argument.name.clone(),
+                description: argument.description.clone(),
+            });
+            self.pending_prompt = Some(invocation);
+            return Ok(());
+        }
+        let text = invocation.text().await?;
+        self.send_message_with(text, None, None, event_sender).await;
+        Ok(())
+    }
+
+    /// Content of a user message, with the MCP resources it references inlined.
+    ///
+    /// Resources come first as content blocks, then the message text.
+    /// Resources that cannot be read are reported and left as written.
+    async fn user_content(
+        &self,
+        msg: &str,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> serde_json::Value {
+        let names: Vec<&str> = self.mcp.iter().map(|server| server.name()).collect();
+        let mut blocks = Vec::new();
+        for reference in mcp::resources::references(msg, &names) {
+            let Some(server) = self
+                .mcp
+                .iter()
+                .find(|server| server.name() == reference.server)
+            else {
+                continue;
+            };
+            match server.read_resource(&reference.uri).await {
+                Ok(contents) => {
+                    blocks.extend(mcp::resources::blocks(&reference, &contents));
+                    self.emit(
+                        event_sender,
+                        CoreEvent::McpResource {
+                            server: reference.server,
+                            uri: reference.uri,
+                        },
+                    );
+                },
+                Err(e) => {
+                    self.emit(
+                        event_sender,
+                        CoreEvent::Error(format!(
+                            "Cannot read @{}:{}: {e:#}",
+                            reference.server, reference.uri
+                        )),
+                    );
+                },
+            }
+        }
+        if blocks.is_empty() {
+            return json!(msg);
+        }
+        blocks.push(json!({"type": "text", "text": msg}));
+        serde_json::Value::Array(blocks)
+    }
+
+    /// Get the slash commands available in interactive mode.
+    #[must_use]
+    pub fn commands(&self) -> Arc<CommandRegistry> {
+        Arc::clone(&self.commands)
+    }
+
+    /// Get reference to the loop guard (limits and usage totals).
+    #[must_use]
+    pub const fn guard(&self) -> &Guard {
+        &self.guard
+    }
+
     /// Run the agent loop with the current session state.
     ///
     /// # Arguments
@@ -193,31 +699,430 @@ impl Session {
         &mut self,
         event_sender: &mpsc::UnboundedSender<CoreEvent>,
     ) -> Result<()> {
+        let system_prompt = self.system_prompt();
         self.client
             .run_agent_loop_stream(
-                &mut self.messages,
-                &self.system_prompt,
+                &mut self.history,
+                &system_prompt,
                 &self.schema,
                 Some(event_sender),
+                &mut self.guard,
+                Some(&self.steering),
             )
             .await
             .context("Agent loop error")
     }
 
-    /// Parse user input into a command.
+    /// Get the text of each user turn in the history, oldest first.
+    #[must_use]
+    pub fn turns(&self) -> Vec<String> {
+        let messages = self.history.messages();
+        self.history
+            .turn_starts()
+            .into_iter()
+            .filter_map(|index| messages.get(index).and_then(history::user_text))
+            .collect()
+    }
+
+    /// Rewind the conversation to just before a user turn.
+    ///
+    /// The turn and everything after it are removed from the history, so the
+    /// message can be edited and sent again. The transcript keeps the
+    /// removed messages.
+    ///
+    /// # Arguments
+    ///
+    /// * `turn` - 1-based number of the user turn, as listed by [`Self::turns`]
+    ///
+    /// # Returns
+    ///
+    /// The text of the removed user message.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such turn.
+    pub fn rewind(&mut self, turn: usize) -> Result<String> {
+        let starts = self.history.turn_starts();
+        let index = turn
+            .checked_sub(1)
+            .and_then(|i| starts.get(i))
+            .copied()
+            .with_context(|| {
+                format!(
+                    "No message {turn} to rewind to ({} in history)",
+                    starts.len()
+                )
+            })?;
+        let text = self
+            .history
+            .messages()
+            .get(index)
+            .and_then(history::user_text)
+            .unwrap_or_default();
+
+        self.history.truncate(index);
+        self.guard.reset_context();
+        Ok(text)
+    }
+
+    /// Fork the session into a new branch that keeps the shared history.
+    ///
+    /// The conversation continues in the branch; the original session is
+    /// left unchanged and can still be resumed.
+    ///
+    /// # Returns
+    ///
+    /// The id of the new branch.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the session is not persisted.
+    pub fn fork(&mut self) -> Result<String> {
+        let settings = self.client.config();
+        let branch = self
+            .history
+            .transcript()
+            .context("Cannot fork: the session is not persisted")?
+            .branch(&self.cwd, &settings.name, &settings.model);
+        let id = branch.id().to_string();
+        self.history.fork(branch);
+        Ok(id)
+    }
+
+    /// Continue the conversation with another provider or model.
+    ///
+    /// The history is kept as it is: switching is limited to providers
+    /// speaking the Anthropic Messages format, so no conversion is needed.
+    /// The switch is recorded in the transcript, so that the session is
+    /// listed with its current model.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - Provider settings of the model to switch to
+    ///
+    /// # Returns
+    ///
+    /// The event describing the switch.
+    pub fn switch_model(&mut self, settings: ProviderSettings) -> CoreEvent {
+        let previous = self.client.config();
+        let from = format!("{}/{}", previous.name, previous.model);
+        if let Some(transcript) = self.history.transcript() {
+            transcript.record_model(&self.cwd, &settings.name, &settings.model);
+        }
+        self.guard.set_pricing(settings.pricing);
+        let event = CoreEvent::ModelSwitched {
+            from,
+            provider: settings.name.clone(),
+            model: settings.model.clone(),
+        };
+        self.client.set_config(settings);
+        event
+    }
+
+    /// Talk as another persona, changing only the identity section of the prompt.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Profile of the persona, or `None` for the default identity
+    ///
+    /// # Returns
+    ///
+    /// The event announcing the persona.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such persona.
+    pub fn set_persona(&mut self, name: Option<&str>) -> Result<CoreEvent> {
+        let identity = match name {
+            Some(name) => {
+                let persona = self.personas.get(name).with_context(|| {
+                    format!(
+                        "No persona '{name}' (personas: {})",
+                        self.persona_names().join(", ")
+                    )
+                })?;
+                persona::identity(name, persona)
+            },
+            None => prompt::IDENTITY.to_string(),
+        };
+        self.prompt.set("identity", identity);
+        self.persona = name.map(str::to_string);
+        Ok(self.persona_event())
+    }
+
+    /// Get the profile of the current persona, `None` for the default identity.
+    #[must_use]
+    pub fn persona(&self) -> Option<&str> {
+        self.persona.as_deref()
+    }
+
+    /// Get the names of the persona profiles.
+    #[must_use]
+    pub fn persona_names(&self) -> Vec<String> {
+        self.personas.keys().cloned().collect()
+    }
+
+    /// Event announcing the current persona.
+    fn persona_event(&self) -> CoreEvent {
+        let profile = self
+            .persona
+            .as_ref()
+            .and_then(|id| self.personas.get(id).map(|persona| (id, persona)));
+        CoreEvent::Persona {
+            name: profile.map(|(id, persona)| persona.name.clone().unwrap_or_else(|| id.clone())),
+            greeting: profile.and_then(|(_, persona)| persona.greeting.clone()),
+        }
+    }
+
+    /// List the file checkpoints, oldest first.
+    #[must_use]
+    pub fn checkpoints(&self) -> Vec<checkpoint::Summary> {
+        self.checkpoints
+            .as_ref()
+            .map(checkpoint::Store::list)
+            .unwrap_or_default()
+    }
+
+    /// Plan restoring files to their state before a checkpoint.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if checkpoints are disabled, or there is no such checkpoint.
+    pub fn plan_restore(&self, checkpoint: usize) -> Result<checkpoint::Plan> {
+        self.checkpoints
+            .as_ref()
+            .context("File checkpoints are not enabled")?
+            .plan(checkpoint)
+    }
+
+    /// Plan undoing the file changes of the last turns that changed files.
+    ///
+    /// # Arguments
+    ///
+    /// * `turns` - Number of turns to undo
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if checkpoints are disabled, or fewer turns changed files.
+    pub fn plan_undo(&self, turns: usize) -> Result<checkpoint::Plan> {
+        let store = self
+            .checkpoints
+            .as_ref()
+            .context("File checkpoints are not enabled")?;
+        let checkpoint = store.undo_target(turns).with_context(|| {
+            format!(
+                "Cannot undo {turns} turns: {} turns changed files",
+                store.list().len()
+            )
+        })?;
+        store.plan(checkpoint)
+    }
+
+    /// Restore files as planned by [`Self::plan_restore`] or [`Self::plan_undo`].
+    ///
+    /// The conversation history is left as it is.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if checkpoints are disabled or a file cannot be restored.
+    pub fn restore(&self, plan: &checkpoint::Plan) -> Result<()> {
+        self.checkpoints
+            .as_ref()
+            .context("File checkpoints are not enabled")?
+            .restore(plan)
+    }
+
+    /// Get the changes the tools made to files, one per file.
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - First checkpoint to include, or `None` for the whole session
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if checkpoints are disabled, there is no such
+    /// checkpoint, or a snapshot cannot be read.
+    pub fn changes(&self, since: Option<usize>) -> Result<Vec<checkpoint::Change>> {
+        self.checkpoints
+            .as_ref()
+            .context("File checkpoints are not enabled")?
+            .changes(since)
+    }
+
+    /// Report the diff of the session's file changes, saving it as a patch if requested.
+    pub(crate) async fn show_diff(
+        &self,
+        since: Option<usize>,
+        output: Option<String>,
+    ) -> Result<CoreEvent> {
+        let changes = self.changes(since)?;
+        let untracked = self
+            .checkpoints
+            .as_ref()
+            .map(|store| store.untracked(since))
+            .unwrap_or_default();
+        let base = Path::new(&self.cwd);
+        let patch = checkpoint::patch(&changes, base);
+        if let Some(output) = &output {
+            tokio::fs::write(output, &patch)
+                .await
+                .with_context(|| format!("Failed to write {output}"))?;
+        }
+        Ok(CoreEvent::Diff {
+            since,
+            files: changes.iter().map(|change| change.stat(base)).collect(),
+            patch,
+            saved: output,
+            untracked,
+        })
+    }
+
+    /// Build an export of the session from its messages.
+    ///
+    /// Usage totals come from the usage events recorded in the transcript,
+    /// so they cover earlier runs of a resumed session; an unpersisted
+    /// session uses the totals of the loop guard.
+    #[must_use]
+    pub fn document(&self) -> Document {
+        let settings = self.client.config();
+        let records = self.transcript().and_then(|transcript| {
+            transcript::load(transcript.dir().parent()?, transcript.id()).ok()
+        });
+        let info = records.as_deref().and_then(transcript::summarize);
+        Document {
+            id: self.transcript().map(|t| t.id().to_string()),
+            model: settings.model.clone(),
+            cwd: self.cwd.clone(),
+            created_at: info.map(|info| info.created_at),
+            messages: self.history.messages().to_vec(),
+            usage: records
+                .as_deref()
+                .map_or(self.guard.session().usage, export::usage_totals),
+        }
+    }
+
+    /// Export the session to a Markdown or HTML file, chosen by its extension.
+    ///
+    /// Secrets matching the redaction settings and the provider's API key
+    /// are redacted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a redaction pattern is invalid or the file cannot be written.
+    pub async fn export(&self, path: &Path) -> Result<()> {
+        let mut redaction = self.redaction.clone();
+        redaction.secrets.push(self.client.config().api_key.clone());
+        let redactor = export::Redactor::new(&redaction)?;
+
+        let content = self.document().render(Format::from_path(path), &redactor);
+        tokio::fs::write(path, content)
+            .await
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Show a planned restore and wait for the user to confirm it.
+    ///
+    /// A plan with no changed files is applied right away.
+    pub(crate) fn preview_restore(
+        &mut self,
+        plan: checkpoint::Plan,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) {
+        if plan.files.is_empty() {
+            self.finish_restore(&plan, event_sender);
+            return;
+        }
+        let _ = event_sender.send(CoreEvent::RestorePreview {
+            checkpoint: plan.checkpoint,
+            diff: plan.preview(),
+            files: plan.paths(),
+            untracked: plan.untracked.clone(),
+        });
+        self.pending_restore = Some(plan);
+    }
+
+    /// Restore files and report the outcome.
+    fn finish_restore(
+        &self,
+        plan: &checkpoint::Plan,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) {
+        match self.restore(plan) {
+            Ok(()) => {
+                let _ = event_sender.send(CoreEvent::Restored {
+                    checkpoint: plan.checkpoint,
+                    files: plan.paths(),
+                    untracked: plan.untracked.clone(),
+                });
+            },
+            Err(e) => {
+                let _ = event_sender.send(CoreEvent::Error(format!("Error: {e:#}")));
+            },
+        }
+    }
+
+    /// Compact the history, replacing older turns by a model-written summary.
+    ///
+    /// The most recent turns (see [`Compaction::keep_turns`]) are kept
+    /// verbatim. The replaced messages remain in the session transcript.
     ///
     /// # Arguments
     ///
-    /// * `input` - Raw user input string
+    /// * `focus` - Optional instructions on what the summary should focus on
+    /// * `automatic` - Whether compaction was triggered by the context size
+    /// * `event_sender` - Sender for core events
     ///
     /// # Returns
     ///
-    /// Parsed command
-    fn parse_input(input: &str) -> Command {
-        match input {
-            "/q" | "exit" => Command::Quit,
-            "/c" => Command::Clear,
-            msg => Command::Message(msg.to_string()),
+    /// `false` if there were no older turns to compact.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the summarisation request fails or returns no text.
+    pub async fn compact(
+        &mut self,
+        focus: Option<&str>,
+        automatic: bool,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        let Some(split) =
+            compaction::split_point(self.history.messages(), self.compaction.keep_turns)
+        else {
+            return Ok(false);
+        };
+        compaction::compact(
+            &self.client,
+            &mut self.history,
+            &mut self.guard,
+            split,
+            focus,
+            automatic,
+            Some(event_sender),
+        )
+        .await?;
+        Ok(true)
+    }
+
+    /// Compact the history if its context nears the configured limit.
+    ///
+    /// Uses the size of the last request, or an estimate when no request
+    /// has been made yet (e.g. after resuming). Failures are reported and
+    /// the conversation continues uncompacted.
+    async fn compact_if_due(&mut self, event_sender: &mpsc::UnboundedSender<CoreEvent>) {
+        let context_tokens = match self.guard.context_tokens() {
+            0 => compaction::estimate_tokens(self.history.messages()),
+            tokens => tokens,
+        };
+        if !self.compaction.is_due(context_tokens) {
+            return;
+        }
+
+        tracing::info!(context_tokens, "Compacting conversation");
+        if let Err(e) = self.compact(None, true, event_sender).await {
+            self.emit(
+                event_sender,
+                CoreEvent::Error(format!("Compaction failed: {e:#}")),
+            );
         }
     }
 
@@ -231,58 +1136,202 @@ impl Session {
     /// # Returns
     ///
     /// Ok(true) to continue the loop, Ok(false) to exit, Err on error
-    async fn handle_command(
+    pub(crate) async fn handle_command(
         &mut self,
         command: Command,
         event_sender: &mpsc::UnboundedSender<CoreEvent>,
     ) -> Result<bool> {
+        // A previewed restore is applied on "y"; any other input cancels it
+        if let Some(plan) = self.pending_restore.take() {
+            if let Command::Message(answer) = &command
+                && matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+            {
+                self.finish_restore(&plan, event_sender);
+                return Ok(true);
+            }
+            let _ = event_sender.send(CoreEvent::RestoreCancelled);
+        }
+        // An MCP prompt asking for an argument takes the next message as its value
+        if let Some(mut invocation) = self.pending_prompt.take() {
+            if let Command::Message(value) = &command {
+                invocation.answer(value.trim());
+                if let Err(e) = self.invoke_prompt(invocation, event_sender).await {
+                    let _ = event_sender.send(CoreEvent::Error(format!("Error: {e:#}")));
+                }
+                return Ok(true);
+            }
+            let _ = event_sender.send(CoreEvent::PromptCancelled {
+                command: invocation.command().to_string(),
+            });
+        }
+
         match command {
-            Command::Quit => Ok(false),
-            Command::Clear => {
-                self.clear_history();
-                let _ = event_sender.send(CoreEvent::Error("Conversation cleared".to_string()));
+            Command::Slash { name, args } => {
+                let Some(command) = self.commands.get(&name) else {
+                    let _ = event_sender.send(CoreEvent::Error(self.commands.unknown(&name)));
+                    return Ok(true);
+                };
+                match command.run(&args, self, event_sender).await {
+                    Ok(should_continue) => Ok(should_continue),
+                    Err(e) => {
+                        let _ = event_sender.send(CoreEvent::Error(format!("Error: {e:#}")));
+                        Ok(true)
+                    },
+                }
+            },
+            Command::Unknown(name) => {
+                let _ = event_sender.send(CoreEvent::Error(self.commands.unknown(&name)));
                 Ok(true)
             },
             Command::Message(msg) => {
-                self.messages.push(json!({
-                    "role": "user",
-                    "content": msg,
-                }));
-
-                if let Err(e) = self
-                    .client
-                    .run_agent_loop_stream(
-                        &mut self.messages,
-                        &self.system_prompt,
-                        &self.schema,
-                        Some(event_sender),
-                    )
-                    .await
-                {
-                    let _ = event_sender.send(CoreEvent::Error(format!("Error: {e}")));
-                }
-
+                self.send_message(msg, event_sender).await;
                 Ok(true)
             },
         }
     }
+
+    /// Send a user message and run the agent loop until the turn ends.
+    ///
+    /// The environment and memories sections are refreshed and instruction
+    /// files changed on disk are re-read before each turn.
+    /// Messages queued for steering after the last tool batch are sent
+    /// as a follow-up turn.
+    async fn send_message(&mut self, msg: String, event_sender: &mpsc::UnboundedSender<CoreEvent>) {
+        let mut pending = Some(msg);
+        while let Some(msg) = pending.take() {
+            self.compact_if_due(event_sender).await;
+            self.refresh_environment().await;
+            self.refresh_memories().await;
+            if let Some(text) = self
+                .instructions
+                .as_mut()
+                .and_then(instructions::Files::refresh)
+            {
+                self.prompt.set("project", text);
+            }
+            if let Some(checkpoints) = &self.checkpoints {
+                checkpoints.begin(&msg);
+            }
+            let content = self.user_content(&msg, event_sender).await;
+            self.history.push(json!({
+                "role": "user",
+                "content": content,
+            }));
+
+            self.steering.begin();
+            let system_prompt = self.system_prompt();
+            let result = self
+                .client
+                .run_agent_loop_stream(
+                    &mut self.history,
+                    &system_prompt,
+                    &self.schema,
+                    Some(event_sender),
+                    &mut self.guard,
+                    Some(&self.steering),
+                )
+                .await;
+            let undelivered = self.steering.finish();
+
+            if let Err(e) = result {
+                self.emit(event_sender, CoreEvent::Error(format!("Error: {e}")));
+            }
+
+            // Messages queued after the last tool batch become the next turn
+            if !undelivered.is_empty() {
+                for text in &undelivered {
+                    self.emit(event_sender, CoreEvent::SteeringDelivered(text.clone()));
+                }
+                pending = Some(undelivered.join("\n\n"));
+            }
+        }
+    }
+
+    /// Re-render the environment section, so git state and the directory
+    /// listing are current for the next turn.
+    ///
+    /// Rendering runs git, so it happens on a blocking thread.
+    async fn refresh_environment(&mut self) {
+        let cwd = self.cwd.clone();
+        let settings = self.environment.clone();
+        match tokio::task::spawn_blocking(move || environment::render(&cwd, &settings)).await {
+            Ok(text) => self.prompt.set("environment", text),
+            Err(e) => tracing::warn!("Failed to render the environment: {e}"),
+        }
+    }
+
+    /// Re-render the memories section, so memories saved, forgotten or gone
+    /// stale since the last turn are reflected.
+    ///
+    /// Staleness checks run git, so rendering happens on a blocking thread.
+    async fn refresh_memories(&mut self) {
+        let Some(store) = self.memory.clone() else {
+            return;
+        };
+        match tokio::task::spawn_blocking(move || store.section()).await {
+            Ok(text) => self.prompt.set("memories", text),
+            Err(e) => tracing::warn!("Failed to render the memories: {e}"),
+        }
+    }
+
+    /// Send a user message with the offered tools and the model overridden for this turn.
+    ///
+    /// An overridden model is switched to and back like with `/model`, so
+    /// the transcript and the renderer see which model answered the turn.
+    ///
+    /// # Arguments
+    ///
+    /// * `msg` - The message to send
+    /// * `tools` - Names of the tools offered to the model and allowed to run,
+    ///   or `None` for all tools
+    /// * `model` - Provider settings of the model to use, or `None` for the session's model
+    /// * `event_sender` - Sender for core events
+    pub(crate) async fn send_message_with(
+        &mut self,
+        msg: String,
+        tools: Option<&[String]>,
+        model: Option<ProviderSettings>,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) {
+        let schema = tools.map(|allowed| {
+            let offered = self
+                .schema
+                .iter()
+                .filter(|tool| {
+                    tool.get("name")
+                        .and_then(serde_json::Value::as_str)
+                        .is_some_and(|name| allowed.iter().any(|allowed| allowed == name))
+                })
+                .cloned()
+                .collect();
+            std::mem::replace(&mut self.schema, offered)
+        });
+        let settings = model.map(|model| {
+            let previous = self.client.config().clone();
+            let event = self.switch_model(model);
+            self.emit(event_sender, event);
+            previous
+        });
+
+        self.client.tool_registry().set_allowed(tools);
+
+        self.send_message(msg, event_sender).await;
+
+        self.client.tool_registry().set_allowed(None);
+        if let Some(schema) = schema {
+            self.schema = schema;
+        }
+        if let Some(settings) = settings {
+            let event = self.switch_model(settings);
+            self.emit(event_sender, event);
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_session_parse_input() {
-        assert_eq!(Session::parse_input("/q"), Command::Quit);
-        assert_eq!(Session::parse_input("exit"), Command::Quit);
-        assert_eq!(Session::parse_input("/c"), Command::Clear);
-        assert_eq!(
-            Session::parse_input("hello"),
-            Command::Message("hello".to_string())
-        );
-    }
-
     #[tokio::test]
     async fn test_session_clear_history() {
         let mut registry = crate::ProviderRegistry::global().write().await;
@@ -293,12 +1342,12 @@ mod tests {
         let mut session = Session::new(config, "/test");
 
         session
-            .messages
+            .history
             .push(json!({"role": "user", "content": "test"}));
-        assert!(!session.messages.is_empty());
+        assert!(!session.messages().is_empty());
 
         session.clear_history();
-        assert!(session.messages.is_empty());
+        assert!(session.messages().is_empty());
     }
 
     #[tokio::test]
@@ -310,8 +1359,718 @@ mod tests {
         let config = ProviderSettings::from_env().await.unwrap();
         let session = Session::new(config, "/test");
 
-        assert!(session.messages.is_empty());
-        assert!(!session.system_prompt.is_empty());
+        assert!(session.messages().is_empty());
+        assert!(session.system_prompt().contains("Working directory: /test"));
         assert!(!session.schema.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_session_resume_repairs_tool_use() {
+        let mut registry = crate::ProviderRegistry::global().write().await;
+        registry.register_defaults();
+        drop(registry);
+
+        let sessions_dir = crate::testing::TempDir::new("session-resume");
+        let stored = Transcript::create(&sessions_dir, "/test", "anthropic", "claude");
+        stored.record_message(&json!({"role": "user", "content": "list files"}));
+        stored.record_message(&json!({"role": "assistant", "content": [
+            {"type": "tool_use", "id": "call_1", "name": "bash", "input": {"cmd": "ls"}}
+        ]}));
+
+        let mut config = Config {
+            cwd: "/test".to_string(),
+            limits: Limits::default(),
+            output_limits: crate::config::OutputLimits::default(),
+            compaction: Compaction::default(),
+            pruning: crate::config::Pruning::default(),
+            redaction: crate::config::Redaction::default(),
+            prompt: crate::config::Prompt::default(),
+            instructions: crate::config::Instructions::default(),
+            environment: crate::config::Environment::default(),
+            memory: crate::config::Memory::default(),
+            memory_dir: None,
+            fork: false,
+            sessions_dir: Some(sessions_dir.to_path_buf()),
+            resume: Resume::Latest,
+            commands_dirs: Vec::new(),
+            skills_dirs: Vec::new(),
+            mcp_servers: IndexMap::new(),
+            tool_search: crate::config::ToolSearch::default(),
+            persona: None,
+            personas: IndexMap::new(),
+            personas_dirs: Vec::new(),
+        };
+        let provider = ProviderSettings::from_env().await.unwrap();
+        let session = Session::new(provider.clone(), "/test")
+            .with_config(&config)
+            .unwrap();
+
+        assert_eq!(session.transcript().map(Transcript::id), Some(stored.id()));
+        assert_eq!(session.messages().len(), 3);
+        let repaired = session.messages().last().unwrap();
+        let result = repaired.pointer("/content/0").unwrap();
+        assert_eq!(result.get("tool_use_id"), Some(&json!("call_1")));
+        assert_eq!(result.get("is_error"), Some(&json!(true)));
+
+        config.resume = Resume::Id("missing".to_string());
+        let result = Session::new(provider, "/test").with_config(&config);
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_session_keeps_and_exports_thinking() {
+        let server = crate::api::anthropic::fake::serve(
+            vec![
+                crate::api::anthropic::fake::Reply::Thinking("Read the file first", "Done"),
+                crate::api::anthropic::fake::Reply::Text("Again"),
+            ],
+            10,
+        )
+        .await
+        .unwrap();
+        let mut session = Session::new(server.settings.clone(), "/test");
+        let (sender, _receiver) = mpsc::unbounded_channel();
+        session.send_message("Fix it".to_string(), &sender).await;
+
+        let thinking = json!({
+            "type": "thinking",
+            "thinking": "Read the file first",
+            "signature": "signed"
+        });
+        let reply = session.messages().get(1).unwrap();
+        assert_eq!(reply.pointer("/content/0"), Some(&thinking));
+        assert_eq!(reply.pointer("/content/1/text"), Some(&json!("Done")));
+
+        session.send_message("Once more".to_string(), &sender).await;
+        let second = server.requests().get(1).cloned().unwrap();
+        assert_eq!(second.pointer("/messages/1/content/0"), Some(&thinking));
+
+        let redactor = crate::export::Redactor::new(&Redaction::default()).unwrap();
+        let markdown = session
+            .document()
+            .render(crate::export::Format::Markdown, &redactor);
+        assert!(markdown.contains("Read the file first"));
+    }
+
+    #[tokio::test]
+    async fn test_session_records_events_as_sent() {
+        let sessions_dir = crate::testing::TempDir::new("session-events");
+        let server = crate::api::anthropic::fake::serve(
+            vec![crate::api::anthropic::fake::Reply::Text("hello")],
+            10,
+        )
+        .await
+        .unwrap();
+        let config = Config {
+            cwd: "/test".to_string(),
+            limits: Limits::default(),
+            output_limits: crate::config::OutputLimits::default(),
+            compaction: Compaction::default(),
+            pruning: crate::config::Pruning::default(),
+            redaction: crate::config::Redaction::default(),
+            prompt: crate::config::Prompt::default(),
+            instructions: crate::config::Instructions::default(),
+            environment: crate::config::Environment::default(),
+            memory: crate::config::Memory::default(),
+            memory_dir: None,
+            fork: false,
+            sessions_dir: Some(sessions_dir.to_path_buf()),
+            resume: Resume::New,
+            commands_dirs: Vec::new(),
+            skills_dirs: Vec::new(),
+            mcp_servers: IndexMap::new(),
+            tool_search: crate::config::ToolSearch::default(),
+            persona: None,
+            personas: IndexMap::new(),
+            personas_dirs: Vec::new(),
+        };
+        let mut session = Session::new(server.settings.clone(), "/test")
+            .with_config(&config)
+            .unwrap();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        session.send_message("Hi".to_string(), &sender).await;
+
+        let id = session.transcript().unwrap().id().to_string();
+        let recorded: Vec<serde_json::Value> = transcript::load(&sessions_dir, &id)
+            .unwrap()
+            .into_iter()
+            .filter_map(|record| match record {
+                transcript::Record::Event { event, .. } => serde_json::to_value(event).ok(),
+                _ => None,
+            })
+            .collect();
+        let mut sent = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            if !matches!(
+                event,
+                CoreEvent::TextDelta(_) | CoreEvent::MessageStart | CoreEvent::MessageStop
+            ) {
+                sent.push(serde_json::to_value(event).unwrap());
+            }
+        }
+        assert!(!recorded.is_empty());
+        assert_eq!(recorded, sent);
+    }
+
+    #[tokio::test]
+    async fn test_session_records_model_override() {
+        let sessions_dir = crate::testing::TempDir::new("session-override");
+        let server = crate::api::anthropic::fake::serve(
+            vec![crate::api::anthropic::fake::Reply::Text("cheap answer")],
+            10,
+        )
+        .await
+        .unwrap();
+        let mut session = Session::new(server.settings.clone(), "/test");
+        let transcript = Transcript::create(&sessions_dir, "/test", "anthropic", "claude");
+        session.history = History::restored(Vec::new(), Some(transcript.clone()));
+        let cheaper = ProviderSettings {
+            model: "cheaper".to_string(),
+            ..server.settings.clone()
+        };
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        session
+            .send_message_with("Hi".to_string(), None, Some(cheaper), &sender)
+            .await;
+
+        let request = server.requests().first().cloned().unwrap();
+        assert_eq!(request.get("model"), Some(&json!("cheaper")));
+        assert_eq!(session.client().config().model, server.settings.model);
+
+        let mut switches = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            if let CoreEvent::ModelSwitched { model, .. } = event {
+                switches.push(model);
+            }
+        }
+        assert_eq!(
+            switches,
+            ["cheaper".to_string(), server.settings.model.clone()]
+        );
+
+        let models: Vec<String> = transcript::load(&sessions_dir, transcript.id())
+            .unwrap()
+            .into_iter()
+            .filter_map(|record| match record {
+                transcript::Record::Meta { model, .. } => Some(model),
+                _ => None,
+            })
+            .collect();
+        assert!(models.ends_with(&["cheaper".to_string(), server.settings.model.clone()]));
+    }
+
+    #[tokio::test]
+    async fn test_session_rewind() {
+        let mut registry = crate::ProviderRegistry::global().write().await;
+        registry.register_defaults();
+        drop(registry);
+
+        let config = ProviderSettings::from_env().await.unwrap();
+        let mut session = Session::new(config, "/test");
+        for message in [
+            json!({"role": "user", "content": "first"}),
+            json!({"role": "assistant", "content": "one"}),
+            json!({"role": "user", "content": "second"}),
+            json!({"role": "assistant", "content": "two"}),
+        ] {
+            session.history.push(message);
+        }
+        assert_eq!(session.turns(), vec!["first", "second"]);
+
+        let error = session.rewind(3).unwrap_err();
+        assert!(error.to_string().contains("No message 3"));
+        session.rewind(0).unwrap_err();
+        assert_eq!(session.rewind(2).unwrap(), "second");
+        assert_eq!(session.messages().len(), 2);
+
+        let error = session.fork().unwrap_err();
+        assert!(error.to_string().contains("not persisted"));
+    }
+
+    #[tokio::test]
+    async fn test_session_rewind_and_compact_across_steered_round() {
+        let server = crate::api::anthropic::fake::serve(
+            vec![crate::api::anthropic::fake::Reply::Text("summary")],
+            0,
+        )
+        .await
+        .unwrap();
+        let mut session = Session::new(server.settings.clone(), "/test");
+        for message in [
+            json!({"role": "user", "content": "first"}),
+            json!({"role": "assistant", "content": [
+                {"type": "tool_use", "id": "c1", "name": "read", "input": {"path": "a.rs"}}
+            ]}),
+            json!({"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": "c1", "content": "ok"},
+                {"type": "text", "text": "use b.rs instead"}
+            ]}),
+            json!({"role": "assistant", "content": "one"}),
+            json!({"role": "user", "content": "second"}),
+            json!({"role": "assistant", "content": "two"}),
+        ] {
+            session.history.push(message);
+        }
+        assert_eq!(session.turns(), vec!["first", "second"]);
+
+        assert_eq!(session.rewind(2).unwrap(), "second");
+        assert_eq!(session.messages().len(), 4);
+
+        session
+            .history
+            .push(json!({"role": "user", "content": "third"}));
+        session
+            .history
+            .push(json!({"role": "assistant", "content": "three"}));
+        let (sender, _receiver) = mpsc::unbounded_channel();
+        session.compaction.keep_turns = 2;
+        assert!(!session.compact(None, false, &sender).await.unwrap());
+
+        session.compaction.keep_turns = 1;
+        assert!(session.compact(None, false, &sender).await.unwrap());
+        let summarised = server.requests().first().unwrap().to_string();
+        assert!(summarised.contains("use b.rs instead"));
+        assert_eq!(session.messages().len(), 2);
+        assert_eq!(session.turns(), vec!["third"]);
+    }
+
+    #[tokio::test]
+    async fn test_session_environment_refreshed_each_turn() {
+        let dir = crate::testing::TempDir::new("session-environment");
+        let server = crate::api::anthropic::fake::serve(
+            vec![
+                crate::api::anthropic::fake::Reply::Text("one"),
+                crate::api::anthropic::fake::Reply::Text("two"),
+            ],
+            0,
+        )
+        .await
+        .unwrap();
+        let mut session = Session::new(server.settings.clone(), &dir.display().to_string());
+        let (sender, _receiver) = mpsc::unbounded_channel();
+
+        session.send_message("first".to_string(), &sender).await;
+        std::fs::write(dir.join("added.txt"), "").unwrap();
+        session.send_message("second".to_string(), &sender).await;
+
+        let requests = server.requests();
+        let system = |index: usize| {
+            requests
+                .get(index)
+                .unwrap()
+                .pointer("/system")
+                .unwrap()
+                .to_string()
+        };
+        assert!(!system(0).contains("added.txt"));
+        assert!(system(1).contains("added.txt"));
+    }
+
+    #[tokio::test]
+    async fn test_session_memories_refreshed_each_turn() {
+        let dir = crate::testing::TempDir::new("session-memories");
+        let server = crate::api::anthropic::fake::serve(
+            vec![
+                crate::api::anthropic::fake::Reply::Text("one"),
+                crate::api::anthropic::fake::Reply::Text("two"),
+            ],
+            0,
+        )
+        .await
+        .unwrap();
+        let mut session = Session::new(server.settings.clone(), &dir.display().to_string());
+        let store = memory::Store::open(&dir, &dir, crate::config::Memory::default());
+        session.memory = Some(store.clone());
+        let (sender, _receiver) = mpsc::unbounded_channel();
+
+        session.send_message("first".to_string(), &sender).await;
+        store
+            .save(&memory::Draft {
+                summary: "Tests need the fixtures server".to_string(),
+                content: "Run ./fixtures.sh first.".to_string(),
+                ..memory::Draft::default()
+            })
+            .unwrap();
+        session.send_message("second".to_string(), &sender).await;
+
+        let requests = server.requests();
+        let system = |index: usize| {
+            requests
+                .get(index)
+                .unwrap()
+                .pointer("/system")
+                .unwrap()
+                .to_string()
+        };
+        assert!(system(0).contains("No memories yet"));
+        assert!(system(1).contains("Tests need the fixtures server"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_session_mcp_tool_name_collision() {
+        let mut registry = crate::ProviderRegistry::global().write().await;
+        registry.register_defaults();
+        drop(registry);
+
+        let (dir, settings) = crate::mcp::fake::fixture().unwrap();
+        let config = ProviderSettings::from_env().await.unwrap();
+        let mut session = Session::new(config, &dir.display().to_string());
+        let mut servers = IndexMap::new();
+        servers.insert("fix ture".to_string(), settings.clone());
+        servers.insert("fix_ture".to_string(), settings);
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+
+        session.connect_mcp(&servers, &sender).await;
+
+        let mut connected = Vec::new();
+        let mut errors = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            match event {
+                CoreEvent::McpConnected { server, tools, .. } => connected.push((server, tools)),
+                CoreEvent::Error(message) => errors.push(message),
+                _ => {},
+            }
+        }
+        assert_eq!(
+            connected,
+            [("fix ture".to_string(), 2), ("fix_ture".to_string(), 0)]
+        );
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|e| e.contains("already taken")));
+        let echo = session
+            .schema
+            .iter()
+            .filter(|tool| tool.get("name") == Some(&json!("mcp__fix_ture__echo")))
+            .count();
+        assert_eq!(echo, 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_session_mcp_not_started_with_shared_tools() {
+        let mut registry = crate::ProviderRegistry::global().write().await;
+        registry.register_defaults();
+        drop(registry);
+
+        let (dir, settings) = crate::mcp::fake::fixture().unwrap();
+        let config = ProviderSettings::from_env().await.unwrap();
+        let mut session = Session::new(config, &dir.display().to_string());
+        let shared = Arc::new(ToolRegistry::new());
+        session.client.set_tool_registry(Arc::clone(&shared));
+        let mut servers = IndexMap::new();
+        servers.insert("fixture".to_string(), settings);
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+
+        session.connect_mcp(&servers, &sender).await;
+
+        assert!(session.mcp_servers().is_empty());
+        assert!(matches!(
+            receiver.try_recv(),
+            Ok(CoreEvent::Error(message)) if message.contains("not started")
+        ));
+        receiver.try_recv().unwrap_err();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_session_tool_search_loads_deferred_mcp_tool() {
+        let (dir, settings) = crate::mcp::fake::fixture().unwrap();
+        let server = crate::api::anthropic::fake::serve(
+            vec![
+                crate::api::anthropic::fake::Reply::ToolUse(
+                    "tool_search",
+                    json!({"query": "echo text"}),
+                ),
+                crate::api::anthropic::fake::Reply::Text("done"),
+            ],
+            0,
+        )
+        .await
+        .unwrap();
+        let config = Config {
+            cwd: dir.display().to_string(),
+            limits: Limits::default(),
+            output_limits: crate::config::OutputLimits::default(),
+            compaction: Compaction::default(),
+            pruning: crate::config::Pruning::default(),
+            redaction: crate::config::Redaction::default(),
+            prompt: crate::config::Prompt::default(),
+            instructions: crate::config::Instructions::default(),
+            environment: crate::config::Environment::default(),
+            memory: crate::config::Memory::default(),
+            memory_dir: None,
+            fork: false,
+            sessions_dir: None,
+            resume: Resume::New,
+            commands_dirs: Vec::new(),
+            skills_dirs: Vec::new(),
+            mcp_servers: IndexMap::from([("fix ture".to_string(), settings)]),
+            tool_search: crate::config::ToolSearch {
+                enabled: true,
+                ..crate::config::ToolSearch::default()
+            },
+            persona: None,
+            personas: IndexMap::new(),
+            personas_dirs: Vec::new(),
+        };
+        let mut session = Session::new(server.settings.clone(), &config.cwd)
+            .with_config(&config)
+            .unwrap();
+        let (sender, _receiver) = mpsc::unbounded_channel();
+        session.connect_mcp(&config.mcp_servers, &sender).await;
+
+        session
+            .send_message("Echo something".to_string(), &sender)
+            .await;
+
+        let offered = |index: usize| -> Vec<String> {
+            server
+                .requests()
+                .get(index)
+                .unwrap()
+                .pointer("/tools")
+                .and_then(serde_json::Value::as_array)
+                .unwrap()
+                .iter()
+                .filter_map(|tool| tool.get("name").and_then(serde_json::Value::as_str))
+                .map(str::to_string)
+                .collect()
+        };
+        let first = offered(0);
+        assert!(first.iter().any(|name| name == "read"));
+        assert!(first.iter().any(|name| name == "tool_search"));
+        assert!(!first.iter().any(|name| name.starts_with("mcp__")));
+        let second = offered(1);
+        assert!(second.iter().any(|name| name == "mcp__fix_ture__echo"));
+    }
+
+    #[tokio::test]
+    async fn test_session_slash_commands() {
+        let mut registry = crate::ProviderRegistry::global().write().await;
+        registry.register_defaults();
+        drop(registry);
+
+        let config = ProviderSettings::from_env().await.unwrap();
+        let mut session = Session::new(config, "/test");
+        let commands = session.commands();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+
+        for input in ["/xyz", "/undo all"] {
+            let should_continue = session
+                .handle_command(commands.parse(input), &sender)
+                .await
+                .unwrap();
+            assert!(should_continue);
+        }
+        assert!(matches!(
+            receiver.recv().await,
+            Some(CoreEvent::Error(error)) if error.starts_with("Unknown command /xyz")
+        ));
+        assert!(matches!(
+            receiver.recv().await,
+            Some(CoreEvent::Error(error)) if error == "Error: Usage: /undo [turns]"
+        ));
+        assert!(session.messages().is_empty());
+
+        session
+            .handle_command(commands.parse("/help"), &sender)
+            .await
+            .unwrap();
+        assert!(matches!(
+            receiver.recv().await,
+            Some(CoreEvent::Commands(list)) if list.len() == commands.list().len()
+        ));
+
+        session
+            .messages_mut()
+            .push(json!({"role": "user", "content": "hi"}));
+        session
+            .handle_command(commands.parse("/clear"), &sender)
+            .await
+            .unwrap();
+        assert!(matches!(receiver.recv().await, Some(CoreEvent::Cleared)));
+        assert!(session.messages().is_empty());
+
+        let should_continue = session
+            .handle_command(commands.parse("/q"), &sender)
+            .await
+            .unwrap();
+        assert!(!should_continue);
+    }
+
+    #[tokio::test]
+    async fn test_session_persona_changes_identity_only() {
+        let mut registry = crate::ProviderRegistry::global().write().await;
+        registry.register_defaults();
+        drop(registry);
+
+        let config = ProviderSettings::from_env().await.unwrap();
+        let mut session = Session::new(config, "/test");
+        let tools = |session: &Session| {
+            let prompt = session.system_prompt();
+            prompt
+                .get(prompt.find("# Using tools").unwrap()..)
+                .unwrap()
+                .to_string()
+        };
+        let before = tools(&session);
+
+        let event = session.set_persona(Some("neko")).unwrap();
+        assert!(matches!(
+            event,
+            CoreEvent::Persona { name: Some(ref name), greeting: Some(_) } if name == "Neco"
+        ));
+        assert!(session.system_prompt().starts_with("You are Neco,"));
+        assert_eq!(tools(&session), before);
+        assert_eq!(session.persona(), Some("neko"));
+
+        session.set_persona(Some("missing")).unwrap_err();
+        assert_eq!(session.persona(), Some("neko"));
+        session.set_persona(None).unwrap();
+        assert!(session.system_prompt().starts_with(prompt::IDENTITY));
+    }
+
+    #[tokio::test]
+    async fn test_session_switch_model() {
+        let mut registry = crate::ProviderRegistry::global().write().await;
+        registry.register_defaults();
+        drop(registry);
+
+        let config = ProviderSettings::from_env().await.unwrap();
+        let mut session = Session::new(config.clone(), "/test");
+        session
+            .history
+            .push(json!({"role": "user", "content": "plan it"}));
+        session
+            .history
+            .push(json!({"role": "assistant", "content": [
+                {"type": "thinking", "thinking": "steps", "signature": "sig"},
+                {"type": "text", "text": "plan"}
+            ]}));
+
+        let same_provider = ProviderSettings {
+            model: "cheaper".to_string(),
+            ..config.clone()
+        };
+        let event = session.switch_model(same_provider);
+        assert!(matches!(
+            event,
+            CoreEvent::ModelSwitched { model, .. } if model == "cheaper"
+        ));
+        assert_eq!(session.client().config().model, "cheaper");
+
+        let other_provider = ProviderSettings {
+            name: format!("{}-other", config.name),
+            ..config
+        };
+        let before = session.messages().to_vec();
+        session.switch_model(other_provider);
+        assert_eq!(session.messages(), before.as_slice());
+    }
+
+    #[tokio::test]
+    async fn test_session_undo_with_confirmation() {
+        let mut registry = crate::ProviderRegistry::global().write().await;
+        registry.register_defaults();
+        drop(registry);
+
+        let dir = crate::testing::TempDir::new("session-undo");
+        let file = dir.join("main.rs");
+        std::fs::write(&file, "before\n").unwrap();
+
+        let config = Config {
+            cwd: "/test".to_string(),
+            limits: Limits::default(),
+            output_limits: crate::config::OutputLimits::default(),
+            compaction: Compaction::default(),
+            pruning: crate::config::Pruning::default(),
+            redaction: crate::config::Redaction::default(),
+            prompt: crate::config::Prompt::default(),
+            instructions: crate::config::Instructions::default(),
+            environment: crate::config::Environment::default(),
+            memory: crate::config::Memory::default(),
+            memory_dir: None,
+            fork: false,
+            sessions_dir: Some(dir.join("sessions")),
+            resume: Resume::New,
+            commands_dirs: Vec::new(),
+            skills_dirs: Vec::new(),
+            mcp_servers: IndexMap::new(),
+            tool_search: crate::config::ToolSearch::default(),
+            persona: None,
+            personas: IndexMap::new(),
+            personas_dirs: Vec::new(),
+        };
+        let provider = ProviderSettings::from_env().await.unwrap();
+        let mut session = Session::new(provider, "/test")
+            .with_config(&config)
+            .unwrap();
+        let checkpoints = session.checkpoints.clone().unwrap();
+        checkpoints.begin("change main.rs");
+        checkpoints.snapshot(&file).unwrap();
+        std::fs::write(&file, "after\n").unwrap();
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let patch_path = dir.join("changes.patch");
+        session
+            .handle_command(
+                session
+                    .commands()
+                    .parse(&format!("/diff {}", patch_path.display())),
+                &sender,
+            )
+            .await
+            .unwrap();
+        let Some(CoreEvent::Diff { files, patch, .. }) = receiver.recv().await else {
+            panic!("Expected a diff");
+        };
+        assert_eq!(files.len(), 1);
+        assert!(patch.contains("-before\n+after\n"));
+        assert_eq!(std::fs::read_to_string(&patch_path).unwrap(), patch);
+
+        session
+            .handle_command(session.commands().parse("/undo"), &sender)
+            .await
+            .unwrap();
+        let Some(CoreEvent::RestorePreview {
+            checkpoint, diff, ..
+        }) = receiver.recv().await
+        else {
+            panic!("Expected a restore preview");
+        };
+        assert_eq!(checkpoint, 1);
+        assert!(diff.contains("-after\n+before\n"));
+
+        // Any other input cancels the restore
+        session
+            .handle_command(session.commands().parse("/checkpoints"), &sender)
+            .await
+            .unwrap();
+        assert!(matches!(
+            receiver.recv().await,
+            Some(CoreEvent::RestoreCancelled)
+        ));
+        assert!(matches!(
+            receiver.recv().await,
+            Some(CoreEvent::Checkpoints(list)) if list.len() == 1
+        ));
+
+        session
+            .handle_command(session.commands().parse("/undo 1"), &sender)
+            .await
+            .unwrap();
+        let _ = receiver.recv().await;
+        session
+            .handle_command(Command::Message("y".to_string()), &sender)
+            .await
+            .unwrap();
+        assert!(matches!(
+            receiver.recv().await,
+            Some(CoreEvent::Restored { checkpoint: 1, .. })
+        ));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "before\n");
+        assert!(session.checkpoints().is_empty());
+        assert!(session.messages().is_empty());
+    }
 }
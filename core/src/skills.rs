@@ -0,0 +1,279 @@
+//! Skills: packaged workflows loaded on demand.
+//!
+//! A skill is a directory holding a `SKILL.md` file and any scripts or
+//! resources it needs:
+//!
+//! ```markdown
+//! ---
+//! name: release
+//! description: Cut a release: changelog, version bump, tag
+//! allowed-tools: bash, read, edit
+//! ---
+//! 1. Run `scripts/check.sh` ...
+//! ```
+//!
+//! Only the one-line index of the skills is part of the system prompt. The
+//! body and the list of bundled files are loaded when the model calls the
+//! `skill` tool, or when the user runs the skill as `/<name>`.
+
+use crate::commands::SlashCommand;
+use crate::commands::template::{split_frontmatter, tool_list};
+use crate::events::CoreEvent;
+use crate::session::Session;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+
+/// Name of the file describing a skill.
+const SKILL_FILE: &str = "SKILL.md";
+
+/// Maximum number of bundled files listed when a skill is loaded.
+const MAX_FILES: usize = 50;
+
+/// A skill read from its `SKILL.md`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Skill {
+    /// Skill name, from the frontmatter or the directory name
+    pub name: String,
+    /// One-line description shown in the index
+    pub description: String,
+    /// Names of the tools the skill uses, or `None` for all tools
+    pub allowed_tools: Option<Vec<String>>,
+    /// Instructions of the skill
+    pub body: String,
+    /// Directory holding the skill and its bundled files
+    pub dir: PathBuf,
+}
+
+impl Skill {
+    /// Parse a skill file.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Directory of the skill; its name is the default skill name
+    /// * `text` - Contents of the `SKILL.md` file
+    ///
+    /// # Returns
+    ///
+    /// The skill, or `None` if its name is not a valid command name.
+    #[must_use]
+    pub fn parse(dir: &Path, text: &str) -> Option<Self> {
+        let mut skill = Self {
+            name: dir.file_name()?.to_str()?.to_string(),
+            description: String::new(),
+            allowed_tools: None,
+            body: text.trim().to_string(),
+            dir: dir.to_path_buf(),
+        };
+        if let Some((frontmatter, body)) = split_frontmatter(text) {
+            skill.body = body.trim().to_string();
+            for line in frontmatter.lines() {
+                let Some((key, value)) = line.split_once(':') else {
+                    continue;
+                };
+                let value = value.trim().trim_matches('"');
+                match key.trim() {
+                    "name" if !value.is_empty() => skill.name = value.to_string(),
+                    "description" => skill.description = value.to_string(),
+                    "allowed-tools" | "allowed_tools" => {
+                        skill.allowed_tools = Some(tool_list(value));
+                    },
+                    _ => {},
+                }
+            }
+        }
+        if skill.name.is_empty() || skill.name.contains(char::is_whitespace) {
+            return None;
+        }
+        if skill.description.is_empty() {
+            skill.description = format!("Skill in {}", dir.display());
+        }
+        Some(skill)
+    }
+
+    /// Render the full skill: instructions, bundled files and tools.
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut text = format!(
+            "<skill name=\"{}\">\n{}\n</skill>\n\nSkill directory: {} \
+             (paths in the skill are relative to it)",
+            self.name,
+            self.body,
+            self.dir.display()
+        );
+
+        let files = self.files();
+        if !files.is_empty() {
+            text.push_str("\nBundled files, read or run them as the skill instructs:");
+            for (path, size) in files.iter().take(MAX_FILES) {
+                let _ = write!(text, "\n- {} ({size} bytes)", path.display());
+            }
+            if files.len() > MAX_FILES {
+                let _ = write!(text, "\n... and {} more", files.len() - MAX_FILES);
+            }
+        }
+        if let Some(tools) = &self.allowed_tools {
+            let _ = write!(text, "\nTools for this skill: {}", tools.join(", "));
+        }
+        text
+    }
+
+    /// Files bundled with the skill, relative to its directory, with their sizes.
+    fn files(&self) -> Vec<(PathBuf, u64)> {
+        let mut files: Vec<(PathBuf, u64)> = walkdir::WalkDir::new(&self.dir)
+            .min_depth(1)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .filter_map(|entry| {
+                let relative = entry.path().strip_prefix(&self.dir).ok()?.to_path_buf();
+                let size = entry.metadata().ok()?.len();
+                Some((relative, size))
+            })
+            .filter(|(path, _)| path != Path::new(SKILL_FILE))
+            .collect();
+        files.sort();
+        files
+    }
+}
+
+#[async_trait]
+impl SlashCommand for Skill {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn args(&self) -> &'static str {
+        "[task]"
+    }
+
+    fn help(&self) -> &str {
+        &self.description
+    }
+
+    async fn run(
+        &self,
+        args: &str,
+        session: &mut Session,
+        event_sender: &mpsc::UnboundedSender<CoreEvent>,
+    ) -> Result<bool> {
+        let task = if args.is_empty() {
+            "Follow the skill."
+        } else {
+            args
+        };
+        let prompt = format!("{}\n\n{task}", self.render());
+        session
+            .send_message_with(prompt, self.allowed_tools.as_deref(), None, event_sender)
+            .await;
+        Ok(true)
+    }
+}
+
+/// Load the skills of the given directories.
+///
+/// Each subdirectory holding a `SKILL.md` is a skill. A skill in a later
+/// directory replaces one of the same name in an earlier directory, so
+/// project skills override user skills.
+///
+/// # Arguments
+///
+/// * `dirs` - Skills directories, lowest precedence first
+#[must_use]
+pub fn load(dirs: &[PathBuf]) -> Vec<Skill> {
+    let mut skills: Vec<Skill> = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.join(SKILL_FILE).is_file())
+            .collect();
+        paths.sort();
+        for path in paths {
+            let Some(skill) = std::fs::read_to_string(path.join(SKILL_FILE))
+                .ok()
+                .and_then(|text| Skill::parse(&path, &text))
+            else {
+                tracing::warn!(path = %path.display(), "Skipping invalid skill");
+                continue;
+            };
+            skills.retain(|loaded| loaded.name != skill.name);
+            skills.push(skill);
+        }
+    }
+    skills
+}
+
+/// Render the `skills` section of the system prompt: one line per skill.
+///
+/// # Returns
+///
+/// The section text, empty if there are no skills.
+#[must_use]
+pub fn index(skills: &[Skill]) -> String {
+    if skills.is_empty() {
+        return String::new();
+    }
+    let mut text = "# Skills\n\
+                    Before a task one of these skills covers, load it with the skill tool \
+                    and follow it."
+        .to_string();
+    for skill in skills {
+        let _ = write!(text, "\n- {}: {}", skill.name, skill.description);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_index_and_render() {
+        let root = crate::testing::TempDir::new("skills");
+        let user = root.join("user");
+        let project = root.join("project");
+        let release = project.join("release");
+        std::fs::create_dir_all(user.join("release")).unwrap();
+        std::fs::create_dir_all(user.join("notes")).unwrap();
+        std::fs::create_dir_all(release.join("scripts")).unwrap();
+        std::fs::create_dir_all(project.join("empty")).unwrap();
+        std::fs::write(user.join("release").join(SKILL_FILE), "Old release steps").unwrap();
+        std::fs::write(user.join("notes").join(SKILL_FILE), "Write notes").unwrap();
+        std::fs::write(
+            release.join(SKILL_FILE),
+            "---\nname: release\ndescription: Cut a release\nallowed-tools: bash, read\n---\nRun scripts/check.sh first.",
+        )
+        .unwrap();
+        std::fs::write(release.join("scripts").join("check.sh"), "exit 0").unwrap();
+
+        let skills = load(&[user.clone(), project]);
+        let names: Vec<&str> = skills.iter().map(|skill| skill.name.as_str()).collect();
+        assert_eq!(names, ["notes", "release"]);
+
+        let index = index(&skills);
+        assert!(index.contains("- release: Cut a release"));
+        assert!(index.contains(&format!(
+            "- notes: Skill in {}",
+            user.join("notes").display()
+        )));
+        assert!(!index.contains("scripts/check.sh"));
+
+        let release = skills.iter().find(|skill| skill.name == "release").unwrap();
+        assert_eq!(
+            release.allowed_tools,
+            Some(vec!["bash".to_string(), "read".to_string()])
+        );
+        let text = release.render();
+        assert!(
+            text.starts_with("<skill name=\"release\">\nRun scripts/check.sh first.\n</skill>")
+        );
+        assert!(text.contains("- scripts/check.sh (6 bytes)"));
+        assert!(!text.contains("- SKILL.md"));
+        assert!(text.ends_with("Tools for this skill: bash, read"));
+    }
+}
@@ -0,0 +1,111 @@
+//! Mid-turn steering of the agent loop.
+//!
+//! While the agent loop is running, user input is queued here instead of
+//! becoming a separate turn. The loop delivers queued messages together
+//! with the next batch of tool results, so the user can redirect the model
+//! ("stop, use the other file") without cancelling the turn.
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Shared state of the steering queue.
+#[derive(Debug, Default)]
+struct State {
+    /// Whether a turn is running and accepts steering messages
+    active: bool,
+    /// Messages waiting to be delivered, oldest first
+    queue: Vec<String>,
+}
+
+/// Queue of user messages typed while the agent loop runs.
+///
+/// Cloning is cheap; all clones share the same queue.
+#[derive(Debug, Clone, Default)]
+pub struct Steering {
+    /// Shared queue state
+    state: Arc<Mutex<State>>,
+}
+
+impl Steering {
+    /// Create an inactive, empty steering queue.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run a closure with the locked state.
+    fn with_state<T>(&self, f: impl FnOnce(&mut State) -> T) -> T {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        f(&mut state)
+    }
+
+    /// Mark the start of a turn; messages are accepted from now on.
+    pub fn begin(&self) {
+        self.with_state(|state| state.active = true);
+    }
+
+    /// Queue a message if a turn is running.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the message was queued, `false` if no turn is running and
+    /// the caller should handle it as a regular input instead.
+    #[must_use]
+    pub fn offer(&self, text: &str) -> bool {
+        self.with_state(|state| {
+            if state.active {
+                state.queue.push(text.to_string());
+            }
+            state.active
+        })
+    }
+
+    /// Take all queued messages for delivery.
+    #[must_use]
+    pub fn take(&self) -> Vec<String> {
+        self.with_state(|state| std::mem::take(&mut state.queue))
+    }
+
+    /// Mark the end of a turn and return any messages that were not delivered.
+    #[must_use]
+    pub fn finish(&self) -> Vec<String> {
+        self.with_state(|state| {
+            state.active = false;
+            std::mem::take(&mut state.queue)
+        })
+    }
+
+    /// Whether a turn is currently running.
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        self.with_state(|state| state.active)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offer_requires_active_turn() {
+        let steering = Steering::new();
+        assert!(!steering.is_active());
+        assert!(!steering.offer("ignored"));
+        assert!(steering.take().is_empty());
+    }
+
+    #[test]
+    fn test_take_and_finish() {
+        let steering = Steering::new();
+        let input_side = steering.clone();
+
+        steering.begin();
+        assert!(input_side.offer("use the other file"));
+        assert_eq!(steering.take(), vec!["use the other file".to_string()]);
+        assert!(steering.take().is_empty());
+
+        assert!(input_side.offer("and run the tests"));
+        assert_eq!(steering.finish(), vec!["and run the tests".to_string()]);
+        assert!(!steering.is_active());
+        assert!(!input_side.offer("too late"));
+    }
+}
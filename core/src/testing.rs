@@ -0,0 +1,44 @@
+//! Fixtures shared by the unit tests.
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// A fresh temporary directory, removed with its contents on drop.
+pub struct TempDir {
+    /// Path of the directory
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Create an empty directory named after the test.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the directory cannot be created.
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "neco-{name}-{}-{}",
+            std::process::id(),
+            crate::clock::new_id()
+        ));
+        if let Err(e) = std::fs::create_dir_all(&path) {
+            panic!("Failed to create {}: {e}", path.display());
+        }
+        Self { path }
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
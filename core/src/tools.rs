@@ -1,15 +1,24 @@
 //! Tool implementations for nanocode.
 //!
-//! Provides six async tools: read, write, edit, glob, grep, bash.
+//! Provides six async tools: read, write, edit, glob, grep, bash, plus the
+//! memory tool, registered when memory is enabled, the skill tool,
+//! registered when there are skills, and the tool search tool, registered
+//! when tools are loaded lazily.
 //!
 //! This module defines the tool abstraction layer including:
 //! - Tool trait for uniform tool interface
 //! - `ToolRegistry` for centralized tool management
+//! - Output size limits with spill-to-file for oversized results
+//! - File checkpoints taken before mutating tool calls
 
-use anyhow::Result;
+use crate::checkpoint;
+use crate::config::OutputLimits;
+use anyhow::{Context, Result};
 use indexmap::IndexMap;
 use serde_json::{Value, json};
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
 
 use async_trait::async_trait;
 
@@ -17,14 +26,23 @@ pub mod bash;
 pub mod edit;
 pub mod glob;
 pub mod grep;
+pub mod memory;
 pub mod read;
+pub mod resources;
+pub mod search;
+pub mod skill;
+pub mod truncate;
 pub mod write;
 
 pub use bash::{Bash, bash};
 pub use edit::{Edit, edit};
 pub use glob::{Glob, glob};
 pub use grep::{Grep, grep};
+pub use memory::Memory;
 pub use read::{Read, read};
+pub use resources::Resources;
+pub use search::Search;
+pub use skill::Skill;
 pub use write::{Write, write};
 
 /// Tool trait defining the interface for all tools.
@@ -52,6 +70,41 @@ pub trait Tool: Send + Sync {
     ///
     /// The result of the tool execution as a string, or an error.
     async fn execute(&self, input: &Value) -> Result<String>;
+
+    /// Returns the files the tool would change for the given input.
+    ///
+    /// Their contents are checkpointed before the tool runs. Tools that do
+    /// not change files, or whose changes cannot be known in advance, return
+    /// no paths.
+    fn affected_paths(&self, input: &Value) -> Vec<PathBuf> {
+        let _ = input;
+        Vec::new()
+    }
+    /// Whether the tool may change files that [`Tool::affected_paths`] cannot name.
+    ///
+    /// Such changes are not checkpointed; checkpoints record that the tool
+    /// ran so undo and diff can warn about it.
+    fn changes_unknown_files(&self) -> bool {
+        false
+    }
+}
+
+/// Resolve the `path` input of a file tool against the working directory.
+///
+/// # Returns
+///
+/// The absolute path, or no path if the input has none.
+pub(crate) fn input_path(input: &Value) -> Vec<PathBuf> {
+    let Some(path) = input.get("path").and_then(Value::as_str) else {
+        return Vec::new();
+    };
+    let path = Path::new(path);
+    if path.is_absolute() {
+        return vec![path.to_path_buf()];
+    }
+    std::env::current_dir()
+        .map(|cwd| vec![cwd.join(path)])
+        .unwrap_or_default()
 }
 
 /// Tool registry for managing and executing tools.
@@ -61,6 +114,18 @@ pub trait Tool: Send + Sync {
 pub struct ToolRegistry {
     /// Registered tools indexed by name
     tools: IndexMap<String, Arc<dyn Tool>>,
+    /// Output size limits applied to tool results
+    output_limits: OutputLimits,
+    /// Directory where full oversized outputs are saved
+    scratch_dir: Option<PathBuf>,
+    /// Whether the scratch directory is removed when the registry is dropped
+    temporary_scratch: bool,
+    /// Counter used to name scratch files
+    spill_count: AtomicUsize,
+    /// Store receiving file snapshots before mutating tool calls
+    checkpoints: Option<checkpoint::Store>,
+    /// Names of the tools that may run, or `None` for all tools
+    allowed: Mutex<Option<Vec<String>>>,
 }
 
 impl ToolRegistry {
@@ -69,11 +134,54 @@ impl ToolRegistry {
     pub fn new() -> Self {
         let mut registry = Self {
             tools: IndexMap::new(),
+            output_limits: OutputLimits::default(),
+            scratch_dir: None,
+            temporary_scratch: false,
+            spill_count: AtomicUsize::new(0),
+            checkpoints: None,
+            allowed: Mutex::new(None),
         };
         registry.register_all();
         registry
     }
 
+    /// Set the output size limits applied to tool results.
+    pub fn set_output_limits(&mut self, limits: OutputLimits) {
+        self.output_limits = limits;
+    }
+
+    /// Set the directory where full oversized outputs are saved.
+    ///
+    /// Without a scratch directory, oversized output is still truncated
+    /// but the full output is not kept.
+    pub fn set_scratch_dir(&mut self, dir: PathBuf) {
+        self.scratch_dir = Some(dir);
+        self.temporary_scratch = false;
+    }
+
+    /// Set a scratch directory that is removed when the registry is dropped.
+    ///
+    /// Used when the session is not persisted, so nothing refers to the
+    /// saved outputs once it ends.
+    pub fn set_temporary_scratch_dir(&mut self, dir: PathBuf) {
+        self.scratch_dir = Some(dir);
+        self.temporary_scratch = true;
+    }
+
+    /// Set the store receiving file snapshots before mutating tool calls.
+    pub fn set_checkpoints(&mut self, checkpoints: checkpoint::Store) {
+        self.checkpoints = Some(checkpoints);
+    }
+
+    /// Restrict the tools that may run, e.g. to those a command allows.
+    ///
+    /// # Arguments
+    ///
+    /// * `tools` - Names of the tools that may run, or `None` for all tools
+    pub fn set_allowed(&self, tools: Option<&[String]>) {
+        *self.allowed.lock().unwrap_or_else(PoisonError::into_inner) = tools.map(<[_]>::to_vec);
+    }
+
     /// Register all default tools.
     fn register_all(&mut self) {
         self.register(Arc::new(Read));
@@ -84,6 +192,12 @@ impl ToolRegistry {
         self.register(Arc::new(Bash));
     }
 
+    /// Whether a tool of the given name is registered.
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.tools.contains_key(name)
+    }
+
     /// Register a tool with the registry.
     pub fn register(&mut self, tool: Arc<dyn Tool>) {
         self.tools.insert(tool.name().to_string(), tool);
@@ -91,6 +205,12 @@ impl ToolRegistry {
 
     /// Execute a tool by name with the given input.
     ///
+    /// Files the tool is about to change are checkpointed first, and their
+    /// new content is recorded afterwards; the tool does not run if a
+    /// checkpoint fails. Output exceeding the tool's size
+    /// limits is truncated to its head and tail; the full output is saved to
+    /// the scratch directory and its path is included in the result.
+    ///
     /// # Arguments
     ///
     /// * `name` - The name of the tool to execute
@@ -104,13 +224,101 @@ impl ToolRegistry {
     ///
     /// Returns error if:
     /// - Tool not found
+    /// - Tool not allowed (see [`Self::set_allowed`])
+    /// - A file the tool would change cannot be checkpointed
     /// - Tool execution fails
     pub async fn execute(&self, name: &str, input: &Value) -> Result<String> {
-        self.tools
+        let tool = self
+            .tools
             .get(name)
-            .ok_or_else(|| anyhow::anyhow!("Unknown tool: {name}"))?
-            .execute(input)
-            .await
+            .ok_or_else(|| anyhow::anyhow!("Unknown tool: {name}"))?;
+        if let Some(allowed) = &*self.allowed.lock().unwrap_or_else(PoisonError::into_inner)
+            && !allowed.iter().any(|allowed| allowed == name)
+        {
+            anyhow::bail!("Tool {name} is not allowed for this command");
+        }
+
+        let affected = self
+            .checkpoints
+            .as_ref()
+            .map(|_| tool.affected_paths(input))
+            .unwrap_or_default();
+        if let Some(checkpoints) = &self.checkpoints {
+            for path in &affected {
+                checkpoints.snapshot(path).with_context(|| {
+                    format!(
+                        "Failed to checkpoint {}, so it was not changed",
+                        path.display()
+                    )
+                })?;
+            }
+        }
+
+        if tool.changes_unknown_files()
+            && let Some(checkpoints) = &self.checkpoints
+            && let Err(e) = checkpoints.record_untracked(name)
+        {
+            tracing::warn!(tool = name, "Failed to record an untracked change: {e:#}");
+        }
+
+        let result = tool.execute(input).await;
+
+        if let Some(checkpoints) = &self.checkpoints {
+            for path in &affected {
+                if let Err(e) = checkpoints.record(path) {
+                    tracing::warn!(tool = name, "Failed to record {}: {e:#}", path.display());
+                }
+            }
+        }
+
+        let output = result?;
+
+        Ok(self.limit_output(name, output).await)
+    }
+
+    /// Apply output size limits, spilling oversized output to a scratch file.
+    async fn limit_output(&self, name: &str, output: String) -> String {
+        let (max_lines, max_bytes) = self.output_limits.for_tool(name);
+        let Some(truncated) = truncate::head_tail(&output, max_lines, max_bytes) else {
+            return output;
+        };
+
+        let saved = match self.spill(name, &output).await {
+            Ok(Some(path)) => format!(
+                "Full output ({} lines, {} bytes) saved to {}; use the read tool with offset/limit to inspect it.",
+                output.lines().count(),
+                output.len(),
+                path.display()
+            ),
+            Ok(None) => "Full output was not saved.".to_string(),
+            Err(e) => {
+                tracing::warn!(tool = name, "Failed to save full tool output: {e}");
+                "Full output could not be saved.".to_string()
+            },
+        };
+
+        format!(
+            "{}\n\n... [{} lines ({} bytes) omitted. {saved}] ...\n\n{}",
+            truncated.head, truncated.omitted_lines, truncated.omitted_bytes, truncated.tail
+        )
+    }
+
+    /// Save full output to a new scratch file.
+    ///
+    /// # Returns
+    ///
+    /// The path of the scratch file, or `None` if no scratch directory is set.
+    async fn spill(&self, name: &str, output: &str) -> Result<Option<PathBuf>> {
+        let Some(dir) = &self.scratch_dir else {
+            return Ok(None);
+        };
+
+        tokio::fs::create_dir_all(dir).await?;
+        let index = self.spill_count.fetch_add(1, Ordering::Relaxed) + 1;
+        let path = dir.join(format!("{name}-{index}.txt"));
+        tokio::fs::write(&path, output).await?;
+
+        Ok(Some(path))
     }
 
     /// Get all tool definitions for API requests.
@@ -122,19 +330,224 @@ impl ToolRegistry {
     pub fn tool_definitions(&self) -> Vec<Value> {
         self.tools
             .values()
-            .map(|tool| {
-                json!({
-                    "name": tool.name(),
-                    "description": tool.description(),
-                    "input_schema": tool.input_schema()
-                })
-            })
+            .map(|tool| definition(tool.as_ref()))
             .collect()
     }
 }
 
+/// Tool definition for API requests.
+///
+/// # Arguments
+///
+/// * `tool` - The tool to describe
+#[must_use]
+pub fn definition(tool: &dyn Tool) -> Value {
+    json!({
+        "name": tool.name(),
+        "description": tool.description(),
+        "input_schema": tool.input_schema()
+    })
+}
+
+impl Drop for ToolRegistry {
+    fn drop(&mut self) {
+        if self.temporary_scratch
+            && let Some(dir) = &self.scratch_dir
+            && let Err(e) = std::fs::remove_dir_all(dir)
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            tracing::warn!("Failed to remove {}: {e}", dir.display());
+        }
+    }
+}
+
 impl Default for ToolRegistry {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::OutputLimit;
+
+    /// Tool producing a fixed number of numbered lines.
+    struct Lines;
+
+    #[async_trait]
+    impl Tool for Lines {
+        fn name(&self) -> &'static str {
+            "lines"
+        }
+
+        fn description(&self) -> &'static str {
+            "Print numbered lines"
+        }
+
+        fn input_schema(&self) -> Value {
+            json!({"type": "object"})
+        }
+
+        async fn execute(&self, input: &Value) -> Result<String> {
+            let count = input.get("count").and_then(Value::as_u64).unwrap_or(0);
+            Ok((1..=count)
+                .map(|i| format!("line {i}"))
+                .collect::<Vec<_>>()
+                .join("\n"))
+        }
+    }
+
+    fn registry(scratch_dir: Option<PathBuf>) -> ToolRegistry {
+        let mut registry = ToolRegistry::new();
+        registry.register(Arc::new(Lines));
+        let mut limits = OutputLimits::default();
+        limits.tools.insert(
+            "lines".to_string(),
+            OutputLimit {
+                max_lines: Some(10),
+                max_bytes: None,
+            },
+        );
+        registry.set_output_limits(limits);
+        if let Some(dir) = scratch_dir {
+            registry.set_scratch_dir(dir);
+        }
+        registry
+    }
+
+    #[tokio::test]
+    async fn test_output_within_limits() {
+        let registry = registry(None);
+        let output = registry
+            .execute("lines", &json!({"count": 10}))
+            .await
+            .unwrap();
+        assert_eq!(output.lines().count(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_output_spilled_to_scratch_file() {
+        let dir = crate::testing::TempDir::new("tools-spill");
+        let registry = registry(Some(dir.to_path_buf()));
+
+        let output = registry
+            .execute("lines", &json!({"count": 100}))
+            .await
+            .unwrap();
+
+        assert!(output.starts_with("line 1\n"));
+        assert!(output.ends_with("line 100"));
+        assert!(output.contains("90 lines"));
+        let path = dir.join("lines-1.txt");
+        assert!(output.contains(&path.display().to_string()));
+
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(saved.lines().count(), 100);
+    }
+
+    #[tokio::test]
+    async fn test_temporary_scratch_dir_removed_on_drop() {
+        let root = crate::testing::TempDir::new("tools-scratch");
+        let dir = root.join("scratch");
+        let mut registry = registry(None);
+        registry.set_temporary_scratch_dir(dir.clone());
+        registry
+            .execute("lines", &json!({"count": 100}))
+            .await
+            .unwrap();
+        assert!(dir.join("lines-1.txt").exists());
+
+        drop(registry);
+        assert!(!dir.exists());
+    }
+
+    #[tokio::test]
+    async fn test_output_truncated_without_scratch_dir() {
+        let registry = registry(None);
+        let output = registry
+            .execute("lines", &json!({"count": 100}))
+            .await
+            .unwrap();
+        assert!(output.contains("Full output was not saved."));
+    }
+
+    #[tokio::test]
+    async fn test_only_allowed_tools_run() {
+        let registry = registry(None);
+        registry.set_allowed(Some(&["read".to_string()]));
+        let error = registry
+            .execute("lines", &json!({"count": 1}))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("not allowed"));
+
+        registry.set_allowed(None);
+        registry
+            .execute("lines", &json!({"count": 1}))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_write_is_checkpointed() {
+        let dir = crate::testing::TempDir::new("tools-checkpoint");
+        let file = dir.join("file.txt");
+        std::fs::write(&file, "before").unwrap();
+
+        let store = checkpoint::Store::open(&dir.join("checkpoints"));
+        store.begin("change the file");
+        let mut registry = registry(None);
+        registry.set_checkpoints(store.clone());
+        registry
+            .execute(
+                "write",
+                &json!({"path": file.display().to_string(), "content": "after"}),
+            )
+            .await
+            .unwrap();
+        registry
+            .execute("lines", &json!({"count": 1}))
+            .await
+            .unwrap();
+        registry
+            .execute("bash", &json!({"cmd": "true"}))
+            .await
+            .unwrap();
+        assert_eq!(store.untracked(None), vec!["bash".to_string()]);
+
+        let list = store.list();
+        assert_eq!(list.len(), 1);
+        assert_eq!(
+            list.first().map(|c| c.files.clone()),
+            Some(vec![file.display().to_string()])
+        );
+        let changes = store.changes(None).unwrap();
+        assert!(checkpoint::patch(&changes, &dir).contains("-before\n+after\n"));
+        store.restore(&store.plan(1).unwrap()).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "before");
+    }
+
+    #[tokio::test]
+    async fn test_write_fails_when_checkpoint_fails() {
+        let dir = crate::testing::TempDir::new("tools-checkpoint-fail");
+        let file = dir.join("file.txt");
+        std::fs::write(&file, "before").unwrap();
+        // A file where the store expects its directory
+        std::fs::write(dir.join("checkpoints"), "").unwrap();
+
+        let store = checkpoint::Store::open(&dir.join("checkpoints"));
+        store.begin("change the file");
+        let mut registry = registry(None);
+        registry.set_checkpoints(store);
+        let error = registry
+            .execute(
+                "write",
+                &json!({"path": file.display().to_string(), "content": "after"}),
+            )
+            .await
+            .unwrap_err();
+        assert!(format!("{error:#}").contains("Failed to checkpoint"));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "before");
+    }
+}
@@ -34,6 +34,7 @@ pub async fn bash(cmd: &str) -> Result<String> {
         .arg(cmd)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
+        .kill_on_drop(true)
         .spawn()
         .with_context(|| format!("Failed to execute command: {cmd}"))?;
 
@@ -96,6 +97,7 @@ pub async fn bash(cmd: &str) -> Result<String> {
         .args(["/C", cmd])
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
+        .kill_on_drop(true)
         .spawn()
         .with_context(|| format!("Failed to execute command: {cmd}"))?;
 
@@ -169,4 +171,8 @@ impl Tool for Bash {
             .ok_or_else(|| anyhow::anyhow!("Missing cmd"))?;
         bash(cmd).await
     }
+
+    fn changes_unknown_files(&self) -> bool {
+        true
+    }
 }
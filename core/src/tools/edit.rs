@@ -3,9 +3,10 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use serde_json::Value;
+use std::path::PathBuf;
 use tokio::fs;
 
-use crate::tools::Tool;
+use crate::tools::{Tool, input_path};
 
 /// Edit file by replacing old string with new string.
 ///
@@ -110,4 +111,8 @@ impl Tool for Edit {
         let all = input.get("all").and_then(serde_json::Value::as_bool);
         edit(path, old, new, all).await
     }
+
+    fn affected_paths(&self, input: &Value) -> Vec<PathBuf> {
+        input_path(input)
+    }
 }
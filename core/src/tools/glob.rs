@@ -51,7 +51,7 @@ pub fn glob(pat: &str, path: Option<&str>) -> Result<String> {
         }
     }
 
-    files.sort_by(|a, b| b.1.cmp(&a.1));
+    files.sort_by_key(|(_, mtime)| std::cmp::Reverse(*mtime));
 
     if files.is_empty() {
         Ok("none".to_string())
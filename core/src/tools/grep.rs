@@ -6,6 +6,9 @@ use serde_json::Value;
 
 use crate::tools::Tool;
 
+/// Maximum number of matches returned by a search.
+const MAX_MATCHES: usize = 50;
+
 /// Search files for regex pattern matches.
 ///
 /// # Arguments
@@ -15,7 +18,8 @@ use crate::tools::Tool;
 ///
 /// # Returns
 ///
-/// Newline-separated matches in format "path:line:content", up to 50 matches
+/// Newline-separated matches in format "path:line:content", up to 50 matches,
+/// followed by a note with the number of omitted matches if there were more
 ///
 /// # Errors
 ///
@@ -54,10 +58,18 @@ pub async fn grep(pat: &str, path: Option<&str>) -> Result<String> {
         }
 
         if hits.is_empty() {
-            Ok("none".to_string())
-        } else {
-            Ok(hits.iter().take(50).cloned().collect::<Vec<_>>().join("\n"))
+            return Ok("none".to_string());
+        }
+
+        let total = hits.len();
+        hits.truncate(MAX_MATCHES);
+        if total > MAX_MATCHES {
+            hits.push(format!(
+                "... {} more matches omitted ({total} total); narrow the pattern or path to see them",
+                total - MAX_MATCHES
+            ));
         }
+        Ok(hits.join("\n"))
     })
     .await
     .context("Task join error")?
@@ -102,3 +114,26 @@ impl Tool for Grep {
         grep(pat, path).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_grep_reports_omitted_matches() {
+        let dir = crate::testing::TempDir::new("grep");
+        let content = (0..60)
+            .map(|i| format!("needle {i}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        std::fs::write(dir.join("hay.txt"), content).unwrap();
+
+        let result = grep("needle", dir.to_str()).await.unwrap();
+
+        assert_eq!(
+            result.lines().filter(|l| l.contains("hay.txt:")).count(),
+            50
+        );
+        assert!(result.contains("10 more matches omitted (60 total)"));
+    }
+}
@@ -0,0 +1,192 @@
+//! Persistent memory tool.
+
+use crate::clock;
+use crate::memory::{Draft, Scope, Store, Summary};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use crate::tools::Tool;
+
+/// Tool saving, searching, reading and forgetting memories.
+pub struct Memory {
+    /// Memories of the session's project
+    store: Store,
+}
+
+impl Memory {
+    /// Create the memory tool over a store.
+    #[must_use]
+    pub const fn new(store: Store) -> Self {
+        Self { store }
+    }
+
+    /// Save a memory from the tool input.
+    fn save(&self, input: &Value) -> Result<String> {
+        let text = |key: &str| {
+            input
+                .get(key)
+                .and_then(Value::as_str)
+                .with_context(|| format!("Missing {key}"))
+        };
+        let scope = match input.get("scope").and_then(Value::as_str) {
+            None | Some("project") => Scope::Project,
+            Some("global") => Scope::Global,
+            Some(other) => anyhow::bail!("Unknown scope '{other}' (project or global)"),
+        };
+        let depends_on = input
+            .get("depends_on")
+            .and_then(Value::as_array)
+            .map(|paths| {
+                paths
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(PathBuf::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let id = self.store.save(&Draft {
+            summary: text("summary")?.to_string(),
+            content: text("content")?.to_string(),
+            scope,
+            depends_on,
+            pin_commit: input
+                .get("pin_commit")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+        })?;
+        Ok(format!("Saved memory {id}"))
+    }
+
+    /// Read a memory with its metadata.
+    fn read(&self, id: &str) -> Result<String> {
+        let (memory, stale) = self.store.read(id)?;
+        let scope = match memory.scope() {
+            Scope::Project => "project",
+            Scope::Global => "global",
+        };
+        let mut text = format!(
+            "# {}\nid: {}, scope: {scope}, saved: {}",
+            memory.summary,
+            memory.id,
+            clock::format_date(memory.created)
+        );
+        if let Some(reason) = stale {
+            let _ = write!(
+                text,
+                "\nStale: {reason}. Check it against the code before relying on it, \
+                 then save an updated memory and forget this one."
+            );
+        }
+        let _ = write!(text, "\n\n{}", memory.content);
+        Ok(text)
+    }
+}
+
+/// Render memory summaries as a list.
+fn list(summaries: &[Summary]) -> String {
+    let mut text = String::new();
+    for summary in summaries {
+        let _ = write!(text, "- [{}] {}", summary.id, summary.summary);
+        if summary.scope == Scope::Global {
+            text.push_str(" (global)");
+        }
+        if let Some(reason) = &summary.stale {
+            let _ = write!(text, " (stale: {reason})");
+        }
+        text.push('\n');
+    }
+    text.trim_end().to_string()
+}
+
+#[async_trait]
+impl Tool for Memory {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn description(&self) -> &'static str {
+        "Persistent memory shared between sessions. Summaries of saved memories are listed in the system prompt; use action=read to get the full content of one. Use action=save for knowledge worth keeping beyond this session: user preferences, project conventions, decisions and their reasons, pitfalls discovered. Give a one-line summary and the full content. Scope is 'project' (default, only this repository) or 'global'. List files the memory describes in depends_on, or set pin_commit, so it is flagged as stale when they change. Use action=search to find memories by words, and action=forget to delete wrong or outdated ones."
+    }
+
+    fn input_schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "action": {
+                    "type": "string",
+                    "enum": ["save", "search", "read", "forget"],
+                    "description": "What to do"
+                },
+                "summary": {
+                    "type": "string",
+                    "description": "save: one-line summary, always visible in later sessions"
+                },
+                "content": {
+                    "type": "string",
+                    "description": "save: full content of the memory"
+                },
+                "scope": {
+                    "type": "string",
+                    "enum": ["project", "global"],
+                    "description": "save: where the memory applies (default: project)"
+                },
+                "depends_on": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "save: files the memory describes; it becomes stale when they change"
+                },
+                "pin_commit": {
+                    "type": "boolean",
+                    "description": "save: the memory becomes stale when another commit is checked out"
+                },
+                "query": {
+                    "type": "string",
+                    "description": "search: words the memory must all contain"
+                },
+                "id": {
+                    "type": "string",
+                    "description": "read, forget: id of the memory"
+                }
+            },
+            "required": ["action"]
+        })
+    }
+
+    async fn execute(&self, input: &Value) -> Result<String> {
+        let action = input
+            .get("action")
+            .and_then(Value::as_str)
+            .context("Missing action")?;
+        let id = || {
+            input
+                .get("id")
+                .and_then(Value::as_str)
+                .context("Missing id")
+        };
+        match action {
+            "save" => self.save(input),
+            "search" => {
+                let query = input
+                    .get("query")
+                    .and_then(Value::as_str)
+                    .context("Missing query")?;
+                let found = self.store.search(query)?;
+                if found.is_empty() {
+                    Ok(format!("No memories match '{query}'"))
+                } else {
+                    Ok(list(&found))
+                }
+            },
+            "read" => self.read(id()?),
+            "forget" => {
+                let memory = self.store.forget(id()?)?;
+                Ok(format!("Forgot memory {}: {}", memory.id, memory.summary))
+            },
+            other => anyhow::bail!("Unknown action '{other}' (save, search, read or forget)"),
+        }
+    }
+}
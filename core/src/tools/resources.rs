@@ -0,0 +1,119 @@
+//! MCP resources tool.
+
+use crate::mcp::{self, resources};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::fmt::Write as _;
+use std::sync::Arc;
+
+use crate::tools::Tool;
+
+/// Tool listing and reading the resources of MCP servers.
+pub struct Resources {
+    /// Connected servers offering resources
+    servers: Vec<Arc<mcp::Server>>,
+}
+
+impl Resources {
+    /// Create the resources tool over the servers offering resources.
+    #[must_use]
+    pub const fn new(servers: Vec<Arc<mcp::Server>>) -> Self {
+        Self { servers }
+    }
+
+    /// Find a server by name.
+    fn server(&self, name: &str) -> Result<&Arc<mcp::Server>> {
+        self.servers
+            .iter()
+            .find(|server| server.name() == name)
+            .with_context(|| {
+                let names: Vec<&str> = self.servers.iter().map(|server| server.name()).collect();
+                format!(
+                    "No MCP server '{name}' with resources (servers: {})",
+                    names.join(", ")
+                )
+            })
+    }
+
+    /// List the resources of one server, or of all servers.
+    async fn list(&self, server: Option<&str>) -> Result<String> {
+        let servers = match server {
+            Some(name) => vec![self.server(name)?],
+            None => self.servers.iter().collect(),
+        };
+        let mut text = String::new();
+        for server in servers {
+            for resource in server.list_resources().await? {
+                let _ = write!(text, "- @{}:{}", server.name(), resource.uri);
+                if !resource.name.is_empty() {
+                    let _ = write!(text, " ({})", resource.name);
+                }
+                if !resource.description.is_empty() {
+                    let _ = write!(text, ": {}", resource.description);
+                }
+                if let Some(mime_type) = &resource.mime_type {
+                    let _ = write!(text, " [{mime_type}]");
+                }
+                text.push('\n');
+            }
+        }
+        if text.is_empty() {
+            return Ok("No resources".to_string());
+        }
+        Ok(text.trim_end().to_string())
+    }
+}
+
+#[async_trait]
+impl Tool for Resources {
+    fn name(&self) -> &'static str {
+        "mcp_resources"
+    }
+
+    fn description(&self) -> &'static str {
+        "List and read the resources of connected MCP servers: documents, records, files and other context they expose. Use action=list to see the resources, optionally of one server, then action=read with the server and the URI of a resource to get its contents. Resources are listed as @server:uri, the form users reference them by."
+    }
+
+    fn input_schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "action": {
+                    "type": "string",
+                    "enum": ["list", "read"],
+                    "description": "What to do"
+                },
+                "server": {
+                    "type": "string",
+                    "description": "Name of the MCP server (list: optional, read: required)"
+                },
+                "uri": {
+                    "type": "string",
+                    "description": "read: URI of the resource"
+                }
+            },
+            "required": ["action"]
+        })
+    }
+
+    async fn execute(&self, input: &Value) -> Result<String> {
+        let action = input
+            .get("action")
+            .and_then(Value::as_str)
+            .context("Missing action")?;
+        let server = input.get("server").and_then(Value::as_str);
+        match action {
+            "list" => self.list(server).await,
+            "read" => {
+                let server = self.server(server.context("Missing server")?)?;
+                let uri = input
+                    .get("uri")
+                    .and_then(Value::as_str)
+                    .context("Missing uri")?;
+                Ok(resources::text(&server.read_resource(uri).await?))
+            },
+            other => anyhow::bail!("Unknown action '{other}' (list or read)"),
+        }
+    }
+}
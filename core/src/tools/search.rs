@@ -0,0 +1,320 @@
+//! Lazy loading of tools through the tool search tool.
+//!
+//! Deferred tools are left out of requests until the model finds them with
+//! `tool_search`, which searches their names and descriptions and activates
+//! the best matches. Activated tools are offered from the next request on.
+
+use crate::config;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use glob::Pattern;
+use indexmap::IndexSet;
+use serde_json::Value;
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::tools::Tool;
+
+/// Name of the tool search tool, which is never deferred.
+const NAME: &str = "tool_search";
+
+/// A deferred tool, as last offered.
+struct Entry {
+    /// Name of the tool
+    name: String,
+    /// Description of the tool
+    description: String,
+}
+
+/// Deferred tools and the tools activated by searches.
+struct State {
+    /// Deferred tools of the last request
+    deferred: Vec<Entry>,
+    /// Names of the activated tools, in activation order
+    active: IndexSet<String>,
+}
+
+/// Catalog deciding which tools are offered with a request.
+///
+/// Clones share the activated tools, so the session, the client and the
+/// tool search tool see the same catalog.
+#[derive(Clone)]
+pub struct Catalog {
+    /// Tools offered with every request
+    always_loaded: Arc<Vec<Pattern>>,
+    /// Tools offered only once activated
+    deferred: Arc<Vec<Pattern>>,
+    /// Maximum number of tools activated by one search
+    max_results: usize,
+    /// Deferred and activated tools
+    state: Arc<Mutex<State>>,
+}
+
+impl Catalog {
+    /// Create the catalog of the configured patterns.
+    ///
+    /// Invalid patterns are logged and ignored.
+    #[must_use]
+    pub fn new(settings: &config::ToolSearch) -> Self {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .filter_map(|pattern| {
+                    Pattern::new(pattern)
+                        .inspect_err(|e| tracing::warn!(%pattern, "Invalid tool pattern: {e}"))
+                        .ok()
+                })
+                .collect::<Vec<_>>()
+        };
+        Self {
+            always_loaded: Arc::new(compile(&settings.always_loaded)),
+            deferred: Arc::new(compile(&settings.deferred)),
+            max_results: settings.max_results.max(1),
+            state: Arc::new(Mutex::new(State {
+                deferred: Vec::new(),
+                active: IndexSet::new(),
+            })),
+        }
+    }
+
+    /// Whether a tool is left out of requests until activated.
+    #[must_use]
+    pub fn is_deferred(&self, name: &str) -> bool {
+        name != NAME
+            && self.deferred.iter().any(|pattern| pattern.matches(name))
+            && !self
+                .always_loaded
+                .iter()
+                .any(|pattern| pattern.matches(name))
+    }
+
+    /// Choose the tool definitions offered with a request.
+    ///
+    /// The deferred tools among the definitions are remembered for searches.
+    ///
+    /// # Arguments
+    ///
+    /// * `tools` - Definitions of all available tools
+    ///
+    /// # Returns
+    ///
+    /// The definitions of the tools that are not deferred or were activated.
+    #[must_use]
+    pub fn offer(&self, tools: &[Value]) -> Vec<Value> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.deferred.clear();
+        let mut offered = Vec::new();
+        for tool in tools {
+            let field = |key: &str| {
+                tool.get(key)
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            let name = field("name");
+            if !self.is_deferred(&name) || state.active.contains(&name) {
+                offered.push(tool.clone());
+            } else {
+                state.deferred.push(Entry {
+                    name,
+                    description: field("description"),
+                });
+            }
+        }
+        offered
+    }
+
+    /// Names of the activated tools, in activation order.
+    #[must_use]
+    pub fn active(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.active.iter().cloned().collect()
+    }
+
+    /// Search the deferred tools and activate the best matches.
+    ///
+    /// Each word of the query scores a tool: an exact name match scores
+    /// highest, then a match in the name, then in the description.
+    ///
+    /// # Returns
+    ///
+    /// The names and descriptions of the activated tools, best match first.
+    #[must_use]
+    pub fn search(&self, query: &str) -> Vec<(String, String)> {
+        let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut scored: Vec<(usize, &Entry)> = state
+            .deferred
+            .iter()
+            .filter_map(|entry| {
+                let name = entry.name.to_lowercase();
+                let description = entry.description.to_lowercase();
+                let score: usize = words
+                    .iter()
+                    .map(|word| {
+                        if name == *word {
+                            10
+                        } else if name.contains(word.as_str()) {
+                            3
+                        } else {
+                            usize::from(description.contains(word.as_str()))
+                        }
+                    })
+                    .sum();
+                (score > 0).then_some((score, entry))
+            })
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        let found: Vec<(String, String)> = scored
+            .into_iter()
+            .take(self.max_results)
+            .map(|(_, entry)| (entry.name.clone(), entry.description.clone()))
+            .collect();
+        state
+            .active
+            .extend(found.iter().map(|(name, _)| name.clone()));
+        found
+    }
+
+    /// Number of deferred tools not activated yet.
+    fn remaining(&self) -> usize {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state
+            .deferred
+            .iter()
+            .filter(|entry| !state.active.contains(&entry.name))
+            .count()
+    }
+}
+
+/// Tool searching the deferred tools and loading the matches.
+pub struct Search {
+    /// Catalog of the deferred tools
+    catalog: Catalog,
+}
+
+impl Search {
+    /// Create the tool search tool over a catalog.
+    #[must_use]
+    pub const fn new(catalog: Catalog) -> Self {
+        Self { catalog }
+    }
+}
+
+#[async_trait]
+impl Tool for Search {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn description(&self) -> &'static str {
+        "Find and load tools that are not loaded yet. Only some tools are offered up front; others, such as the tools of MCP servers, are loaded on demand. Search with keywords describing the task (e.g. \"github pull request\") or with the exact name of a tool. The best matching tools are loaded and can be called from your next step on."
+    }
+
+    fn input_schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "Keywords describing the tools to find, or tool names"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    async fn execute(&self, input: &Value) -> Result<String> {
+        let query = input
+            .get("query")
+            .and_then(Value::as_str)
+            .context("Missing query")?;
+        let found = self.catalog.search(query);
+        if found.is_empty() {
+            return Ok(format!(
+                "No tools match '{query}' ({} tools not loaded); try other keywords",
+                self.catalog.remaining()
+            ));
+        }
+        let mut text = String::from("Loaded tools:\n");
+        for (name, description) in found {
+            let _ = writeln!(text, "- {name}: {description}");
+        }
+        Ok(text.trim_end().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_deferred_tools_are_loaded_by_search() {
+        let catalog = Catalog::new(&config::ToolSearch {
+            enabled: true,
+            always_loaded: vec!["read".to_string(), "mcp__git__status".to_string()],
+            deferred: vec!["mcp__*".to_string(), "memory".to_string()],
+            max_results: 2,
+        });
+        let tool = |name: &str, description: &str| json!({"name": name, "description": description, "input_schema": {}});
+        let tools = [
+            tool("read", "Read a file"),
+            tool("memory", "Remember facts about the project"),
+            tool("mcp__git__status", "Show the working tree status"),
+            tool("mcp__github__create_issue", "Open an issue on GitHub"),
+            tool("mcp__github__list_pulls", "List pull requests on GitHub"),
+            tool("mcp__jira__search", "Search issues in Jira"),
+            tool(NAME, "Find tools"),
+        ];
+        let names = |offered: &[Value]| -> Vec<String> {
+            offered
+                .iter()
+                .filter_map(|tool| tool.get("name").and_then(Value::as_str))
+                .map(str::to_string)
+                .collect()
+        };
+        assert_eq!(
+            names(&catalog.offer(&tools)),
+            ["read", "mcp__git__status", NAME]
+        );
+
+        let search = Search::new(catalog.clone());
+        let result = search
+            .execute(&json!({"query": "github issue"}))
+            .await
+            .unwrap();
+        assert_eq!(
+            result,
+            "Loaded tools:\n- mcp__github__create_issue: Open an issue on GitHub\n- mcp__github__list_pulls: List pull requests on GitHub"
+        );
+        assert_eq!(
+            names(&catalog.offer(&tools)),
+            [
+                "read",
+                "mcp__git__status",
+                "mcp__github__create_issue",
+                "mcp__github__list_pulls",
+                NAME
+            ]
+        );
+
+        let result = search.execute(&json!({"query": "calendar"})).await.unwrap();
+        assert_eq!(
+            result,
+            "No tools match 'calendar' (2 tools not loaded); try other keywords"
+        );
+        assert_eq!(
+            search.execute(&json!({"query": "MEMORY"})).await.unwrap(),
+            "Loaded tools:\n- memory: Remember facts about the project"
+        );
+        assert_eq!(
+            catalog.active(),
+            [
+                "mcp__github__create_issue",
+                "mcp__github__list_pulls",
+                "memory"
+            ]
+        );
+    }
+}
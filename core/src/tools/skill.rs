@@ -0,0 +1,67 @@
+//! Skill loading tool.
+
+use crate::skills;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::tools::Tool;
+
+/// Tool loading the full instructions of a skill.
+pub struct Skill {
+    /// Available skills
+    skills: Arc<Vec<skills::Skill>>,
+}
+
+impl Skill {
+    /// Create the skill tool over the available skills.
+    #[must_use]
+    pub const fn new(skills: Arc<Vec<skills::Skill>>) -> Self {
+        Self { skills }
+    }
+}
+
+#[async_trait]
+impl Tool for Skill {
+    fn name(&self) -> &'static str {
+        "skill"
+    }
+
+    fn description(&self) -> &'static str {
+        "Load a skill listed in the system prompt: its full instructions and the scripts and resources bundled with it. Load a skill before starting a task it covers, then follow its instructions."
+    }
+
+    fn input_schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "name": {
+                    "type": "string",
+                    "description": "Name of the skill, as listed in the system prompt"
+                }
+            },
+            "required": ["name"]
+        })
+    }
+
+    async fn execute(&self, input: &Value) -> Result<String> {
+        let name = input
+            .get("name")
+            .and_then(Value::as_str)
+            .context("Missing name")?;
+        let skill = self
+            .skills
+            .iter()
+            .find(|skill| skill.name == name)
+            .with_context(|| {
+                let names: Vec<&str> = self
+                    .skills
+                    .iter()
+                    .map(|skill| skill.name.as_str())
+                    .collect();
+                format!("No skill '{name}' (skills: {})", names.join(", "))
+            })?;
+        Ok(skill.render())
+    }
+}
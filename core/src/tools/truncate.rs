@@ -0,0 +1,165 @@
+//! Head+tail truncation of oversized tool output.
+
+/// Result of truncating a tool output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Truncated {
+    /// Beginning of the output that is kept
+    pub head: String,
+    /// End of the output that is kept
+    pub tail: String,
+    /// Number of lines left out entirely
+    pub omitted_lines: usize,
+    /// Number of bytes left out
+    pub omitted_bytes: usize,
+}
+
+/// Truncate output to its first and last parts if it exceeds the limits.
+///
+/// Half of each budget goes to the head and half to the tail. Lines longer
+/// than the byte budget are cut at a character boundary.
+///
+/// # Arguments
+///
+/// * `output` - Full tool output
+/// * `max_lines` - Maximum number of lines to keep
+/// * `max_bytes` - Maximum number of bytes to keep
+///
+/// # Returns
+///
+/// `None` if the output is within both limits.
+#[must_use]
+pub fn head_tail(output: &str, max_lines: usize, max_bytes: usize) -> Option<Truncated> {
+    let lines: Vec<&str> = output.lines().collect();
+    if lines.len() <= max_lines && output.len() <= max_bytes {
+        return None;
+    }
+
+    let head_lines = max_lines.div_ceil(2);
+    let tail_lines = max_lines / 2;
+    let head_bytes = max_bytes.div_ceil(2);
+    let tail_bytes = max_bytes / 2;
+
+    // Lines kept in full by the head; a first line cut short stays
+    // available to the tail, which keeps its end
+    let mut head = Vec::new();
+    let mut full_head = 0;
+    let mut used = 0;
+    for line in lines.iter().take(head_lines) {
+        let remaining = head_bytes.saturating_sub(used);
+        if line.len() + 1 > remaining {
+            if head.is_empty() {
+                head.push(prefix_within(line, remaining));
+            }
+            break;
+        }
+        used += line.len() + 1;
+        head.push(line);
+        full_head += 1;
+    }
+
+    let mut tail = Vec::new();
+    let mut tail_start = lines.len();
+    let mut used = 0;
+    for (index, line) in lines
+        .iter()
+        .enumerate()
+        .skip(full_head)
+        .rev()
+        .take(tail_lines)
+    {
+        let remaining = tail_bytes.saturating_sub(used);
+        if line.len() + 1 > remaining {
+            if tail.is_empty() {
+                tail.push(suffix_within(line, remaining));
+                tail_start = index;
+            }
+            break;
+        }
+        used += line.len() + 1;
+        tail.push(line);
+        tail_start = index;
+    }
+    tail.reverse();
+
+    // Lines between the head and the tail, a line kept in part counting as kept
+    let head_end = head.len();
+    let omitted_lines = tail_start.max(head_end) - head_end;
+    let head = head.join("\n");
+    let tail = tail.join("\n");
+
+    Some(Truncated {
+        omitted_lines,
+        omitted_bytes: output.len().saturating_sub(head.len() + tail.len()),
+        head,
+        tail,
+    })
+}
+
+/// Longest prefix of `line` that fits in `max` bytes.
+fn prefix_within(line: &str, max: usize) -> &str {
+    let mut end = max.min(line.len());
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    line.get(..end).unwrap_or_default()
+}
+
+/// Longest suffix of `line` that fits in `max` bytes.
+fn suffix_within(line: &str, max: usize) -> &str {
+    let mut start = line.len().saturating_sub(max);
+    while !line.is_char_boundary(start) {
+        start += 1;
+    }
+    line.get(start..).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_within_limits() {
+        assert!(head_tail("a\nb\nc", 3, 100).is_none());
+    }
+
+    #[test]
+    fn test_line_limit() {
+        let output = (1..=100)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let truncated = head_tail(&output, 10, 10_000).unwrap();
+
+        assert_eq!(truncated.head, "1\n2\n3\n4\n5");
+        assert_eq!(truncated.tail, "96\n97\n98\n99\n100");
+        assert_eq!(truncated.omitted_lines, 90);
+    }
+
+    #[test]
+    fn test_byte_limit_on_single_line() {
+        let output = "é".repeat(100);
+        let truncated = head_tail(&output, 10, 21).unwrap();
+
+        assert_eq!(truncated.head, "é".repeat(5));
+        assert_eq!(truncated.tail, "é".repeat(5));
+        assert_eq!(truncated.omitted_lines, 0);
+        assert_eq!(truncated.omitted_bytes, 180);
+    }
+
+    #[test]
+    fn test_byte_limit_on_long_first_line() {
+        let output = format!("{}\nshort\nend", "x".repeat(100));
+        let truncated = head_tail(&output, 10, 20).unwrap();
+
+        assert_eq!(truncated.head, "x".repeat(10));
+        assert_eq!(truncated.tail, "short\nend");
+        assert_eq!(truncated.omitted_lines, 0);
+
+        let output = format!("{}\n{}\nend", "x".repeat(100), "y".repeat(100));
+        let truncated = head_tail(&output, 10, 20).unwrap();
+
+        assert_eq!(truncated.head, "x".repeat(10));
+        assert_eq!(truncated.tail, "end");
+        assert_eq!(truncated.omitted_lines, 1);
+    }
+}
@@ -3,9 +3,10 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use serde_json::Value;
+use std::path::PathBuf;
 use tokio::fs;
 
-use crate::tools::Tool;
+use crate::tools::{Tool, input_path};
 
 /// Write content to a file.
 ///
@@ -71,4 +72,8 @@ impl Tool for Write {
             .ok_or_else(|| anyhow::anyhow!("Missing content"))?;
         write(path, content).await
     }
+
+    fn affected_paths(&self, input: &Value) -> Vec<PathBuf> {
+        input_path(input)
+    }
 }
@@ -0,0 +1,749 @@
+//! Persistent session transcripts.
+//!
+//! Each session is stored in its own directory under the sessions directory
+//! (`$XDG_DATA_HOME/neco/sessions/<id>/`). The conversation is kept in an
+//! append-only JSONL file, `transcript.jsonl`, with one [`Record`] per line.
+//! Replaying the records rebuilds the message history of the session.
+
+use crate::clock;
+use crate::events::CoreEvent;
+use crate::history::user_text;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Name of the transcript file inside a session directory.
+const TRANSCRIPT_FILE: &str = "transcript.jsonl";
+
+/// A single line of a transcript.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Record {
+    /// Session metadata, written when the session starts or is resumed
+    Meta {
+        /// Session identifier
+        id: String,
+        /// Working directory of the session
+        cwd: String,
+        /// Provider name
+        provider: String,
+        /// Model name
+        model: String,
+        /// Session this one was branched from
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parent: Option<String>,
+        /// Unix timestamp
+        at: u64,
+    },
+    /// A message appended to the conversation history
+    Message {
+        /// Unix timestamp
+        at: u64,
+        /// The message in Anthropic Messages format
+        message: Value,
+    },
+    /// A core event emitted while processing the conversation
+    Event {
+        /// Unix timestamp
+        at: u64,
+        /// The event
+        event: CoreEvent,
+    },
+    /// The conversation history was cleared
+    Clear {
+        /// Unix timestamp
+        at: u64,
+    },
+    /// The conversation history was compacted
+    ///
+    /// The messages before this record stay in the transcript; the history
+    /// continues from the compacted messages.
+    Compact {
+        /// Unix timestamp
+        at: u64,
+        /// The history after compaction
+        messages: Vec<Value>,
+    },
+    /// The conversation was rewound to an earlier point
+    Rewind {
+        /// Unix timestamp
+        at: u64,
+        /// Number of messages kept
+        len: usize,
+    },
+}
+
+/// Metadata describing a stored session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionInfo {
+    /// Session identifier
+    pub id: String,
+    /// Working directory of the session
+    pub cwd: String,
+    /// Provider name
+    pub provider: String,
+    /// Model name
+    pub model: String,
+    /// Session this one was branched from
+    pub parent: Option<String>,
+    /// Unix timestamp of the first record
+    pub created_at: u64,
+    /// Unix timestamp of the last record
+    pub updated_at: u64,
+    /// Number of messages in the rebuilt history
+    pub messages: usize,
+    /// First user message, used as a title
+    pub title: Option<String>,
+}
+
+/// Metadata written at the top of a new transcript.
+#[derive(Debug)]
+struct Pending {
+    /// Working directory of the session
+    cwd: String,
+    /// Provider name
+    provider: String,
+    /// Model name
+    model: String,
+    /// Session this one was branched from
+    parent: Option<String>,
+}
+
+/// Lazily opened transcript file.
+#[derive(Debug)]
+struct Writer {
+    /// Open file handle, created on the first record
+    file: Option<File>,
+    /// Metadata still to be written before the first record
+    pending: Option<Pending>,
+}
+
+/// Append-only writer for a session transcript.
+///
+/// The session directory and file are created on the first record, so a
+/// session that never receives a message leaves nothing on disk. Cloning is
+/// cheap; all clones append to the same file.
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    /// Session identifier
+    id: String,
+    /// Session directory
+    dir: PathBuf,
+    /// Session this one was branched from
+    parent: Option<String>,
+    /// Shared file writer
+    writer: Arc<Mutex<Writer>>,
+}
+
+impl Transcript {
+    /// Create a transcript for a new session.
+    ///
+    /// # Arguments
+    ///
+    /// * `sessions_dir` - Directory holding all session directories
+    /// * `cwd` - Working directory of the session
+    /// * `provider` - Provider name
+    /// * `model` - Model name
+    #[must_use]
+    pub fn create(sessions_dir: &Path, cwd: &str, provider: &str, model: &str) -> Self {
+        Self::open(sessions_dir, clock::new_id(), cwd, provider, model, None)
+    }
+
+    /// Create a transcript for a new branch of this session.
+    ///
+    /// The branch is stored next to this session and records it as its
+    /// parent. The caller seeds it with the shared messages.
+    ///
+    /// # Arguments
+    ///
+    /// * `cwd` - Working directory of the branch
+    /// * `provider` - Provider name
+    /// * `model` - Model name
+    #[must_use]
+    pub fn branch(&self, cwd: &str, provider: &str, model: &str) -> Self {
+        let sessions_dir = self.dir.parent().unwrap_or(&self.dir);
+        Self::open(
+            sessions_dir,
+            clock::new_id(),
+            cwd,
+            provider,
+            model,
+            Some(self.id.clone()),
+        )
+    }
+
+    /// Create a transcript writer whose metadata is written with the first record.
+    fn open(
+        sessions_dir: &Path,
+        id: String,
+        cwd: &str,
+        provider: &str,
+        model: &str,
+        parent: Option<String>,
+    ) -> Self {
+        Self {
+            dir: sessions_dir.join(&id),
+            id,
+            parent: parent.clone(),
+            writer: Arc::new(Mutex::new(Writer {
+                file: None,
+                pending: Some(Pending {
+                    cwd: cwd.to_string(),
+                    provider: provider.to_string(),
+                    model: model.to_string(),
+                    parent,
+                }),
+            })),
+        }
+    }
+
+    /// Open the transcript of an existing session for appending.
+    ///
+    /// A new metadata record is written with the current provider and model.
+    ///
+    /// # Arguments
+    ///
+    /// * `sessions_dir` - Directory holding all session directories
+    /// * `id` - Session identifier or unique id prefix
+    /// * `cwd` - Working directory of the resumed session
+    /// * `provider` - Provider name
+    /// * `model` - Model name
+    ///
+    /// # Returns
+    ///
+    /// The transcript and the records read from it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the session does not exist or cannot be read.
+    pub fn resume(
+        sessions_dir: &Path,
+        id: &str,
+        cwd: &str,
+        provider: &str,
+        model: &str,
+    ) -> Result<(Self, Vec<Record>)> {
+        let id = resolve_id(sessions_dir, id)?;
+        let records = load(sessions_dir, &id)?;
+        let parent = summarize(&records).and_then(|info| info.parent);
+        let transcript = Self::open(sessions_dir, id, cwd, provider, model, parent);
+        Ok((transcript, records))
+    }
+
+    /// Session identifier.
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Session directory, holding the transcript and other session files.
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Append a record to the transcript.
+    ///
+    /// Failures are logged and otherwise ignored, so that a full disk never
+    /// interrupts the conversation.
+    pub fn append(&self, record: &Record) {
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Err(e) = self.write_record(&mut writer, record) {
+            tracing::warn!(session = %self.id, "Failed to write transcript: {e}");
+        }
+    }
+
+    /// Write a record, creating the file and metadata first if needed.
+    fn write_record(&self, writer: &mut Writer, record: &Record) -> Result<()> {
+        if writer.file.is_none() {
+            fs::create_dir_all(&self.dir)
+                .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+            let path = self.dir.join(TRANSCRIPT_FILE);
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .with_context(|| format!("Failed to open {}", path.display()))?;
+            writer.file = Some(file);
+        }
+
+        let mut lines = Vec::new();
+        if let Some(pending) = writer.pending.take() {
+            lines.push(serde_json::to_string(&Record::Meta {
+                id: self.id.clone(),
+                cwd: pending.cwd,
+                provider: pending.provider,
+                model: pending.model,
+                parent: pending.parent,
+                at: clock::now(),
+            })?);
+        }
+        lines.push(serde_json::to_string(record)?);
+
+        if let Some(file) = writer.file.as_mut() {
+            for line in lines {
+                writeln!(file, "{line}")?;
+            }
+            file.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Record a message appended to the history.
+    pub fn record_message(&self, message: &Value) {
+        self.append(&Record::Message {
+            at: clock::now(),
+            message: message.clone(),
+        });
+    }
+
+    /// Record a core event.
+    ///
+    /// Streaming text deltas are skipped; the text is part of the messages.
+    pub fn record_event(&self, event: &CoreEvent) {
+        if matches!(
+            event,
+            CoreEvent::TextDelta(_) | CoreEvent::MessageStart | CoreEvent::MessageStop
+        ) {
+            return;
+        }
+        self.append(&Record::Event {
+            at: clock::now(),
+            event: event.clone(),
+        });
+    }
+
+    /// Record that the history was cleared.
+    pub fn record_clear(&self) {
+        self.append(&Record::Clear { at: clock::now() });
+    }
+
+    /// Record that the history was truncated to its first `len` messages.
+    pub fn record_rewind(&self, len: usize) {
+        self.append(&Record::Rewind {
+            at: clock::now(),
+            len,
+        });
+    }
+
+    /// Record that the session continues with another provider or model.
+    ///
+    /// Before the first record, only the pending metadata is updated.
+    pub fn record_model(&self, cwd: &str, provider: &str, model: &str) {
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(pending) = writer.pending.as_mut() {
+            pending.provider = provider.to_string();
+            pending.model = model.to_string();
+            return;
+        }
+        drop(writer);
+        self.append(&Record::Meta {
+            id: self.id.clone(),
+            cwd: cwd.to_string(),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            parent: self.parent.clone(),
+            at: clock::now(),
+        });
+    }
+
+    /// Record that the history was replaced by a compacted one.
+    pub fn record_compact(&self, messages: &[Value]) {
+        self.append(&Record::Compact {
+            at: clock::now(),
+            messages: messages.to_vec(),
+        });
+    }
+}
+
+/// Default directory holding all sessions.
+///
+/// Follows the XDG Base Directory specification:
+/// `$XDG_DATA_HOME/neco/sessions` (default `~/.local/share/neco/sessions`).
+#[must_use]
+pub fn default_sessions_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("neco").join("sessions"))
+}
+
+/// Shortest id prefix accepted when resolving a session.
+const MIN_PREFIX: usize = 4;
+
+/// Check that a session id or prefix only has the characters of generated ids.
+///
+/// Rejects anything that could name a path outside the sessions directory,
+/// such as `..` or an absolute path, and prefixes too short to be meant.
+fn validate_id(id: &str) -> Result<()> {
+    if id.len() < MIN_PREFIX {
+        anyhow::bail!("Session id '{id}' is too short (at least {MIN_PREFIX} characters)");
+    }
+    if !id
+        .chars()
+        .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c) || c == '-')
+    {
+        anyhow::bail!("Invalid session id '{id}'");
+    }
+    Ok(())
+}
+
+/// Resolve a session id or unique id prefix to a full id.
+///
+/// # Errors
+///
+/// Returns an error if the id is not a valid id or prefix, or no session or
+/// more than one session matches.
+pub fn resolve_id(sessions_dir: &Path, id: &str) -> Result<String> {
+    validate_id(id)?;
+    if sessions_dir.join(id).join(TRANSCRIPT_FILE).is_file() {
+        return Ok(id.to_string());
+    }
+
+    let matches: Vec<String> = session_ids(sessions_dir)
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(id))
+        .collect();
+    match matches.as_slice() {
+        [only] => Ok(only.clone()),
+        [] => Err(anyhow::anyhow!("Session '{id}' not found")),
+        _ => Err(anyhow::anyhow!(
+            "Session id '{id}' is ambiguous ({} matches)",
+            matches.len()
+        )),
+    }
+}
+
+/// Ids of all sessions that have a transcript.
+fn session_ids(sessions_dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(sessions_dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(std::result::Result::ok)
+        .filter(|entry| entry.path().join(TRANSCRIPT_FILE).is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect()
+}
+
+/// Load all records of a session.
+///
+/// Lines that cannot be parsed (e.g. a partial line after a crash) are skipped.
+///
+/// # Errors
+///
+/// Returns an error if the session does not exist or cannot be read.
+pub fn load(sessions_dir: &Path, id: &str) -> Result<Vec<Record>> {
+    let id = resolve_id(sessions_dir, id)?;
+    let path = sessions_dir.join(&id).join(TRANSCRIPT_FILE);
+    let file = File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(std::result::Result::ok)
+        .filter_map(|line| match serde_json::from_str::<Record>(&line) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                tracing::warn!(session = %id, "Skipping unreadable transcript line: {e}");
+                None
+            },
+        })
+        .collect())
+}
+
+/// Delete a session and all its files.
+///
+/// # Errors
+///
+/// Returns an error if the session does not exist or cannot be removed.
+pub fn delete(sessions_dir: &Path, id: &str) -> Result<String> {
+    let id = resolve_id(sessions_dir, id)?;
+    let dir = sessions_dir.join(&id);
+    let inside = fs::canonicalize(&dir)
+        .ok()
+        .zip(fs::canonicalize(sessions_dir).ok())
+        .is_some_and(|(dir, root)| dir.parent() == Some(root.as_path()));
+    if !inside {
+        anyhow::bail!("Session '{id}' is not in {}", sessions_dir.display());
+    }
+    fs::remove_dir_all(&dir).with_context(|| format!("Failed to delete {}", dir.display()))?;
+    Ok(id)
+}
+
+/// List all stored sessions, most recently updated first.
+#[must_use]
+pub fn list(sessions_dir: &Path) -> Vec<SessionInfo> {
+    let mut sessions: Vec<SessionInfo> = session_ids(sessions_dir)
+        .iter()
+        .filter_map(|id| load(sessions_dir, id).ok())
+        .filter_map(|records| summarize(&records))
+        .collect();
+    sessions.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then(b.id.cmp(&a.id)));
+    sessions
+}
+
+/// Most recently updated session started in the given directory.
+#[must_use]
+pub fn latest_for_cwd(sessions_dir: &Path, cwd: &str) -> Option<SessionInfo> {
+    list(sessions_dir).into_iter().find(|info| info.cwd == cwd)
+}
+
+/// Summarize a session from its records.
+///
+/// # Returns
+///
+/// `None` if the records contain no metadata.
+#[must_use]
+pub fn summarize(records: &[Record]) -> Option<SessionInfo> {
+    let mut info: Option<SessionInfo> = None;
+    let mut updated_at = 0;
+    let mut title = None;
+
+    for record in records {
+        let at = match record {
+            Record::Meta { at, .. }
+            | Record::Message { at, .. }
+            | Record::Event { at, .. }
+            | Record::Clear { at }
+            | Record::Compact { at, .. }
+            | Record::Rewind { at, .. } => *at,
+        };
+        updated_at = updated_at.max(at);
+
+        // The first user message since the history was last cleared; a
+        // compaction summary never becomes the title
+        match record {
+            Record::Message { message, .. } if title.is_none() => title = user_text(message),
+            Record::Clear { .. } => title = None,
+            _ => {},
+        }
+
+        if let Record::Meta {
+            id,
+            cwd,
+            provider,
+            model,
+            parent,
+            at,
+        } = record
+        {
+            let created_at = info.as_ref().map_or(*at, |i| i.created_at);
+            info = Some(SessionInfo {
+                id: id.clone(),
+                cwd: cwd.clone(),
+                provider: provider.clone(),
+                model: model.clone(),
+                parent: parent.clone(),
+                created_at,
+                updated_at,
+                messages: 0,
+                title: None,
+            });
+        }
+    }
+
+    info.map(|info| SessionInfo {
+        updated_at,
+        messages: rebuild_history(records).len(),
+        title,
+        ..info
+    })
+}
+
+/// Rebuild the message history from transcript records.
+///
+/// Messages are replayed in order, `Clear` records reset the history,
+/// `Compact` records replace it and `Rewind` records truncate it.
+/// The result may end with unanswered tool calls; see [`unanswered_tool_results`].
+#[must_use]
+pub fn rebuild_history(records: &[Record]) -> Vec<Value> {
+    let mut history = Vec::new();
+    for record in records {
+        match record {
+            Record::Message { message, .. } => history.push(message.clone()),
+            Record::Clear { .. } => history.clear(),
+            Record::Compact { messages, .. } => history.clone_from(messages),
+            Record::Rewind { len, .. } => history.truncate(*len),
+            Record::Meta { .. } | Record::Event { .. } => {},
+        }
+    }
+    history
+}
+
+/// Tool results answering tool calls left open at the end of a history.
+///
+/// When a session ends (e.g. crashes) after the assistant requested tool
+/// calls but before their results were recorded, the API would reject the
+/// history. This returns a user message with an error result for each
+/// unanswered call, or `None` if the history is consistent.
+#[must_use]
+pub fn unanswered_tool_results(history: &[Value]) -> Option<Value> {
+    let last = history.last()?;
+    if last.get("role").and_then(Value::as_str) != Some("assistant") {
+        return None;
+    }
+
+    let results: Vec<Value> = last
+        .get("content")
+        .and_then(Value::as_array)?
+        .iter()
+        .filter(|block| block.get("type").and_then(Value::as_str) == Some("tool_use"))
+        .filter_map(|block| block.get("id").and_then(Value::as_str))
+        .map(|id| {
+            json!({
+                "type": "tool_result",
+                "tool_use_id": id,
+                "content": "error: the session ended before this tool call completed",
+                "is_error": true
+            })
+        })
+        .collect();
+
+    (!results.is_empty()).then(|| json!({"role": "user", "content": results}))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_transcript_lazy_creation() {
+        let dir = TempDir::new("transcript-lazy");
+        let transcript = Transcript::create(&dir, "/project", "anthropic", "claude");
+        assert!(!transcript.dir().exists());
+
+        transcript.record_message(&json!({"role": "user", "content": "hello"}));
+        assert!(transcript.dir().join(TRANSCRIPT_FILE).is_file());
+    }
+
+    #[test]
+    fn test_transcript_roundtrip_and_list() {
+        let dir = TempDir::new("transcript-roundtrip");
+        let transcript = Transcript::create(&dir, "/project", "anthropic", "claude");
+        transcript.record_message(&json!({"role": "user", "content": "first"}));
+        transcript.record_clear();
+        transcript.record_message(&json!({"role": "user", "content": "second"}));
+        transcript.record_event(&CoreEvent::TextDelta("skipped".to_string()));
+        transcript.record_event(&CoreEvent::Error("kept".to_string()));
+
+        let records = load(&dir, transcript.id()).unwrap();
+        assert_eq!(records.len(), 5);
+        assert_eq!(
+            rebuild_history(&records),
+            vec![json!({"role": "user", "content": "second"})]
+        );
+
+        let sessions = list(&dir);
+        assert_eq!(sessions.len(), 1);
+        let info = sessions.first().unwrap();
+        assert_eq!(info.id, transcript.id());
+        assert_eq!(info.messages, 1);
+        assert_eq!(info.title.as_deref(), Some("second"));
+        assert_eq!(
+            latest_for_cwd(&dir, "/project").map(|i| i.id),
+            Some(transcript.id().to_string())
+        );
+        assert!(latest_for_cwd(&dir, "/elsewhere").is_none());
+
+        let prefix = transcript.id().get(..15).unwrap();
+        assert_eq!(resolve_id(&dir, prefix).unwrap(), transcript.id());
+        for invalid in ["", "20", "../x", "/tmp", "2026/../..", "ABCD"] {
+            assert!(resolve_id(&dir, invalid).is_err(), "{invalid}");
+            assert!(delete(&dir, invalid).is_err(), "{invalid}");
+        }
+        assert!(dir.join(transcript.id()).is_dir());
+
+        delete(&dir, transcript.id()).unwrap();
+        assert!(list(&dir).is_empty());
+    }
+
+    #[test]
+    fn test_resume_appends_meta() {
+        let dir = TempDir::new("transcript-resume");
+        let transcript = Transcript::create(&dir, "/project", "anthropic", "claude");
+        transcript.record_message(&json!({"role": "user", "content": "hi"}));
+
+        let (resumed, records) =
+            Transcript::resume(&dir, transcript.id(), "/project", "zhipuai", "glm").unwrap();
+        assert_eq!(records.len(), 2);
+        resumed.record_message(&json!({"role": "assistant", "content": "hello"}));
+
+        let info = summarize(&load(&dir, transcript.id()).unwrap()).unwrap();
+        assert_eq!(info.model, "glm");
+        assert_eq!(info.messages, 2);
+
+        let result = Transcript::resume(&dir, "19990101-000000", "/", "p", "m");
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("not found"));
+    }
+
+    #[test]
+    fn test_record_model_switch() {
+        let dir = TempDir::new("transcript-model");
+        let transcript = Transcript::create(&dir, "/project", "anthropic", "opus");
+        transcript.record_model("/project", "anthropic", "sonnet");
+        transcript.record_message(&json!({"role": "user", "content": "plan"}));
+        let records = load(&dir, transcript.id()).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(summarize(&records).unwrap().model, "sonnet");
+
+        transcript.record_model("/project", "zhipuai", "glm");
+        let info = summarize(&load(&dir, transcript.id()).unwrap()).unwrap();
+        assert_eq!(
+            (info.provider.as_str(), info.model.as_str()),
+            ("zhipuai", "glm")
+        );
+        assert_eq!(info.messages, 1);
+    }
+
+    #[test]
+    fn test_rebuild_history_after_compact() {
+        let first = json!({"role": "user", "content": "first"});
+        let summary = json!({"role": "user", "content": "summary"});
+        let records = vec![
+            Record::Message {
+                at: 1,
+                message: first,
+            },
+            Record::Compact {
+                at: 2,
+                messages: vec![summary.clone()],
+            },
+            Record::Message {
+                at: 3,
+                message: json!({"role": "assistant", "content": "ok"}),
+            },
+        ];
+
+        let history = rebuild_history(&records);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.first(), Some(&summary));
+    }
+
+    #[test]
+    fn test_unanswered_tool_results() {
+        let history = vec![
+            json!({"role": "user", "content": "list files"}),
+            json!({"role": "assistant", "content": [
+                {"type": "text", "text": ""},
+                {"type": "tool_use", "id": "call_1", "name": "bash", "input": {"cmd": "ls"}},
+                {"type": "tool_use", "id": "call_2", "name": "glob", "input": {"pat": "*"}}
+            ]}),
+        ];
+
+        let repair = unanswered_tool_results(&history).unwrap();
+        let results = repair.get("content").and_then(Value::as_array).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            results.first().and_then(|r| r.get("tool_use_id")),
+            Some(&json!("call_1"))
+        );
+
+        let consistent =
+            vec![json!({"role": "assistant", "content": [{"type": "text", "text": "done"}]})];
+        assert!(unanswered_tool_results(&consistent).is_none());
+        assert!(unanswered_tool_results(&[]).is_none());
+    }
+}
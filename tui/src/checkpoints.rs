@@ -0,0 +1,110 @@
+//! Rendering of file checkpoint, restore and diff events.
+
+use crate::{output, separator};
+use crossterm::style::Stylize;
+use neco_core::{CoreEvent, clock};
+
+/// Print an event about file checkpoints; other events are ignored.
+pub fn render(event: CoreEvent) {
+    match event {
+        CoreEvent::Checkpoints(checkpoints) => {
+            if checkpoints.is_empty() {
+                output::println(format_args!("{}", "No file checkpoints yet".yellow()));
+            }
+            for checkpoint in &checkpoints {
+                output::println(format_args!(
+                    "{:>3}  {}  {}",
+                    checkpoint.id.to_string().bold(),
+                    clock::format_datetime(checkpoint.at).dim(),
+                    checkpoint.label
+                ));
+                for file in &checkpoint.files {
+                    output::println(format_args!("       {}", file.as_str().dim()));
+                }
+            }
+            output::println(format_args!(
+                "{}",
+                "Use /restore <n> to restore files to before checkpoint n, or /undo [turns]".dim()
+            ));
+            output::print(format_args!("{}", separator()));
+        },
+        CoreEvent::RestorePreview {
+            checkpoint,
+            diff,
+            files,
+            untracked,
+        } => {
+            output::diff(&diff);
+            warn_untracked(&untracked, "restored");
+            output::println(format_args!(
+                "{} Restore {} files to before checkpoint {checkpoint}? [y/N]",
+                "⏪".yellow(),
+                files.len()
+            ));
+        },
+        CoreEvent::Restored {
+            checkpoint,
+            files,
+            untracked,
+        } => {
+            tracing::info!(checkpoint, files = files.len(), "Files restored");
+            if files.is_empty() {
+                output::println(format_args!(
+                    "{} Files already match checkpoint {checkpoint}",
+                    "⏺".green()
+                ));
+                warn_untracked(&untracked, "restored");
+            }
+            for file in &files {
+                output::println(format_args!("{} Restored {file}", "⏪".green()));
+            }
+            output::print(format_args!("{}", separator()));
+        },
+        CoreEvent::Diff {
+            since,
+            files,
+            patch,
+            saved,
+            untracked,
+        } => {
+            if files.is_empty() {
+                output::println(format_args!("{}", "No file changes".yellow()));
+            } else if saved.is_none() {
+                output::diff(&patch);
+            }
+            output::stats(&files);
+            warn_untracked(&untracked, "shown");
+            if let Some(path) = saved {
+                output::println(format_args!("{} Saved patch to {path}", "⏺".green()));
+            }
+            if let Some(since) = since {
+                output::println(format_args!(
+                    "{}",
+                    format!("Since checkpoint {since}").dim()
+                ));
+            }
+            output::print(format_args!("{}", separator()));
+        },
+        CoreEvent::RestoreCancelled => {
+            output::println(format_args!("{}", "Restore cancelled".yellow()));
+        },
+        _ => {},
+    }
+}
+
+/// Warn that files changed by some tools are not covered by checkpoints.
+///
+/// # Arguments
+///
+/// * `untracked` - Tools that ran and may have changed files without a snapshot
+/// * `outcome` - What did not happen to their changes, e.g. `restored`
+pub fn warn_untracked(untracked: &[String], outcome: &str) {
+    if untracked.is_empty() {
+        return;
+    }
+    output::println(format_args!(
+        "{} Files changed through {} are not checkpointed and are not {outcome}",
+        "⚠".yellow(),
+        untracked.join(", ")
+    ));
+}
@@ -0,0 +1,181 @@
+//! Rendering of events answering informational slash commands.
+
+use crate::{output, separator};
+use crossterm::style::Stylize;
+use neco_core::{CoreEvent, clock, memory};
+
+/// Print an event answering `/help`, `/model`, `/cost`, `/prompt`, `/tools`, `/persona` or
+/// `/memory`; other events are ignored.
+pub fn render(event: CoreEvent) {
+    match event {
+        CoreEvent::Commands(commands) => {
+            let width = commands
+                .iter()
+                .map(|command| command.usage.chars().count())
+                .max()
+                .unwrap_or_default();
+            for command in &commands {
+                let aliases = if command.aliases.is_empty() {
+                    String::new()
+                } else {
+                    format!(" ({})", command.aliases.join(", "))
+                };
+                output::println(format_args!(
+                    "  {:<width$}  {}{}",
+                    command.usage.as_str().bold(),
+                    command.help,
+                    aliases.dim()
+                ));
+            }
+            output::print(format_args!("{}", separator()));
+        },
+        CoreEvent::Model { provider, model } => {
+            output::println(format_args!("{} {provider}/{}", "⏺".green(), model.bold()));
+            output::print(format_args!("{}", separator()));
+        },
+        CoreEvent::ModelSwitched {
+            from,
+            provider,
+            model,
+        } => {
+            tracing::info!(from = %from, provider = %provider, model = %model, "Model switched");
+            output::println(format_args!(
+                "{} Switched from {from} to {provider}/{}",
+                "⏺".green(),
+                model.bold()
+            ));
+            output::print(format_args!("{}", separator()));
+        },
+        CoreEvent::Cost { usage, cost } => {
+            output::println(format_args!(
+                "{} {} input tokens ({} cached), {} output tokens",
+                "⏺".green(),
+                usage.total_input(),
+                usage.cache_read_input_tokens,
+                usage.output_tokens
+            ));
+            match cost {
+                Some(cost) => output::println(format_args!("  Cost: ${cost:.4}")),
+                None => output::println(format_args!(
+                    "  {}",
+                    "Cost unknown: no pricing configured for this model".dim()
+                )),
+            }
+            output::print(format_args!("{}", separator()));
+        },
+        CoreEvent::Prompt { text, sections } => {
+            output::println(format_args!("{}", text.as_str().dim()));
+            output::print(format_args!("{}", separator()));
+            for section in &sections {
+                let state = match (section.included, section.lazy) {
+                    (true, _) => String::new(),
+                    (false, true) => " (lazy, /prompt load to include)".to_string(),
+                    (false, false) if section.tokens == 0 => " (empty)".to_string(),
+                    (false, false) => " (disabled)".to_string(),
+                };
+                output::println(format_args!(
+                    "  {:<12} {:>6} tokens{}",
+                    section.name,
+                    section.tokens,
+                    state.dim()
+                ));
+            }
+            let total: u64 = sections
+                .iter()
+                .filter(|section| section.included)
+                .map(|section| section.tokens)
+                .sum();
+            output::println(format_args!("  {:<12} {:>6} tokens", "total".bold(), total));
+            output::print(format_args!("{}", separator()));
+        },
+        CoreEvent::Tools(tools) => {
+            for (name, description) in &tools {
+                let summary = description.lines().next().unwrap_or_default();
+                output::println(format_args!(
+                    "  {}  {}",
+                    name.as_str().bold(),
+                    summary.dim()
+                ));
+            }
+            output::print(format_args!("{}", separator()));
+        },
+        CoreEvent::Memories(memories) => {
+            if memories.is_empty() {
+                output::println(format_args!("  {}", "No memories".dim()));
+            }
+            for memory in &memories {
+                let scope = if memory.scope == memory::Scope::Global {
+                    " (global)"
+                } else {
+                    ""
+                };
+                output::println(format_args!(
+                    "  {}  {}{}  {}",
+                    memory.id.as_str().bold(),
+                    memory.summary,
+                    scope.dim(),
+                    format!("used {}", clock::format_date(memory.used)).dim()
+                ));
+                if let Some(reason) = &memory.stale {
+                    output::println(format_args!("    {}", format!("stale: {reason}").yellow()));
+                }
+            }
+            output::print(format_args!("{}", separator()));
+        },
+        CoreEvent::Memory { memory, stale } => {
+            output::println(format_args!(
+                "{} {}",
+                "⏺".green(),
+                memory.summary.as_str().bold()
+            ));
+            output::println(format_args!(
+                "  {}",
+                format!(
+                    "{} · saved {} · used {}",
+                    memory.id,
+                    clock::format_date(memory.created),
+                    clock::format_date(memory.used)
+                )
+                .dim()
+            ));
+            for file in &memory.files {
+                output::println(format_args!(
+                    "  {}",
+                    format!("depends on {}", file.path.display()).dim()
+                ));
+            }
+            if let Some(reason) = stale {
+                output::println(format_args!("  {}", format!("stale: {reason}").yellow()));
+            }
+            output::println(format_args!("\n{}", memory.content));
+            output::print(format_args!("{}", separator()));
+        },
+        CoreEvent::MemoryForgotten { id, summary } => {
+            output::println(format_args!(
+                "{} Forgot {}: {summary}",
+                "⏺".green(),
+                id.bold()
+            ));
+            output::print(format_args!("{}", separator()));
+        },
+        CoreEvent::Persona { name, greeting } => {
+            let name = name.unwrap_or_else(|| "default".to_string());
+            output::println(format_args!("{} Persona: {}", "⏺".green(), name.bold()));
+            if let Some(greeting) = greeting {
+                output::println(format_args!("  {}", greeting.italic()));
+            }
+            output::print(format_args!("{}", separator()));
+        },
+        CoreEvent::Personas { current, names } => {
+            let marker = |name: Option<&str>| {
+                if current.as_deref() == name { "*" } else { " " }
+            };
+            output::println(format_args!("{} default", marker(None).green()));
+            for name in &names {
+                output::println(format_args!("{} {name}", marker(Some(name)).green()));
+            }
+            output::print(format_args!("{}", separator()));
+        },
+        _ => {},
+    }
+}
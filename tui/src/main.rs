@@ -1,22 +1,26 @@
 //! nanocode - minimal Claude code alternative in Rust
 
 use anyhow::Context;
-use clap::Parser;
+use clap::{ArgGroup, Parser, Subcommand};
 use crossterm::style::{Attribute, Stylize};
 use std::io::{self, BufRead, Write};
 use std::path::Path;
 use std::process::ExitCode;
 use tokio::sync::mpsc;
 
+mod checkpoints;
 mod colors;
+mod commands;
 mod logging;
+mod mcp;
 mod output;
 mod separator;
+mod sessions;
 
 pub use colors::*;
 pub use separator::separator;
 
-use neco_core::{App, Config, CoreEvent, ProviderRegistry};
+use neco_core::{App, Config, CoreEvent, Limits, ProviderRegistry, Resume};
 
 /// Initialize the logging system, returns success status.
 fn setup_logging(config: &Config) -> bool {
@@ -33,6 +37,7 @@ fn setup_logging(config: &Config) -> bool {
 /// AI programming assistant - Claude Code Rust implementation
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(group(ArgGroup::new("resume_target").args(["continue_session", "resume"])))]
 struct CliArgs {
     /// Send message directly and execute (non-interactive mode)
     #[arg(short = 'm', long = "message")]
@@ -41,6 +46,88 @@ struct CliArgs {
     /// Model specification (e.g., "anthropic/claude-opus-4-5" or "claude-sonnet-4-5")
     #[arg(short = 'M', long = "model")]
     model: Option<String>,
+
+    /// Persona to talk as (e.g. "neko"), from the configuration or `personas/<name>.toml`
+    #[arg(short = 'p', long = "persona")]
+    persona: Option<String>,
+
+    /// Maximum tool iterations per user message
+    #[arg(long = "max-turns")]
+    max_turns: Option<u32>,
+
+    /// Maximum wall-clock seconds per user message
+    #[arg(long = "max-duration")]
+    max_duration: Option<u64>,
+
+    /// Maximum tokens per user message
+    #[arg(long = "max-turn-tokens")]
+    max_turn_tokens: Option<u64>,
+
+    /// Maximum tokens per session
+    #[arg(long = "max-session-tokens")]
+    max_session_tokens: Option<u64>,
+
+    /// Maximum cost in USD per user message (requires provider pricing)
+    #[arg(long = "max-turn-cost")]
+    max_turn_cost: Option<f64>,
+
+    /// Maximum cost in USD per session (requires provider pricing)
+    #[arg(long = "max-session-cost")]
+    max_session_cost: Option<f64>,
+
+    /// Resume the most recent session started in the current directory
+    #[arg(short = 'c', long = "continue", conflicts_with = "resume")]
+    continue_session: bool,
+
+    /// Resume the session with the given id (or unique id prefix)
+    #[arg(short = 'r', long = "resume", value_name = "ID")]
+    resume: Option<String>,
+
+    /// Continue the resumed session in a new branch, leaving the original unchanged
+    #[arg(long = "fork", requires = "resume_target")]
+    fork: bool,
+
+    /// Subcommand to run instead of a conversation
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+/// Subcommands of the CLI.
+#[derive(Subcommand, Debug)]
+enum CliCommand {
+    /// Manage stored sessions
+    Sessions {
+        /// Session management action
+        #[command(subcommand)]
+        action: sessions::Action,
+    },
+    /// Analyse the project and write a starter instruction file
+    Init {
+        /// Overwrite an existing instruction file
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+impl CliArgs {
+    /// Override configured limits with the ones given on the command line.
+    fn apply_limits(&self, limits: &mut Limits) {
+        limits.max_turns = self.max_turns.or(limits.max_turns);
+        limits.max_duration_secs = self.max_duration.or(limits.max_duration_secs);
+        limits.max_turn_tokens = self.max_turn_tokens.or(limits.max_turn_tokens);
+        limits.max_session_tokens = self.max_session_tokens.or(limits.max_session_tokens);
+        limits.max_turn_cost = self.max_turn_cost.or(limits.max_turn_cost);
+        limits.max_session_cost = self.max_session_cost.or(limits.max_session_cost);
+    }
+
+    /// Which session to start with, from `--continue` and `--resume`.
+    fn resume(&self) -> Resume {
+        match (&self.resume, self.continue_session) {
+            (Some(id), _) => Resume::Id(id.clone()),
+            (None, true) => Resume::Latest,
+            (None, false) => Resume::New,
+        }
+    }
 }
 
 /// Async task to handle core events (rendering logic).
@@ -68,12 +155,12 @@ async fn handle_core_events(
                 output::print(format_args!("{}", separator()));
             },
             CoreEvent::Error(error) => {
-                if error.contains("Conversation cleared") {
-                    output::println(format_args!("{}", "⏺ Cleared conversation".green()));
-                } else {
-                    tracing::error!(error = %error, "Core error occurred");
-                    output::println(format_args!("\n{} Error: {}", "❌".red(), error));
-                }
+                tracing::error!(error = %error, "Core error occurred");
+                output::println(format_args!("\n{} Error: {}", "❌".red(), error));
+                output::print(format_args!("{}", separator()));
+            },
+            CoreEvent::Cleared => {
+                output::println(format_args!("{}", "⏺ Cleared conversation".green()));
                 output::print(format_args!("{}", separator()));
             },
             CoreEvent::MessageStart => {
@@ -85,6 +172,140 @@ async fn handle_core_events(
                 output::println(format_args!(""));
                 output::print(format_args!("{}", separator()));
             },
+            CoreEvent::LimitReached(exceeded) => {
+                tracing::warn!(limit = %exceeded.limit, "Limit reached");
+                output::println(format_args!(
+                    "\n{} Limit reached ({}): {}",
+                    "⏹".yellow(),
+                    exceeded.limit.to_string().yellow().bold(),
+                    exceeded.message
+                ));
+                output::print(format_args!("{}", separator()));
+            },
+            CoreEvent::RepetitionDetected {
+                name,
+                repeats,
+                paused,
+            } => {
+                tracing::warn!(tool = %name, repeats, paused, "Repeated tool calls");
+                output::println(format_args!(
+                    "\n{} {} repeated {} times in a row",
+                    "🔁".yellow(),
+                    name.yellow().bold(),
+                    repeats
+                ));
+                if paused {
+                    output::println(format_args!(
+                        "{}",
+                        "Paused. Reply to continue, or give new instructions.".yellow()
+                    ));
+                    output::print(format_args!("{}", separator()));
+                }
+            },
+            CoreEvent::SteeringQueued(text) => {
+                tracing::debug!(len = text.len(), "Steering message queued");
+                output::println(format_args!("\n{} Pending: {}", "⏳".yellow(), text.dim()));
+            },
+            CoreEvent::SteeringDelivered(text) => {
+                tracing::debug!(len = text.len(), "Steering message delivered");
+                output::println(format_args!("\n{} Delivered: {}", "📨".green(), text.dim()));
+            },
+            CoreEvent::Turns(turns) => {
+                if turns.is_empty() {
+                    output::println(format_args!("{}", "No messages to rewind to".yellow()));
+                }
+                for (number, text) in turns.iter().enumerate() {
+                    let line = text.lines().next().unwrap_or_default();
+                    output::println(format_args!(
+                        "{:>3}  {}",
+                        (number + 1).to_string().bold(),
+                        line
+                    ));
+                }
+                output::println(format_args!(
+                    "{}",
+                    "Use /rewind <n> to go back to before message n".dim()
+                ));
+                output::print(format_args!("{}", separator()));
+            },
+            CoreEvent::Rewound { turn, text } => {
+                tracing::info!(turn, "Conversation rewound");
+                output::println(format_args!(
+                    "{} Rewound to before message {turn}. Edit and send it again:",
+                    "⏪".green()
+                ));
+                output::println(format_args!("{}", text.dim()));
+                output::print(format_args!("{}", separator()));
+            },
+            CoreEvent::Forked { id, parent } => {
+                tracing::info!(session = %id, parent = %parent, "Session forked");
+                output::println(format_args!(
+                    "{} Continuing in branch {} (from {})",
+                    "⑂".green(),
+                    id.bold(),
+                    parent
+                ));
+                output::print(format_args!("{}", separator()));
+            },
+            CoreEvent::Compacted {
+                replaced,
+                kept,
+                automatic,
+            } => {
+                tracing::info!(replaced, kept, automatic, "Conversation compacted");
+                let trigger = if automatic {
+                    "Context nearly full: "
+                } else {
+                    ""
+                };
+                output::println(format_args!(
+                    "\n{} {trigger}summarised {replaced} earlier messages, kept the last {kept}",
+                    "⏺".green()
+                ));
+                output::print(format_args!("{}", separator()));
+            },
+            event @ (CoreEvent::Checkpoints(_)
+            | CoreEvent::RestorePreview { .. }
+            | CoreEvent::Restored { .. }
+            | CoreEvent::RestoreCancelled
+            | CoreEvent::Diff { .. }) => checkpoints::render(event),
+            event @ (CoreEvent::Commands(_)
+            | CoreEvent::Model { .. }
+            | CoreEvent::ModelSwitched { .. }
+            | CoreEvent::Cost { .. }
+            | CoreEvent::Prompt { .. }
+            | CoreEvent::Tools(_)
+            | CoreEvent::Memories(_)
+            | CoreEvent::Memory { .. }
+            | CoreEvent::MemoryForgotten { .. }
+            | CoreEvent::Persona { .. }
+            | CoreEvent::Personas { .. }) => commands::render(event),
+            CoreEvent::Usage(usage) => {
+                tracing::debug!(
+                    input = usage.total_input(),
+                    output = usage.output_tokens,
+                    "Response usage"
+                );
+            },
+            CoreEvent::Exported { path } => {
+                tracing::info!(path = %path, "Session exported");
+                output::println(format_args!("{} Exported session to {path}", "⏺".green()));
+                output::print(format_args!("{}", separator()));
+            },
+            event @ (CoreEvent::McpConnected { .. }
+            | CoreEvent::McpResource { .. }
+            | CoreEvent::PromptArgument { .. }
+            | CoreEvent::PromptCancelled { .. }) => mcp::render(event),
+            CoreEvent::SessionResumed { id, messages } => {
+                tracing::info!(session = %id, messages, "Session resumed");
+                output::println(format_args!(
+                    "{} Resumed session {} ({} messages)",
+                    "↩".green(),
+                    id.bold(),
+                    messages
+                ));
+                output::print(format_args!("{}", separator()));
+            },
         }
         io::stdout().flush().context("Failed to flush stdout")?;
     }
@@ -93,10 +314,45 @@ async fn handle_core_events(
 
 fn main() -> ExitCode {
     let args = CliArgs::parse();
-    let config = Config::from_env();
+    let mut config = Config::from_env();
+    args.apply_limits(&mut config.limits);
+    config.resume = args.resume();
+    config.fork = args.fork;
+    config.persona = args.persona.clone().or(config.persona);
+
+    if let Some(CliCommand::Sessions { action }) = &args.command {
+        let result = config
+            .sessions_dir
+            .as_deref()
+            .context("No data directory available for sessions")
+            .and_then(|dir| sessions::run(action, dir, &config.redaction));
+        if let Err(e) = result {
+            output::println(format_args!("{} {e:#}", "❌".red()));
+            return ExitCode::FAILURE;
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    let mut message = args.message.clone();
+    if let Some(CliCommand::Init { force }) = &args.command {
+        let name = config
+            .instructions
+            .file_names
+            .first()
+            .map_or("AGENTS.md", String::as_str);
+        if !force && Path::new(&config.cwd).join(name).exists() {
+            output::println(format_args!(
+                "{} {name} already exists; use --force to overwrite it",
+                "❌".red()
+            ));
+            return ExitCode::FAILURE;
+        }
+        message = Some(neco_core::instructions::init_prompt(name));
+    }
+
     let _logging_enabled = setup_logging(&config);
 
-    if let Err(e) = run(&args, config) {
+    if let Err(e) = run(&args, message, config) {
         tracing::error!("Application error: {e}");
         return ExitCode::FAILURE;
     }
@@ -111,13 +367,14 @@ fn main() -> ExitCode {
 /// # Arguments
 ///
 /// * `args` - Parsed command line arguments
+/// * `message` - Message to send in non-interactive mode
 /// * `config` - Application configuration loaded from environment
 ///
 /// # Returns
 ///
 /// Returns `Ok(ExitCode::SUCCESS)` on successful execution,
 /// or an error if initialization or execution fails.
-fn run(args: &CliArgs, config: Config) -> anyhow::Result<ExitCode> {
+fn run(args: &CliArgs, message: Option<String>, config: Config) -> anyhow::Result<ExitCode> {
     let rt = tokio::runtime::Runtime::new().context("Failed to create Tokio runtime")?;
     rt.block_on(async {
         let mut registry = ProviderRegistry::global().write().await;
@@ -126,14 +383,10 @@ fn run(args: &CliArgs, config: Config) -> anyhow::Result<ExitCode> {
 
     let (input_sender, input_receiver) = mpsc::unbounded_channel();
 
-    let (event_receiver, main_handle, provider_config) = App::run(
-        config,
-        input_receiver,
-        args.message.clone(),
-        args.model.clone(),
-        &rt,
-    )
-    .context("Failed to start application")?;
+    let interactive = message.is_none();
+    let (event_receiver, main_handle, provider_config) =
+        App::run(config, input_receiver, message, args.model.clone(), &rt)
+            .context("Failed to start application")?;
 
     output::println(format_args!(
         "{} | {} | {} | {}\n",
@@ -149,7 +402,7 @@ fn run(args: &CliArgs, config: Config) -> anyhow::Result<ExitCode> {
         }
     });
 
-    if args.message.is_none() {
+    if interactive {
         let stdin = io::stdin();
         for line in stdin.lock().lines() {
             match line {
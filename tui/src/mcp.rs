@@ -0,0 +1,46 @@
+//! Rendering of MCP server, resource and prompt events.
+
+use crate::output;
+use crossterm::style::Stylize;
+use neco_core::CoreEvent;
+
+/// Print an event about MCP servers; other events are ignored.
+pub fn render(event: CoreEvent) {
+    match event {
+        CoreEvent::McpConnected {
+            server,
+            tools,
+            prompts,
+        } => {
+            tracing::info!(%server, tools, prompts, "MCP server connected");
+            output::println(format_args!(
+                "{} MCP server {} ({tools} tools, {prompts} prompts)",
+                "⚡".green(),
+                server.bold()
+            ));
+        },
+        CoreEvent::McpResource { server, uri } => {
+            output::println(format_args!("{} Attached @{server}:{uri}", "📎".green()));
+        },
+        CoreEvent::PromptArgument {
+            command,
+            name,
+            description,
+        } => {
+            let description = if description.is_empty() {
+                String::new()
+            } else {
+                format!(" ({description})")
+            };
+            output::println(format_args!(
+                "{} /{command}: enter {}{description}",
+                "?".yellow(),
+                name.bold()
+            ));
+        },
+        CoreEvent::PromptCancelled { command } => {
+            output::println(format_args!("{}", format!("/{command} cancelled").yellow()));
+        },
+        _ => {},
+    }
+}
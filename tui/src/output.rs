@@ -2,6 +2,8 @@
 //!
 //! Provides print/println functions that bypass clippy's `print_stdout` lint.
 
+use crossterm::style::Stylize;
+use neco_core::checkpoint::Stat;
 use std::io::{self, Write};
 
 /// Print formatted arguments to stdout.
@@ -14,3 +16,43 @@ pub fn println(args: std::fmt::Arguments<'_>) {
     let _ = io::stdout().write_fmt(args);
     let _ = io::stdout().write_all(b"\n");
 }
+
+/// Print a unified diff with added and removed lines highlighted.
+pub fn diff(text: &str) {
+    for line in text.lines() {
+        if line.starts_with("+++") || line.starts_with("---") {
+            println(format_args!("{}", line.bold()));
+        } else if line.starts_with("@@") {
+            println(format_args!("{}", line.cyan()));
+        } else if line.starts_with('+') {
+            println(format_args!("{}", line.green()));
+        } else if line.starts_with('-') {
+            println(format_args!("{}", line.red()));
+        } else {
+            println(format_args!("{line}"));
+        }
+    }
+}
+
+/// Print the number of changed lines per file, with a total.
+pub fn stats(files: &[Stat]) {
+    if files.is_empty() {
+        return;
+    }
+    for file in files {
+        println(format_args!(
+            " {}  {} {}",
+            file.path,
+            format!("+{}", file.added).green(),
+            format!("-{}", file.removed).red()
+        ));
+    }
+    let added: usize = files.iter().map(|file| file.added).sum();
+    let removed: usize = files.iter().map(|file| file.removed).sum();
+    println(format_args!(
+        " {} files changed, {} insertions(+), {} deletions(-)",
+        files.len(),
+        added,
+        removed
+    ));
+}
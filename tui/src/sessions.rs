@@ -0,0 +1,311 @@
+//! `neco sessions` subcommands for managing stored sessions.
+
+use crate::{checkpoints, output};
+use anyhow::Context;
+use clap::Subcommand;
+use crossterm::style::Stylize;
+use neco_core::export::{Document, Format, Redactor};
+use neco_core::transcript::{self, Record};
+use neco_core::{Redaction, checkpoint, clock};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Maximum number of characters of a session title shown in listings.
+const TITLE_WIDTH: usize = 50;
+
+/// Maximum number of lines of a tool result shown by `sessions show`.
+const RESULT_LINES: usize = 10;
+
+/// Session management action.
+#[derive(Subcommand, Debug)]
+pub enum Action {
+    /// List stored sessions, most recent first
+    List,
+    /// Show the conversation of a session
+    Show {
+        /// Session id (or unique id prefix)
+        id: String,
+    },
+    /// Show the diff of all file changes the agent made in a session
+    Diff {
+        /// Session id (or unique id prefix)
+        id: String,
+        /// Only include changes from this checkpoint on
+        #[arg(long)]
+        since: Option<usize>,
+        /// Write the diff to a patch file instead of printing it
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Export a session to Markdown or a self-contained HTML page
+    Export {
+        /// Session id (or unique id prefix)
+        id: String,
+        /// File to write (printed to stdout if omitted)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+        /// Format: md or html (defaults to the output file's extension, else md)
+        #[arg(short, long)]
+        format: Option<Format>,
+    },
+    /// Delete a session
+    Delete {
+        /// Session id (or unique id prefix)
+        id: String,
+    },
+}
+
+/// Run a session management action.
+///
+/// # Arguments
+///
+/// * `action` - Action to run
+/// * `sessions_dir` - Directory holding the sessions
+///
+/// # Errors
+///
+/// Returns an error if the session cannot be found, read or deleted.
+pub fn run(action: &Action, sessions_dir: &Path, redaction: &Redaction) -> anyhow::Result<()> {
+    match action {
+        Action::List => list(sessions_dir),
+        Action::Show { id } => show(sessions_dir, id)?,
+        Action::Diff { id, since, output } => diff(sessions_dir, id, *since, output.as_deref())?,
+        Action::Export { id, output, format } => {
+            export(sessions_dir, id, output.as_deref(), *format, redaction)?;
+        },
+        Action::Delete { id } => {
+            let id = transcript::delete(sessions_dir, id)?;
+            output::println(format_args!(
+                "{} Deleted session {}",
+                "⏺".green(),
+                id.bold()
+            ));
+        },
+    }
+    Ok(())
+}
+
+/// Print a table of all stored sessions.
+fn list(sessions_dir: &Path) {
+    let sessions = transcript::list(sessions_dir);
+    if sessions.is_empty() {
+        output::println(format_args!("No sessions in {}", sessions_dir.display()));
+        return;
+    }
+
+    for info in sessions {
+        let title = info.title.as_deref().map(first_line).unwrap_or_default();
+        output::println(format_args!(
+            "{}  {}  {:>4} msgs  {}  {}",
+            info.id.bold(),
+            clock::format_datetime(info.updated_at).dim(),
+            info.messages,
+            info.model.green(),
+            info.cwd.dim()
+        ));
+        if let Some(parent) = &info.parent {
+            output::println(format_args!(
+                "    {} {}",
+                "⑂ branch of".dim(),
+                parent.as_str().dim()
+            ));
+        }
+        if !title.is_empty() {
+            output::println(format_args!("    {title}"));
+        }
+    }
+}
+
+/// Print the conversation of a session.
+fn show(sessions_dir: &Path, id: &str) -> anyhow::Result<()> {
+    let records = transcript::load(sessions_dir, id)?;
+    let info = transcript::summarize(&records).context("Session has no metadata")?;
+    output::println(format_args!(
+        "{} | {} | {} | {}\n",
+        info.id.bold(),
+        info.model.green(),
+        info.cwd.dim(),
+        clock::format_datetime(info.created_at).dim()
+    ));
+
+    let mut started = false;
+    for record in &records {
+        match record {
+            Record::Meta {
+                provider,
+                model,
+                at,
+                ..
+            } => {
+                if started {
+                    output::println(format_args!(
+                        "{} Resumed with {provider}/{model} at {}\n",
+                        "↩".dim(),
+                        clock::format_datetime(*at)
+                    ));
+                }
+                started = true;
+            },
+            Record::Message { message, .. } => show_message(message),
+            Record::Rewind { len, .. } => {
+                output::println(format_args!(
+                    "{}\n",
+                    format!("⏪ Rewound to the first {len} messages").green()
+                ));
+            },
+            Record::Clear { .. } => {
+                output::println(format_args!("{}\n", "⏺ Cleared conversation".green()));
+            },
+            Record::Compact { messages, .. } => {
+                output::println(format_args!(
+                    "{}\n",
+                    format!("⏺ Compacted conversation to {} messages", messages.len()).green()
+                ));
+            },
+            Record::Event { event, .. } => {
+                if let neco_core::CoreEvent::Error(error)
+                | neco_core::CoreEvent::LimitReached(neco_core::guard::LimitExceeded {
+                    message: error,
+                    ..
+                }) = event
+                {
+                    output::println(format_args!("{} {error}\n", "❌".red()));
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Print or save the diff of the file changes of a session.
+fn diff(
+    sessions_dir: &Path,
+    id: &str,
+    since: Option<usize>,
+    output: Option<&Path>,
+) -> anyhow::Result<()> {
+    let id = transcript::resolve_id(sessions_dir, id)?;
+    let records = transcript::load(sessions_dir, &id)?;
+    let info = transcript::summarize(&records).context("Session has no metadata")?;
+    let store = checkpoint::Store::open(&sessions_dir.join(&id).join("checkpoints"));
+    let changes = store.changes(since)?;
+    let base = Path::new(&info.cwd);
+    let patch = checkpoint::patch(&changes, base);
+    let stats: Vec<checkpoint::Stat> = changes.iter().map(|change| change.stat(base)).collect();
+    let untracked = store.untracked(since);
+
+    if changes.is_empty() {
+        output::println(format_args!("No file changes in session {id}"));
+        checkpoints::warn_untracked(&untracked, "shown");
+        return Ok(());
+    }
+    if let Some(file) = output {
+        std::fs::write(file, &patch)
+            .with_context(|| format!("Failed to write {}", file.display()))?;
+        output::stats(&stats);
+        output::println(format_args!(
+            "{} Saved patch to {}",
+            "⏺".green(),
+            file.display()
+        ));
+    } else {
+        output::diff(&patch);
+        output::stats(&stats);
+    }
+    checkpoints::warn_untracked(&untracked, "shown");
+    Ok(())
+}
+
+/// Export a session, to a file or stdout.
+fn export(
+    sessions_dir: &Path,
+    id: &str,
+    output: Option<&Path>,
+    format: Option<Format>,
+    redaction: &Redaction,
+) -> anyhow::Result<()> {
+    let records = transcript::load(sessions_dir, id)?;
+    let document = Document::from_records(&records).context("Session has no metadata")?;
+    let format = format
+        .or_else(|| output.map(Format::from_path))
+        .unwrap_or(Format::Markdown);
+    let content = document.render(format, &Redactor::new(redaction)?);
+
+    match output {
+        Some(path) => {
+            std::fs::write(path, content)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            output::println(format_args!(
+                "{} Exported session to {}",
+                "⏺".green(),
+                path.display()
+            ));
+        },
+        None => output::print(format_args!("{content}")),
+    }
+    Ok(())
+}
+
+/// Print a single message.
+fn show_message(message: &Value) {
+    let role = message.get("role").and_then(Value::as_str).unwrap_or("?");
+    let blocks = match message.get("content") {
+        Some(Value::String(text)) => vec![serde_json::json!({"type": "text", "text": text})],
+        Some(Value::Array(blocks)) => blocks.clone(),
+        _ => Vec::new(),
+    };
+
+    for block in &blocks {
+        let text = block.get("text").and_then(Value::as_str).unwrap_or("");
+        match block.get("type").and_then(Value::as_str) {
+            Some("text") if text.is_empty() => {},
+            Some("text") if role == "user" => {
+                output::println(format_args!("{} {}\n", "❯".blue().bold(), text.bold()));
+            },
+            Some("text") => output::println(format_args!("{text}\n")),
+            Some("tool_use") => {
+                let name = block.get("name").and_then(Value::as_str).unwrap_or("?");
+                let input = block.get("input").map(Value::to_string).unwrap_or_default();
+                output::println(format_args!("🔧 {} {}", name.yellow().bold(), input.dim()));
+            },
+            Some("tool_result") => {
+                let content = block.get("content").and_then(Value::as_str).unwrap_or("");
+                let lines: Vec<&str> = content.lines().collect();
+                for line in lines.iter().take(RESULT_LINES) {
+                    output::println(format_args!("  {}", line.dim()));
+                }
+                if lines.len() > RESULT_LINES {
+                    output::println(format_args!(
+                        "  {}",
+                        format!("... {} more lines", lines.len() - RESULT_LINES).dim()
+                    ));
+                }
+                output::println(format_args!(""));
+            },
+            _ => {},
+        }
+    }
+}
+
+/// First line of a text, shortened for listings.
+fn first_line(text: &str) -> String {
+    let line = text.lines().next().unwrap_or_default();
+    if line.chars().count() > TITLE_WIDTH {
+        let short: String = line.chars().take(TITLE_WIDTH).collect();
+        format!("{short}…")
+    } else {
+        line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_line() {
+        assert_eq!(first_line("fix the bug\nin main.rs"), "fix the bug");
+        let long = "x".repeat(TITLE_WIDTH + 10);
+        assert_eq!(first_line(&long).chars().count(), TITLE_WIDTH + 1);
+    }
+}